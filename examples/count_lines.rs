@@ -0,0 +1,15 @@
+//! `cargo run --release --example count_lines -- <file>`
+//!
+//! Counts a file's lines: the ones `slice::split` finds with the fastest kernel the CPU has, so a
+//! last line without a newline counts too, unlike with `wc -l`.
+
+use split_bench::slice;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: count_lines <file>")?;
+    let input = std::fs::read_to_string(&path)?;
+    let mut lines = Vec::new();
+    slice::split(&input, &mut lines);
+    println!("{} {path}", lines.len());
+    Ok(())
+}
@@ -0,0 +1,31 @@
+//! `cargo run --release --example line_index -- <file> [line]`
+//!
+//! Builds a `compressed::LineIndex` of a file, at about 2 bytes per line, then answers queries
+//! from it without scanning the input again: a line by its number (from 0, the middle one by
+//! default), and the line a byte offset is in.
+
+use split_bench::compressed::{self, LineIndex, NewlineIndex};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().ok_or("usage: line_index <file> [line]")?;
+    let input = std::fs::read_to_string(&path)?;
+    let mut index = LineIndex::default();
+    compressed::build(&input, &mut index);
+    let line_count = index.line_count(input.len());
+    println!(
+        "{line_count} lines, {} bytes of index",
+        index.memory_usage().used_bytes()
+    );
+    if line_count == 0 {
+        return Ok(());
+    }
+    let i = match args.next() {
+        Some(arg) => arg.parse::<usize>()?.min(line_count - 1),
+        None => line_count / 2,
+    };
+    println!("line {i}: {:?}", index.line(&input, i));
+    let pos = input.len() / 2;
+    println!("byte {pos} is in line {}", index.line_of(pos));
+    Ok(())
+}
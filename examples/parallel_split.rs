@@ -0,0 +1,32 @@
+//! `cargo run --release --example parallel_split -- <file> [threads]`
+//!
+//! Splits a file on several threads, one per core by default: `sharded::ShardedLines` cuts it
+//! into about as many pieces as threads, just after a newline, and each thread splits its piece
+//! into its own `Vec`. The result is indexed and iterated like one `Vec<&str>`.
+
+use split_bench::sharded::ShardedLines;
+use std::time::Instant;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .ok_or("usage: parallel_split <file> [threads]")?;
+    let threads = match args.next() {
+        Some(arg) => arg.parse()?,
+        None => std::thread::available_parallelism()?.get(),
+    };
+    let input = std::fs::read_to_string(&path)?;
+    let mut lines = ShardedLines::default();
+    let start = Instant::now();
+    lines.split(&input, threads);
+    let elapsed = start.elapsed();
+    println!(
+        "{} lines in {elapsed:.2?}, threads: {threads}",
+        lines.line_count()
+    );
+    if let Some(last) = lines.line_count().checked_sub(1) {
+        println!("last line: {:?}", lines[last]);
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! `some-command | cargo run --release --example stdin_lines`
+//!
+//! Splits stdin as it arrives, without reading all of it first: `blocks::LineBlocks` hands out
+//! the complete lines read so far, keeping the partial line at the end for the next block. A
+//! `pool::LinePool` keeps the lines' `Vec` from one block to the next, though each block's lines
+//! borrow the buffer.
+
+use split_bench::blocks::LineBlocks;
+use split_bench::pool::LinePool;
+use split_bench::slice;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut blocks = LineBlocks::new(std::io::stdin().lock());
+    let mut line_pool = LinePool::default();
+    let (mut line_count, mut longest) = (0, 0);
+    while let Some(block) = blocks.next_block()? {
+        let mut lines = line_pool.take();
+        slice::split(std::str::from_utf8(block)?, &mut lines);
+        line_count += lines.len();
+        longest = lines
+            .iter()
+            .map(|line| line.len())
+            .fold(longest, usize::max);
+        line_pool.put(lines);
+    }
+    println!("{line_count} lines, the longest {longest} bytes");
+    Ok(())
+}
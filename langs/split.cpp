@@ -0,0 +1,87 @@
+// The `langs` subcommand's C++ splitter, simdjson-style: a 64-bit newline mask per 64-byte block
+// (two AVX2 compares, or a byte loop without AVX2), drained with count-trailing-zeros into a
+// reused std::vector<std::string_view>.
+//
+// usage: split <file> <iterations>
+// prints: <lines>\t<median nanoseconds per iteration>
+
+#include <algorithm>
+#include <chrono>
+#include <cstdint>
+#include <cstdio>
+#include <fstream>
+#include <iterator>
+#include <string>
+#include <string_view>
+#include <vector>
+#ifdef __AVX2__
+#include <immintrin.h>
+#endif
+
+static uint64_t newline_mask(const char *block) {
+#ifdef __AVX2__
+    const __m256i nl = _mm256_set1_epi8('\n');
+    __m256i lo = _mm256_loadu_si256(reinterpret_cast<const __m256i *>(block));
+    __m256i hi = _mm256_loadu_si256(reinterpret_cast<const __m256i *>(block + 32));
+    uint64_t lo_mask = uint32_t(_mm256_movemask_epi8(_mm256_cmpeq_epi8(lo, nl)));
+    uint64_t hi_mask = uint32_t(_mm256_movemask_epi8(_mm256_cmpeq_epi8(hi, nl)));
+    return lo_mask | (hi_mask << 32);
+#else
+    uint64_t mask = 0;
+    for (int i = 0; i < 64; i++) {
+        mask |= uint64_t(block[i] == '\n') << i;
+    }
+    return mask;
+#endif
+}
+
+// the same lines as Rust's str::lines, minus its \r handling (the inputs have none)
+static void split(std::string_view input, std::vector<std::string_view> &out) {
+    size_t line_start = 0;
+    size_t blocks = input.size() / 64;
+    for (size_t block = 0; block < blocks; block++) {
+        uint64_t mask = newline_mask(input.data() + block * 64);
+        while (mask != 0) {
+            size_t line_end = block * 64 + __builtin_ctzll(mask);
+            out.emplace_back(input.data() + line_start, line_end - line_start);
+            line_start = line_end + 1;
+            mask &= mask - 1;
+        }
+    }
+    for (size_t i = blocks * 64; i < input.size(); i++) {
+        if (input[i] == '\n') {
+            out.emplace_back(input.data() + line_start, i - line_start);
+            line_start = i + 1;
+        }
+    }
+    if (line_start != input.size()) {
+        out.emplace_back(input.data() + line_start, input.size() - line_start);
+    }
+}
+
+int main(int argc, char **argv) {
+    if (argc != 3) {
+        std::fprintf(stderr, "usage: %s <file> <iterations>\n", argv[0]);
+        return 2;
+    }
+    std::ifstream file(argv[1], std::ios::binary);
+    if (!file) {
+        std::fprintf(stderr, "can't open %s\n", argv[1]);
+        return 1;
+    }
+    std::string input((std::istreambuf_iterator<char>(file)), std::istreambuf_iterator<char>());
+    int iterations = std::max(1, std::atoi(argv[2]));
+
+    std::vector<std::string_view> lines;
+    std::vector<long long> times;
+    for (int i = 0; i < iterations; i++) {
+        lines.clear();
+        auto start = std::chrono::steady_clock::now();
+        split(input, lines);
+        auto end = std::chrono::steady_clock::now();
+        times.push_back(std::chrono::duration_cast<std::chrono::nanoseconds>(end - start).count());
+    }
+    std::sort(times.begin(), times.end());
+    std::printf("%zu\t%lld\n", lines.size(), times[times.size() / 2]);
+    return 0;
+}
@@ -0,0 +1,45 @@
+// The `langs` subcommand's Go splitter: bytes.Split, the usual way to get a file's lines at once.
+//
+// usage: split <file> <iterations>
+// prints: <lines>\t<median nanoseconds per iteration>
+package main
+
+import (
+	"bytes"
+	"fmt"
+	"os"
+	"sort"
+	"strconv"
+	"time"
+)
+
+func main() {
+	if len(os.Args) != 3 {
+		fmt.Fprintf(os.Stderr, "usage: %s <file> <iterations>\n", os.Args[0])
+		os.Exit(2)
+	}
+	input, err := os.ReadFile(os.Args[1])
+	if err != nil {
+		fmt.Fprintln(os.Stderr, err)
+		os.Exit(1)
+	}
+	iterations, err := strconv.Atoi(os.Args[2])
+	if err != nil || iterations < 1 {
+		iterations = 1
+	}
+
+	var lines [][]byte
+	times := make([]int64, 0, iterations)
+	for i := 0; i < iterations; i++ {
+		start := time.Now()
+		lines = bytes.Split(input, []byte{'\n'})
+		times = append(times, time.Since(start).Nanoseconds())
+	}
+	sort.Slice(times, func(a, b int) bool { return times[a] < times[b] })
+	// a trailing newline leaves an empty last element, which isn't a line
+	count := len(lines)
+	if len(lines[count-1]) == 0 {
+		count--
+	}
+	fmt.Printf("%d\t%d\n", count, times[len(times)/2])
+}
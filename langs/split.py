@@ -0,0 +1,27 @@
+"""The `langs` subcommand's Python splitter: str.splitlines.
+
+usage: split.py <file> <iterations>
+prints: <lines>\t<median nanoseconds per iteration>
+"""
+
+import sys
+import time
+
+
+def main():
+    if len(sys.argv) != 3:
+        sys.exit(f"usage: {sys.argv[0]} <file> <iterations>")
+    with open(sys.argv[1], encoding="utf-8", newline="") as file:
+        text = file.read()
+    iterations = max(1, int(sys.argv[2]))
+
+    times = []
+    for _ in range(iterations):
+        start = time.perf_counter_ns()
+        lines = text.splitlines()
+        times.append(time.perf_counter_ns() - start)
+    times.sort()
+    print(f"{len(lines)}\t{times[len(times) // 2]}")
+
+
+main()
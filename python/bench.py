@@ -0,0 +1,38 @@
+"""Times the `split_bench` module (`maturin develop --release`) against Python's own
+splitters, on a file such as the `langs` subcommand's `target/langs/*.txt`. `bytes.split`
+counts one more line, the empty one after the last newline.
+
+usage: bench.py <file> <iterations>
+prints: <name>\t<lines>\t<median nanoseconds per iteration>
+"""
+
+import sys
+import time
+
+import split_bench
+
+
+def main():
+    if len(sys.argv) != 3:
+        sys.exit(f"usage: {sys.argv[0]} <file> <iterations>")
+    with open(sys.argv[1], "rb") as file:
+        data = file.read()
+    iterations = max(1, int(sys.argv[2]))
+
+    splitters = [
+        ("bytes.split", lambda: data.split(b"\n")),
+        ("bytes.splitlines", data.splitlines),
+        ("split_bench.split_lines", lambda: split_bench.split_lines(data)),
+        ("split_bench.line_index", lambda: split_bench.line_index(data)),
+    ]
+    for name, split in splitters:
+        times = []
+        for _ in range(iterations):
+            start = time.perf_counter_ns()
+            lines = split()
+            times.append(time.perf_counter_ns() - start)
+        times.sort()
+        print(f"{name}\t{len(lines)}\t{times[len(times) // 2]}")
+
+
+main()
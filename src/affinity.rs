@@ -0,0 +1,82 @@
+//! Pinning the bench to one core, so the scheduler can't migrate it halfway through a run.
+//! std has no API for this, but libc (or kernel32 on Windows) is linked anyway, so the calls are
+//! declared here.
+
+use std::io;
+
+/// Pins the current thread to the core it's running on, and returns that core
+#[cfg(target_os = "linux")]
+pub fn pin_to_current_core() -> io::Result<usize> {
+    // glibc's `cpu_set_t`: a 1024-bit mask
+    type CpuSet = [u64; 16];
+    extern "C" {
+        fn sched_getcpu() -> i32;
+        fn sched_setaffinity(pid: i32, size: usize, mask: *const CpuSet) -> i32;
+    }
+    let cpu = unsafe { sched_getcpu() };
+    if cpu < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let cpu = cpu as usize;
+    let mut mask: CpuSet = [0; 16];
+    *mask
+        .get_mut(cpu / 64)
+        .ok_or_else(|| io::Error::other(format!("core {cpu} doesn't fit in a cpu_set_t")))? |=
+        1 << (cpu % 64);
+    // pid 0 is the calling thread
+    if unsafe { sched_setaffinity(0, size_of::<CpuSet>(), &mask) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cpu)
+}
+
+/// Pins the current thread to the core it's running on, and returns that core's number within
+/// its processor group
+#[cfg(windows)]
+pub fn pin_to_current_core() -> io::Result<usize> {
+    use std::ffi::c_void;
+    extern "system" {
+        fn GetCurrentProcessorNumber() -> u32;
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+    }
+    // the thread's processor group has at most 64 cores, so its number fits in the mask
+    let cpu = unsafe { GetCurrentProcessorNumber() } as usize;
+    // returns the previous mask, 0 on failure
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << cpu) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cpu)
+}
+
+/// macOS can't bind a thread to a core (affinity tags only group threads on a shared L2, and
+/// Apple Silicon ignores them). What moves the numbers there is which kind of core runs the
+/// thread, so this asks for the highest QoS class, which keeps it on the performance cores, and
+/// returns the core it's on now.
+#[cfg(target_os = "macos")]
+pub fn pin_to_current_core() -> io::Result<usize> {
+    const QOS_CLASS_USER_INTERACTIVE: u32 = 0x21;
+    extern "C" {
+        fn pthread_set_qos_class_self_np(qos_class: u32, relative_priority: i32) -> i32;
+        fn pthread_cpu_number_np(cpu: *mut usize) -> i32;
+    }
+    // both return an errno value rather than setting errno
+    let err = unsafe { pthread_set_qos_class_self_np(QOS_CLASS_USER_INTERACTIVE, 0) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+    let mut cpu = 0;
+    let err = unsafe { pthread_cpu_number_np(&mut cpu) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+    Ok(cpu)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn pin_to_current_core() -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pinning is only implemented for Linux, macOS and Windows",
+    ))
+}
@@ -0,0 +1,20 @@
+//! The global allocator, picked with the `mimalloc` or `jemalloc` features (the system one
+//! otherwise). Kernels that grow their output with `reserve` call into it every few thousand lines,
+//! and how it grows a large block (in place with `mremap`, or by copying) differs between them.
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// The allocator this build uses, as its cargo feature is named
+pub const NAME: &str = if cfg!(feature = "mimalloc") {
+    "mimalloc"
+} else if cfg!(feature = "jemalloc") {
+    "jemalloc"
+} else {
+    "system"
+};
@@ -0,0 +1,191 @@
+//! Splitting into a bump arena, for parser frontends that already keep their data in one. The
+//! kernel writes the lines into the free space at the end of the arena's current chunk, the way
+//! the slice kernels write into a `Vec`'s spare capacity, and the arena then keeps as much of it
+//! as was written. There's no `Vec` to grow: if the free space is too small, the lines go into a
+//! chunk made for exactly as many of them, after a second scan. [`BumpArena`] is what an arena
+//! needs to provide, and [`Bump`] a minimal one.
+
+use crate::uninit::{split_into, TooSmall};
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::mem::{self, MaybeUninit};
+use core::ptr::NonNull;
+
+/// An arena that hands out the free space at the end of its current chunk and allocates the part
+/// of it that was written. Methods take `&self`, as in `bumpalo`, so what it allocated stays
+/// borrowed from it while it allocates more.
+///
+/// # Safety
+///
+/// The space [`BumpArena::spare`] returns must be valid for writes and not in use, and once
+/// [`BumpArena::commit`]ted, stay in place and unused until the arena is borrowed mutably or
+/// dropped.
+pub unsafe trait BumpArena {
+    /// The free bytes at the end of the current chunk, starting at a multiple of `align` (a
+    /// power of two, up to 16), and at least `min_bytes` of them, in a new chunk if the current
+    /// one has fewer
+    fn spare(&self, min_bytes: usize, align: usize) -> (NonNull<u8>, usize);
+
+    /// Allocates the first `len` bytes of the space the last [`BumpArena::spare`] returned
+    ///
+    /// # Safety
+    ///
+    /// `len` is at most the length `spare` returned, with no other call in between.
+    unsafe fn commit(&self, len: usize);
+}
+
+/// The lines of `input`, as `slice::split` finds them, in memory from `arena`
+// the memory is the arena's to hand out, as with `bumpalo::Bump::alloc`
+#[allow(clippy::mut_from_ref)]
+pub fn split_in<'arena, 'input>(
+    input: &'input str,
+    arena: &'arena impl BumpArena,
+) -> &'arena mut [&'input str] {
+    let mut out = spare(arena, 0);
+    let written = match split_into(input, out) {
+        Ok(written) => written,
+        Err(TooSmall { needed }) => {
+            out = spare(arena, needed);
+            split_into(input, out).unwrap()
+        }
+    };
+    unsafe {
+        arena.commit(written * mem::size_of::<&str>());
+        // the first `written` slots were just initialized
+        &mut *(&mut out[..written] as *mut [MaybeUninit<&'input str>] as *mut [&'input str])
+    }
+}
+
+/// The arena's free space as slots for `T`s, at least `min` of them
+#[allow(clippy::mut_from_ref)]
+fn spare<T>(arena: &impl BumpArena, min: usize) -> &mut [MaybeUninit<T>] {
+    let (ptr, len) = arena.spare(min * mem::size_of::<T>(), mem::align_of::<T>());
+    unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr().cast(), len / mem::size_of::<T>()) }
+}
+
+const CHUNK_ALIGN: usize = 16;
+const FIRST_CHUNK: usize = 4096;
+
+/// A bump arena over chunks from the global allocator, each twice the size of the one before it
+/// or as large as a request that doesn't fit, like `bumpalo::Bump`
+pub struct Bump {
+    /// Every chunk and its size, the current one last
+    chunks: RefCell<Vec<(NonNull<u8>, usize)>>,
+    /// Bytes of the current chunk allocated so far
+    used: Cell<usize>,
+}
+
+impl Bump {
+    pub fn new() -> Self {
+        Bump {
+            chunks: RefCell::new(Vec::new()),
+            used: Cell::new(0),
+        }
+    }
+
+    /// Frees everything allocated, keeping only the current chunk, the largest, for reuse
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        let last = chunks.pop();
+        for (ptr, size) in chunks.drain(..) {
+            unsafe { dealloc(ptr.as_ptr(), chunk_layout(size)) };
+        }
+        chunks.extend(last);
+        self.used.set(0);
+    }
+
+    /// Bytes held in chunks, allocated or not
+    pub fn chunk_bytes(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.1).sum()
+    }
+}
+
+impl Default for Bump {
+    fn default() -> Self {
+        Bump::new()
+    }
+}
+
+fn chunk_layout(size: usize) -> Layout {
+    Layout::from_size_align(size, CHUNK_ALIGN).unwrap()
+}
+
+unsafe impl BumpArena for Bump {
+    fn spare(&self, min_bytes: usize, align: usize) -> (NonNull<u8>, usize) {
+        assert!(align.is_power_of_two() && align <= CHUNK_ALIGN);
+        let mut chunks = self.chunks.borrow_mut();
+        if let Some(&(ptr, size)) = chunks.last() {
+            let start = self.used.get().next_multiple_of(align);
+            if start <= size && size - start >= min_bytes {
+                self.used.set(start);
+                return (unsafe { ptr.add(start) }, size - start);
+            }
+        }
+        let size = chunks
+            .last()
+            .map_or(FIRST_CHUNK, |chunk| chunk.1 * 2)
+            .max(min_bytes.next_multiple_of(CHUNK_ALIGN));
+        let layout = chunk_layout(size);
+        let ptr =
+            NonNull::new(unsafe { alloc(layout) }).unwrap_or_else(|| handle_alloc_error(layout));
+        chunks.push((ptr, size));
+        self.used.set(0);
+        (ptr, size)
+    }
+
+    unsafe fn commit(&self, len: usize) {
+        self.used.set(self.used.get() + len);
+    }
+}
+
+impl Drop for Bump {
+    fn drop(&mut self) {
+        for &(ptr, size) in self.chunks.get_mut().iter() {
+            unsafe { dealloc(ptr.as_ptr(), chunk_layout(size)) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_in() {
+        let long = "ab\n\n\ncdefghijklmnopqrstuvwxyz0123456789\n".repeat(400) + "x";
+        let mut arena = Bump::new();
+        for round in 0..2 {
+            // each input's lines stay put while the ones after it are added, in the first chunk
+            // while they fit and in larger ones after that
+            let inputs: Vec<&str> = [0, 1, 3, 100, 1000, long.len(), 7, long.len()]
+                .iter()
+                .map(|&len| &long[..len])
+                .collect();
+            let lines: Vec<&[&str]> = inputs
+                .iter()
+                .map(|input| &*split_in(input, &arena))
+                .collect();
+            for (input, lines) in inputs.iter().zip(lines) {
+                assert_eq!(lines, crate::slice::std(input), "round {round}");
+            }
+            arena.reset();
+            assert_eq!(arena.chunks.borrow().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_bump() {
+        let arena = Bump::new();
+        let (first, len) = arena.spare(10, 8);
+        assert_eq!(len, FIRST_CHUNK);
+        unsafe { arena.commit(3) };
+        // aligned past what was allocated
+        let (second, _) = arena.spare(0, 8);
+        assert_eq!(second.as_ptr() as usize - first.as_ptr() as usize, 8);
+        let (third, len) = arena.spare(FIRST_CHUNK * 3, 8);
+        assert_ne!(third, second);
+        assert_eq!(len, FIRST_CHUNK * 3);
+        assert_eq!(arena.chunk_bytes(), FIRST_CHUNK * 4);
+    }
+}
@@ -0,0 +1,187 @@
+//! Hand-written inline assembly for the hottest inner loop (`--features asm`): turning a 64-byte
+//! chunk into a newline mask and storing one offset per set bit. Everything around it (the
+//! reserve, the 64 KiB blocks, the tail) is the same Rust as in the intrinsics kernels they're
+//! benchmarked against, so any difference in the tables is the compiler's codegen for the chunk.
+//!
+//! What the intrinsics versions compile to (rustc 1.95, `--emit asm`), compared to the asm:
+//! * The mask is built the same way: two `vpcmpeqb` with a memory operand, two `vpmovmskb`, then
+//!   `shl` + `or`, whose flags feed the skip of chunks without newlines.
+//! * The bit loop is the same six instructions: `tzcnt`, adding the chunk's base (LLVM uses `or`,
+//!   since the low 6 bits are clear), the store, `inc`, `blsr`, `jnz`.
+//! * The only difference is outside the bit loop: LLVM recomputes the chunk's address and base
+//!   from `chunk_i` with a `shl` each, where the asm version's caller bumps them by 64.
+//!
+//! So instruction selection isn't where the time goes. On a one-core AVX-512 VM, the asm came out
+//! 2-10% ahead on the stages with 10 to 120 byte lines, and about 30% behind on `0-0` (every byte
+//! a newline), i.e. neither side wins clearly. What limits both is the bit loop's mispredicted
+//! exit, which takes a different algorithm to fix, like the AVX-512 compress kernels.
+
+use crate::compressed::{self, LineIndex};
+use crate::flat;
+use std::arch::asm;
+use std::arch::x86_64::*;
+
+/// Writes the offsets of the newlines in the 64 bytes at `ptr`, plus `base`, to
+/// `out[*write_i..]` and advances `write_i`
+#[target_feature(enable = "avx2,bmi1")]
+#[inline]
+unsafe fn chunk_u16(
+    ptr: *const u8,
+    nl_v: __m256i,
+    base: usize,
+    out: *mut u16,
+    write_i: &mut usize,
+) {
+    asm!(
+        "vpcmpeqb {v1}, {nl_v}, ymmword ptr [{ptr}]",
+        "vpcmpeqb {v2}, {nl_v}, ymmword ptr [{ptr} + 32]",
+        "vpmovmskb {lo:e}, {v1}",
+        "vpmovmskb {mask:e}, {v2}",
+        "shl {mask}, 32",
+        "or {mask}, {lo}",
+        "jz 3f",
+        "2:",
+        "tzcnt {lo}, {mask}",
+        "add {lo:e}, {base:e}",
+        "mov word ptr [{out} + {write_i} * 2], {lo:x}",
+        "inc {write_i}",
+        "blsr {mask}, {mask}",
+        "jnz 2b",
+        "3:",
+        ptr = in(reg) ptr,
+        nl_v = in(ymm_reg) nl_v,
+        base = in(reg) base,
+        out = in(reg) out,
+        write_i = inout(reg) *write_i,
+        v1 = out(ymm_reg) _,
+        v2 = out(ymm_reg) _,
+        lo = out(reg) _,
+        mask = out(reg) _,
+        options(nostack),
+    );
+}
+
+/// [`chunk_u16`] with `u32` offsets
+#[target_feature(enable = "avx2,bmi1")]
+#[inline]
+unsafe fn chunk_u32(
+    ptr: *const u8,
+    nl_v: __m256i,
+    base: usize,
+    out: *mut u32,
+    write_i: &mut usize,
+) {
+    asm!(
+        "vpcmpeqb {v1}, {nl_v}, ymmword ptr [{ptr}]",
+        "vpcmpeqb {v2}, {nl_v}, ymmword ptr [{ptr} + 32]",
+        "vpmovmskb {lo:e}, {v1}",
+        "vpmovmskb {mask:e}, {v2}",
+        "shl {mask}, 32",
+        "or {mask}, {lo}",
+        "jz 3f",
+        "2:",
+        "tzcnt {lo}, {mask}",
+        "add {lo:e}, {base:e}",
+        "mov dword ptr [{out} + {write_i} * 4], {lo:e}",
+        "inc {write_i}",
+        "blsr {mask}, {mask}",
+        "jnz 2b",
+        "3:",
+        ptr = in(reg) ptr,
+        nl_v = in(ymm_reg) nl_v,
+        base = in(reg) base,
+        out = in(reg) out,
+        write_i = inout(reg) *write_i,
+        v1 = out(ymm_reg) _,
+        v2 = out(ymm_reg) _,
+        lo = out(reg) _,
+        mask = out(reg) _,
+        options(nostack),
+    );
+}
+
+/// [`compressed::x86_64::avx2_unrollx2`] with the chunk in asm
+#[target_feature(enable = "avx2,bmi1")]
+pub unsafe fn compressed_avx2_unrollx2(input: &str, out: &mut LineIndex) {
+    let nl_v = _mm256_set1_epi8(b'\n' as i8);
+    for chunk_64k in input.as_bytes().chunks(1 << 16) {
+        out.high_starts.push(out.lows.len());
+        let mut chunk_i = 0;
+        let stop_chunk_i = chunk_64k.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0usize;
+            out.lows.reserve(256);
+            let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = chunk_64k.as_ptr().add(chunk_i * 64);
+                chunk_u16(
+                    ptr,
+                    nl_v,
+                    chunk_i * 64,
+                    out_arr.as_mut_ptr().cast(),
+                    &mut write_i,
+                );
+                chunk_i += 1;
+            }
+            out.lows.set_len(out.lows.len() + write_i);
+        }
+    }
+    compressed::tail(64, input, out);
+}
+
+/// [`flat::x86_64::avx2`] with the chunk in asm
+#[target_feature(enable = "avx2,bmi1")]
+pub unsafe fn flat_avx2(input: &str, out: &mut Vec<u32>) {
+    assert!(input.len() <= u32::MAX as usize);
+    let nl_v = _mm256_set1_epi8(b'\n' as i8);
+    let mut chunk_i = 0;
+    let stop_chunk_i = input.len() / 64;
+    while chunk_i < stop_chunk_i {
+        let mut write_i = 0usize;
+        out.reserve(256);
+        let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+        while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+            let ptr = input.as_ptr().add(chunk_i * 64);
+            chunk_u32(
+                ptr,
+                nl_v,
+                chunk_i * 64,
+                out_arr.as_mut_ptr().cast(),
+                &mut write_i,
+            );
+            chunk_i += 1;
+        }
+        out.set_len(out.len() + write_i);
+    }
+    flat::tail(stop_chunk_i * 64, input, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_against_intrinsics() {
+        if !crate::slice::x86_64::can_run_avx2() {
+            return;
+        }
+        let long = "ab\n\n\n0123456789\n".repeat(20_000) + &"\n".repeat(300);
+        for len in [0, 1, 63, 64, 65, 1000, 65536 + 7, long.len()] {
+            let input = &long[..len];
+            let (mut expected, mut index) = (LineIndex::default(), LineIndex::default());
+            unsafe {
+                compressed::x86_64::avx2_unrollx2(input, &mut expected);
+                compressed_avx2_unrollx2(input, &mut index);
+            }
+            assert!(index == expected, "len {len}");
+
+            let (mut expected, mut offsets) = (Vec::new(), vec![7]);
+            unsafe {
+                flat::x86_64::avx2(input, &mut expected);
+                flat_avx2(input, &mut offsets);
+            }
+            assert_eq!(offsets[0], 7, "len {len}");
+            assert_eq!(offsets[1..], expected, "len {len}");
+        }
+    }
+}
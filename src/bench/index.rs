@@ -0,0 +1,491 @@
+//! The index formats, and the lookups and conversions the stage's reference index is used for
+
+use super::{Buffers, Cases, Stage};
+#[cfg(feature = "gpu")]
+use crate::gpu;
+use crate::{
+    compressed, flat, ranges, shared, shuffled, slice, Rng, CONCURRENT_READERS, CONVERSIONS,
+    RANDOM_ACCESS, RANGE_SCALE, SPAN_CASES,
+};
+#[cfg(feature = "roaring")]
+use crate::{newline_set, NEWLINE_SET_QUERIES, NEWLINE_SET_ROWS};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// The compressed kernels, checked against `compressed::iter`, which is left in
+/// `bufs.test_compressed` as the stage's reference index
+pub fn compressed(stage: &Stage, cases: &Cases, bufs: &mut Buffers) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let mut thrpts = Vec::new();
+    let reference = &mut bufs.test_compressed;
+    let out = &mut bufs.compressed;
+    reference.lows.clear();
+    reference.high_starts.clear();
+    compressed::iter(stage.input, reference);
+    assert!(
+        reference.lows.len() == stage.newlines,
+        "(compressed) the reference found {} newlines instead of {} during {stage_label}",
+        reference.lows.len(),
+        stage.newlines
+    );
+    for (fn_label, _, fnc) in &cases.compressed {
+        let thrpt = stage.measure("compressed", fn_label, &mut || {
+            bench.pretouch.reset(&mut out.lows);
+            out.high_starts.clear();
+            let input = stage.next_input();
+            let start = Instant::now();
+            unsafe { fnc(input, out) };
+            let duration = start.elapsed();
+            black_box(&mut *out);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            out == reference,
+            "(compressed) {fn_label} failed during {stage_label}"
+        );
+    }
+    progress!(bench, "{:<13}: {}", "memory", out.memory_usage());
+    // the reference index isn't reused for timing, so it can give its spare capacity back
+    reference.shrink_to_fit();
+    progress!(bench, "{:<13}: {}", "shrunk", reference.memory_usage());
+    thrpts
+}
+
+/// The `CONVERSIONS` between `reference`, the stage's index, and slices
+pub fn conversions<'a>(
+    stage: &Stage<'a>,
+    reference: &compressed::LineIndex,
+    out: &mut Vec<&'a str>,
+) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    if let Some(skipped) = stage.skip_capped(CONVERSIONS.len()) {
+        return skipped;
+    }
+    // slices from scratch, for scale
+    let split_thrpt = stage.measure("conversions", "split", &mut || {
+        bench.pretouch.reset(out);
+        let input = stage.next_input();
+        let start = Instant::now();
+        slice::split(input, out);
+        let duration = start.elapsed();
+        black_box(&mut *out);
+        duration
+    });
+    progress!(
+        bench,
+        "{:<13}: {split_thrpt:>8.0} ({})",
+        "split",
+        stage.efficiency(split_thrpt)
+    );
+    // the index is the same for every copy of the input
+    let to_slices_thrpt = stage.measure("conversions", "to_slices", &mut || {
+        bench.pretouch.reset(out);
+        let input = stage.next_input();
+        let start = Instant::now();
+        reference.to_slices(input, out);
+        let duration = start.elapsed();
+        black_box(&mut *out);
+        duration
+    });
+    progress!(
+        bench,
+        "{:<13}: {to_slices_thrpt:>8.0} ({})",
+        "to_slices",
+        stage.efficiency(to_slices_thrpt)
+    );
+    if let Some(Err(err)) = stage.expected_slices.as_ref().map(|e| e.check(out)) {
+        panic!("(conversions) to_slices failed during {stage_label}: {err}");
+    }
+    // reads the slices rather than the input, so it always gets the lines of `input`
+    out.clear();
+    reference.to_slices(stage.input, out);
+    let mut from_slices = compressed::LineIndex::default();
+    let from_slices_thrpt = stage.measure("conversions", "from_slices", &mut || {
+        drop(std::mem::take(&mut from_slices));
+        let start = Instant::now();
+        from_slices = compressed::LineIndex::from_slices(stage.input, out);
+        let duration = start.elapsed();
+        black_box(&mut from_slices);
+        duration
+    });
+    progress!(
+        bench,
+        "{:<13}: {from_slices_thrpt:>8.0} ({})",
+        "from_slices",
+        stage.efficiency(from_slices_thrpt)
+    );
+    assert!(
+        from_slices == *reference,
+        "(conversions) from_slices failed during {stage_label}"
+    );
+    vec![split_thrpt, to_slices_thrpt, from_slices_thrpt]
+}
+
+/// The MB/s of the last GPU kernel run's compute alone, if there's a GPU to run on
+#[cfg(feature = "gpu")]
+pub fn gpu_compute(stage: &Stage) -> Option<f64> {
+    if !gpu::can_run() {
+        return None;
+    }
+    let timings = gpu::last_timings();
+    progress!(
+        stage.bench,
+        "{:<13}: upload {:.1} ms, compute {:.1} ms, download {:.1} ms",
+        "gpu breakdown",
+        timings.upload.as_secs_f64() * 1000.,
+        timings.compute.as_secs_f64() * 1000.,
+        timings.download.as_secs_f64() * 1000.,
+    );
+    Some(stage.len as f64 / timings.compute.as_secs_f64() / 1_000_000.)
+}
+
+/// Every line of `reference`, the stage's index, in the `RANDOM_ACCESS` orders
+pub fn random_access(stage: &Stage, reference: &compressed::LineIndex) -> Vec<f64> {
+    let bench = stage.bench;
+    let line_count = compressed::NewlineIndex::line_count(reference, stage.len);
+    if let Some(skipped) = stage.skip_capped(RANDOM_ACCESS.len()) {
+        return skipped;
+    }
+    if line_count < shuffled::PREFETCH_DISTANCE {
+        // a handful of lookups take no measurable time
+        progress!(bench, "skipped, too few lines");
+        return vec![f64::NAN; RANDOM_ACCESS.len()];
+    }
+    let in_order: Vec<usize> = (0..line_count).collect();
+    let mut shuffled = in_order.clone();
+    // not the stages' RNG, so the inputs don't depend on this
+    Rng::new(bench.seed).shuffle(&mut shuffled);
+    let orders = [
+        (&in_order, 0),
+        (&shuffled, 0),
+        (&shuffled, shuffled::PREFETCH_DISTANCE),
+    ];
+    let mut thrpts = Vec::new();
+    let mut expected_sum = None;
+    for (fn_label, (order, distance)) in RANDOM_ACCESS.into_iter().zip(orders) {
+        let mut sum = 0;
+        let thrpt = stage.measure("random access", fn_label, &mut || {
+            let input = stage.next_input();
+            let start = Instant::now();
+            sum = shuffled::ShuffledLines::new(input, reference, order, distance)
+                .map(|line| {
+                    let bytes = line.as_bytes();
+                    bytes.len()
+                        + bytes.first().map_or(0, |&b| b as usize)
+                        + bytes.last().map_or(0, |&b| b as usize)
+                })
+                .sum::<usize>();
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            *expected_sum.get_or_insert(sum) == sum,
+            "(random access) {fn_label} failed during {}",
+            stage.label
+        );
+    }
+    thrpts
+}
+
+/// Shuffled lookups in a [`shared::SharedLines`], shared by the `CONCURRENT_READERS`
+pub fn concurrent(stage: &Stage, reference: &compressed::LineIndex) -> Vec<f64> {
+    let bench = stage.bench;
+    let line_count = compressed::NewlineIndex::line_count(reference, stage.len);
+    if let Some(skipped) = stage.skip_capped(CONCURRENT_READERS.len()) {
+        return skipped;
+    }
+    if line_count < CONCURRENT_READERS[CONCURRENT_READERS.len() - 1].1 {
+        progress!(bench, "skipped, too few lines");
+        return vec![f64::NAN; CONCURRENT_READERS.len()];
+    }
+    // untimed, like a service's index built at startup
+    let shared_lines = shared::SharedLines::new(stage.input);
+    let mut shuffled: Vec<usize> = (0..shared_lines.line_count()).collect();
+    Rng::new(bench.seed).shuffle(&mut shuffled);
+    let mut thrpts = Vec::new();
+    let mut expected_sum = None;
+    for (fn_label, readers) in CONCURRENT_READERS {
+        let mut sum = 0;
+        let thrpt = stage.measure("concurrent lookups", fn_label, &mut || {
+            let start = Instant::now();
+            sum = std::thread::scope(|scope| {
+                let handles: Vec<_> = shuffled
+                    .chunks(shuffled.len().div_ceil(readers))
+                    .map(|share| {
+                        let lines = shared_lines.clone();
+                        scope.spawn(move || {
+                            share
+                                .iter()
+                                .map(|&i| lines.line(i).unwrap().len())
+                                .sum::<usize>()
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).sum()
+            });
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            *expected_sum.get_or_insert(sum) == sum,
+            "(concurrent lookups) {fn_label} failed during {}",
+            stage.label
+        );
+    }
+    thrpts
+}
+
+/// The `SPAN_CASES` at an offset in every line of `reference`, the stage's index
+pub fn span(stage: &Stage, reference: &compressed::LineIndex) -> Vec<f64> {
+    let bench = stage.bench;
+    let len = stage.len;
+    if let Some(skipped) = stage.skip_capped(SPAN_CASES.len()) {
+        return skipped;
+    }
+    let line_count = compressed::NewlineIndex::line_count(reference, len);
+    // an offset in every line, so the scans cover the input about once
+    let mut rng = Rng::new(bench.seed);
+    let (positions, expected_sum): (Vec<usize>, usize) = (0..line_count)
+        .map(|i| {
+            let range = compressed::NewlineIndex::line_range(reference, i, len);
+            (range.start + rng.below(range.len() + 1), range.len())
+        })
+        .fold(
+            (Vec::with_capacity(line_count), 0),
+            |(mut positions, sum), (pos, line_len)| {
+                positions.push(pos);
+                (positions, sum + line_len)
+            },
+        );
+    let mut thrpts = Vec::new();
+    for (fn_label, find) in SPAN_CASES {
+        let mut sum = 0;
+        let thrpt = stage.measure("line around an offset", fn_label, &mut || {
+            let input = stage.next_input();
+            let start = Instant::now();
+            sum = positions.iter().map(|&pos| find(input, pos).len()).sum();
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            sum == expected_sum,
+            "(line around an offset) {fn_label} found {sum} bytes of lines instead of {expected_sum} during {}",
+            stage.label
+        );
+    }
+    thrpts
+}
+
+/// A `LineIndex` against a roaring bitmap of the newlines, in the `NEWLINE_SET_ROWS`
+#[cfg(feature = "roaring")]
+pub fn newline_sets(stage: &Stage, reference: &compressed::LineIndex) -> Vec<f64> {
+    use compressed::NewlineIndex;
+
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let len = stage.len;
+    if let Some(skipped) = stage.skip_capped(NEWLINE_SET_ROWS.len()) {
+        return skipped;
+    }
+    let line_count = reference.line_count(len);
+    let mut rng = Rng::new(bench.seed);
+    let positions: Vec<usize> = (0..NEWLINE_SET_QUERIES)
+        .map(|_| rng.below(len + 1))
+        .collect();
+    // MB/s of input to queries per microsecond
+    let per_query = |thrpt: f64| thrpt * NEWLINE_SET_QUERIES as f64 / len as f64;
+    let mut index = compressed::LineIndex::default();
+    let index_build = stage.measure("newline sets", "LineIndex build", &mut || {
+        drop(std::mem::take(&mut index));
+        let input = stage.next_input();
+        let start = Instant::now();
+        compressed::build(input, &mut index);
+        let duration = start.elapsed();
+        black_box(&mut index);
+        duration
+    });
+    let mut set = newline_set::NewlineSet(Default::default());
+    let set_build = stage.measure("newline sets", "roaring build", &mut || {
+        drop(std::mem::take(&mut set.0));
+        let input = stage.next_input();
+        let start = Instant::now();
+        set = newline_set::NewlineSet::build(input);
+        let duration = start.elapsed();
+        black_box(&mut set);
+        duration
+    });
+    assert!(
+        set.len() == stage.newlines && index.len() == stage.newlines,
+        "(newline sets) found {} and {} newlines instead of {} during {stage_label}",
+        index.len(),
+        set.len(),
+        stage.newlines
+    );
+    let mut sums = Vec::new();
+    let mut time_queries = |algo_name, query: &dyn Fn(usize) -> usize| {
+        let mut sum = 0;
+        let thrpt = stage.measure("newline sets", algo_name, &mut || {
+            let start = Instant::now();
+            sum = positions.iter().map(|&pos| query(pos)).sum::<usize>();
+            start.elapsed()
+        });
+        sums.push(black_box(sum));
+        per_query(thrpt)
+    };
+    let index_rank = time_queries("LineIndex line_of", &|pos| index.line_of(pos));
+    let set_rank = time_queries("roaring line_of", &|pos| set.line_of(pos));
+    let index_contains = time_queries("LineIndex is_newline", &|pos| {
+        index.is_newline(pos) as usize
+    });
+    let set_contains = time_queries("roaring is_newline", &|pos| set.is_newline(pos) as usize);
+    assert!(
+        sums[0] == sums[1] && sums[2] == sums[3],
+        "(newline sets) roaring doesn't match LineIndex during {stage_label}"
+    );
+    index.shrink_to_fit();
+    let index_memory = index.memory_usage().used_bytes() as f64 / line_count as f64;
+    let set_memory = set.memory_bytes() as f64 / line_count as f64;
+    let thrpts = vec![
+        index_build,
+        set_build,
+        index_rank,
+        set_rank,
+        index_contains,
+        set_contains,
+        index_memory,
+        set_memory,
+    ];
+    for (fn_label, value) in NEWLINE_SET_ROWS.iter().zip(&thrpts) {
+        progress!(bench, "{fn_label:<26}: {value:>8.1}");
+    }
+    thrpts
+}
+
+/// The `RANGE_SCALE` rows, then the ranges kernels, checked against `ranges::iter`
+pub fn ranges<'a>(
+    stage: &Stage<'a>,
+    cases: &Cases,
+    bufs: &mut Buffers,
+    out: &mut Vec<&'a str>,
+) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    if let Some(skipped) = stage.skip_capped(RANGE_SCALE.len() + cases.ranges.len()) {
+        return skipped;
+    }
+    let slices_thrpt = stage.measure("ranges", "slices", &mut || {
+        bench.pretouch.reset(out);
+        let input = stage.next_input();
+        let start = Instant::now();
+        slice::split(input, out);
+        let duration = start.elapsed();
+        black_box(&mut *out);
+        duration
+    });
+    let out_compressed = &mut bufs.compressed;
+    let index_thrpt = stage.measure("ranges", "LineIndex", &mut || {
+        bench.pretouch.reset(&mut out_compressed.lows);
+        out_compressed.high_starts.clear();
+        let input = stage.next_input();
+        let start = Instant::now();
+        compressed::build(input, out_compressed);
+        let duration = start.elapsed();
+        black_box(&mut *out_compressed);
+        duration
+    });
+    let mut thrpts = vec![slices_thrpt, index_thrpt];
+    for (label, thrpt) in RANGE_SCALE.iter().zip(&thrpts) {
+        progress!(
+            bench,
+            "{label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(*thrpt)
+        );
+    }
+    let reference = &mut bufs.test_ranges;
+    reference.clear();
+    ranges::iter(stage.input, reference);
+    out.clear();
+    ranges::to_slices(stage.input, reference, out);
+    if let Some(Err(err)) = stage.expected_slices.as_ref().map(|e| e.check(out)) {
+        panic!("(ranges) the reference failed during {stage_label}: {err}");
+    }
+    let out_ranges = &mut bufs.ranges;
+    for (fn_label, _, fnc) in &cases.ranges {
+        let thrpt = stage.measure("ranges", fn_label, &mut || {
+            bench.pretouch.reset(out_ranges);
+            let input = stage.next_input();
+            let start = Instant::now();
+            unsafe { fnc(input, out_ranges) };
+            let duration = start.elapsed();
+            black_box(&mut *out_ranges);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            out_ranges == reference,
+            "(ranges) {fn_label} failed during {stage_label}"
+        );
+    }
+    thrpts
+}
+
+/// The flat kernels, checked against the offsets of the stage's reference index
+pub fn flat(stage: &Stage, cases: &Cases, bufs: &mut Buffers) -> Vec<f64> {
+    let bench = stage.bench;
+    let mut thrpts = Vec::new();
+    let reference = &mut bufs.test_flat;
+    reference.clear();
+    flat::from_line_index(&bufs.test_compressed, reference);
+    let out = &mut bufs.flat;
+    for (fn_label, _, fnc) in &cases.flat {
+        let thrpt = stage.measure("flat u32", fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            unsafe { fnc(input, out) };
+            let duration = start.elapsed();
+            black_box(&mut *out);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            out == reference,
+            "(flat u32) {fn_label} failed during {}",
+            stage.label
+        );
+    }
+    thrpts
+}
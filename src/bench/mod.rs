@@ -0,0 +1,789 @@
+//! The default subcommand: every stage's input through every table's kernels, then the tables.
+//! [`run`] makes each stage's input and hands it, as a [`Stage`], to one function per table: the
+//! slice kernels and what's built on them in `slices`, other splits of the same input in
+//! `variants`, the index formats in `index`, and the scans that keep nothing in `scans`. The
+//! tables with inputs of their own are in `once`, and `tables` prints everything.
+
+#[cfg(feature = "grep")]
+use crate::GrepFn;
+use crate::{
+    affinity, alloc, cli, compressed, disasm, dispatch, guard, history, large_pages, measure_thrpt,
+    measure_thrpt_within, missing_features, pool, prep_vec_fill, prep_vec_mixed, prep_vec_range,
+    prep_vec_tsv, pretouch, segments, slice, verify, watchdog, ByteSplitFn, ClassifyFn,
+    CompressSplitFn, Features, FlatSplitFn, LineStatsFn, Measurement, ParagraphFn, PrepFn,
+    RangesSplitFn, Rng, Sampling, SliceSplitFn, SubstringFn, CLASSIFY_BENCH_CASES,
+    COMPRESSED_BENCH_CASES, CRLF_BENCH_CASES, DELIM_BENCH_CASES, DELIM_SET_BENCH_CASES,
+    FLAT_BENCH_CASES, HANG_FLOOR, HANG_LIMITS, INCLUSIVE_BENCH_CASES, LINE_STATS_BENCH_CASES,
+    NUL_BENCH_CASES, PARAGRAPH_BENCH_CASES, RANGES_BENCH_CASES, SLICE_BENCH_CASES,
+    SUBSTRING_BENCH_CASES, TIMEOUT_FLOOR,
+};
+use std::cell::{Cell, RefCell};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Per-stage output, which `--deterministic` leaves out so that only the tables are printed
+macro_rules! progress {
+    ($bench:expr, $($arg:tt)*) => {
+        if !$bench.deterministic {
+            println!($($arg)*);
+        }
+    };
+}
+
+mod index;
+mod once;
+mod scans;
+mod slices;
+mod tables;
+mod variants;
+
+// Stages are sized by line count rather than bytes, so dense and sparse stages put a similar load
+// on the output side.
+const BENCHMARK_STAGES: &[(&str, PrepFn)] = &[
+    ("single line", |vec, _, _| {
+        prep_vec_fill(vec, 1024 * 1024 * 1024, b'a')
+    }),
+    ("0-1", prep_vec_range::<0, 1>),
+    ("0-2", prep_vec_range::<0, 2>),
+    ("1-20", prep_vec_range::<1, 20>),
+    ("5-20", prep_vec_range::<5, 20>),
+    ("10-30", prep_vec_range::<10, 30>),
+    ("0-40", prep_vec_range::<0, 40>),
+    ("0-80", prep_vec_range::<0, 80>),
+    ("40-120", prep_vec_range::<40, 120>),
+    ("mixed", prep_vec_mixed),
+    // every line 40 bytes, for `stride::split`
+    ("fixed 40", prep_vec_range::<39, 39>),
+    // tab-separated fields, for the delimiter table
+    ("tsv", prep_vec_tsv),
+    ("0-0", |vec, lines, _| prep_vec_fill(vec, lines, b'\n')),
+];
+
+/// The options of a run, and what its measurements report besides their MB/s
+struct Bench {
+    deterministic: bool,
+    seed: u64,
+    lines: usize,
+    verify: verify::Verify,
+    max_stored_lines: Option<usize>,
+    pretouch: pretouch::Pretouch,
+    sampling: Sampling,
+    /// `--timeout`'s multiple of std's run time
+    timeout: Option<f64>,
+    watchdog: Option<watchdog::Watchdog>,
+    /// The slice kernel rerun during the slice table, see `--sentinel`
+    sentinel: String,
+    sentinel_every: usize,
+    threads: usize,
+    /// (table, algo, stage, measurement) for the results that stayed noisy
+    noisy: RefCell<Vec<(&'static str, &'static str, &'static str, Measurement)>>,
+    timed_out: RefCell<Vec<TimedOut>>,
+    /// (table, algo, relative stddev of the kept batch), for the stability table
+    spreads: RefCell<Vec<(&'static str, &'static str, f64)>>,
+}
+
+/// (table, algo, stage, the run that went over, its limit)
+type TimedOut = (&'static str, &'static str, &'static str, Duration, Duration);
+
+type Runnable<F> = Vec<&'static (&'static str, Features, F)>;
+
+/// The cases of each table that this CPU can run, in the order of the table's rows
+struct Cases {
+    slice: Runnable<SliceSplitFn>,
+    /// `None` for std, which isn't a `SliceSplitFn`
+    sentinel: Option<SliceSplitFn>,
+    compressed: Runnable<CompressSplitFn>,
+    flat: Runnable<FlatSplitFn>,
+    ranges: Runnable<RangesSplitFn>,
+    classify: Runnable<ClassifyFn>,
+    #[cfg(feature = "grep")]
+    grep: Runnable<GrepFn>,
+    line_stats: Runnable<LineStatsFn>,
+    paragraph: Runnable<ParagraphFn>,
+    prefixed: Runnable<SliceSplitFn>,
+    trimmed: Runnable<SliceSplitFn>,
+    crlf: Runnable<SliceSplitFn>,
+    delim: Runnable<SliceSplitFn>,
+    delim_set: Runnable<SliceSplitFn>,
+    substring: Runnable<SubstringFn>,
+    nul: Runnable<ByteSplitFn>,
+    inclusive: Runnable<SliceSplitFn>,
+    /// (table, algo, missing features), reported with the results
+    skipped: Vec<(&'static str, &'static str, Vec<&'static str>)>,
+}
+
+impl Cases {
+    fn new(sentinel: &str) -> Result<Self, String> {
+        let mut skipped = Vec::new();
+        let slice = runnable("slice", SLICE_BENCH_CASES, &mut skipped);
+        let sentinel = match sentinel {
+            "std" => None,
+            name => Some(
+                slice
+                    .iter()
+                    .find(|case| case.0 == name)
+                    .ok_or_else(|| format!("--sentinel: `{name}` isn't a runnable slice kernel"))?
+                    .2,
+            ),
+        };
+        Ok(Cases {
+            slice,
+            sentinel,
+            compressed: runnable("compressed", COMPRESSED_BENCH_CASES, &mut skipped),
+            flat: runnable("flat u32", FLAT_BENCH_CASES, &mut skipped),
+            ranges: runnable("ranges", RANGES_BENCH_CASES, &mut skipped),
+            classify: runnable("classify", CLASSIFY_BENCH_CASES, &mut skipped),
+            #[cfg(feature = "grep")]
+            grep: runnable("grep", scans::GREP_BENCH_CASES, &mut skipped),
+            line_stats: runnable("line stats", LINE_STATS_BENCH_CASES, &mut skipped),
+            paragraph: runnable("paragraphs", PARAGRAPH_BENCH_CASES, &mut skipped),
+            prefixed: runnable("prefixed", variants::PREFIXED_BENCH_CASES, &mut skipped),
+            trimmed: runnable("trimmed", variants::TRIMMED_BENCH_CASES, &mut skipped),
+            crlf: runnable("crlf", CRLF_BENCH_CASES, &mut skipped),
+            delim: runnable("delimiter", DELIM_BENCH_CASES, &mut skipped),
+            delim_set: runnable("delimiter set", DELIM_SET_BENCH_CASES, &mut skipped),
+            substring: runnable("substring", SUBSTRING_BENCH_CASES, &mut skipped),
+            nul: runnable("nul", NUL_BENCH_CASES, &mut skipped),
+            inclusive: runnable("inclusive", INCLUSIVE_BENCH_CASES, &mut skipped),
+            skipped,
+        })
+    }
+}
+
+/// The cases whose features this CPU has, adding the others to `skipped`
+fn runnable<F>(
+    table: &'static str,
+    cases: &'static [(&'static str, Features, F)],
+    skipped: &mut Vec<(&'static str, &'static str, Vec<&'static str>)>,
+) -> Runnable<F> {
+    cases
+        .iter()
+        .filter(|(algo_name, features, _)| {
+            let missing = missing_features(features);
+            let runnable = missing.is_empty();
+            if !runnable {
+                skipped.push((table, algo_name, missing));
+            }
+            runnable
+        })
+        .collect()
+}
+
+/// (table, algo, (cold, warm)) of a kernel's first calls
+type FirstCall = (&'static str, &'static str, (Duration, Duration));
+
+/// Every table's results, one `Vec` of MB/s per stage for the stage tables
+#[derive(Default)]
+struct Results {
+    first_calls: Vec<FirstCall>,
+    first_call_len: usize,
+    /// (table, algo, MB/s)
+    small_inputs: Vec<(&'static str, &'static str, f64)>,
+    small_input_len: usize,
+    substring: Vec<Vec<f64>>,
+    nul: Vec<Vec<f64>>,
+    /// std, then the kernels
+    slice: Vec<Vec<f64>>,
+    /// (stage, the sentinel's runs, the first being the reference)
+    drifts: Vec<(&'static str, Vec<f64>)>,
+    adaptive: Vec<Vec<f64>>,
+    pipeline: Vec<Vec<f64>>,
+    compressed: Vec<Vec<f64>>,
+    conversions: Vec<Vec<f64>>,
+    #[cfg(feature = "gpu")]
+    gpu_compute: Vec<f64>,
+    random_access: Vec<Vec<f64>>,
+    concurrent: Vec<Vec<f64>>,
+    span: Vec<Vec<f64>>,
+    #[cfg(feature = "roaring")]
+    newline_sets: Vec<Vec<f64>>,
+    line_stats: Vec<Vec<f64>>,
+    /// (dispatch, detect, known width)
+    fixed_stride: Vec<Vec<f64>>,
+    lazy: Vec<Vec<f64>>,
+    paragraphs: Vec<Vec<f64>>,
+    trimmed: Vec<Vec<f64>>,
+    crlf: Vec<Vec<f64>>,
+    delim: Vec<Vec<f64>>,
+    delim_set: Vec<Vec<f64>>,
+    inclusive: Vec<Vec<f64>>,
+    prefixed: Vec<Vec<f64>>,
+    /// (concat, view)
+    parallel: Vec<Vec<f64>>,
+    /// the `RANGE_SCALE` rows, then the kernels
+    ranges: Vec<Vec<f64>>,
+    arena: Vec<Vec<f64>>,
+    flat: Vec<Vec<f64>>,
+    classify: Vec<Vec<f64>>,
+    #[cfg(feature = "grep")]
+    grep: Vec<Vec<f64>>,
+    /// (memcpy, memchr)
+    ceilings: Vec<(f64, f64)>,
+}
+
+/// One stage's input, and what its tables share about it
+struct Stage<'a> {
+    bench: &'a Bench,
+    label: &'static str,
+    len: usize,
+    /// Known from how the input was made, see [`Prepared`]
+    newlines: usize,
+    expected_lines: usize,
+    /// The first copy of the input, which the references run on
+    input: &'a str,
+    /// Every copy of it, see `--input-copies`
+    inputs: Vec<&'a str>,
+    run_count: Cell<usize>,
+    segments: Vec<std::ops::Range<usize>>,
+    /// Only the last segment's lines are ever stored, so only the counts can be checked
+    capped: bool,
+    expected_slices: Option<verify::Expected<'a>>,
+    /// (memcpy, memchr)
+    ceilings: (f64, f64),
+    /// Set once std is measured, std itself has no limit
+    std_run: Cell<Option<Duration>>,
+}
+
+impl<'a> Stage<'a> {
+    /// The copy of the input for the next run, so runs don't find more of it in cache than the
+    /// runs before them did
+    fn next_input(&self) -> &'a str {
+        self.run_count.set(self.run_count.get() + 1);
+        self.inputs[self.run_count.get() % self.inputs.len()]
+    }
+
+    /// The MB/s of `run`, which times one run of `algo_name`, or NaN if it went over its
+    /// `--timeout`. Noisy and timed out results are recorded for their tables.
+    fn measure(
+        &self,
+        table: &'static str,
+        algo_name: &'static str,
+        run: &mut dyn FnMut() -> Duration,
+    ) -> f64 {
+        let bench = self.bench;
+        let limit = bench
+            .timeout
+            .zip(self.std_run.get())
+            .map(|(k, std_run): (f64, Duration)| std_run.mul_f64(k).max(TIMEOUT_FLOOR));
+        let measurement = match (limit, &bench.watchdog) {
+            (Some(limit), Some(watchdog)) => {
+                let mut watched = || {
+                    watchdog.arm(
+                        (limit * HANG_LIMITS).max(HANG_FLOOR),
+                        (table, algo_name, self.label),
+                    );
+                    let duration = run();
+                    watchdog.disarm();
+                    duration
+                };
+                measure_thrpt_within(bench.sampling, self.len, Some(limit), &mut watched)
+            }
+            _ => Ok(measure_thrpt(bench.sampling, self.len, run)),
+        };
+        let measurement = match measurement {
+            Ok(measurement) => measurement,
+            Err(took) => {
+                progress!(
+                    bench,
+                    "{algo_name:<13}: timed out, {took:.1?} against a limit of {:.1?}",
+                    limit.unwrap()
+                );
+                bench.timed_out.borrow_mut().push((
+                    table,
+                    algo_name,
+                    self.label,
+                    took,
+                    limit.unwrap(),
+                ));
+                return f64::NAN;
+            }
+        };
+        let thrpt = measurement.thrpt;
+        bench
+            .spreads
+            .borrow_mut()
+            .push((table, algo_name, measurement.rel_stddev));
+        if measurement.noisy {
+            progress!(
+                bench,
+                "{algo_name:<13}: noisy, {:.1}% stddev after {} reruns",
+                100. * measurement.rel_stddev,
+                measurement.reruns
+            );
+            bench
+                .noisy
+                .borrow_mut()
+                .push((table, algo_name, self.label, measurement));
+        }
+        thrpt
+    }
+
+    fn efficiency(&self, thrpt: f64) -> String {
+        let (memcpy_thrpt, memchr_thrpt) = self.ceilings;
+        format!(
+            "{:>4.0}% of memcpy, {:>4.0}% of memchr",
+            100. * thrpt / memcpy_thrpt,
+            100. * thrpt / memchr_thrpt
+        )
+    }
+
+    /// A row of NaNs for a table with `rows` rows that needs every line, if the stage is capped
+    fn skip_capped(&self, rows: usize) -> Option<Vec<f64>> {
+        if !self.capped {
+            return None;
+        }
+        progress!(self.bench, "skipped, over --max-stored-lines");
+        Some(vec![f64::NAN; rows])
+    }
+
+    /// The reference's `lines`, unless the stage is capped and they aren't all of them
+    fn expected(&self, lines: Vec<&'a str>) -> Option<verify::Expected<'a>> {
+        (!self.capped).then(|| verify::Expected::new(self.bench.verify, self.input, lines))
+    }
+}
+
+/// The outputs that aren't slices, reused by every stage
+struct Buffers {
+    compressed: compressed::LineIndex,
+    /// The reference's index of the current stage, which the index tables read
+    test_compressed: compressed::LineIndex,
+    flat: Vec<u32>,
+    test_flat: Vec<u32>,
+    ranges: Vec<std::ops::Range<u32>>,
+    test_ranges: Vec<std::ops::Range<u32>>,
+}
+
+/// `[--lines N] [--input-copies N] [--seed N] [--deterministic] [--history FILE]
+/// [--pretouch none|write|madvise] [--prefer KERNEL,...] [--sparse KERNEL] [--guard-pages]
+/// [--large-pages] [--sentinel KERNEL] [--sentinel-every N] [--emit-asm TABLE/KERNEL] [--timeout K]`
+///
+/// `--lines` is the number of lines in each stage (default 10 M), except for the single line one.
+///
+/// `--input-copies` rotates the timed runs between N copies of each stage's input, so later runs
+/// don't find more of it in cache (or the TLB) than earlier ones did.
+///
+/// `--deterministic` is for CI jobs comparing runs against each other: the input seed is fixed,
+/// the process is pinned to one core, every kernel runs a fixed number of times (reporting the
+/// median), and the only output is the tables, normalized to a baseline kernel per stage.
+///
+/// `--pretouch` picks how the output buffers get their pages, see `pretouch`. The default,
+/// `write`, fills them once up front, like a warmed up service would have them.
+///
+/// `--prefer` puts slice kernels in front of the CPU family's default order in the dispatcher
+/// behind `slice::split`, which is benchmarked as `dispatch`. `--sparse` picks the kernel it runs
+/// on long lines in the adaptive dispatch table.
+///
+/// `--history` appends the results to a JSON Lines file, see `history` and the `history`
+/// subcommand.
+///
+/// `--verify` picks how kernels' lines are checked against the reference's, see `verify`. The
+/// default, `hash`, compares fingerprints made on every core.
+///
+/// `--sentinel` names the slice kernel (`std` by default) that is timed again during each stage's
+/// slice kernels, after every `--sentinel-every` of them (default 0, only after the last), and
+/// compared to its first run. The "Drift" table flags the stages where it moved by more than
+/// `--noise-threshold`: the machine got faster or slower while they ran, so their rows aren't
+/// comparable with each other.
+///
+/// `--guard-pages` moves each stage's input (every copy of it) to the end of its own mapping,
+/// right before an unmapped page, so a kernel reading past the input crashes the bench instead of
+/// passing, see `guard`. The tests always run the kernels that way.
+///
+/// `--large-pages` moves each stage's input (every copy of it) to large pages, see `large_pages`.
+/// Where they can't be had (no `SeLockMemoryPrivilege` on Windows, no transparent huge pages on
+/// Linux), the bench says so once and keeps the inputs in normal pages.
+///
+/// `--timeout` gives up on a case whose run takes more than K times as long as `std`'s in the
+/// same stage (a misdetected feature, a debug build): the case is recorded as timed out, with no
+/// result, and the bench moves on to the next one. A run that doesn't return at all ends the
+/// bench, see `watchdog`.
+///
+/// `--emit-asm` prints the disassembly of a slice, compressed or flat kernel (`slice/avx2`,
+/// `compressed/avx2 lut`, `flat/avx2`) after the tables, under its row, see `disasm`. Loops are
+/// marked, so the hot one is easy to find.
+///
+/// `--max-stored-lines` caps the slices kept at once (16 bytes each) for stages with more lines:
+/// the slice, trimmed and prefixed kernels get the input in segments of that many lines, still
+/// scanning all of it, and are only checked by line count. The tables that need every line at once
+/// skip those stages.
+pub fn run(mut args: cli::Args) -> cli::Result {
+    let input_copies = args.parsed("--input-copies")?.unwrap_or(1usize).max(1);
+    // Slices take 16 bytes per line, so a billion is too many
+    let lines = args.parsed("--lines")?.unwrap_or(10_000_000);
+    let deterministic = args.flag("--deterministic");
+    let seed = match args.parsed("--seed")? {
+        Some(seed) => seed,
+        None if deterministic => 0,
+        None => Rng::seed_from_time(),
+    };
+    let history_path = args.value("--history")?;
+    let verify = args.parsed("--verify")?.unwrap_or(verify::Verify::Hash);
+    let max_stored_lines = args
+        .parsed::<usize>("--max-stored-lines")?
+        .map(|lines| lines.max(1));
+    let pretouch = args
+        .parsed("--pretouch")?
+        .unwrap_or(pretouch::Pretouch::Write);
+    let guard_pages = args.flag("--guard-pages");
+    let mut large_pages = args.flag("--large-pages");
+    if guard_pages && large_pages {
+        return Err("--guard-pages and --large-pages both move the inputs, pick one".into());
+    }
+    let sentinel = args
+        .value("--sentinel")?
+        .unwrap_or_else(|| "std".to_owned());
+    let sentinel_every = args.parsed::<usize>("--sentinel-every")?.unwrap_or(0);
+    let emit_asm = args.value("--emit-asm")?;
+    let timeout = args.parsed::<f64>("--timeout")?;
+    let sampling = Sampling {
+        iterations: args
+            .parsed("--iterations")?
+            .unwrap_or(if deterministic { 5 } else { 1 })
+            .max(1),
+        noise_threshold: args.parsed("--noise-threshold")?.unwrap_or(5.) / 100.,
+        max_reruns: args.parsed("--reruns")?.unwrap_or(3),
+    };
+    let mut dispatcher = dispatch::Dispatcher::builder();
+    if let Some(prefer) = args.value("--prefer")? {
+        for kernel in prefer.split(',') {
+            dispatcher =
+                dispatcher.prefer(kernel.parse().map_err(|err| format!("--prefer: {err}"))?);
+        }
+    }
+    if let Some(sparse) = args.value("--sparse")? {
+        dispatcher = dispatcher.sparse(sparse.parse().map_err(|err| format!("--sparse: {err}"))?);
+    }
+    args.finish()?;
+    let bench = Bench {
+        deterministic,
+        seed,
+        lines,
+        verify,
+        max_stored_lines,
+        pretouch,
+        sampling,
+        timeout,
+        watchdog: timeout.map(|_| watchdog::Watchdog::spawn()),
+        sentinel,
+        sentinel_every,
+        threads: std::thread::available_parallelism()?.get(),
+        noisy: RefCell::default(),
+        timed_out: RefCell::default(),
+        spreads: RefCell::default(),
+    };
+    dispatcher
+        .build()
+        .install()
+        .map_err(|_| "a slice kernel was dispatched before --prefer took effect")?;
+    let run = history::Run::new(seed, lines, deterministic, pretouch.name());
+    if deterministic {
+        affinity::pin_to_current_core().map_err(|err| format!("pinning to a core: {err}"))?;
+    }
+    progress!(bench, "input seed {seed}");
+    progress!(bench, "dispatch: {}", dispatch::global());
+    progress!(bench, "allocator: {}", alloc::NAME);
+    let mut rng = Rng::new(seed);
+
+    let cases = Cases::new(&bench.sentinel)?;
+    // disassembled up front so a missing objdump doesn't wait for the whole run
+    let emit_asm = emit_asm
+        .map(|kernel| disassemble(&kernel, &cases))
+        .transpose()?;
+    for (table, algo_name, missing) in &cases.skipped {
+        progress!(
+            bench,
+            "skipping {table} {algo_name}, missing {}",
+            missing.join(", ")
+        );
+    }
+
+    let mut results = Results::default();
+    progress!(bench, "\n\t\tfirst call");
+    (results.first_call_len, results.first_calls) = once::first_call(&bench, &cases);
+    progress!(bench, "\n\t\tsmall inputs");
+    (results.small_input_len, results.small_inputs) = once::small_inputs(&bench, &cases);
+    results.substring = once::substring(&bench, &cases);
+    results.nul = once::nul(&bench, &cases);
+
+    let mut b = Vec::new();
+    let mut memcpy_dst = black_box(vec![0u8; 64 * 1024 * 1024]);
+    // the first copy is `b` itself
+    let mut extra_copies = vec![Vec::new(); input_copies - 1];
+
+    // by default, pre-fill the vecs (beyond just reserving) so that the first fn doesn't pay for
+    // all the page misses (some OSs may give CoW zero pages for `Vec::with_capacity(...)` )
+    let mut line_pool = pool::LinePool::default();
+    line_pool.put(black_box(pretouch.alloc(64 * 1024 * 1024)?));
+    let mut bufs = Buffers {
+        compressed: compressed::LineIndex {
+            lows: pretouch.alloc(64 * 1024 * 1024)?,
+            high_starts: Vec::with_capacity(16),
+        },
+        test_compressed: compressed::LineIndex {
+            lows: Vec::new(),
+            high_starts: Vec::new(),
+        },
+        flat: pretouch.alloc(64 * 1024 * 1024)?,
+        test_flat: Vec::new(),
+        ranges: pretouch.alloc(64 * 1024 * 1024)?,
+        test_ranges: Vec::new(),
+    };
+
+    for (stage_label, prep_fn) in BENCHMARK_STAGES {
+        progress!(bench, "\n\t\t{stage_label}");
+
+        let prep_start = Instant::now();
+        let prepared = prep_fn(&mut b, lines, &mut rng);
+        let len = prepared.len;
+        let input = std::str::from_utf8(&b[..len]).unwrap();
+        let segments = segments(input, max_stored_lines);
+        let capped = segments.len() > 1;
+        let expected_slices = (!capped).then(|| {
+            let mut lines = Vec::new();
+            slice::std_reuse(input, &mut lines);
+            verify::Expected::new(verify, input, lines)
+        });
+        progress!(
+            bench,
+            "{:.0} MB, generated in {:.0} ms",
+            len as f64 / 1_000_000.,
+            prep_start.elapsed().as_secs_f64() * 1000.
+        );
+        if capped {
+            progress!(
+                bench,
+                "{} segments of up to {} lines",
+                segments.len(),
+                max_stored_lines.unwrap()
+            );
+        }
+        for copy in &mut extra_copies {
+            copy.clear();
+            copy.extend_from_slice(input.as_bytes());
+        }
+        // the copies are of `input`, so still UTF-8
+        let inputs: Vec<&str> = std::iter::once(input)
+            .chain(
+                extra_copies
+                    .iter()
+                    .map(|copy| unsafe { std::str::from_utf8_unchecked(copy) }),
+            )
+            .collect();
+        let guarded = if guard_pages {
+            inputs
+                .iter()
+                .map(|input| guard::GuardedInput::new(input))
+                .collect::<std::io::Result<Vec<_>>>()
+                .map_err(|err| format!("--guard-pages: {err}"))?
+        } else {
+            Vec::new()
+        };
+        let large = if large_pages {
+            let large = inputs
+                .iter()
+                .map(|input| large_pages::LargePageInput::new(input))
+                .collect::<std::io::Result<Vec<_>>>();
+            large.unwrap_or_else(|err| {
+                eprintln!("--large-pages: {err}, using normal pages");
+                large_pages = false;
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        let inputs: Vec<&str> = if guard_pages {
+            guarded.iter().map(guard::GuardedInput::as_str).collect()
+        } else if large_pages {
+            large
+                .iter()
+                .map(large_pages::LargePageInput::as_str)
+                .collect()
+        } else {
+            inputs
+        };
+        let mut stage = Stage {
+            bench: &bench,
+            label: stage_label,
+            len,
+            newlines: prepared.newlines,
+            expected_lines: prepared.lines(input),
+            input: inputs[0],
+            inputs,
+            run_count: Cell::new(0),
+            segments,
+            capped,
+            expected_slices,
+            ceilings: (f64::NAN, f64::NAN),
+            std_run: Cell::new(None),
+        };
+        let mut out = line_pool.take();
+
+        progress!(bench, "\tceilings");
+        stage.ceilings = scans::ceilings(&stage, &mut memcpy_dst);
+        results.ceilings.push(stage.ceilings);
+
+        progress!(bench, "\tslices");
+        let (slice_thrpts, drift) = slices::slice(&stage, &cases, &mut out)?;
+        results.drifts.push((stage_label, drift));
+        progress!(bench, "\tadaptive dispatch");
+        results.adaptive.push(slices::adaptive(&stage, &mut out));
+        progress!(bench, "\tslices, with a consumer");
+        results.pipeline.push(slices::pipeline(&stage, &cases));
+
+        progress!(bench, "\tcompressed");
+        results
+            .compressed
+            .push(index::compressed(&stage, &cases, &mut bufs));
+        progress!(bench, "\tLineIndex conversions");
+        let reference = &bufs.test_compressed;
+        results
+            .conversions
+            .push(index::conversions(&stage, reference, &mut out));
+        #[cfg(feature = "gpu")]
+        if let Some(thrpt) = index::gpu_compute(&stage) {
+            results.gpu_compute.push(thrpt);
+        }
+        progress!(bench, "\trandom line access");
+        results
+            .random_access
+            .push(index::random_access(&stage, reference));
+        progress!(bench, "\tconcurrent lookups");
+        results
+            .concurrent
+            .push(index::concurrent(&stage, reference));
+        progress!(bench, "\tline around an offset");
+        results.span.push(index::span(&stage, reference));
+        #[cfg(feature = "roaring")]
+        {
+            progress!(bench, "\tnewline sets");
+            results
+                .newline_sets
+                .push(index::newline_sets(&stage, reference));
+        }
+
+        progress!(bench, "\tline length stats");
+        results
+            .line_stats
+            .push(slices::line_stats(&stage, &cases, &mut out));
+        progress!(bench, "\tfixed stride");
+        results.fixed_stride.push(slices::fixed_stride(
+            &stage,
+            &cases,
+            &slice_thrpts,
+            &mut out,
+        ));
+        results.slice.push(slice_thrpts);
+        progress!(bench, "\tlazy lines");
+        results.lazy.push(slices::lazy(&stage, &mut out));
+
+        progress!(bench, "\tparagraphs");
+        results
+            .paragraphs
+            .push(variants::paragraphs(&stage, &cases, &mut out));
+        progress!(bench, "\ttrimmed lines");
+        results.trimmed.push(variants::filtered(
+            &stage,
+            "trimmed",
+            crate::trimmed::scalar,
+            &cases.trimmed,
+            &mut out,
+        ));
+        progress!(bench, "\tCRLF lines");
+        results.crlf.push(variants::checked(
+            &stage,
+            "crlf",
+            CRLF_BENCH_CASES[0].2,
+            &cases.crlf,
+            &mut out,
+        ));
+        progress!(bench, "\ttab-delimited fields");
+        results.delim.push(variants::checked(
+            &stage,
+            "delimiter",
+            DELIM_BENCH_CASES[0].2,
+            &cases.delim,
+            &mut out,
+        ));
+        progress!(bench, "\tdelimiter set");
+        results.delim_set.push(variants::checked(
+            &stage,
+            "delimiter set",
+            DELIM_SET_BENCH_CASES[0].2,
+            &cases.delim_set,
+            &mut out,
+        ));
+        progress!(bench, "\tinclusive lines");
+        results.inclusive.push(variants::checked(
+            &stage,
+            "inclusive",
+            INCLUSIVE_BENCH_CASES[0].2,
+            &cases.inclusive,
+            &mut out,
+        ));
+        progress!(bench, "\tlines starting with {:?}", crate::BENCH_PREFIX);
+        results.prefixed.push(variants::filtered(
+            &stage,
+            "prefixed",
+            |input, out| crate::prefixed::scalar(input, crate::BENCH_PREFIX, out),
+            &cases.prefixed,
+            &mut out,
+        ));
+
+        progress!(bench, "\tparallel slices, threads: {}", bench.threads);
+        results.parallel.push(slices::parallel(&stage, &mut out));
+        progress!(bench, "\tline ranges");
+        results
+            .ranges
+            .push(index::ranges(&stage, &cases, &mut bufs, &mut out));
+        progress!(bench, "\tarena output");
+        results.arena.push(slices::arena(&stage, &mut out));
+
+        line_pool.put(out);
+
+        progress!(bench, "\tflat u32");
+        results.flat.push(index::flat(&stage, &cases, &mut bufs));
+        progress!(bench, "\tdelimiter classes");
+        results.classify.push(scans::classify(&stage, &cases));
+        #[cfg(feature = "grep")]
+        {
+            progress!(bench, "\tgrep");
+            results.grep.push(scans::grep(&stage, &cases));
+        }
+    }
+
+    tables::print(&bench, &cases, &results, emit_asm.as_ref());
+    if let Some(path) = history_path {
+        history::append(&path, &tables::history_records(&run, &cases, &results))?;
+    }
+    Ok(())
+}
+
+/// (table, index in its cases, the kernel's code) of an `--emit-asm` kernel
+fn disassemble(
+    kernel: &str,
+    cases: &Cases,
+) -> Result<(&'static str, usize, disasm::Function), String> {
+    let not_found = || format!("--emit-asm: `{kernel}` isn't a runnable kernel");
+    let (table, name) = kernel.split_once('/').ok_or_else(not_found)?;
+    let (table, idx, addr) = match table {
+        "slice" => cases
+            .slice
+            .iter()
+            .position(|case| case.0 == name)
+            .map(|idx| ("slice", idx, cases.slice[idx].2 as usize)),
+        "compressed" => cases
+            .compressed
+            .iter()
+            .position(|case| case.0 == name)
+            .map(|idx| ("compressed", idx, cases.compressed[idx].2 as usize)),
+        "flat" => cases
+            .flat
+            .iter()
+            .position(|case| case.0 == name)
+            .map(|idx| ("flat", idx, cases.flat[idx].2 as usize)),
+        _ => None,
+    }
+    .ok_or_else(not_found)?;
+    let function = disasm::disassemble(addr).map_err(|err| format!("--emit-asm: {err}"))?;
+    Ok((table, idx, function))
+}
@@ -0,0 +1,224 @@
+//! The tables with inputs of their own, run once before the stages: first calls, small inputs,
+//! and the substring and NUL stages
+
+use super::{Bench, Cases, FirstCall};
+use crate::{
+    compressed, first_call_times, measure_thrpt, prep_record_stage, prep_small_inputs,
+    prep_vec_range, slice, Rng, FIRST_CALL_LINES, FIRST_CALL_WARM_RUNS, NUL_BENCH_CASES,
+    NUL_STAGES, SMALL_INPUTS, SUBSTRING_BENCH_CASES, SUBSTRING_STAGES,
+};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// An application that splits once per request sees each kernel's first call, not the steady
+/// state: the output Vec starts empty (allocation and page faults), and the code and its
+/// constants aren't in cache yet. So before anything else runs, each kernel is called once with
+/// a fresh output on a request-sized input, then a few more times reusing the output. Returns the
+/// input's length and each kernel's times.
+pub fn first_call(bench: &Bench, cases: &Cases) -> (usize, Vec<FirstCall>) {
+    let mut first_call_input = Vec::new();
+    let first_call_len = prep_vec_range::<0, 80>(
+        &mut first_call_input,
+        FIRST_CALL_LINES,
+        &mut Rng::new(bench.seed),
+    )
+    .len;
+    let first_call_input = std::str::from_utf8(&first_call_input[..first_call_len]).unwrap();
+    progress!(bench, "{:.0} KB", first_call_len as f64 / 1000.);
+    let mut first_calls = Vec::new();
+    first_calls.push((
+        "slice",
+        "std",
+        first_call_times(FIRST_CALL_WARM_RUNS, || {
+            let start = Instant::now();
+            black_box(slice::std(first_call_input));
+            start.elapsed()
+        }),
+    ));
+    for (fn_label, _, fnc) in &cases.slice {
+        let mut out = Vec::new();
+        let times = first_call_times(FIRST_CALL_WARM_RUNS, || {
+            out.clear();
+            let start = Instant::now();
+            fnc(first_call_input, &mut out);
+            let duration = start.elapsed();
+            black_box(&mut out);
+            duration
+        });
+        first_calls.push(("slice", *fn_label, times));
+    }
+    for (fn_label, _, fnc) in &cases.compressed {
+        let mut out = compressed::LineIndex::default();
+        let times = first_call_times(FIRST_CALL_WARM_RUNS, || {
+            out.lows.clear();
+            out.high_starts.clear();
+            let start = Instant::now();
+            unsafe { fnc(first_call_input, &mut out) };
+            let duration = start.elapsed();
+            black_box(&mut out);
+            duration
+        });
+        first_calls.push(("compressed", *fn_label, times));
+    }
+    for (fn_label, _, fnc) in &cases.flat {
+        let mut out = Vec::new();
+        let times = first_call_times(FIRST_CALL_WARM_RUNS, || {
+            out.clear();
+            let start = Instant::now();
+            unsafe { fnc(first_call_input, &mut out) };
+            let duration = start.elapsed();
+            black_box(&mut out);
+            duration
+        });
+        first_calls.push(("flat u32", *fn_label, times));
+    }
+    for (table, algo_name, (cold, warm)) in &first_calls {
+        progress!(
+            bench,
+            "{:<26}: cold {:>8.1} us, warm {:>8.1} us",
+            format!("{table} {algo_name}"),
+            cold.as_secs_f64() * 1e6,
+            warm.as_secs_f64() * 1e6,
+        );
+    }
+    (first_call_len, first_calls)
+}
+
+/// Splitting many short messages one at a time: no input has a whole 64-byte chunk, so each call
+/// is the kernel's prologue and its tail, which is what this table isolates. Returns the inputs'
+/// total length and (table, algo, MB/s) per kernel.
+pub fn small_inputs(
+    bench: &Bench,
+    cases: &Cases,
+) -> (usize, Vec<(&'static str, &'static str, f64)>) {
+    let mut small_input = Vec::new();
+    let small_pieces = prep_small_inputs(&mut small_input, SMALL_INPUTS, &mut Rng::new(bench.seed));
+    let small_input = std::str::from_utf8(&small_input).unwrap();
+    // `run` gets the range of each input in turn
+    let time_small = |run: &mut dyn FnMut(std::ops::Range<usize>)| {
+        measure_thrpt(bench.sampling, small_input.len(), &mut || {
+            let start = Instant::now();
+            for piece in &small_pieces {
+                run(piece.clone());
+            }
+            start.elapsed()
+        })
+        .thrpt
+    };
+    let mut small_inputs = Vec::new();
+    for (fn_label, _, fnc) in &cases.slice {
+        let mut out = Vec::new();
+        let thrpt = time_small(&mut |piece| {
+            out.clear();
+            fnc(&small_input[piece], &mut out);
+            black_box(&mut out);
+        });
+        small_inputs.push(("slice", *fn_label, thrpt));
+    }
+    for (fn_label, _, fnc) in &cases.compressed {
+        let mut out = compressed::LineIndex::default();
+        let thrpt = time_small(&mut |piece| {
+            out.lows.clear();
+            out.high_starts.clear();
+            unsafe { fnc(&small_input[piece], &mut out) };
+            black_box(&mut out);
+        });
+        small_inputs.push(("compressed", *fn_label, thrpt));
+    }
+    for (fn_label, _, fnc) in &cases.flat {
+        let mut out = Vec::new();
+        let thrpt = time_small(&mut |piece| {
+            out.clear();
+            unsafe { fnc(&small_input[piece], &mut out) };
+            black_box(&mut out);
+        });
+        small_inputs.push(("flat u32", *fn_label, thrpt));
+    }
+    for (table, algo_name, thrpt) in &small_inputs {
+        progress!(
+            bench,
+            "{:<26}: {thrpt:>8.0}",
+            format!("{table} {algo_name}")
+        );
+    }
+    (small_input.len(), small_inputs)
+}
+
+/// Each substring delimiter needs input that has it, so this table has its own stages. One `Vec`
+/// of MB/s per stage, like the stage tables.
+pub fn substring(bench: &Bench, cases: &Cases) -> Vec<Vec<f64>> {
+    let mut substring_thrpts = Vec::new();
+    let mut substring_input = Vec::new();
+    for (stage_label, delim, record) in SUBSTRING_STAGES {
+        prep_record_stage(
+            &mut substring_input,
+            bench.lines,
+            &mut Rng::new(bench.seed),
+            *record,
+        );
+        let input = std::str::from_utf8(&substring_input).unwrap();
+        progress!(bench, "\n\t\t{stage_label}, split on {delim:?}");
+        let mut expected = Vec::new();
+        (SUBSTRING_BENCH_CASES[0].2)(input, delim, &mut expected);
+        let mut out = Vec::new();
+        let mut cur_substring_thrpts = Vec::new();
+        for (fn_label, _, fnc) in &cases.substring {
+            let thrpt = measure_thrpt(bench.sampling, input.len(), &mut || {
+                out.clear();
+                let start = Instant::now();
+                fnc(input, delim, &mut out);
+                let duration = start.elapsed();
+                black_box(&mut out);
+                duration
+            })
+            .thrpt;
+            progress!(bench, "{fn_label:<13}: {thrpt:>8.0}");
+            cur_substring_thrpts.push(thrpt);
+            assert!(
+                out == expected,
+                "(substring) {fn_label} failed during {stage_label}"
+            );
+        }
+        substring_thrpts.push(cur_substring_thrpts);
+    }
+    substring_thrpts
+}
+
+/// The same for NUL records, which the stages don't have
+pub fn nul(bench: &Bench, cases: &Cases) -> Vec<Vec<f64>> {
+    let mut nul_thrpts = Vec::new();
+    let mut nul_input = Vec::new();
+    for (stage_label, record) in NUL_STAGES {
+        prep_record_stage(
+            &mut nul_input,
+            bench.lines,
+            &mut Rng::new(bench.seed),
+            *record,
+        );
+        let input = &nul_input[..];
+        progress!(bench, "\n\t\t{stage_label}, split on '\\0'");
+        let mut expected = Vec::new();
+        (NUL_BENCH_CASES[0].2)(input, &mut expected);
+        let mut out = Vec::new();
+        let mut cur_nul_thrpts = Vec::new();
+        for (fn_label, _, fnc) in &cases.nul {
+            let thrpt = measure_thrpt(bench.sampling, input.len(), &mut || {
+                out.clear();
+                let start = Instant::now();
+                fnc(input, &mut out);
+                let duration = start.elapsed();
+                black_box(&mut out);
+                duration
+            })
+            .thrpt;
+            progress!(bench, "{fn_label:<13}: {thrpt:>8.0}");
+            cur_nul_thrpts.push(thrpt);
+            assert!(
+                out == expected,
+                "(nul) {fn_label} failed during {stage_label}"
+            );
+        }
+        nul_thrpts.push(cur_nul_thrpts);
+    }
+    nul_thrpts
+}
@@ -0,0 +1,123 @@
+//! The scans that keep no lines: the memcpy and memchr ceilings, delimiter classes and grep
+
+use super::{Cases, Stage};
+use crate::roofline;
+#[cfg(feature = "grep")]
+use crate::{grep, Features, GrepFn, GREP_NEEDLE};
+use std::hint::black_box;
+use std::time::Instant;
+
+#[cfg(feature = "grep")]
+pub const GREP_BENCH_CASES: &[(&str, Features, GrepFn)] = &[
+    ("grep-searcher", &[], |matcher, input| {
+        let mut counts = (0, 0);
+        grep_searcher::Searcher::new().search_slice(
+            matcher,
+            input.as_bytes(),
+            grep_searcher::sinks::UTF8(|_, line| {
+                counts.0 += 1;
+                counts.1 += line.len();
+                Ok(true)
+            }),
+        )?;
+        Ok(counts)
+    }),
+    ("kernel lines", &[], |matcher, input| {
+        let mut counts = (0, 0);
+        grep::search_slice(
+            matcher,
+            input,
+            grep_searcher::sinks::UTF8(|_, line| {
+                counts.0 += 1;
+                counts.1 += line.len();
+                Ok(true)
+            }),
+        )?;
+        Ok(counts)
+    }),
+];
+
+/// The MB/s of copying the input and of scanning it for a byte it doesn't have, (memcpy, memchr)
+pub fn ceilings(stage: &Stage, memcpy_dst: &mut [u8]) -> (f64, f64) {
+    let bench = stage.bench;
+    let len = stage.len;
+    // best of 2, so a cold first pass doesn't lower the ceiling
+    let memcpy_thrpt = (0..2)
+        .map(|_| {
+            let input = stage.next_input();
+            let start = Instant::now();
+            roofline::memcpy(input.as_bytes(), memcpy_dst);
+            let duration = start.elapsed().as_secs_f64();
+            black_box(&mut *memcpy_dst);
+            len as f64 / duration / 1_000_000.
+        })
+        .fold(0., f64::max);
+    // the inputs never contain NUL, so this scans everything
+    let memchr_thrpt = (0..2)
+        .map(|_| {
+            let input = stage.next_input();
+            let start = Instant::now();
+            black_box(roofline::memchr(0, black_box(input.as_bytes())));
+            let duration = start.elapsed().as_secs_f64();
+            len as f64 / duration / 1_000_000.
+        })
+        .fold(0., f64::max);
+    progress!(bench, "{:<13}: {memcpy_thrpt:>8.0}", "memcpy");
+    progress!(bench, "{:<13}: {memchr_thrpt:>8.0}", "memchr");
+    (memcpy_thrpt, memchr_thrpt)
+}
+
+pub fn classify(stage: &Stage, cases: &Cases) -> Vec<f64> {
+    let bench = stage.bench;
+    let mut thrpts = Vec::new();
+    let mut expected_counts = None;
+    for (fn_label, _, fnc) in &cases.classify {
+        let mut counts = [0; 4];
+        let thrpt = stage.measure("classify", fn_label, &mut || {
+            let input = stage.next_input();
+            let start = Instant::now();
+            counts = black_box(unsafe { fnc(input.as_bytes()) });
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            *expected_counts.get_or_insert(counts) == counts,
+            "(classify) {fn_label} failed during {}",
+            stage.label
+        );
+    }
+    thrpts
+}
+
+#[cfg(feature = "grep")]
+pub fn grep(stage: &Stage, cases: &Cases) -> Vec<f64> {
+    let bench = stage.bench;
+    let mut thrpts = Vec::new();
+    let mut expected_counts = None;
+    for (fn_label, _, fnc) in &cases.grep {
+        let mut counts = (0, 0);
+        let thrpt = stage.measure("grep", fn_label, &mut || {
+            let input = stage.next_input();
+            let start = Instant::now();
+            counts = black_box(fnc(&grep::Literal(GREP_NEEDLE), input).unwrap());
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            *expected_counts.get_or_insert(counts) == counts,
+            "(grep) {fn_label} failed during {}",
+            stage.label
+        );
+    }
+    thrpts
+}
@@ -0,0 +1,544 @@
+//! The slice kernels, and the tables of what's built on them: dispatch, a consumer, line stats,
+//! lazy and parallel splitting, and where the slices go
+
+use super::{Cases, Stage};
+use crate::{
+    arena, density, lazy, line_stats, pipeline, sampled_lines, sharded, simd_lines, slice,
+    split_segments, stride, LazyFn, ADAPTIVE_CASES, ARENA_CASES,
+};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// every pair is the same consumer over eagerly and lazily made slices, and the rest read every line
+// as it's found, from a `SimdLines` (either end) or a `for_each_line` callback
+pub const LAZY_BENCH_CASES: &[(&str, LazyFn)] = &[
+    ("split, all", |input, lines| {
+        slice::split(input, lines);
+        lines.iter().map(|line| line.len()).sum()
+    }),
+    ("lazy, all", |input, _| {
+        let lazy = lazy::LazyLines::new(input);
+        (0..lazy.line_count())
+            .map(|i| lazy.get(i).unwrap().len())
+            .sum()
+    }),
+    ("split, first 1%", |input, lines| {
+        slice::split(input, lines);
+        lines[..lines.len() / 100]
+            .iter()
+            .map(|line| line.len())
+            .sum()
+    }),
+    ("lazy, first 1%", |input, _| {
+        let lazy = lazy::LazyLines::new(input);
+        (0..lazy.line_count() / 100)
+            .map(|i| lazy.get(i).unwrap().len())
+            .sum()
+    }),
+    ("split, 1000 sampled", |input, lines| {
+        slice::split(input, lines);
+        sampled_lines(lines.len()).map(|i| lines[i].len()).sum()
+    }),
+    ("lazy, 1000 sampled", |input, _| {
+        let lazy = lazy::LazyLines::new(input);
+        sampled_lines(lazy.line_count())
+            .map(|i| lazy.get(i).unwrap().len())
+            .sum()
+    }),
+    ("SimdLines, all", |input, _| {
+        simd_lines::SimdLines::new(input)
+            .map(|line| line.len())
+            .sum()
+    }),
+    ("SimdLines, all reversed", |input, _| {
+        simd_lines::SimdLines::new(input)
+            .rev()
+            .map(|line| line.len())
+            .sum()
+    }),
+    ("for_each_line, all", |input, _| {
+        let mut bytes = 0;
+        slice::for_each_line(input, |line| bytes += line.len());
+        bytes
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2 for_each_line, all", |input, _| {
+        let mut bytes = 0;
+        slice::x86_64::sse2_for_each_line(input, |line| bytes += line.len());
+        bytes
+    }),
+];
+
+/// std, then the slice kernels, with the `--sentinel` kernel rerun among them. Returns their MB/s,
+/// and the sentinel's, its first run first.
+pub fn slice<'a>(
+    stage: &Stage<'a>,
+    cases: &Cases,
+    out: &mut Vec<&'a str>,
+) -> std::io::Result<(Vec<f64>, Vec<f64>)> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let expected_lines = stage.expected_lines;
+    let mut thrpts = Vec::new();
+    let mut time_std = || {
+        let input = stage.next_input();
+        let start = Instant::now();
+        for segment in &stage.segments {
+            black_box(slice::std(&input[segment.clone()]));
+        }
+        start.elapsed()
+    };
+    {
+        let thrpt = stage.measure("slice", "std", &mut time_std);
+        stage.std_run.set(Some(Duration::from_secs_f64(
+            stage.len as f64 / thrpt / 1_000_000.,
+        )));
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt),
+            fn_label = "std"
+        );
+        thrpts.push(thrpt);
+    }
+    // its own buffer, with the same pages as the kernels' one, so the first run doesn't pay for
+    // growing it
+    let mut sentinel_buf = match cases.sentinel {
+        Some(_) => bench.pretouch.alloc(
+            bench
+                .max_stored_lines
+                .map_or(expected_lines, |max| max.min(expected_lines)),
+        )?,
+        None => Vec::new(),
+    };
+    let mut time_sentinel = || {
+        bench.pretouch.reset(&mut sentinel_buf);
+        let input = stage.next_input();
+        let start = Instant::now();
+        match cases.sentinel {
+            Some(fnc) => {
+                split_segments(fnc, input, &stage.segments, &mut sentinel_buf);
+            }
+            None => {
+                for segment in &stage.segments {
+                    black_box(slice::std(&input[segment.clone()]));
+                }
+            }
+        }
+        let duration = start.elapsed();
+        black_box(&mut sentinel_buf);
+        duration
+    };
+    let sentinel = &bench.sentinel;
+    // std's own row is the reference run when it's the sentinel
+    let mut sentinel_thrpts = match cases.sentinel {
+        Some(_) => {
+            let thrpt = stage.measure("sentinel", "first run", &mut time_sentinel);
+            progress!(bench, "{sentinel:<13}: {thrpt:>8.0} (first run)");
+            vec![thrpt]
+        }
+        None => vec![thrpts[0]],
+    };
+    let mut rerun_sentinel = |sentinel_thrpts: &mut Vec<f64>| {
+        let thrpt = stage.measure("sentinel", "rerun", &mut time_sentinel);
+        progress!(
+            bench,
+            "{sentinel:<13}: {thrpt:>8.0} ({:+.1}% from its first run)",
+            100. * (thrpt / sentinel_thrpts[0] - 1.)
+        );
+        sentinel_thrpts.push(thrpt);
+    };
+    for (kernel_i, (fn_label, _, fnc)) in cases.slice.iter().enumerate() {
+        if bench.sentinel_every > 0 && kernel_i > 0 && kernel_i % bench.sentinel_every == 0 {
+            rerun_sentinel(&mut sentinel_thrpts);
+        }
+        let mut found = 0;
+        let thrpt = stage.measure("slice", fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            found = split_segments(*fnc, input, &stage.segments, out);
+            let duration = start.elapsed();
+            black_box(&mut *out);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            found == expected_lines,
+            "(slice) {fn_label} found {found} lines instead of {expected_lines} during {stage_label}",
+        );
+        if let Some(Err(err)) = stage.expected_slices.as_ref().map(|e| e.check(out)) {
+            panic!("(slice) {fn_label} failed during {stage_label}: {err}");
+        }
+    }
+    // and once more at the end, to show the results aren't sensitive to order (e.g. cache)
+    rerun_sentinel(&mut sentinel_thrpts);
+    Ok((thrpts, sentinel_thrpts))
+}
+
+/// The `ADAPTIVE_CASES`, then the density estimate on its own
+pub fn adaptive<'a>(stage: &Stage<'a>, out: &mut Vec<&'a str>) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let expected_lines = stage.expected_lines;
+    let mut thrpts = Vec::new();
+    let density = density::estimate_line_density(stage.input);
+    progress!(
+        bench,
+        "{:<13}: {:.1} bytes per line",
+        "estimate",
+        density.bytes_per_line()
+    );
+    for (fn_label, fnc) in ADAPTIVE_CASES {
+        let mut found = 0;
+        let thrpt = stage.measure("adaptive dispatch", fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            found = split_segments(fnc, input, &stage.segments, out);
+            let duration = start.elapsed();
+            black_box(&mut *out);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            found == expected_lines,
+            "(adaptive dispatch) {fn_label} found {found} lines instead of {expected_lines} during {stage_label}",
+        );
+    }
+    // what `adaptive` pays on top of the kernel it picks
+    let thrpt = stage.measure("adaptive dispatch", "sample only", &mut || {
+        let input = stage.next_input();
+        let start = Instant::now();
+        for segment in &stage.segments {
+            black_box(density::estimate_line_density(&input[segment.clone()]));
+        }
+        start.elapsed()
+    });
+    progress!(bench, "{:<13}: {thrpt:>8.0}", "sample only");
+    thrpts.push(thrpt);
+    thrpts
+}
+
+/// The slice kernels again, each with a consumer reading the lines as they're made
+pub fn pipeline(stage: &Stage, cases: &Cases) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let expected_lines = stage.expected_lines;
+    let mut thrpts = Vec::new();
+    let mut expected_stats = None;
+    for (fn_label, _, fnc) in &cases.slice {
+        let mut stats = Default::default();
+        let thrpt = stage.measure("pipeline", fn_label, &mut || {
+            let input = stage.next_input();
+            let start = Instant::now();
+            stats = pipeline::split_with_consumer(input, 1024 * 1024, *fnc);
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            stats.lines == expected_lines,
+            "(pipeline) {fn_label} saw {} lines instead of {expected_lines} during {stage_label}",
+            stats.lines
+        );
+        assert!(
+            *expected_stats.get_or_insert_with(|| pipeline::split_with_consumer(
+                stage.input,
+                1024 * 1024,
+                slice::std_reuse
+            )) == stats,
+            "(pipeline) {fn_label} failed during {stage_label}"
+        );
+    }
+    thrpts
+}
+
+pub fn line_stats<'a>(stage: &Stage<'a>, cases: &Cases, out: &mut Vec<&'a str>) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let expected_lines = stage.expected_lines;
+    if let Some(skipped) = stage.skip_capped(cases.line_stats.len()) {
+        return skipped;
+    }
+    let mut thrpts = Vec::new();
+    let expected_stats = line_stats::line_length_stats(stage.input);
+    assert!(
+        expected_stats.lines == expected_lines,
+        "(line stats) the reference counted {} lines instead of {expected_lines} during {stage_label}",
+        expected_stats.lines
+    );
+    progress!(
+        bench,
+        "{:<13}: max {}, p50 <= {}, p99 <= {}",
+        "lengths",
+        expected_stats.max,
+        expected_stats.percentile(0.5),
+        expected_stats.percentile(0.99)
+    );
+    for (fn_label, _, fnc) in &cases.line_stats {
+        let mut stats = Default::default();
+        let thrpt = stage.measure("line stats", fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            stats = black_box(unsafe { fnc(input, out) });
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            expected_stats == stats,
+            "(line stats) {fn_label} failed during {stage_label}"
+        );
+    }
+    thrpts
+}
+
+/// The dispatch row of `slice_thrpts` (the stage's slice table results), then `stride::detect`,
+/// then `stride::from_stride` with the width it detects, on the stages it accepts
+pub fn fixed_stride<'a>(
+    stage: &Stage<'a>,
+    cases: &Cases,
+    slice_thrpts: &[f64],
+    out: &mut Vec<&'a str>,
+) -> Vec<f64> {
+    let bench = stage.bench;
+    let dispatch_idx = cases
+        .slice
+        .iter()
+        .position(|case| case.0 == "dispatch")
+        .unwrap();
+    let detect_thrpt = stage.measure("fixed stride", "detect", &mut || {
+        let input = stage.next_input();
+        let start = Instant::now();
+        black_box(stride::detect(input));
+        start.elapsed()
+    });
+    progress!(
+        bench,
+        "{:<13}: {detect_thrpt:>8.0} ({})",
+        "detect",
+        stage.efficiency(detect_thrpt)
+    );
+    // only for the stages it accepts, and the whole input's lines at once
+    let known_width_thrpt = match stride::detect(stage.input) {
+        Some(width) if !stage.capped => {
+            let thrpt = stage.measure("fixed stride", "known width", &mut || {
+                bench.pretouch.reset(out);
+                let input = stage.next_input();
+                let start = Instant::now();
+                stride::from_stride(input, width, out);
+                start.elapsed()
+            });
+            progress!(
+                bench,
+                "{:<13}: {thrpt:>8.0} ({})",
+                "known width",
+                stage.efficiency(thrpt)
+            );
+            assert!(
+                out.len() == stage.expected_lines,
+                "(fixed stride) known width failed during {}",
+                stage.label
+            );
+            thrpt
+        }
+        _ => f64::NAN,
+    };
+    vec![
+        slice_thrpts[dispatch_idx + 1],
+        detect_thrpt,
+        known_width_thrpt,
+    ]
+}
+
+pub fn lazy<'a>(stage: &Stage<'a>, out: &mut Vec<&'a str>) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    if let Some(skipped) = stage.skip_capped(LAZY_BENCH_CASES.len()) {
+        return skipped;
+    }
+    let mut thrpts = Vec::new();
+    let mut touched = Vec::new();
+    for (fn_label, fnc) in LAZY_BENCH_CASES {
+        let mut bytes = 0;
+        let thrpt = stage.measure("lazy lines", fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            bytes = black_box(fnc(input, out));
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        touched.push(bytes);
+    }
+    // the three pairs, then the streaming consumers
+    let (pairs, streamed) = touched.split_at(6);
+    for pair in pairs.chunks(2) {
+        assert_eq!(pair[0], pair[1], "(lazy lines) failed during {stage_label}");
+    }
+    for bytes in streamed {
+        assert_eq!(
+            *bytes, touched[0],
+            "(lazy lines) a streaming consumer failed during {stage_label}"
+        );
+    }
+    let sampled = lazy::LazyLines::new(stage.input);
+    sampled_lines(sampled.line_count()).for_each(|i| {
+        sampled.get(i);
+    });
+    progress!(
+        bench,
+        "{:<13}: {} of {} blocks made",
+        "sampled",
+        sampled.materialized_blocks(),
+        stage.input.len().div_ceil(1 << 16)
+    );
+    thrpts
+}
+
+/// `sharded::ShardedLines` on every core, concatenated into one `Vec` and viewed in place
+pub fn parallel<'a>(stage: &Stage<'a>, out: &mut Vec<&'a str>) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let expected_lines = stage.expected_lines;
+    let threads = bench.threads;
+    if let Some(skipped) = stage.skip_capped(2) {
+        return skipped;
+    }
+    let mut sharded_lines = sharded::ShardedLines::default();
+    // untimed, so neither case pays for growing the per-shard Vecs
+    sharded_lines.split(stage.input, threads);
+    let concat_thrpt = stage.measure("parallel", "concat", &mut || {
+        bench.pretouch.reset(out);
+        let input = stage.next_input();
+        let start = Instant::now();
+        sharded_lines.split(input, threads);
+        sharded_lines.concat(out);
+        let duration = start.elapsed();
+        black_box(&mut *out);
+        duration
+    });
+    progress!(
+        bench,
+        "{:<13}: {concat_thrpt:>8.0} ({})",
+        "concat",
+        stage.efficiency(concat_thrpt)
+    );
+    let view_thrpt = stage.measure("parallel", "view", &mut || {
+        let input = stage.next_input();
+        let start = Instant::now();
+        sharded_lines.split(input, threads);
+        let duration = start.elapsed();
+        black_box(&mut sharded_lines);
+        duration
+    });
+    progress!(
+        bench,
+        "{:<13}: {view_thrpt:>8.0} ({})",
+        "view",
+        stage.efficiency(view_thrpt)
+    );
+    assert!(
+        sharded_lines.line_count() == expected_lines,
+        "(parallel) found {} lines instead of {expected_lines} during {stage_label}",
+        sharded_lines.line_count()
+    );
+    assert!(
+        sharded_lines.line_count() == out.len()
+            && sharded_lines.iter().eq(out.iter().copied())
+            && (0..out.len())
+                .step_by(out.len() / 16 + 1)
+                .all(|i| sharded_lines[i] == out[i]),
+        "(parallel) view doesn't match concat during {stage_label}"
+    );
+    if let Some(Err(err)) = stage.expected_slices.as_ref().map(|e| e.check(out)) {
+        panic!("(parallel) concat failed during {stage_label}: {err}");
+    }
+    vec![concat_thrpt, view_thrpt]
+}
+
+/// The `ARENA_CASES`
+pub fn arena<'a>(stage: &Stage<'a>, out: &mut Vec<&'a str>) -> Vec<f64> {
+    let bench = stage.bench;
+    if let Some(skipped) = stage.skip_capped(ARENA_CASES.len()) {
+        return skipped;
+    }
+    let vec_thrpt = stage.measure("arena", "Vec", &mut || {
+        bench.pretouch.reset(out);
+        let input = stage.next_input();
+        let start = Instant::now();
+        slice::split(input, out);
+        let duration = start.elapsed();
+        black_box(&mut *out);
+        duration
+    });
+    let fresh_vec_thrpt = stage.measure("arena", "fresh Vec", &mut || {
+        let input = stage.next_input();
+        let start = Instant::now();
+        let mut lines = Vec::new();
+        slice::split(input, &mut lines);
+        let duration = start.elapsed();
+        black_box(&mut lines);
+        duration
+    });
+    // one per stage, since it holds the stage's lines
+    let mut bump = arena::Bump::new();
+    let arena_thrpt = stage.measure("arena", "arena", &mut || {
+        bump.reset();
+        let input = stage.next_input();
+        let start = Instant::now();
+        let lines = arena::split_in(input, &bump);
+        let duration = start.elapsed();
+        black_box(lines);
+        duration
+    });
+    let fresh_arena_thrpt = stage.measure("arena", "fresh arena", &mut || {
+        let input = stage.next_input();
+        let start = Instant::now();
+        let fresh = arena::Bump::new();
+        let lines = arena::split_in(input, &fresh);
+        let duration = start.elapsed();
+        black_box(lines);
+        duration
+    });
+    let thrpts = vec![vec_thrpt, fresh_vec_thrpt, arena_thrpt, fresh_arena_thrpt];
+    for (label, thrpt) in ARENA_CASES.iter().zip(&thrpts) {
+        progress!(
+            bench,
+            "{label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(*thrpt)
+        );
+    }
+    bump.reset();
+    let lines = arena::split_in(stage.input, &bump);
+    if let Some(Err(err)) = stage.expected_slices.as_ref().map(|e| e.check(lines)) {
+        panic!("(arena) split_in failed during {}: {err}", stage.label);
+    }
+    progress!(bench, "{:<13}: {} KiB", "chunks", bump.chunk_bytes() / 1024);
+    thrpts
+}
@@ -0,0 +1,710 @@
+//! The markdown tables printed after the stages, and the history records of the same results
+
+use super::{Bench, Cases, Results, Runnable, BENCHMARK_STAGES};
+#[cfg(feature = "grep")]
+use crate::GREP_NEEDLE;
+#[cfg(feature = "roaring")]
+use crate::NEWLINE_SET_ROWS;
+use crate::{
+    cli, disasm, dispatch, history, rel_stddev, ADAPTIVE_CASES, ARENA_CASES, BENCH_PREFIX,
+    CONCURRENT_READERS, CONVERSIONS, FIRST_CALL_LINES, NUL_STAGES, RANDOM_ACCESS, RANGE_SCALE,
+    SMALL_INPUTS, SMALL_INPUT_MAX_LEN, SPAN_CASES, SUBSTRING_STAGES,
+};
+
+/// Prints the cells of the tables with a column per stage
+struct Printer {
+    /// Every cell is relative to its table's baseline row instead, since absolute numbers aren't
+    /// comparable between CI machines
+    deterministic: bool,
+}
+
+impl Printer {
+    /// NaN for a case that didn't run in a stage, see `--max-stored-lines`
+    fn cell(&self, thrpts: &[f64], idx: usize, baseline_idx: usize) -> String {
+        if thrpts[idx].is_nan() {
+            "-".to_owned()
+        } else if self.deterministic {
+            format!("{:.2}", thrpts[idx] / thrpts[baseline_idx])
+        } else {
+            format!("{:.0}", thrpts[idx])
+        }
+    }
+
+    fn relative_to(&self, baseline: &str) -> String {
+        if self.deterministic {
+            format!(" (relative to {baseline})")
+        } else {
+            String::new()
+        }
+    }
+
+    fn header(&self) {
+        print!("| algo |");
+        for (stage_label, ..) in BENCHMARK_STAGES {
+            print!(" {stage_label} |");
+        }
+        println!();
+        print!("| :-- |");
+        for _ in BENCHMARK_STAGES {
+            print!(" --: |");
+        }
+        println!();
+    }
+
+    /// | algo | thrpts... |, the `idx`th algo's being the `idx`th of each stage's results
+    fn rows<'n>(
+        &self,
+        algo_names: impl IntoIterator<Item = &'n str>,
+        thrpts: &[Vec<f64>],
+        baseline_idx: usize,
+    ) {
+        for (idx, algo_name) in algo_names.into_iter().enumerate() {
+            print!("| {algo_name} |");
+            for thrpts in thrpts {
+                print!(" {} |", self.cell(thrpts, idx, baseline_idx));
+            }
+            println!();
+        }
+    }
+
+    /// A whole table, relative to its first row, `baseline`
+    fn table<'n>(
+        &self,
+        title: &str,
+        baseline: &str,
+        algo_names: impl IntoIterator<Item = &'n str>,
+        thrpts: &[Vec<f64>],
+    ) {
+        println!("\n## {title}{}\n", self.relative_to(baseline));
+        self.header();
+        self.rows(algo_names, thrpts, 0);
+    }
+}
+
+fn algo_names<F>(cases: &Runnable<F>) -> Vec<&'static str> {
+    cases.iter().map(|case| case.0).collect()
+}
+
+pub fn print(
+    bench: &Bench,
+    cases: &Cases,
+    results: &Results,
+    emit_asm: Option<&(&'static str, usize, disasm::Function)>,
+) {
+    let printer = Printer {
+        deterministic: bench.deterministic,
+    };
+    print_stage_tables(&printer, bench, cases, results);
+    print_once_tables(&printer, cases, results);
+    print_reliability(bench, cases, results);
+    print_efficiency(&printer, cases, results);
+    if let Some((table, idx, function)) = emit_asm {
+        print_asm(&printer, cases, results, table, *idx, function);
+    }
+}
+
+fn print_stage_tables(printer: &Printer, bench: &Bench, cases: &Cases, results: &Results) {
+    println!("\n## Slice{}\n", printer.relative_to("std_reuse"));
+    printer.header();
+    // std_reuse is the first case, after std
+    let slice_rows = std::iter::once("std").chain(algo_names(&cases.slice));
+    printer.rows(slice_rows, &results.slice, 1);
+
+    printer.table(
+        "Slice, with a concurrent consumer",
+        "std_reuse",
+        algo_names(&cases.slice),
+        &results.pipeline,
+    );
+
+    printer.table(
+        &format!(
+            "Adaptive dispatch, sparse kernel: {}",
+            dispatch::global().sparse_kernel().name()
+        ),
+        "dispatch",
+        ADAPTIVE_CASES
+            .iter()
+            .map(|case| case.0)
+            .chain(["sample only"]),
+        &results.adaptive,
+    );
+
+    printer.table(
+        "Compressed format",
+        "iter",
+        algo_names(&cases.compressed),
+        &results.compressed,
+    );
+    #[cfg(feature = "gpu")]
+    if !results.gpu_compute.is_empty() && !printer.deterministic {
+        print!("| gpu (compute only) |");
+        for thrpt in &results.gpu_compute {
+            print!(" {thrpt:.0} |")
+        }
+        println!();
+    }
+
+    printer.table(
+        "Delimiter classes",
+        "scalar",
+        algo_names(&cases.classify),
+        &results.classify,
+    );
+    printer.table(
+        "LineIndex conversions",
+        "split",
+        CONVERSIONS,
+        &results.conversions,
+    );
+    printer.table(
+        "Random line access",
+        "in order",
+        RANDOM_ACCESS,
+        &results.random_access,
+    );
+    printer.table(
+        "Line around an offset",
+        "std",
+        SPAN_CASES.iter().map(|case| case.0),
+        &results.span,
+    );
+
+    #[cfg(feature = "roaring")]
+    {
+        println!("\n## Newline sets{}\n", printer.relative_to("LineIndex"));
+        printer.header();
+        for (idx, algo_name) in NEWLINE_SET_ROWS.iter().enumerate() {
+            print!("| {algo_name} |");
+            for thrpts in &results.newline_sets {
+                // each roaring row is relative to the LineIndex row above it
+                print!(" {} |", printer.cell(thrpts, idx, idx & !1));
+            }
+            println!();
+        }
+    }
+
+    let threads = bench.threads;
+    printer.table(
+        &format!("Concurrent lookups in a shared index, cores: {threads}"),
+        "1 reader",
+        CONCURRENT_READERS.iter().map(|case| case.0),
+        &results.concurrent,
+    );
+    printer.table(
+        &format!("Parallel slices, threads: {threads}"),
+        "concat",
+        ["concat", "view"],
+        &results.parallel,
+    );
+    printer.table(
+        "Line length stats",
+        "split + pass",
+        algo_names(&cases.line_stats),
+        &results.line_stats,
+    );
+    // the upper bound a known width gives, and what checking for one costs
+    printer.table(
+        "Fixed stride",
+        "dispatch",
+        ["dispatch", "detect", "known width"],
+        &results.fixed_stride,
+    );
+    printer.table(
+        "Lazy lines",
+        "split, all",
+        super::slices::LAZY_BENCH_CASES.iter().map(|case| case.0),
+        &results.lazy,
+    );
+    printer.table(
+        "Paragraphs",
+        "scalar",
+        algo_names(&cases.paragraph),
+        &results.paragraphs,
+    );
+    printer.table(
+        "Trimmed lines",
+        "split + filter",
+        algo_names(&cases.trimmed),
+        &results.trimmed,
+    );
+    printer.table("CRLF lines", "std", algo_names(&cases.crlf), &results.crlf);
+    printer.table(
+        "Tab-delimited fields",
+        "std",
+        algo_names(&cases.delim),
+        &results.delim,
+    );
+    printer.table(
+        "Delimiter sets",
+        "std",
+        algo_names(&cases.delim_set),
+        &results.delim_set,
+    );
+    printer.table(
+        "Inclusive lines",
+        "std",
+        algo_names(&cases.inclusive),
+        &results.inclusive,
+    );
+    printer.table(
+        &format!("Lines starting with {BENCH_PREFIX:?}"),
+        "split + filter",
+        algo_names(&cases.prefixed),
+        &results.prefixed,
+    );
+    #[cfg(feature = "grep")]
+    printer.table(
+        &format!("grep, lines of {}+ bytes", GREP_NEEDLE.len()),
+        "grep-searcher",
+        algo_names(&cases.grep),
+        &results.grep,
+    );
+    printer.table(
+        "Flat u32 offsets",
+        "iter",
+        algo_names(&cases.flat),
+        &results.flat,
+    );
+    printer.table(
+        "Line ranges",
+        "slices",
+        RANGE_SCALE.into_iter().chain(algo_names(&cases.ranges)),
+        &results.ranges,
+    );
+    printer.table("Arena output", "Vec", ARENA_CASES, &results.arena);
+}
+
+/// The tables with inputs of their own, which have their own columns
+fn print_once_tables(printer: &Printer, cases: &Cases, results: &Results) {
+    let deterministic = printer.deterministic;
+    // absolute times aren't comparable between machines, but the ratio is
+    println!("\n## First call ({FIRST_CALL_LINES} lines)\n");
+    if deterministic {
+        println!("| table | algo | cold / warm |");
+        println!("| :-- | :-- | --: |");
+    } else {
+        println!("| table | algo | cold (us) | warm (us) | cold / warm |");
+        println!("| :-- | :-- | --: | --: | --: |");
+    }
+    for (table, algo_name, (cold, warm)) in &results.first_calls {
+        let ratio = cold.as_secs_f64() / warm.as_secs_f64();
+        if deterministic {
+            println!("| {table} | {algo_name} | {ratio:.2} |");
+        } else {
+            println!(
+                "| {table} | {algo_name} | {:.1} | {:.1} | {ratio:.2} |",
+                cold.as_secs_f64() * 1e6,
+                warm.as_secs_f64() * 1e6,
+            );
+        }
+    }
+
+    println!("\n## Substring delimiters{}\n", printer.relative_to("std"));
+    print!("| algo |");
+    for (stage_label, delim, _) in SUBSTRING_STAGES {
+        print!(" {stage_label} ({delim:?}) |");
+    }
+    println!("\n| :-- |{}", " --: |".repeat(SUBSTRING_STAGES.len()));
+    printer.rows(algo_names(&cases.substring), &results.substring, 0);
+
+    println!("\n## NUL records{}\n", printer.relative_to("std"));
+    print!("| algo |");
+    for (stage_label, _) in NUL_STAGES {
+        print!(" {stage_label} |");
+    }
+    println!("\n| :-- |{}", " --: |".repeat(NUL_STAGES.len()));
+    printer.rows(algo_names(&cases.nul), &results.nul, 0);
+
+    // each table's first row is its baseline, like in the stage tables
+    println!(
+        "\n## Small inputs ({SMALL_INPUTS} inputs of 1-{SMALL_INPUT_MAX_LEN} bytes, one call each){}\n",
+        printer.relative_to("each table's first row")
+    );
+    println!("| table | algo | MB/s | ns per call |");
+    println!("| :-- | :-- | --: | --: |");
+    let small_inputs = &results.small_inputs;
+    for &(table, algo_name, thrpt) in small_inputs {
+        let baseline = small_inputs.iter().find(|row| row.0 == table).unwrap().2;
+        let ns_per_call =
+            results.small_input_len as f64 / (thrpt * 1e6) / SMALL_INPUTS as f64 * 1e9;
+        if deterministic {
+            println!("| {table} | {algo_name} | {:.2} | - |", thrpt / baseline);
+        } else {
+            println!("| {table} | {algo_name} | {thrpt:.0} | {ns_per_call:.1} |");
+        }
+    }
+}
+
+/// How far the results can be trusted: the noisy and timed out ones, drift and stability, and
+/// the cases that couldn't run
+fn print_reliability(bench: &Bench, cases: &Cases, results: &Results) {
+    let sampling = bench.sampling;
+    let noisy = bench.noisy.borrow();
+    if !noisy.is_empty() {
+        println!(
+            "\n## Noisy (over {:.0}% stddev after {} reruns)\n",
+            100. * sampling.noise_threshold,
+            sampling.max_reruns
+        );
+        println!("| table | algo | stage | stddev |");
+        println!("| :-- | :-- | :-- | --: |");
+        for (table, algo_name, stage_label, measurement) in noisy.iter() {
+            println!(
+                "| {table} | {algo_name} | {stage_label} | {:.1}% |",
+                100. * measurement.rel_stddev
+            );
+        }
+    }
+
+    let timed_out = bench.timed_out.borrow();
+    if !timed_out.is_empty() {
+        println!(
+            "\n## Timed out (over {}x std's run time)\n",
+            bench.timeout.unwrap()
+        );
+        println!("| table | algo | stage | run | limit |");
+        println!("| :-- | :-- | :-- | --: | --: |");
+        for (table, algo_name, stage_label, took, limit) in timed_out.iter() {
+            println!("| {table} | {algo_name} | {stage_label} | {took:.1?} | {limit:.1?} |");
+        }
+    }
+
+    println!(
+        "\n## Drift ({} rerun during the slice kernels, % of its first run)\n",
+        bench.sentinel
+    );
+    println!("| stage | reruns | largest drift | shifted |");
+    println!("| :-- | :-- | --: | :-- |");
+    for (stage_label, thrpts) in &results.drifts {
+        let reruns: Vec<String> = thrpts[1..]
+            .iter()
+            .map(|thrpt| format!("{:.0}%", 100. * thrpt / thrpts[0]))
+            .collect();
+        let drift = thrpts[1..]
+            .iter()
+            .map(|thrpt| thrpt / thrpts[0] - 1.)
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap();
+        let shifted = if drift.abs() > sampling.noise_threshold {
+            "yes"
+        } else {
+            ""
+        };
+        println!(
+            "| {stage_label} | {} | {:+.1}% | {shifted} |",
+            reruns.join(", "),
+            100. * drift
+        );
+    }
+
+    // a kernel that's a bit slower but about as fast on every input shape can be the better pick
+    println!("\n## Stability (coefficient of variation, lower is flatter)\n");
+    let spreads = bench.spreads.borrow();
+    let show_iterations = !bench.deterministic && sampling.iterations > 1;
+    if show_iterations {
+        println!("| table | algo | across stages | across iterations |");
+        println!("| :-- | :-- | --: | --: |");
+    } else {
+        println!("| table | algo | across stages |");
+        println!("| :-- | :-- | --: |");
+    }
+    // (table, algos, thrpts, index of the first algo in the thrpts)
+    let stability_tables = [
+        (
+            "slice",
+            algo_names(&cases.slice),
+            &results.slice,
+            // after std, which the table starts with
+            1,
+        ),
+        (
+            "compressed",
+            algo_names(&cases.compressed),
+            &results.compressed,
+            0,
+        ),
+        ("flat u32", algo_names(&cases.flat), &results.flat, 0),
+    ];
+    for (table, algo_names, thrpts, first_idx) in &stability_tables {
+        for (idx, algo_name) in algo_names.iter().enumerate() {
+            let across_stages = rel_stddev(
+                &thrpts
+                    .iter()
+                    .map(|t| t[first_idx + idx])
+                    .collect::<Vec<_>>(),
+            );
+            print!("| {table} | {algo_name} | {:.0}% |", 100. * across_stages);
+            if show_iterations {
+                let iterations: Vec<f64> = spreads
+                    .iter()
+                    .filter(|(t, a, _)| t == table && a == algo_name)
+                    .map(|(.., spread)| *spread)
+                    .collect();
+                let mean = iterations.iter().sum::<f64>() / iterations.len() as f64;
+                print!(" {:.1}% |", 100. * mean);
+            }
+            println!();
+        }
+    }
+
+    if !cases.skipped.is_empty() {
+        println!("\n## Skipped\n");
+        println!("| table | algo | missing |");
+        println!("| :-- | :-- | :-- |");
+        for (table, algo_name, missing) in &cases.skipped {
+            println!("| {table} | {algo_name} | {} |", missing.join(", "));
+        }
+    }
+}
+
+/// The ceilings, and the main tables as fractions of them
+fn print_efficiency(printer: &Printer, cases: &Cases, results: &Results) {
+    let ceilings = &results.ceilings;
+    // absolute, so not in the `--deterministic` output, but the efficiency tables are ratios
+    if !printer.deterministic {
+        println!("\n## Ceilings\n");
+        printer.header();
+        print!("| memcpy |");
+        for (memcpy_thrpt, _) in ceilings {
+            print!(" {memcpy_thrpt:.0} |");
+        }
+        println!();
+        print!("| memchr |");
+        for (_, memchr_thrpt) in ceilings {
+            print!(" {memchr_thrpt:.0} |");
+        }
+        println!();
+    }
+
+    let print_efficiency_row = |algo_name: &str, thrpts: &mut dyn Iterator<Item = f64>| {
+        print!("| {algo_name} |");
+        for (thrpt, (memcpy_thrpt, memchr_thrpt)) in thrpts.zip(ceilings) {
+            print!(
+                " {:.0}% / {:.0}% |",
+                100. * thrpt / memcpy_thrpt,
+                100. * thrpt / memchr_thrpt
+            );
+        }
+        println!();
+    };
+    println!("\n## Slice efficiency (% of memcpy / % of memchr)\n");
+    printer.header();
+    print_efficiency_row("std", &mut results.slice.iter().map(|vec| vec[0]));
+    for (idx, algo_name) in algo_names(&cases.slice).into_iter().enumerate() {
+        print_efficiency_row(algo_name, &mut results.slice.iter().map(|vec| vec[idx + 1]));
+    }
+
+    println!("\n## Compressed format efficiency (% of memcpy / % of memchr)\n");
+    printer.header();
+    for (idx, algo_name) in algo_names(&cases.compressed).into_iter().enumerate() {
+        print_efficiency_row(
+            algo_name,
+            &mut results.compressed.iter().map(|vec| vec[idx]),
+        );
+    }
+
+    println!("\n## Flat u32 efficiency (% of memcpy / % of memchr)\n");
+    printer.header();
+    for (idx, algo_name) in algo_names(&cases.flat).into_iter().enumerate() {
+        print_efficiency_row(algo_name, &mut results.flat.iter().map(|vec| vec[idx]));
+    }
+}
+
+/// `--emit-asm`'s kernel, the `idx`th of `table`: its row, then its disassembly
+fn print_asm(
+    printer: &Printer,
+    cases: &Cases,
+    results: &Results,
+    table: &str,
+    idx: usize,
+    function: &disasm::Function,
+) {
+    let (algo_name, thrpts): (_, Vec<f64>) = match table {
+        "slice" => (
+            cases.slice[idx].0,
+            results.slice.iter().map(|vec| vec[idx + 1]).collect(),
+        ),
+        "compressed" => (
+            cases.compressed[idx].0,
+            results.compressed.iter().map(|vec| vec[idx]).collect(),
+        ),
+        _ => (
+            cases.flat[idx].0,
+            results.flat.iter().map(|vec| vec[idx]).collect(),
+        ),
+    };
+    println!("\n## Assembly: {table} {algo_name}\n");
+    printer.header();
+    print!("| {algo_name} |");
+    for thrpt in &thrpts {
+        print!(" {thrpt:.0} |");
+    }
+    println!();
+    println!(
+        "\n`{}`, {} instructions, one `|` per loop around each\n",
+        function.symbol,
+        function.instructions.len()
+    );
+    println!("```asm");
+    let max_depth = function.instructions.iter().map(|ins| ins.loop_depth).max();
+    for ins in &function.instructions {
+        let loops = "|".repeat(ins.loop_depth);
+        let width = max_depth.unwrap_or(0) + 1;
+        println!("{loops:<width$}{:>8x}: {}", ins.address, ins.text);
+    }
+    println!("```");
+}
+
+/// One record per result, for `--history`
+pub fn history_records(
+    run: &history::Run,
+    cases: &Cases,
+    results: &Results,
+) -> Vec<history::Record> {
+    let mut records = Vec::new();
+    let mut add_table = |table: &str, algo_names: Vec<&str>, thrpts: &[Vec<f64>]| {
+        for (idx, algo_name) in algo_names.into_iter().enumerate() {
+            for ((stage_label, _), stage_thrpts) in BENCHMARK_STAGES.iter().zip(thrpts) {
+                if stage_thrpts[idx].is_nan() {
+                    continue;
+                }
+                records.push(history::Record {
+                    run: run.clone(),
+                    table: table.to_owned(),
+                    algo: algo_name.to_owned(),
+                    stage: (*stage_label).to_owned(),
+                    thrpt: stage_thrpts[idx],
+                });
+            }
+        }
+    };
+    // the sentinel's reruns go in the "Drift" table instead
+    add_table(
+        "slice",
+        std::iter::once("std")
+            .chain(algo_names(&cases.slice))
+            .collect(),
+        &results.slice,
+    );
+    add_table("pipeline", algo_names(&cases.slice), &results.pipeline);
+    add_table(
+        "adaptive dispatch",
+        ADAPTIVE_CASES
+            .iter()
+            .map(|case| case.0)
+            .chain(["sample only"])
+            .collect(),
+        &results.adaptive,
+    );
+    add_table(
+        "compressed",
+        algo_names(&cases.compressed),
+        &results.compressed,
+    );
+    add_table("classify", algo_names(&cases.classify), &results.classify);
+    add_table("parallel", vec!["concat", "view"], &results.parallel);
+    add_table("conversions", CONVERSIONS.to_vec(), &results.conversions);
+    add_table(
+        "random access",
+        RANDOM_ACCESS.to_vec(),
+        &results.random_access,
+    );
+    add_table(
+        "line around an offset",
+        SPAN_CASES.iter().map(|case| case.0).collect(),
+        &results.span,
+    );
+    add_table(
+        "concurrent lookups",
+        CONCURRENT_READERS.iter().map(|case| case.0).collect(),
+        &results.concurrent,
+    );
+    add_table(
+        "line stats",
+        algo_names(&cases.line_stats),
+        &results.line_stats,
+    );
+    add_table(
+        "lazy lines",
+        super::slices::LAZY_BENCH_CASES
+            .iter()
+            .map(|case| case.0)
+            .collect(),
+        &results.lazy,
+    );
+    add_table(
+        "paragraphs",
+        algo_names(&cases.paragraph),
+        &results.paragraphs,
+    );
+    add_table("trimmed", algo_names(&cases.trimmed), &results.trimmed);
+    add_table("crlf", algo_names(&cases.crlf), &results.crlf);
+    add_table("delimiter", algo_names(&cases.delim), &results.delim);
+    add_table(
+        "delimiter set",
+        algo_names(&cases.delim_set),
+        &results.delim_set,
+    );
+    add_table(
+        "inclusive",
+        algo_names(&cases.inclusive),
+        &results.inclusive,
+    );
+    add_table("prefixed", algo_names(&cases.prefixed), &results.prefixed);
+    #[cfg(feature = "grep")]
+    add_table("grep", algo_names(&cases.grep), &results.grep);
+    add_table("flat u32", algo_names(&cases.flat), &results.flat);
+    add_table(
+        "ranges",
+        RANGE_SCALE
+            .into_iter()
+            .chain(algo_names(&cases.ranges))
+            .collect(),
+        &results.ranges,
+    );
+    add_table("arena", ARENA_CASES.to_vec(), &results.arena);
+    for (table, algo_name, (cold, warm)) in &results.first_calls {
+        for (stage, duration) in [("cold", cold), ("warm", warm)] {
+            records.push(history::Record {
+                run: run.clone(),
+                table: format!("{table} first call"),
+                algo: (*algo_name).to_owned(),
+                stage: stage.to_owned(),
+                thrpt: cli::thrpt(results.first_call_len, *duration),
+            });
+        }
+    }
+    for (idx, algo_name) in algo_names(&cases.substring).into_iter().enumerate() {
+        for ((stage_label, ..), stage_thrpts) in SUBSTRING_STAGES.iter().zip(&results.substring) {
+            records.push(history::Record {
+                run: run.clone(),
+                table: "substring".to_owned(),
+                algo: algo_name.to_owned(),
+                stage: (*stage_label).to_owned(),
+                thrpt: stage_thrpts[idx],
+            });
+        }
+    }
+    for (idx, algo_name) in algo_names(&cases.nul).into_iter().enumerate() {
+        for ((stage_label, _), stage_thrpts) in NUL_STAGES.iter().zip(&results.nul) {
+            records.push(history::Record {
+                run: run.clone(),
+                table: "nul".to_owned(),
+                algo: algo_name.to_owned(),
+                stage: (*stage_label).to_owned(),
+                thrpt: stage_thrpts[idx],
+            });
+        }
+    }
+    for &(table, algo_name, thrpt) in &results.small_inputs {
+        records.push(history::Record {
+            run: run.clone(),
+            table: format!("{table} small inputs"),
+            algo: algo_name.to_owned(),
+            stage: format!("1-{SMALL_INPUT_MAX_LEN}"),
+            thrpt,
+        });
+    }
+    records
+}
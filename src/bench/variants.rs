@@ -0,0 +1,141 @@
+//! Other splits of the stages' input: paragraphs, lines filtered as they're found, and other
+//! terminators
+
+use super::{Cases, Runnable, Stage};
+use crate::{paragraph, prefixed, split_segments, trimmed, Features, SliceSplitFn, BENCH_PREFIX};
+use std::hint::black_box;
+use std::time::Instant;
+
+pub const TRIMMED_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("split + filter", &[], trimmed::split_then_filter),
+    ("scalar", &[], trimmed::scalar),
+    // avx2 where it can run
+    ("fused", &[], trimmed::split),
+];
+
+pub const PREFIXED_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("split + filter", &[], |input, out| {
+        prefixed::split_then_filter(input, BENCH_PREFIX, out)
+    }),
+    ("scalar", &[], |input, out| {
+        prefixed::scalar(input, BENCH_PREFIX, out)
+    }),
+    // avx2 where it can run
+    ("fused", &[], |input, out| {
+        prefixed::split(input, BENCH_PREFIX, out)
+    }),
+];
+
+pub fn paragraphs<'a>(stage: &Stage<'a>, cases: &Cases, out: &mut Vec<&'a str>) -> Vec<f64> {
+    let bench = stage.bench;
+    if let Some(skipped) = stage.skip_capped(cases.paragraph.len()) {
+        return skipped;
+    }
+    let mut thrpts = Vec::new();
+    let mut expected_paragraphs = Vec::new();
+    paragraph::split(stage.input, &mut expected_paragraphs);
+    progress!(bench, "{:<13}: {}", "paragraphs", expected_paragraphs.len());
+    let expected_paragraphs =
+        crate::verify::Expected::new(bench.verify, stage.input, expected_paragraphs);
+    for (fn_label, _, fnc) in &cases.paragraph {
+        let thrpt = stage.measure("paragraphs", fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            unsafe { fnc(input, out) };
+            start.elapsed()
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        if let Err(err) = expected_paragraphs.check(out) {
+            panic!(
+                "(paragraphs) {fn_label} failed during {}: {err}",
+                stage.label
+            );
+        }
+    }
+    thrpts
+}
+
+/// `cases` of `table`, which keep some of the lines: each must keep the lines `reference` does
+pub fn filtered<'a>(
+    stage: &Stage<'a>,
+    table: &'static str,
+    reference: SliceSplitFn,
+    cases: &Runnable<SliceSplitFn>,
+    out: &mut Vec<&'a str>,
+) -> Vec<f64> {
+    let bench = stage.bench;
+    let stage_label = stage.label;
+    let mut thrpts = Vec::new();
+    let mut expected = Vec::new();
+    let kept = split_segments(reference, stage.input, &stage.segments, &mut expected);
+    progress!(bench, "{:<13}: {kept}", "kept");
+    let expected = stage.expected(expected);
+    for (fn_label, _, fnc) in cases {
+        let mut found = 0;
+        let thrpt = stage.measure(table, fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            found = split_segments(*fnc, input, &stage.segments, out);
+            let duration = start.elapsed();
+            black_box(&mut *out);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        assert!(
+            found == kept,
+            "({table}) {fn_label} kept {found} lines instead of {kept} during {stage_label}",
+        );
+        if let Some(Err(err)) = expected.as_ref().map(|e| e.check(out)) {
+            panic!("({table}) {fn_label} failed during {stage_label}: {err}");
+        }
+    }
+    thrpts
+}
+
+/// `cases` of `table`, each checked against `reference`'s output
+pub fn checked<'a>(
+    stage: &Stage<'a>,
+    table: &'static str,
+    reference: SliceSplitFn,
+    cases: &Runnable<SliceSplitFn>,
+    out: &mut Vec<&'a str>,
+) -> Vec<f64> {
+    let bench = stage.bench;
+    let mut thrpts = Vec::new();
+    let mut expected = Vec::new();
+    split_segments(reference, stage.input, &stage.segments, &mut expected);
+    let expected = stage.expected(expected);
+    for (fn_label, _, fnc) in cases {
+        let thrpt = stage.measure(table, fn_label, &mut || {
+            bench.pretouch.reset(out);
+            let input = stage.next_input();
+            let start = Instant::now();
+            split_segments(*fnc, input, &stage.segments, out);
+            let duration = start.elapsed();
+            black_box(&mut *out);
+            duration
+        });
+        progress!(
+            bench,
+            "{fn_label:<13}: {thrpt:>8.0} ({})",
+            stage.efficiency(thrpt)
+        );
+        thrpts.push(thrpt);
+        if let Some(Err(err)) = expected.as_ref().map(|e| e.check(out)) {
+            panic!("({table}) {fn_label} failed during {}: {err}", stage.label);
+        }
+    }
+    thrpts
+}
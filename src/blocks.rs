@@ -0,0 +1,106 @@
+//! Reading a stream in blocks of complete lines, so each block can be split on its own while the
+//! next one is read. A read usually ends mid-line; that partial line is kept and moves to the
+//! front of the buffer to start the next block.
+
+use std::io::{self, Read};
+
+/// 4 MiB, big enough that the copy of the partial line at the end of each block doesn't matter
+pub const BLOCK_SIZE: usize = 4 << 20;
+
+pub struct LineBlocks<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// `buf[..start]` was returned by the last block
+    start: usize,
+    filled: usize,
+    eof: bool,
+}
+
+impl<R: Read> LineBlocks<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(BLOCK_SIZE, reader)
+    }
+
+    /// Reads `capacity` bytes at a time. The buffer doubles when a single line is longer.
+    pub fn with_capacity(capacity: usize, reader: R) -> Self {
+        LineBlocks {
+            reader,
+            buf: vec![0; capacity.max(1)],
+            start: 0,
+            filled: 0,
+            eof: false,
+        }
+    }
+
+    /// The next block, which ends just after a newline unless it's the end of the input, or
+    /// `None` once everything has been returned. Blocks are never empty.
+    pub fn next_block(&mut self) -> io::Result<Option<&[u8]>> {
+        self.buf.copy_within(self.start..self.filled, 0);
+        self.filled -= self.start;
+        self.start = 0;
+        loop {
+            while !self.eof && self.filled < self.buf.len() {
+                match self.reader.read(&mut self.buf[self.filled..]) {
+                    Ok(0) => self.eof = true,
+                    Ok(n) => self.filled += n,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            // only complete lines, unless there's nothing more coming
+            self.start = match self.buf[..self.filled].iter().rposition(|&b| b == b'\n') {
+                _ if self.eof => self.filled,
+                Some(i) => i + 1,
+                None => {
+                    // a single line is longer than the buffer
+                    self.buf.resize(self.buf.len() * 2, 0);
+                    continue;
+                }
+            };
+            return Ok((self.start > 0).then(|| &self.buf[..self.start]));
+        }
+    }
+
+    /// Appends everything not returned as a block yet to `out`, reading the rest of the input
+    pub fn read_rest(mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        out.extend_from_slice(&self.buf[self.start..self.filled]);
+        if !self.eof {
+            self.reader.read_to_end(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_blocks() {
+        let input = b"a\nbb\n\nccccccccccc\nd";
+        // small enough that most reads end mid-line, and the long line doubles the buffer
+        for capacity in 1..=input.len() + 1 {
+            let mut blocks = LineBlocks::with_capacity(capacity, &input[..]);
+            let mut joined = Vec::new();
+            while let Some(block) = blocks.next_block().unwrap() {
+                assert!(
+                    block.ends_with(b"\n") || joined.len() + block.len() == input.len(),
+                    "block {block:?} with capacity {capacity}"
+                );
+                joined.extend_from_slice(block);
+            }
+            assert_eq!(joined, input, "capacity {capacity}");
+        }
+    }
+
+    #[test]
+    fn test_read_rest() {
+        let input = b"a\nbb\nc";
+        let mut blocks = LineBlocks::with_capacity(4, &input[..]);
+        let first = blocks.next_block().unwrap().unwrap().to_vec();
+        assert_eq!(first, b"a\n");
+        let mut rest = Vec::new();
+        blocks.read_rest(&mut rest).unwrap();
+        assert_eq!(rest, b"bb\nc");
+    }
+}
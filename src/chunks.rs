@@ -0,0 +1,79 @@
+//! Cutting an input into pieces of about the same size that end on line boundaries, the step
+//! before handing pieces of a file to other threads, processes or machines. Each cut goes to the
+//! newline nearest a multiple of the target size, found with `span`'s scans from there, so the
+//! pieces don't drift longer the way cutting at the next newline does, and only the bytes around
+//! the cut points are read.
+
+use crate::span::{next_newline, prev_newline};
+use alloc::vec::Vec;
+
+/// `input` in pieces of about `target_chunk_bytes`, each ending just after a newline but maybe
+/// the last. Lines spanning a cut point make the pieces around it longer.
+pub fn chunk_at_line_boundaries(input: &str, target_chunk_bytes: usize) -> Vec<&str> {
+    let bytes = input.as_bytes();
+    let target = target_chunk_bytes.max(1);
+    let mut chunks = Vec::with_capacity(input.len() / target + 1);
+    let mut start = 0;
+    let mut cut_point = target;
+    while cut_point < bytes.len() {
+        let next_cut_point = cut_point + target;
+        // the newlines on either side, between the last cut and the next point
+        let before = prev_newline(&bytes[start..], cut_point - start).map(|nl| start + nl + 1);
+        let after =
+            next_newline(&bytes[..next_cut_point.min(bytes.len())], cut_point).map(|nl| nl + 1);
+        let cut = match (before, after) {
+            (Some(before), Some(after)) if after - cut_point < cut_point - before => Some(after),
+            (Some(before), _) => Some(before),
+            (None, after) => after,
+        };
+        cut_point = next_cut_point;
+        if let Some(cut) = cut {
+            // a cut after a newline is on a char boundary
+            chunks.push(&input[start..cut]);
+            start = cut;
+            while cut_point <= start {
+                cut_point += target;
+            }
+        }
+    }
+    if start < input.len() {
+        chunks.push(&input[start..]);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_at_line_boundaries() {
+        let long = "ab\n\ncdefghijklmnopqrstuvwxyz0123456789\n\u{e9}".repeat(40);
+        let single_line = "a".repeat(1000);
+        for input in ["", "\n", "abc", "abc\n", &long, &single_line] {
+            for target in [0, 1, 2, 7, 64, 100, 1000, 10_000] {
+                let chunks = chunk_at_line_boundaries(input, target);
+                assert_eq!(chunks.concat(), input, "{target}");
+                let (last, rest) = match chunks.split_last() {
+                    Some(split) => split,
+                    None => continue,
+                };
+                assert!(!last.is_empty());
+                for chunk in rest {
+                    assert!(chunk.ends_with('\n'), "{target}");
+                }
+                // each cut is the newline nearest a cut point, so no piece is off by more than a
+                // line or two
+                if input == long && target >= 100 {
+                    for chunk in rest {
+                        assert!(
+                            chunk.len().abs_diff(target) < 80,
+                            "{target}: {}",
+                            chunk.len()
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Matching a small set of delimiters (`\n`, `\r`, `\0`, `\x1e`) in one pass, with a mask per
+//! delimiter class.
+//!
+//! The SIMD version looks up each byte's low and high nibble in a 16-entry table (`pshufb`) and
+//! ANDs the results. Every delimiter has a distinct low nibble, so the AND is exact: a byte gets
+//! its class bit, or 0. Adding a delimiter costs a table entry rather than another compare.
+
+/// Class bits, as stored in the lookup tables
+#[cfg(target_arch = "x86_64")]
+pub const NEWLINE: u8 = 1;
+#[cfg(target_arch = "x86_64")]
+pub const CR: u8 = 2;
+#[cfg(target_arch = "x86_64")]
+pub const NUL: u8 = 4;
+#[cfg(target_arch = "x86_64")]
+pub const RS: u8 = 8;
+
+/// Bit i of each mask is set if byte i of the block is in that class
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ClassMasks {
+    pub newline: u64,
+    pub cr: u64,
+    pub nul: u64,
+    pub rs: u64,
+}
+
+impl ClassMasks {
+    fn counts(&self) -> [usize; 4] {
+        [self.newline, self.cr, self.nul, self.rs].map(|mask| mask.count_ones() as usize)
+    }
+}
+
+/// Up to 64 bytes
+pub fn scalar(block: &[u8]) -> ClassMasks {
+    let mut masks = ClassMasks::default();
+    for (i, &b) in block.iter().enumerate() {
+        let mask = match b {
+            b'\n' => &mut masks.newline,
+            b'\r' => &mut masks.cr,
+            b'\0' => &mut masks.nul,
+            0x1e => &mut masks.rs,
+            _ => continue,
+        };
+        *mask |= 1 << i;
+    }
+    masks
+}
+
+/// Number of bytes in each class (in the order of the fields of [`ClassMasks`]), for the bench
+pub fn count_scalar(input: &[u8]) -> [usize; 4] {
+    add_counts(input.chunks(64).map(scalar))
+}
+
+fn add_counts(blocks: impl Iterator<Item = ClassMasks>) -> [usize; 4] {
+    blocks.fold([0; 4], |mut total, masks| {
+        for (total, count) in total.iter_mut().zip(masks.counts()) {
+            *total += count;
+        }
+        total
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use std::arch::x86_64::*;
+
+    /// Indexed by low nibble
+    const LO_LUT: [u8; 16] = {
+        let mut t = [0; 16];
+        t[0x0] = NUL;
+        t[0xa] = NEWLINE;
+        t[0xd] = CR;
+        t[0xe] = RS;
+        t
+    };
+    /// Indexed by high nibble
+    const HI_LUT: [u8; 16] = {
+        let mut t = [0; 16];
+        t[0x0] = NEWLINE | CR | NUL;
+        t[0x1] = RS;
+        t
+    };
+
+    /// Classifies the 64 bytes at `ptr` with the nibble tables
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn avx2_lut(ptr: *const u8) -> ClassMasks {
+        let lo_lut = _mm256_broadcastsi128_si256(_mm_loadu_si128(LO_LUT.as_ptr().cast()));
+        let hi_lut = _mm256_broadcastsi128_si256(_mm_loadu_si128(HI_LUT.as_ptr().cast()));
+        let nibble_v = _mm256_set1_epi8(0x0f);
+        let classes = [0, 32].map(|offset| {
+            let v = unsafe { _mm256_loadu_si256(ptr.add(offset).cast()) };
+            let lo = _mm256_and_si256(v, nibble_v);
+            // no 8-bit shift, so shift 16-bit lanes and mask off what came from the other byte
+            let hi = _mm256_and_si256(_mm256_srli_epi16::<4>(v), nibble_v);
+            _mm256_and_si256(
+                _mm256_shuffle_epi8(lo_lut, lo),
+                _mm256_shuffle_epi8(hi_lut, hi),
+            )
+        });
+        // each byte is either 0 or exactly one class bit
+        let mask = |class: u8| {
+            let class_v = _mm256_set1_epi8(class as i8);
+            let [a, b] = classes.map(|v| _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, class_v)));
+            ((b as u32 as u64) << 32) | a as u32 as u64
+        };
+        ClassMasks {
+            newline: mask(NEWLINE),
+            cr: mask(CR),
+            nul: mask(NUL),
+            rs: mask(RS),
+        }
+    }
+
+    /// The baseline for [`avx2_lut`]: one compare per delimiter
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn avx2_cmpeq(ptr: *const u8) -> ClassMasks {
+        let vs = [0, 32].map(|offset| unsafe { _mm256_loadu_si256(ptr.add(offset).cast()) });
+        let mask = |delimiter: u8| {
+            let delimiter_v = _mm256_set1_epi8(delimiter as i8);
+            let [a, b] = vs.map(|v| _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, delimiter_v)));
+            ((b as u32 as u64) << 32) | a as u32 as u64
+        };
+        ClassMasks {
+            newline: mask(b'\n'),
+            cr: mask(b'\r'),
+            nul: mask(b'\0'),
+            rs: mask(0x1e),
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn count_avx2_lut(input: &[u8]) -> [usize; 4] {
+        let blocks = input.chunks_exact(64);
+        let rest = scalar(blocks.remainder());
+        add_counts(
+            blocks
+                .map(|block| unsafe { avx2_lut(block.as_ptr()) })
+                .chain([rest]),
+        )
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn count_avx2_cmpeq(input: &[u8]) -> [usize; 4] {
+        let blocks = input.chunks_exact(64);
+        let rest = scalar(blocks.remainder());
+        add_counts(
+            blocks
+                .map(|block| unsafe { avx2_cmpeq(block.as_ptr()) })
+                .chain([rest]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar() {
+        let masks = scalar(b"a\n\r\0\x1e\x1a\x0e\n");
+        assert_eq!(
+            masks,
+            ClassMasks {
+                newline: 0b1000_0010,
+                cr: 0b100,
+                nul: 0b1000,
+                rs: 0b1_0000,
+            }
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        // every byte value, in a few different positions
+        let bytes: Vec<u8> = (0..=255u8).chain((0..=255u8).rev()).chain(0..=63).collect();
+        for block in bytes.windows(64).step_by(7) {
+            let expected = scalar(block);
+            assert_eq!(unsafe { x86_64::avx2_lut(block.as_ptr()) }, expected);
+            assert_eq!(unsafe { x86_64::avx2_cmpeq(block.as_ptr()) }, expected);
+        }
+        let expected = count_scalar(&bytes);
+        assert_eq!(unsafe { x86_64::count_avx2_lut(&bytes) }, expected);
+        assert_eq!(unsafe { x86_64::count_avx2_cmpeq(&bytes) }, expected);
+    }
+}
@@ -0,0 +1,153 @@
+//! Minimal argument handling for the subcommands. Options can appear anywhere after the
+//! subcommand name, as `--name value` or `--name=value`.
+
+use crate::encoding::{self, Encoding};
+use split_bench::blocks::LineBlocks;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+pub type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+pub struct Args(Vec<String>);
+
+impl Args {
+    pub fn from_env() -> Self {
+        Args(std::env::args().skip(1).collect())
+    }
+
+    /// Removes and returns the first argument if it isn't an option
+    pub fn subcommand(&mut self) -> Option<String> {
+        if self.0.first().is_some_and(|arg| !arg.starts_with('-')) {
+            Some(self.0.remove(0))
+        } else {
+            None
+        }
+    }
+
+    pub fn flag(&mut self, name: &str) -> bool {
+        let found = self.0.iter().position(|arg| arg == name);
+        found.map(|i| self.0.remove(i)).is_some()
+    }
+
+    pub fn value(&mut self, name: &str) -> Result<Option<String>> {
+        let Some(i) = self.0.iter().position(|arg| {
+            arg == name || arg.strip_prefix(name).is_some_and(|r| r.starts_with('='))
+        }) else {
+            return Ok(None);
+        };
+        let arg = self.0.remove(i);
+        if let Some(value) = arg.strip_prefix(name).and_then(|r| r.strip_prefix('=')) {
+            return Ok(Some(value.to_owned()));
+        }
+        if i == self.0.len() {
+            return Err(format!("`{name}` expects a value").into());
+        }
+        Ok(Some(self.0.remove(i)))
+    }
+
+    pub fn parsed<T: FromStr>(&mut self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: std::fmt::Display,
+    {
+        match self.value(name)? {
+            None => Ok(None),
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|err| format!("bad value `{value}` for `{name}`: {err}").into()),
+        }
+    }
+
+    /// Takes the next non-option argument. `what` is only used for the error message.
+    pub fn positional(&mut self, what: &str) -> Result<String> {
+        match self
+            .0
+            .iter()
+            .position(|arg| arg == "-" || !arg.starts_with('-'))
+        {
+            Some(i) => Ok(self.0.remove(i)),
+            None => Err(format!("missing {what}").into()),
+        }
+    }
+
+    /// Errors if any arguments weren't consumed
+    pub fn finish(self) -> Result {
+        match self.0.first() {
+            None => Ok(()),
+            Some(arg) => Err(format!("unexpected argument `{arg}`").into()),
+        }
+    }
+}
+
+/// Reads a whole file (or stdin for `-`) as UTF-8, dropping any BOM. UTF-16 files (detected by
+/// their BOM) are transcoded.
+pub fn read_input(path: &str) -> Result<String> {
+    let mut bytes = if path == "-" {
+        let mut buf = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut buf)?;
+        buf
+    } else {
+        std::fs::read(path).map_err(|err| format!("{path}: {err}"))?
+    };
+    let text = match encoding::sniff(&bytes) {
+        (Encoding::Utf8, bom_len) => {
+            bytes.drain(..bom_len);
+            String::from_utf8(bytes).map_err(|err| format!("{path}: {err}"))?
+        }
+        _ => {
+            let mut scratch = String::new();
+            encoding::decode(&bytes, &mut scratch).map_err(|err| format!("{path}: {err}"))?;
+            scratch
+        }
+    };
+    Ok(text)
+}
+
+/// Opens `path` for reading, or stdin for `-`
+pub fn input(path: &str) -> Result<Box<dyn Read>> {
+    Ok(if path == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::fs::File::open(path).map_err(|err| format!("{path}: {err}"))?)
+    })
+}
+
+/// Reads `input` in blocks of complete lines (see [`LineBlocks`]) as UTF-8 without its BOM,
+/// calling `block` with each. UTF-16 input (detected by its BOM) can't be split on `\n` bytes, so
+/// it's decoded in one go and passed as a single block. Returns the number of bytes read.
+pub fn for_each_block(input: impl Read, mut block: impl FnMut(&str) -> Result) -> Result<usize> {
+    let mut blocks = LineBlocks::new(input);
+    let Some(first) = blocks.next_block()? else {
+        return Ok(0);
+    };
+    let (encoding, bom_len) = encoding::sniff(first);
+    if encoding != Encoding::Utf8 {
+        let mut bytes = first.to_vec();
+        blocks.read_rest(&mut bytes)?;
+        let mut scratch = String::new();
+        block(encoding::decode(&bytes, &mut scratch)?)?;
+        return Ok(bytes.len());
+    }
+    let mut total = first.len();
+    block(std::str::from_utf8(&first[bom_len..])?)?;
+    while let Some(next) = blocks.next_block()? {
+        total += next.len();
+        block(std::str::from_utf8(next)?)?;
+    }
+    Ok(total)
+}
+
+/// Opens `path` for writing, or stdout if there is none
+pub fn output(path: Option<&str>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        None | Some("-") => Box::new(std::io::stdout().lock()),
+        Some(path) => {
+            Box::new(std::fs::File::create(path).map_err(|err| format!("{path}: {err}"))?)
+        }
+    })
+}
+
+/// MB/s, the unit used everywhere in the reports
+pub fn thrpt(bytes: usize, duration: std::time::Duration) -> f64 {
+    bytes as f64 / duration.as_secs_f64() / 1_000_000.
+}
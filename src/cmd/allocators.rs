@@ -0,0 +1,166 @@
+//! `allocators [--lines N] [--seed N] [--allocators system,mimalloc,jemalloc]`
+//!
+//! Do the kernels rank the same under another global allocator? Like `matrix`, this rebuilds the
+//! bench with `cargo` (from the source tree), once per allocator feature, in
+//! `target/allocators/<allocator>`, and runs each build's `allocators-run`. That times the cases
+//! that allocate their output as they go: the slice kernels into a new `Vec` each run (growing it
+//! with `reserve`), `std`'s `collect`, and a new `LineIndex`. A kernel writing into a reused `Vec`
+//! is the baseline that doesn't allocate at all.
+
+use crate::cli::{self, Args};
+use crate::cmd::matrix;
+use crate::rng::Rng;
+use crate::{compressed, slice, SliceSplitFn};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::Instant;
+
+const STAGES: &[(&str, crate::PrepFn)] = &[
+    ("0-0", |vec, lines, _| {
+        crate::prep_vec_fill(vec, lines, b'\n')
+    }),
+    ("1-20", crate::prep_vec_range::<1, 20>),
+    ("0-80", crate::prep_vec_range::<0, 80>),
+];
+
+/// Slice kernels timed into a new `Vec`
+const SLICE_CASES: &[(&str, SliceSplitFn)] = &[
+    ("std_reuse", slice::std_reuse),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2_unrollx4", slice::x86_64::sse2_unrollx4),
+    ("dispatch", slice::split),
+];
+
+/// The allocator every other one is compared to
+const REFERENCE: &str = "system";
+
+pub fn run(mut args: Args) -> cli::Result {
+    let lines: usize = args.parsed("--lines")?.unwrap_or(1_000_000);
+    let seed: u64 = args.parsed("--seed")?.unwrap_or(0);
+    let allocators = args
+        .value("--allocators")?
+        .unwrap_or_else(|| "system,mimalloc,jemalloc".to_owned());
+    args.finish()?;
+
+    // (kernel, stage, allocator) -> MB/s
+    let mut results = HashMap::new();
+    let mut kernels: Vec<String> = Vec::new();
+    let mut ran = Vec::new();
+    for allocator in allocators.split(',') {
+        let features: &[&str] = match allocator {
+            "system" => &[],
+            "mimalloc" | "jemalloc" => &[allocator],
+            _ => {
+                return Err(format!(
+                    "unknown allocator `{allocator}`, expected system, mimalloc or jemalloc"
+                )
+                .into())
+            }
+        };
+        let target_dir = format!(
+            "{}/target/allocators/{allocator}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        eprintln!("building with {allocator}");
+        let rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+        matrix::build(&target_dir, &rustflags, features)?;
+        eprintln!("running {allocator}");
+        for (kernel, stage, thrpt) in matrix::run_build(&target_dir, "allocators-run", lines, seed)?
+        {
+            if !kernels.contains(&kernel) {
+                kernels.push(kernel.clone());
+            }
+            results.insert((kernel, stage, allocator), thrpt);
+        }
+        ran.push(allocator);
+    }
+
+    for (stage, _) in STAGES {
+        println!("\n## {stage}, by allocator (MB/s, % of {REFERENCE})\n");
+        print!("| kernel |");
+        for allocator in &ran {
+            print!(" {allocator} |");
+        }
+        println!();
+        println!("| :-- |{}", " --: |".repeat(ran.len()));
+        for kernel in &kernels {
+            print!("| {kernel} |");
+            let get = |allocator: &str| {
+                results
+                    .get(&(kernel.to_owned(), stage.to_string(), allocator))
+                    .copied()
+            };
+            for &allocator in &ran {
+                match (get(allocator), get(REFERENCE)) {
+                    (Some(thrpt), Some(reference)) => {
+                        print!(" {thrpt:.0} ({:.0}%) |", thrpt / reference * 100.)
+                    }
+                    (Some(thrpt), None) => print!(" {thrpt:.0} |"),
+                    _ => print!(" - |"),
+                }
+            }
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// `allocators-run [--lines N] [--seed N]`, what [`run`] runs in each build. Prints one
+/// `kernel\tstage\tMB/s` line per measurement.
+pub fn run_child(mut args: Args) -> cli::Result {
+    let lines: usize = args.parsed("--lines")?.unwrap_or(1_000_000);
+    let seed = args.parsed("--seed")?.unwrap_or(0);
+    args.finish()?;
+
+    let sampling = crate::Sampling {
+        iterations: 5,
+        noise_threshold: 0.05,
+        max_reruns: 3,
+    };
+    let mut rng = Rng::new(seed);
+    let mut buf = Vec::new();
+    for (stage, prep) in STAGES {
+        let prepared = prep(&mut buf, lines, &mut rng);
+        let len = prepared.len;
+        let input = std::str::from_utf8(&buf[..len])?;
+        let expected_lines = prepared.lines(input);
+        // the drops are timed too, since giving a big block back is part of what differs
+        let time = |kernel: &str, run: &mut dyn FnMut() -> usize| -> cli::Result {
+            let mut found = 0;
+            let thrpt = crate::measure_thrpt(sampling, len, &mut || {
+                let start = Instant::now();
+                found = run();
+                start.elapsed()
+            })
+            .thrpt;
+            if found != expected_lines {
+                return Err(format!(
+                    "{kernel} found {found} lines instead of {expected_lines} in {stage}"
+                )
+                .into());
+            }
+            println!("{kernel}\t{stage}\t{thrpt:.0}");
+            Ok(())
+        };
+        let mut reused = Vec::new();
+        time("dispatch, reused Vec", &mut || {
+            reused.clear();
+            slice::split(input, &mut reused);
+            black_box(&mut reused).len()
+        })?;
+        for (kernel, split) in SLICE_CASES {
+            time(kernel, &mut || {
+                let mut lines = Vec::new();
+                split(input, &mut lines);
+                black_box(lines).len()
+            })?;
+        }
+        time("std (collect)", &mut || black_box(slice::std(input)).len())?;
+        time("compressed", &mut || {
+            let mut index = compressed::LineIndex::default();
+            compressed::build(input, &mut index);
+            compressed::NewlineIndex::line_count(&black_box(index), len)
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! `batch <input> [--max-bytes N] [-o output]`
+//!
+//! Cuts the input into batches of whole lines of at most N bytes each (default 64 KiB), the way a
+//! framing layer fills network messages, with [`slice::split_lines_within`]. Each batch is
+//! written as its length in bytes (decimal, on its own line) followed by its lines, written out
+//! one by one from the slices `split_lines_within` returns. A line longer than N gets a batch of
+//! its own, and a last line without a newline is sent as it is.
+
+use crate::cli::{self, Args};
+use crate::slice;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let max_bytes = args.parsed("--max-bytes")?.unwrap_or(64 * 1024usize).max(1);
+    let output = args.value("-o")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = cli::read_input(&path)?;
+    let mut out = BufWriter::with_capacity(1 << 20, cli::output(output.as_deref())?);
+
+    let start = Instant::now();
+    let (mut batches, mut lines, mut oversized) = (0, 0, 0);
+    let mut rest = input.as_str();
+    while !rest.is_empty() {
+        let (batch_lines, next) = slice::split_lines_within(rest, max_bytes);
+        if batch_lines.is_empty() {
+            // the next line doesn't fit on its own (or is the unterminated last one)
+            let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            oversized += usize::from(end > max_bytes);
+            lines += 1;
+            writeln!(out, "{end}")?;
+            out.write_all(&rest.as_bytes()[..end])?;
+            rest = &rest[end..];
+        } else {
+            // every line of the batch had a newline, which the slices leave out
+            writeln!(out, "{}", rest.len() - next.len())?;
+            for line in &batch_lines {
+                out.write_all(line.as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+            lines += batch_lines.len();
+            rest = next;
+        }
+        batches += 1;
+    }
+    out.flush()?;
+    eprintln!(
+        "{lines} lines in {batches} batches ({oversized} lines over {max_bytes} bytes), {:.0} MB/s",
+        cli::thrpt(input.len(), start.elapsed())
+    );
+    Ok(())
+}
@@ -0,0 +1,174 @@
+//! `features [--prefer KERNEL,...]`
+//!
+//! What this build can run here: the cargo features and target features it was compiled with,
+//! the CPU features each bench case needs and whether they were detected (the bench's "Skipped"
+//! table, without running it), and the kernel `slice::split` dispatches to, by input size.
+
+use crate::cli::{self, Args};
+use crate::dispatch::{CpuFamily, Dispatcher};
+use crate::Features;
+
+/// Runtime-detected features the bench cases ask for, see [`crate::has_feature`]
+const CPU_FEATURES: &[&str] = &[
+    #[cfg(target_arch = "x86_64")]
+    "popcnt",
+    #[cfg(target_arch = "x86_64")]
+    "bmi1",
+    #[cfg(target_arch = "x86_64")]
+    "bmi2",
+    #[cfg(target_arch = "x86_64")]
+    "avx2",
+    #[cfg(target_arch = "x86_64")]
+    "avx512f",
+    #[cfg(target_arch = "x86_64")]
+    "avx512bw",
+    #[cfg(target_arch = "x86_64")]
+    "avx512vbmi2",
+    #[cfg(target_arch = "aarch64")]
+    "sve",
+    #[cfg(target_arch = "loongarch64")]
+    "lsx",
+    #[cfg(target_arch = "loongarch64")]
+    "lasx",
+    #[cfg(feature = "gpu")]
+    "gpu",
+];
+
+/// Input sizes the dispatch table is printed for, as (label, largest size in the bucket)
+const SIZE_BUCKETS: [(&str, usize); 4] = [
+    ("under 16 B", 15),
+    ("16 to 63 B", 63),
+    ("64 B to 64 KiB", 64 * 1024),
+    ("over 64 KiB", usize::MAX),
+];
+
+pub fn run(mut args: Args) -> cli::Result {
+    let mut dispatcher = Dispatcher::builder();
+    if let Some(prefer) = args.value("--prefer")? {
+        for kernel in prefer.split(',') {
+            dispatcher =
+                dispatcher.prefer(kernel.parse().map_err(|err| format!("--prefer: {err}"))?);
+        }
+    }
+    args.finish()?;
+    let dispatcher = dispatcher.build();
+
+    println!("## Build\n");
+    println!("| | |");
+    println!("| :-- | :-- |");
+    println!("| target | {} |", std::env::consts::ARCH);
+    let cargo_features = [
+        ("memchr", cfg!(feature = "memchr")),
+        ("utf16", cfg!(feature = "utf16")),
+        ("gpu", cfg!(feature = "gpu")),
+        ("grep", cfg!(feature = "grep")),
+        ("asm", cfg!(feature = "asm")),
+        ("mimalloc", cfg!(feature = "mimalloc")),
+        ("jemalloc", cfg!(feature = "jemalloc")),
+    ];
+    println!("| cargo features | {} |", enabled(&cargo_features));
+    println!("| allocator | {} |", crate::alloc::NAME);
+    println!(
+        "| target features | {} |",
+        enabled(&compiled_target_features())
+    );
+
+    println!("\n## CPU ({:?})\n", CpuFamily::detect());
+    println!("| feature | detected |");
+    println!("| :-- | :-- |");
+    for feature in CPU_FEATURES {
+        println!("| {feature} | {} |", yes_no(crate::has_feature(feature)));
+    }
+
+    println!("\n## Bench cases needing CPU features\n");
+    println!("| table | algo | needs | runs |");
+    println!("| :-- | :-- | :-- | :-- |");
+    let tables: [(&str, Vec<(&str, Features)>); 13] = [
+        ("slice", cases(crate::SLICE_BENCH_CASES)),
+        ("compressed", cases(crate::COMPRESSED_BENCH_CASES)),
+        ("flat u32", cases(crate::FLAT_BENCH_CASES)),
+        ("ranges", cases(crate::RANGES_BENCH_CASES)),
+        ("classify", cases(crate::CLASSIFY_BENCH_CASES)),
+        ("line stats", cases(crate::LINE_STATS_BENCH_CASES)),
+        ("paragraphs", cases(crate::PARAGRAPH_BENCH_CASES)),
+        ("crlf", cases(crate::CRLF_BENCH_CASES)),
+        ("delimiter", cases(crate::DELIM_BENCH_CASES)),
+        ("delimiter set", cases(crate::DELIM_SET_BENCH_CASES)),
+        ("inclusive", cases(crate::INCLUSIVE_BENCH_CASES)),
+        ("substring", cases(crate::SUBSTRING_BENCH_CASES)),
+        ("nul", cases(crate::NUL_BENCH_CASES)),
+    ];
+    for (table, cases) in &tables {
+        for (algo, features) in cases.iter().filter(|(_, features)| !features.is_empty()) {
+            let missing = crate::missing_features(features);
+            let runs = match missing.is_empty() {
+                true => "yes".to_owned(),
+                false => format!("no, missing {}", missing.join(", ")),
+            };
+            println!("| {table} | {algo} | {} | {runs} |", features.join(", "));
+        }
+    }
+
+    println!("\n## slice::split dispatch\n");
+    println!("{dispatcher}\n");
+    println!("| input size | kernel |");
+    println!("| :-- | :-- |");
+    let kernel = dispatcher.kernel();
+    for (label, max_len) in SIZE_BUCKETS {
+        let runs = match kernel.chunk_len() {
+            Some(chunk_len) if max_len < chunk_len => {
+                format!("{}, all in its tail", kernel.name())
+            }
+            _ => kernel.name().to_owned(),
+        };
+        println!("| {label} | {runs} |");
+    }
+    Ok(())
+}
+
+fn cases<F>(cases: &[(&'static str, Features, F)]) -> Vec<(&'static str, Features)> {
+    cases
+        .iter()
+        .map(|(algo, features, _)| (*algo, *features))
+        .collect()
+}
+
+/// The target features the kernels use that were enabled at compile time (`-C target-cpu` or
+/// `-C target-feature`), which the kernels still detect at runtime
+fn compiled_target_features() -> Vec<(&'static str, bool)> {
+    vec![
+        ("sse2", cfg!(target_feature = "sse2")),
+        ("sse4.2", cfg!(target_feature = "sse4.2")),
+        ("popcnt", cfg!(target_feature = "popcnt")),
+        ("bmi1", cfg!(target_feature = "bmi1")),
+        ("bmi2", cfg!(target_feature = "bmi2")),
+        ("avx2", cfg!(target_feature = "avx2")),
+        ("avx512f", cfg!(target_feature = "avx512f")),
+        ("avx512bw", cfg!(target_feature = "avx512bw")),
+        ("avx512vbmi2", cfg!(target_feature = "avx512vbmi2")),
+        ("neon", cfg!(target_feature = "neon")),
+        ("sve", cfg!(target_feature = "sve")),
+        ("v", cfg!(target_feature = "v")),
+        ("lsx", cfg!(target_feature = "lsx")),
+        ("lasx", cfg!(target_feature = "lasx")),
+    ]
+}
+
+fn enabled(flags: &[(&str, bool)]) -> String {
+    let names: Vec<&str> = flags
+        .iter()
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| *name)
+        .collect();
+    match names.is_empty() {
+        true => "none".to_owned(),
+        false => names.join(", "),
+    }
+}
+
+fn yes_no(b: bool) -> &'static str {
+    match b {
+        true => "yes",
+        false => "no",
+    }
+}
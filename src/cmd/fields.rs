@@ -0,0 +1,111 @@
+//! `fields [--lines N] [--seed N] [--iterations N] [--delimiter tab|comma]`
+//!
+//! Times building a `fields::FieldIndex` of generated TSV (or CSV) records in one pass over the
+//! input, finding both delimiters in each chunk, against a pass per delimiter and the walk linking
+//! them. Each stage has a fixed number of fields per record, of random lengths.
+
+use crate::cli::{self, Args};
+use crate::fields::{self, FieldIndex, FieldsFn};
+use crate::rng::Rng;
+use std::time::Instant;
+
+/// (label, fields per record, longest field)
+const STAGES: &[(&str, usize, usize)] = &[
+    ("4 x 0-4", 4, 4),
+    ("8 x 0-12", 8, 12),
+    ("16 x 0-24", 16, 24),
+    ("4 x 0-80", 4, 80),
+];
+
+const CASES: &[(&str, crate::Features, FieldsFn)] = &[
+    ("one pass scalar", &[], fields::scalar),
+    #[cfg(target_arch = "x86_64")]
+    ("one pass avx2", crate::AVX2, fields::x86_64::avx2),
+    #[cfg(target_arch = "x86_64")]
+    ("two passes avx2", crate::AVX2, fields::x86_64::two_passes),
+];
+
+pub fn run(mut args: Args) -> cli::Result {
+    let lines: usize = args.parsed("--lines")?.unwrap_or(1_000_000);
+    let seed: u64 = args.parsed("--seed")?.unwrap_or(0);
+    let iterations: usize = args.parsed("--iterations")?.unwrap_or(5).max(1);
+    let delimiter = match args.value("--delimiter")?.as_deref() {
+        None | Some("tab") => b'\t',
+        Some("comma") => b',',
+        Some(other) => {
+            return Err(format!("unknown delimiter `{other}`, expected tab or comma").into())
+        }
+    };
+    args.finish()?;
+
+    let cases: Vec<_> = CASES
+        .iter()
+        .filter(|(_, features, _)| crate::missing_features(features).is_empty())
+        .collect();
+    let sampling = crate::Sampling {
+        iterations,
+        noise_threshold: 0.05,
+        max_reruns: 0,
+    };
+    let mut rng = Rng::new(seed);
+    // per stage, MB/s per case
+    let mut results = Vec::new();
+    for &(stage, fields_per_record, longest) in STAGES {
+        let mut input = String::new();
+        for _ in 0..lines {
+            for field in 0..fields_per_record {
+                if field > 0 {
+                    input.push(delimiter as char);
+                }
+                input.extend(std::iter::repeat_n('a', rng.below(longest + 1)));
+            }
+            input.push('\n');
+        }
+        eprintln!("{stage}: {:.0} MB", input.len() as f64 / 1_000_000.);
+
+        let mut expected = None;
+        let mut thrpts = Vec::new();
+        let mut out = FieldIndex::default();
+        for (case, _, build) in &cases {
+            let thrpt = crate::measure_thrpt(sampling, input.len(), &mut || {
+                let start = Instant::now();
+                unsafe { build(&input, delimiter, &mut out) };
+                start.elapsed()
+            })
+            .thrpt;
+            if *expected.get_or_insert_with(|| out.clone()) != out {
+                return Err(format!("{case} built another index for {stage}").into());
+            }
+            thrpts.push(thrpt);
+        }
+        // and through the dispatching `build`, reading back a few random fields
+        fields::build(&input, delimiter, &mut out);
+        if Some(&out) != expected.as_ref() || out.record_count() != lines {
+            return Err(format!("fields::build built another index for {stage}").into());
+        }
+        for _ in 0..1000 {
+            let record = rng.below(lines);
+            let field = rng.below(fields_per_record);
+            assert_eq!(out.field_count(record), fields_per_record);
+            let text = out.field(&input, record, field).unwrap();
+            assert!(text.len() <= longest && text.bytes().all(|byte| byte == b'a'));
+        }
+        results.push(thrpts);
+    }
+
+    println!("\n## Fields (MB/s)\n");
+    print!("| case |");
+    for (stage, ..) in STAGES {
+        print!(" {stage} |");
+    }
+    println!();
+    println!("| :-- |{}", " --: |".repeat(STAGES.len()));
+    for (idx, (case, ..)) in cases.iter().enumerate() {
+        print!("| {case} |");
+        for thrpts in &results {
+            print!(" {:.0} |", thrpts[idx]);
+        }
+        println!();
+    }
+    Ok(())
+}
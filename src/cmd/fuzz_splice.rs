@@ -0,0 +1,112 @@
+//! `fuzz-splice [--runs N] [--seed N] [--max-len N]`
+//!
+//! Fuzzes the incremental reindex, [`flat::splice`]. Each run generates a valid input of up to
+//! `--max-len` bytes and a list of mutations (newline-heavy insertions and deletions at random
+//! offsets), applies them one by one while updating the index with `splice`, and compares it to a
+//! full rebuild after every mutation. Run `i` is seeded with `seed + i`, so a failing run is
+//! reproduced on its own with the seed it reports and `--runs 1`.
+
+use crate::cli::{self, Args};
+use crate::flat;
+use crate::rng::Rng;
+use std::ops::Range;
+
+/// Most mutations applied to one input
+const MAX_MUTATIONS: usize = 32;
+
+#[derive(Debug)]
+enum Mutation {
+    Insert { at: usize, text: String },
+    Delete { range: Range<usize> },
+}
+
+pub fn run(mut args: Args) -> cli::Result {
+    let runs: u64 = args.parsed("--runs")?.unwrap_or(10_000);
+    let seed = args.parsed("--seed")?.unwrap_or_else(Rng::seed_from_time);
+    let max_len = args.parsed("--max-len")?.unwrap_or(4096);
+    args.finish()?;
+
+    let mut mutations = 0;
+    for i in 0..runs {
+        let run_seed = seed.wrapping_add(i);
+        mutations += fuzz_one(run_seed, max_len)
+            .map_err(|err| format!("{err}\nreproduce with --seed {run_seed} --runs 1"))?;
+    }
+    println!("{runs} runs from seed {seed}, {mutations} mutations, all matched a full rebuild");
+    Ok(())
+}
+
+/// One run, returning how many mutations it applied
+fn fuzz_one(seed: u64, max_len: usize) -> Result<usize, String> {
+    let mut rng = Rng::new(seed);
+    let len = rng.below(max_len + 1);
+    let mut input = random_text(&mut rng, len, 8);
+    let mut index = Vec::new();
+    flat::iter(&input, &mut index);
+    let count = 1 + rng.below(MAX_MUTATIONS);
+    let mut applied = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mutation = random_mutation(&mut rng, input.len());
+        let (old, new_len) = match &mutation {
+            Mutation::Insert { at, text } => {
+                input.insert_str(*at, text);
+                (*at..*at, text.len())
+            }
+            Mutation::Delete { range } => {
+                input.replace_range(range.clone(), "");
+                (range.clone(), 0)
+            }
+        };
+        flat::splice(&mut index, &input, old, new_len);
+        applied.push(mutation);
+        let mut expected = Vec::new();
+        flat::iter(&input, &mut expected);
+        if index != expected {
+            return Err(format!(
+                "splice disagrees with a full rebuild after {} mutations of a {len}-byte input: \
+                 {applied:?}\nspliced: {index:?}\nrebuilt: {expected:?}",
+                applied.len()
+            ));
+        }
+    }
+    Ok(count)
+}
+
+fn random_mutation(rng: &mut Rng, len: usize) -> Mutation {
+    let at = rng.below(len + 1);
+    if len == 0 || rng.below(2) == 0 {
+        let text_len = 1 + rng.below(8);
+        Mutation::Insert {
+            at,
+            text: random_text(rng, text_len, 2),
+        }
+    } else {
+        let at = at.min(len - 1);
+        let end = (at + 1 + rng.below(16)).min(len);
+        Mutation::Delete { range: at..end }
+    }
+}
+
+/// ASCII, so every offset is a char boundary, with a newline for one byte in `one_in`
+fn random_text(rng: &mut Rng, len: usize, one_in: usize) -> String {
+    (0..len)
+        .map(|_| match rng.below(one_in) {
+            0 => '\n',
+            _ => (b'a' + rng.below(26) as u8) as char,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_splice() {
+        for seed in 0..500 {
+            if let Err(err) = fuzz_one(seed, 300) {
+                panic!("seed {seed}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! `history <file> [--table T] [--algo A] [--stage S] [--lines N]`
+//!
+//! Summarizes a file written by the bench's `--history`: for each kernel in each stage, the
+//! first, last and best throughput over the recorded runs, the change from first to last, and a
+//! sparkline of the latest runs. Stages are sized by `--lines`, so runs with different line counts
+//! shouldn't be mixed; `--lines` picks one.
+
+use crate::cli::{self, Args};
+use crate::history::{self, Record};
+
+/// Runs shown in the sparkline
+const TREND_RUNS: usize = 16;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let table = args.value("--table")?;
+    let algo = args.value("--algo")?;
+    let stage = args.value("--stage")?;
+    let lines: Option<usize> = args.parsed("--lines")?;
+    let path = args.positional("history file")?;
+    args.finish()?;
+
+    let records: Vec<Record> = history::load(&path)?
+        .into_iter()
+        .filter(|record| {
+            table.as_ref().is_none_or(|table| record.table == *table)
+                && algo.as_ref().is_none_or(|algo| record.algo == *algo)
+                && stage.as_ref().is_none_or(|stage| record.stage == *stage)
+                && lines.is_none_or(|lines| record.run.lines == lines)
+        })
+        .collect();
+
+    let mut line_counts: Vec<usize> = records.iter().map(|record| record.run.lines).collect();
+    line_counts.sort_unstable();
+    line_counts.dedup();
+    if line_counts.len() > 1 {
+        eprintln!("warning: mixing runs with --lines {line_counts:?}, pick one with --lines");
+    }
+    let mut runs: Vec<u64> = records.iter().map(|record| record.run.time).collect();
+    runs.sort_unstable();
+    runs.dedup();
+    eprintln!("{} runs, {} records", runs.len(), records.len());
+
+    // (table, algo, stage) -> throughputs in file order, keeping the order things first appeared
+    let mut series: Vec<(&Record, Vec<f64>)> = Vec::new();
+    for record in &records {
+        let key = |other: &Record| {
+            (other.table == record.table)
+                && (other.algo == record.algo)
+                && (other.stage == record.stage)
+        };
+        match series.iter_mut().find(|(first, _)| key(first)) {
+            Some((_, thrpts)) => thrpts.push(record.thrpt),
+            None => series.push((record, vec![record.thrpt])),
+        }
+    }
+
+    println!("| table | algo | stage | runs | first | last | best | change | trend |");
+    println!("| :-- | :-- | :-- | --: | --: | --: | --: | --: | :-- |");
+    for (record, thrpts) in &series {
+        let first = thrpts[0];
+        let last = thrpts[thrpts.len() - 1];
+        let best = thrpts.iter().copied().fold(0., f64::max);
+        println!(
+            "| {} | {} | {} | {} | {first:.0} | {last:.0} | {best:.0} | {:+.1}% | {} |",
+            record.table,
+            record.algo,
+            record.stage,
+            thrpts.len(),
+            100. * (last / first - 1.),
+            sparkline(&thrpts[thrpts.len().saturating_sub(TREND_RUNS)..]),
+        );
+    }
+    Ok(())
+}
+
+/// One bar per value, scaled between the smallest and largest of them
+fn sparkline(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(0., f64::max);
+    values
+        .iter()
+        .map(|&value| {
+            let level = if max > min {
+                (value - min) / (max - min) * (BARS.len() - 1) as f64
+            } else {
+                0.
+            };
+            BARS[level.round() as usize]
+        })
+        .collect()
+}
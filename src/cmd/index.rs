@@ -0,0 +1,46 @@
+//! `index <input or .lidx file> [--save out.lidx]`
+//!
+//! Prints how the lines of an input are spread over its 64 KiB blocks: the total, the densest
+//! and sparsest block, and a histogram of newlines per block. Given a `.lidx` file (recognized by
+//! its magic), the saved index is summarized as is, which is a quick way to sanity-check one.
+//! Given text, the index is built with the fastest compressed kernel, and `--save` writes it out.
+
+use crate::cli::{self, Args};
+use crate::compressed::{self, LineIndex};
+use crate::lidx;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+pub fn run(mut args: Args) -> cli::Result {
+    let save = args.value("--save")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let is_lidx = {
+        let mut file = std::fs::File::open(&path).map_err(|err| format!("{path}: {err}"))?;
+        let mut magic = [0; 4];
+        file.read(&mut magic)? == magic.len() && &magic == lidx::MAGIC
+    };
+
+    let (index, input_len) = if is_lidx {
+        if save.is_some() {
+            return Err(format!("{path} is already a .lidx file").into());
+        }
+        let file = std::fs::File::open(&path).map_err(|err| format!("{path}: {err}"))?;
+        lidx::read(&mut BufReader::new(file)).map_err(|err| format!("{path}: {err}"))?
+    } else {
+        let input = cli::read_input(&path)?;
+        let mut index = LineIndex::default();
+        compressed::build(&input, &mut index);
+        index.shrink_to_fit();
+        if let Some(save) = save {
+            let mut out = BufWriter::new(cli::output(Some(&save))?);
+            lidx::write(&index, input.len(), &mut out)?;
+            out.flush()?;
+        }
+        (index, input.len())
+    };
+
+    print!("{}", lidx::Summary::new(&index, input_len));
+    println!("memory    : {}", index.memory_usage());
+    Ok(())
+}
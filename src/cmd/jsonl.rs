@@ -0,0 +1,47 @@
+//! `jsonl <input> [--filter] [-o output]`
+//!
+//! Counts the lines of a JSON Lines file that look like JSON values, timing the fused kernel
+//! against split-then-check. With `--filter`, writes only those lines.
+
+use crate::cli::{self, Args};
+use crate::jsonl;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let filter = args.flag("--filter");
+    let output = args.value("-o")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = cli::read_input(&path)?;
+    let (mut lines, mut flags) = (Vec::new(), Vec::new());
+
+    let start = Instant::now();
+    jsonl::two_pass(&input, &mut lines, &mut flags);
+    let two_pass_time = start.elapsed();
+
+    lines.clear();
+    flags.clear();
+    let start = Instant::now();
+    jsonl::split(&input, &mut lines, &mut flags);
+    let fused_time = start.elapsed();
+
+    let valid = flags.iter().filter(|&&flag| flag).count();
+    eprintln!("{valid} of {} lines look like JSON", lines.len());
+    eprintln!(
+        "fused: {:.0} MB/s, two pass: {:.0} MB/s",
+        cli::thrpt(input.len(), fused_time),
+        cli::thrpt(input.len(), two_pass_time),
+    );
+
+    if filter {
+        let mut out = BufWriter::with_capacity(1 << 20, cli::output(output.as_deref())?);
+        for (line, _) in lines.iter().zip(&flags).filter(|(_, &flag)| flag) {
+            out.write_all(line.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! `langs [--lines N] [--seed N] [--iterations N] [--langs cpp,go,python]`
+//!
+//! How do the kernels compare to splitting in another language? This writes each stage's input to
+//! `target/langs/` (under the source tree, like `matrix`), builds the small programs in `langs/`
+//! (C++ with `$CXX` or `c++`, Go with `go`) and runs them on those files, next to the Rust cases
+//! timed in this process on the same bytes. Every program reads the whole file first, then times
+//! `--iterations` splits of it and prints its line count and median time, so only the split is
+//! compared. A language whose toolchain isn't installed is skipped.
+
+use crate::cli::{self, Args};
+use crate::rng::Rng;
+use crate::{slice, SliceSplitFn};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const STAGES: &[(&str, crate::PrepFn)] = &[
+    ("1-20", crate::prep_vec_range::<1, 20>),
+    ("0-80", crate::prep_vec_range::<0, 80>),
+    ("40-120", crate::prep_vec_range::<40, 120>),
+];
+
+/// The case every other one is compared to
+const REFERENCE: &str = "rust dispatch";
+
+pub fn run(mut args: Args) -> cli::Result {
+    let lines: usize = args.parsed("--lines")?.unwrap_or(1_000_000);
+    let seed: u64 = args.parsed("--seed")?.unwrap_or(0);
+    let iterations: usize = args.parsed("--iterations")?.unwrap_or(5).max(1);
+    let langs = args
+        .value("--langs")?
+        .unwrap_or_else(|| "cpp,go,python".to_owned());
+    args.finish()?;
+
+    let source_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("langs");
+    let work_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/langs");
+    std::fs::create_dir_all(&work_dir)?;
+    // (case, the command running it, minus its arguments)
+    let mut programs = Vec::new();
+    for lang in langs.split(',') {
+        let program = match lang {
+            "cpp" => {
+                let cxx = std::env::var("CXX").unwrap_or_else(|_| "c++".to_owned());
+                let binary = work_dir.join("split-cpp");
+                let built = Command::new(&cxx)
+                    .args(["-O3", "-march=native", "-std=c++17", "-o"])
+                    .arg(&binary)
+                    .arg(source_dir.join("split.cpp"))
+                    .status();
+                built.ok().filter(|status| status.success()).map(|_| {
+                    (
+                        "c++ simdjson-style",
+                        vec![binary.to_string_lossy().into_owned()],
+                    )
+                })
+            }
+            "go" => {
+                let binary = work_dir.join("split-go");
+                let built = Command::new("go")
+                    .args(["build", "-o"])
+                    .arg(&binary)
+                    .arg(source_dir.join("split.go"))
+                    .status();
+                built.ok().filter(|status| status.success()).map(|_| {
+                    (
+                        "go bytes.Split",
+                        vec![binary.to_string_lossy().into_owned()],
+                    )
+                })
+            }
+            "python" => Command::new("python3")
+                .arg("--version")
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|_| {
+                    (
+                        "python splitlines",
+                        vec![
+                            "python3".to_owned(),
+                            source_dir.join("split.py").to_string_lossy().into_owned(),
+                        ],
+                    )
+                }),
+            _ => {
+                return Err(format!("unknown language `{lang}`, expected cpp, go or python").into())
+            }
+        };
+        match program {
+            Some(program) => programs.push(program),
+            None => eprintln!("skipping {lang}, its toolchain is missing or the build failed"),
+        }
+    }
+
+    let sampling = crate::Sampling {
+        iterations,
+        noise_threshold: 0.05,
+        max_reruns: 0,
+    };
+    let rust_cases: &[(&str, SliceSplitFn)] = &[
+        (REFERENCE, slice::split),
+        ("rust std", |input, _| {
+            black_box(slice::std(input));
+        }),
+    ];
+    let mut cases: Vec<&str> = rust_cases.iter().map(|case| case.0).collect();
+    cases.extend(programs.iter().map(|program| program.0));
+    // (case, stage) -> MB/s
+    let mut results = HashMap::new();
+    let mut rng = Rng::new(seed);
+    let mut buf = Vec::new();
+    for (stage, prep) in STAGES {
+        let prepared = prep(&mut buf, lines, &mut rng);
+        let len = prepared.len;
+        let input = std::str::from_utf8(&buf[..len])?;
+        let expected_lines = prepared.lines(input);
+        let path = work_dir.join(format!("{stage}.txt"));
+        std::fs::write(&path, input)?;
+        eprintln!("{stage}: {:.0} MB", len as f64 / 1_000_000.);
+
+        let mut lines = Vec::new();
+        for (case, split) in rust_cases {
+            let thrpt = crate::measure_thrpt(sampling, len, &mut || {
+                lines.clear();
+                let start = Instant::now();
+                split(input, &mut lines);
+                start.elapsed()
+            })
+            .thrpt;
+            results.insert((*case, *stage), thrpt);
+        }
+        for (case, command) in &programs {
+            let output = Command::new(&command[0])
+                .args(&command[1..])
+                .arg(&path)
+                .arg(iterations.to_string())
+                .output()?;
+            if !output.status.success() {
+                return Err(format!(
+                    "{case} failed on {stage}: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )
+                .into());
+            }
+            let stdout = String::from_utf8(output.stdout)?;
+            let (Some(found), Some(nanos)) = stdout
+                .trim_end()
+                .split_once('\t')
+                .map_or((None, None), |(found, nanos)| {
+                    (found.parse::<usize>().ok(), nanos.parse().ok())
+                })
+            else {
+                return Err(format!("unexpected output from {case}: `{stdout}`").into());
+            };
+            // a program that splits differently would be timing another problem
+            if found != expected_lines {
+                return Err(format!(
+                    "{case} found {found} lines instead of {expected_lines} in {stage}"
+                )
+                .into());
+            }
+            results.insert(
+                (*case, *stage),
+                cli::thrpt(len, Duration::from_nanos(nanos)),
+            );
+        }
+    }
+
+    println!("\n## Across languages (MB/s, % of {REFERENCE})\n");
+    print!("| case |");
+    for (stage, _) in STAGES {
+        print!(" {stage} |");
+    }
+    println!();
+    println!("| :-- |{}", " --: |".repeat(STAGES.len()));
+    for case in &cases {
+        print!("| {case} |");
+        for (stage, _) in STAGES {
+            let thrpt = results[&(*case, *stage)];
+            let reference = results[&(REFERENCE, *stage)];
+            print!(" {thrpt:.0} ({:.0}%) |", thrpt / reference * 100.);
+        }
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! `matrix [--lines N] [--seed N] [--levels v1,v2,v3,v4]`
+//!
+//! How much of the hand-written kernels' lead can the compiler make up for a binary built for a
+//! newer CPU? This rebuilds the bench once per `-C target-cpu=x86-64-vN` level with `cargo` (so
+//! it only works from the source tree, and the first run takes a while), in
+//! `target/matrix/<level>`, and runs each build's `matrix-run`, which times the portable kernels
+//! and the dispatched one. The portable ones are whatever LLVM autovectorizes for that level; the
+//! dispatched one picks its kernel at runtime, so it should be the same on every row. Levels the
+//! CPU can't run are skipped.
+
+use crate::cli::{self, Args};
+use crate::rng::Rng;
+use crate::{compressed, flat, line_stats, paragraph, slice};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::process::Command;
+use std::time::Instant;
+
+const STAGES: &[(&str, crate::PrepFn)] = &[
+    ("1-20", crate::prep_vec_range::<1, 20>),
+    ("0-80", crate::prep_vec_range::<0, 80>),
+    ("40-120", crate::prep_vec_range::<40, 120>),
+];
+
+/// The kernel every other one is compared to
+const REFERENCE: &str = "dispatch";
+
+pub fn run(mut args: Args) -> cli::Result {
+    let lines: usize = args.parsed("--lines")?.unwrap_or(1_000_000);
+    let seed: u64 = args.parsed("--seed")?.unwrap_or(0);
+    let levels = args
+        .value("--levels")?
+        .unwrap_or_else(|| "v1,v2,v3,v4".to_owned());
+    args.finish()?;
+
+    // (kernel, stage, level) -> MB/s
+    let mut results = HashMap::new();
+    let mut kernels: Vec<String> = Vec::new();
+    let mut ran = Vec::new();
+    for level in levels.split(',') {
+        let target_cpu = match level {
+            "v1" => "x86-64".to_owned(),
+            "v2" | "v3" | "v4" => format!("x86-64-{level}"),
+            _ => return Err(format!("unknown level `{level}`, expected v1 to v4").into()),
+        };
+        if !can_run(level) {
+            eprintln!("skipping {level}, this CPU can't run it");
+            continue;
+        }
+        let target_dir = format!("{}/target/matrix/{level}", env!("CARGO_MANIFEST_DIR"));
+        eprintln!("building for {target_cpu}");
+        build(&target_dir, &format!("-C target-cpu={target_cpu}"), &[])?;
+        eprintln!("running {level}");
+        for (kernel, stage, thrpt) in run_build(&target_dir, "matrix-run", lines, seed)? {
+            if !kernels.contains(&kernel) {
+                kernels.push(kernel.clone());
+            }
+            results.insert((kernel, stage, level), thrpt);
+        }
+        ran.push(level);
+    }
+
+    for (stage, _) in STAGES {
+        println!("\n## {stage}, by target-cpu (MB/s, % of {REFERENCE})\n");
+        print!("| kernel |");
+        for level in &ran {
+            print!(" {level} |");
+        }
+        println!();
+        println!("| :-- |{}", " --: |".repeat(ran.len()));
+        for kernel in &kernels {
+            print!("| {kernel} |");
+            for &level in &ran {
+                let get = |kernel: &str| {
+                    results
+                        .get(&(kernel.to_owned(), stage.to_string(), level))
+                        .copied()
+                };
+                match (get(kernel), get(REFERENCE)) {
+                    (Some(thrpt), Some(reference)) => {
+                        print!(" {thrpt:.0} ({:.0}%) |", thrpt / reference * 100.)
+                    }
+                    _ => print!(" - |"),
+                }
+            }
+            println!();
+        }
+    }
+    Ok(())
+}
+
+/// Builds the bench in release mode into `target_dir`, with `rustflags` and the cargo `features`
+pub fn build(target_dir: &str, rustflags: &str, features: &[&str]) -> cli::Result {
+    let mut cargo = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()));
+    cargo
+        .args(["build", "--release", "--target-dir", target_dir])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("RUSTFLAGS", rustflags);
+    if !features.is_empty() {
+        cargo.args(["--features", &features.join(",")]);
+    }
+    let status = cargo
+        .status()
+        .map_err(|err| format!("running cargo: {err}"))?;
+    if !status.success() {
+        return Err(format!("building into {target_dir} failed").into());
+    }
+    Ok(())
+}
+
+/// Runs the `subcommand` of the bench built by [`build`] into `target_dir`, which prints one
+/// `kernel\tstage\tMB/s` line per measurement, like [`run_child`]
+pub fn run_build(
+    target_dir: &str,
+    subcommand: &str,
+    lines: usize,
+    seed: u64,
+) -> cli::Result<Vec<(String, String, f64)>> {
+    let output = Command::new(format!("{target_dir}/release/split-bench"))
+        .args([subcommand, "--lines", &lines.to_string()])
+        .args(["--seed", &seed.to_string()])
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "the build in {target_dir} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    let mut results = Vec::new();
+    for line in String::from_utf8(output.stdout)?.lines() {
+        let mut fields = line.split('\t');
+        let (Some(kernel), Some(stage), Some(thrpt)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(
+                format!("unexpected output from the build in {target_dir}: `{line}`").into(),
+            );
+        };
+        results.push((kernel.to_owned(), stage.to_owned(), thrpt.parse()?));
+    }
+    Ok(results)
+}
+
+/// Whether the CPU has every feature of the `x86-64-vN` level
+fn can_run(level: &str) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use std::arch::is_x86_feature_detected as has;
+        let v2 = || {
+            has!("sse3")
+                && has!("ssse3")
+                && has!("sse4.1")
+                && has!("sse4.2")
+                && has!("popcnt")
+                && has!("cmpxchg16b")
+        };
+        let v3 = || {
+            v2() && has!("avx")
+                && has!("avx2")
+                && has!("bmi1")
+                && has!("bmi2")
+                && has!("f16c")
+                && has!("fma")
+                && has!("lzcnt")
+                && has!("movbe")
+                && has!("xsave")
+        };
+        let v4 = || {
+            v3() && has!("avx512f")
+                && has!("avx512bw")
+                && has!("avx512cd")
+                && has!("avx512dq")
+                && has!("avx512vl")
+        };
+        match level {
+            "v1" => true,
+            "v2" => v2(),
+            "v3" => v3(),
+            _ => v4(),
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = level;
+        false
+    }
+}
+
+/// `matrix-run [--lines N] [--seed N]`, what [`run`] runs in each build. Prints one
+/// `kernel\tstage\tMB/s` line per measurement.
+pub fn run_child(mut args: Args) -> cli::Result {
+    let lines: usize = args.parsed("--lines")?.unwrap_or(1_000_000);
+    let seed = args.parsed("--seed")?.unwrap_or(0);
+    args.finish()?;
+
+    let sampling = crate::Sampling {
+        iterations: 5,
+        noise_threshold: 0.05,
+        max_reruns: 3,
+    };
+    let mut rng = Rng::new(seed);
+    let mut buf = Vec::new();
+    for (stage, prep) in STAGES {
+        let prepared = prep(&mut buf, lines, &mut rng);
+        let len = prepared.len;
+        let input = std::str::from_utf8(&buf[..len])?;
+        let (mut slices, mut offsets) = (Vec::new(), Vec::new());
+        let mut index = compressed::LineIndex::default();
+        let time = |kernel: &str, run: &mut dyn FnMut()| {
+            let thrpt = crate::measure_thrpt(sampling, len, &mut || {
+                let start = Instant::now();
+                run();
+                start.elapsed()
+            })
+            .thrpt;
+            println!("{kernel}\t{stage}\t{thrpt:.0}");
+        };
+        // the generator knows how many lines it made, so a build whose codegen drops some fails
+        // here instead of reporting a throughput
+        let check = |kernel: &str, found: usize, expected: usize| match found == expected {
+            true => Ok(()),
+            false => Err(format!(
+                "{kernel} found {found} lines instead of {expected} in {stage}"
+            )),
+        };
+        let expected_lines = prepared.lines(input);
+        time(REFERENCE, &mut || {
+            slices.clear();
+            slice::split(input, &mut slices);
+        });
+        check(REFERENCE, slices.len(), expected_lines)?;
+        time("std", &mut || {
+            black_box(slice::std(input));
+        });
+        time("std_reuse", &mut || {
+            slices.clear();
+            slice::std_reuse(input, &mut slices);
+        });
+        check("std_reuse", slices.len(), expected_lines)?;
+        time("compressed iter", &mut || {
+            index.lows.clear();
+            index.high_starts.clear();
+            compressed::iter(input, &mut index);
+        });
+        check("compressed iter", index.lows.len(), prepared.newlines)?;
+        time("flat iter", &mut || {
+            offsets.clear();
+            flat::iter(input, &mut offsets);
+        });
+        check("flat iter", offsets.len(), prepared.newlines)?;
+        time("line stats scalar", &mut || {
+            black_box(line_stats::scalar(input));
+        });
+        time("paragraph scalar", &mut || {
+            slices.clear();
+            paragraph::scalar(input, &mut slices);
+        });
+    }
+    Ok(())
+}
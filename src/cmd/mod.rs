@@ -0,0 +1,19 @@
+//! Subcommands. Each one is a small tool built on the kernels, doubling as a realistic workload.
+
+pub mod allocators;
+pub mod batch;
+pub mod features;
+pub mod fields;
+pub mod fuzz_splice;
+pub mod history;
+pub mod index;
+pub mod jsonl;
+pub mod langs;
+pub mod matrix;
+pub mod nl;
+pub mod normalize;
+pub mod regress;
+pub mod report;
+pub mod shuffle;
+pub mod tail;
+pub mod uniq;
@@ -0,0 +1,81 @@
+//! `nl <input> [-o output]`
+//!
+//! Prefixes every line (including empty ones, like `nl -ba`) with its number. The input is read in
+//! blocks (see [`cli::for_each_block`]) and split with the fastest slice kernel, then each line
+//! goes out as three `IoSlice`s (number, line, newline) so the lines themselves are never copied.
+
+use crate::cli::{self, Args};
+use crate::pool::LinePool;
+use crate::slice;
+use std::fmt::Write as _;
+use std::io::{self, IoSlice, Write};
+use std::time::Instant;
+
+/// Most platforms cap a single `writev` at 1024 buffers
+const LINES_PER_WRITE: usize = 1024 / 3;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let output = args.value("-o")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = cli::input(&path)?;
+    let mut out = cli::output(output.as_deref())?;
+
+    let start = Instant::now();
+    let mut line_no = 0;
+    let mut line_pool = LinePool::default();
+    let mut numbers = String::new();
+    let total = cli::for_each_block(input, |text| {
+        let mut lines = line_pool.take();
+        slice::split(text, &mut lines);
+        write_numbered(&mut out, &lines, &mut line_no, &mut numbers)?;
+        line_pool.put(lines);
+        Ok(())
+    })?;
+    out.flush()?;
+    eprintln!(
+        "{line_no} lines, {:.0} MB/s",
+        cli::thrpt(total, start.elapsed())
+    );
+    Ok(())
+}
+
+fn write_numbered(
+    out: &mut impl Write,
+    lines: &[&str],
+    line_no: &mut usize,
+    numbers: &mut String,
+) -> cli::Result {
+    for group in lines.chunks(LINES_PER_WRITE) {
+        numbers.clear();
+        for _ in group {
+            *line_no += 1;
+            write!(numbers, "{line_no:>6}\t")?;
+        }
+        let mut bufs = Vec::with_capacity(group.len() * 3);
+        let mut rest = numbers.as_str();
+        for line in group {
+            // every prefix ends with the only tab in it
+            let (prefix, after) = rest.split_at(rest.find('\t').unwrap() + 1);
+            rest = after;
+            bufs.push(IoSlice::new(prefix.as_bytes()));
+            bufs.push(IoSlice::new(line.as_bytes()));
+            bufs.push(IoSlice::new(b"\n"));
+        }
+        write_all_vectored(out, &mut bufs)?;
+    }
+    Ok(())
+}
+
+fn write_all_vectored(out: &mut impl Write, mut bufs: &mut [IoSlice]) -> io::Result<()> {
+    while !bufs.is_empty() {
+        match out.write_vectored(bufs) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! `normalize <input> [-o output] [--crlf]`
+//!
+//! Converts CRLF and lone CR line endings to LF (or everything to CRLF with `--crlf`). The scan is
+//! `sink::split_any` over [`Delims::LINE_ENDINGS`], the same masks the kernels split with, and a
+//! sink copies each line with one `extend_from_slice` and writes the new ending, so files that are
+//! already normalized are basically a `memcpy`.
+
+use crate::cli::{self, Args};
+use crate::sink::{self, Delims, LineSink};
+use std::io::Write;
+use std::time::Instant;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let crlf = args.flag("--crlf");
+    let output = args.value("-o")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = std::fs::read(&path).map_err(|err| format!("{path}: {err}"))?;
+    let mut out = Vec::with_capacity(input.len() + input.len() / 8);
+
+    let start = Instant::now();
+    if crlf {
+        to_crlf(&input, &mut out);
+    } else {
+        to_lf(&input, &mut out);
+    }
+    let duration = start.elapsed();
+
+    cli::output(output.as_deref())?.write_all(&out)?;
+    eprintln!(
+        "{} -> {} bytes, {:.0} MB/s",
+        input.len(),
+        out.len(),
+        cli::thrpt(input.len(), duration)
+    );
+    Ok(())
+}
+
+pub fn to_lf(input: &[u8], out: &mut Vec<u8>) {
+    let mut sink = Normalized { out, ending: b"\n" };
+    sink::split_any(input, &Delims::LINE_ENDINGS, &mut sink);
+}
+
+pub fn to_crlf(input: &[u8], out: &mut Vec<u8>) {
+    let mut sink = Normalized {
+        out,
+        ending: b"\r\n",
+    };
+    sink::split_any(input, &Delims::LINE_ENDINGS, &mut sink);
+}
+
+/// Appends every line to `out` with `ending` in place of its own, and the last line as is
+struct Normalized<'out> {
+    out: &'out mut Vec<u8>,
+    ending: &'static [u8],
+}
+
+impl<'input> LineSink<'input> for Normalized<'_> {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input [u8], line_start: usize, newline: usize) {
+        // a `\r` right before a line's `\n` is always its pair's: the kernel merges them
+        let end = newline - (newline > line_start && input[newline - 1] == b'\r') as usize;
+        self.out.extend_from_slice(&input[line_start..end]);
+        self.out.extend_from_slice(self.ending);
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input [u8], line_start: usize) {
+        self.out.extend_from_slice(&input[line_start..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TEST_CASES: &[(&str, &str, &str)] = &[
+        // input, lf, crlf
+        ("", "", ""),
+        ("a", "a", "a"),
+        ("a\r\nb", "a\nb", "a\r\nb"),
+        ("a\rb\n", "a\nb\n", "a\r\nb\r\n"),
+        ("\r\r\n\n", "\n\n\n", "\r\n\r\n\r\n"),
+        ("\n\rx", "\n\nx", "\r\n\r\nx"),
+        // 3 bytes long, so some pair straddles a 64-byte block
+        ("a\r\n", "a\n", "a\r\n"),
+    ];
+
+    #[test]
+    fn test_normalize() {
+        for (input, lf, crlf) in TEST_CASES {
+            // repeat so the vector loop sees every case at every alignment
+            for n in [1, 7, 65] {
+                let input = input.repeat(n);
+                let mut out = Vec::new();
+                to_lf(input.as_bytes(), &mut out);
+                assert_eq!(out, lf.repeat(n).as_bytes(), "input: {input:?}");
+                out.clear();
+                to_crlf(input.as_bytes(), &mut out);
+                assert_eq!(out, crlf.repeat(n).as_bytes(), "input: {input:?}");
+            }
+        }
+    }
+}
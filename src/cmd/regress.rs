@@ -0,0 +1,85 @@
+//! `regress check <file> [--keep]` and `regress fuzz [--runs N] [--seed N] [--max-len N]`
+//!
+//! Feeds the regression pack in `tests/regressions/` (see [`crate::regressions`]). `check` runs
+//! every kernel on a file, a bug report's input say, and if one of them gets it wrong, shrinks it
+//! to the smallest input that kernel still gets wrong and saves that; `--keep` saves the file as
+//! it is when they all pass, to guard a case fixed already. `fuzz` does the same with generated
+//! inputs (newlines, CRs and multibyte chars, around chunk sizes), seeded with `seed + i` as in
+//! `fuzz-splice`, stopping at the first failure.
+
+use crate::cli::{self, Args};
+use crate::regressions;
+use crate::rng::Rng;
+
+pub fn run(mut args: Args) -> cli::Result {
+    match args.positional("check or fuzz")?.as_str() {
+        "check" => {
+            let keep = args.flag("--keep");
+            let path = args.positional("input file")?;
+            args.finish()?;
+            let input = std::fs::read_to_string(&path).map_err(|err| format!("{path}: {err}"))?;
+            match regressions::check(&input) {
+                Ok(()) if keep => {
+                    let saved = regressions::save(&input)?;
+                    println!("every kernel passes, saved as {}", saved.display());
+                    Ok(())
+                }
+                Ok(()) => {
+                    println!("every kernel passes");
+                    Ok(())
+                }
+                Err(kernel) => Err(shrink_and_save(&input, &kernel)?.into()),
+            }
+        }
+        "fuzz" => {
+            let runs: u64 = args.parsed("--runs")?.unwrap_or(10_000);
+            let seed = args.parsed("--seed")?.unwrap_or_else(Rng::seed_from_time);
+            let max_len = args.parsed("--max-len")?.unwrap_or(512);
+            args.finish()?;
+            for i in 0..runs {
+                let run_seed = seed.wrapping_add(i);
+                let mut rng = Rng::new(run_seed);
+                let len = rng.below(max_len + 1);
+                let input = random_text(&mut rng, len);
+                if let Err(kernel) = regressions::check(&input) {
+                    let err = shrink_and_save(&input, &kernel)?;
+                    return Err(format!("{err}\nreproduce with --seed {run_seed} --runs 1").into());
+                }
+            }
+            println!("{runs} runs from seed {seed}, every kernel matched its reference");
+            Ok(())
+        }
+        other => Err(format!("unknown regress command `{other}`, expected check or fuzz").into()),
+    }
+}
+
+/// Minimizes an input `kernel` fails on and saves it, returning the error to report
+fn shrink_and_save(input: &str, kernel: &str) -> Result<String, std::io::Error> {
+    let minimized = regressions::minimize(input, |candidate| {
+        regressions::check(candidate).is_err_and(|failing| failing == kernel)
+    });
+    let saved = regressions::save(&minimized)?;
+    Ok(format!(
+        "{kernel} disagrees with its reference on a {}-byte input, minimized to {} bytes: \
+         {minimized:?}\nsaved as {}",
+        input.len(),
+        minimized.len(),
+        saved.display()
+    ))
+}
+
+/// Mostly ASCII, with runs of newlines, CRLFs and 2-4 byte chars, so chunk and tail boundaries
+/// fall next to all of them
+fn random_text(rng: &mut Rng, len: usize) -> String {
+    let mut text = String::with_capacity(len + 4);
+    while text.len() < len {
+        match rng.below(16) {
+            0..=2 => text.push('\n'),
+            3 => text.push_str("\r\n"),
+            4 => text.push(['é', '€', '𝄞'][rng.below(3)]),
+            5 => text.extend(std::iter::repeat_n('\n', rng.below(70))),
+            _ => text.push((b'a' + rng.below(26) as u8) as char),
+        }
+    }
+    text
+}
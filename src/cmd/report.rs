@@ -0,0 +1,252 @@
+//! `report <input> [--queries N] [--seed N]`
+//!
+//! Which representation of the lines to keep for this input. Builds all three (`Vec<&str>`
+//! slices, the compressed `LineIndex` and flat u32 offsets, the last only under 4 GiB) with the
+//! fastest kernel the CPU supports, then measures how long the build takes, the memory it uses, a
+//! full in-order scan of the lines, and a lookup of a random line. The medians of a few runs are
+//! tabled, followed by the representation that wins each use case and by how much.
+
+use crate::cli::{self, Args};
+use crate::compressed::{self, LineIndex, NewlineIndex};
+use crate::rng::Rng;
+use crate::{flat, slice};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Runs per measurement, the median is reported
+const RUNS: usize = 5;
+
+struct Representation {
+    name: &'static str,
+    build: Duration,
+    memory: usize,
+    scan: Duration,
+    /// Seconds per lookup
+    query: f64,
+}
+
+/// What a use case costs with a representation, in seconds or bytes
+type Cost = fn(&Representation) -> f64;
+
+/// (use case, its cost, what less of it means)
+const USE_CASES: [(&str, Cost, &str); 5] = [
+    (
+        "split, then scan once",
+        |r| (r.build + r.scan).as_secs_f64(),
+        "faster",
+    ),
+    ("scan many times", |r| r.scan.as_secs_f64(), "faster"),
+    ("random lookups", |r| r.query, "faster"),
+    (
+        "build, then a few lookups",
+        |r| r.build.as_secs_f64(),
+        "faster",
+    ),
+    (
+        "keep many files' lines in memory",
+        |r| r.memory as f64,
+        "smaller",
+    ),
+];
+
+pub fn run(mut args: Args) -> cli::Result {
+    let query_count = args.parsed("--queries")?.unwrap_or(1_000_000);
+    let seed = args.parsed("--seed")?.unwrap_or(0);
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = cli::read_input(&path)?;
+    let mut lines = Vec::new();
+    slice::split(&input, &mut lines);
+    if lines.is_empty() {
+        return Err(format!("{path} has no lines").into());
+    }
+    let mut rng = Rng::new(seed);
+    let queries: Vec<usize> = (0..query_count).map(|_| rng.below(lines.len())).collect();
+
+    let representations = measure(&input, &mut lines, &queries);
+
+    println!(
+        "## {path}: {:.1} MB, {} lines\n",
+        input.len() as f64 / 1e6,
+        lines.len()
+    );
+    println!("| representation | build (ms) | build (MB/s) | memory (MiB) | bytes per line | full scan (ms) | random lookup (ns) |");
+    println!("| :-- | --: | --: | --: | --: | --: | --: |");
+    for r in &representations {
+        println!(
+            "| {} | {:.2} | {:.0} | {:.1} | {:.2} | {:.2} | {:.1} |",
+            r.name,
+            r.build.as_secs_f64() * 1e3,
+            cli::thrpt(input.len(), r.build),
+            r.memory as f64 / (1 << 20) as f64,
+            r.memory as f64 / lines.len() as f64,
+            r.scan.as_secs_f64() * 1e3,
+            r.query * 1e9,
+        );
+    }
+
+    println!("\n## Recommendation\n");
+    println!("| use case | use | runner-up | by |");
+    println!("| :-- | :-- | :-- | --: |");
+    for row in recommendations(&representations) {
+        println!("{row}");
+    }
+    Ok(())
+}
+
+/// Builds each representation of `input`'s lines, leaving the slices in `lines`, and measures it
+fn measure<'input>(
+    input: &'input str,
+    lines: &mut Vec<&'input str>,
+    queries: &[usize],
+) -> Vec<Representation> {
+    let mut representations = Vec::new();
+    {
+        let build = median(|| {
+            lines.clear();
+            let start = Instant::now();
+            slice::split(input, lines);
+            start.elapsed()
+        });
+        let scan = median(|| {
+            let start = Instant::now();
+            black_box(lines.iter().map(|line| line.len()).sum::<usize>());
+            start.elapsed()
+        });
+        let query = median(|| {
+            let start = Instant::now();
+            black_box(queries.iter().map(|&i| lines[i].len()).sum::<usize>());
+            start.elapsed()
+        });
+        representations.push(Representation {
+            name: "slices",
+            build,
+            memory: lines.len() * size_of::<&str>(),
+            scan,
+            query: query.as_secs_f64() / queries.len().max(1) as f64,
+        });
+    }
+    {
+        let mut index = LineIndex::default();
+        let build = median(|| {
+            index.lows.clear();
+            index.high_starts.clear();
+            let start = Instant::now();
+            compressed::build(input, &mut index);
+            start.elapsed()
+        });
+        representations.push(indexed(
+            "compressed",
+            build,
+            index.memory_usage().used_bytes(),
+            input,
+            &index,
+            queries,
+        ));
+    }
+    if input.len() <= u32::MAX as usize {
+        let mut offsets = Vec::new();
+        let build = median(|| {
+            offsets.clear();
+            let start = Instant::now();
+            flat::build(input, &mut offsets);
+            start.elapsed()
+        });
+        let memory = offsets.len() * size_of::<u32>();
+        representations.push(indexed("flat u32", build, memory, input, &offsets, queries));
+    }
+    representations
+}
+
+/// The recommendation table's rows, the representation that costs least for each use case and by
+/// how much
+fn recommendations(representations: &[Representation]) -> Vec<String> {
+    let mut rows = Vec::new();
+    for (use_case, cost, less_is) in USE_CASES {
+        let mut ranked: Vec<&Representation> = representations.iter().collect();
+        ranked.sort_by(|a, b| cost(a).total_cmp(&cost(b)));
+        let (best, runner_up) = (ranked[0], ranked[1]);
+        let by = match (cost(best), cost(runner_up)) {
+            (best, runner_up) if best == runner_up => "same".to_owned(),
+            // nothing to divide by, e.g. no offsets to store for a single line
+            (0., _) => "n/a".to_owned(),
+            (best, runner_up) => format!("{:.2}x {less_is}", runner_up / best),
+        };
+        rows.push(format!(
+            "| {use_case} | {} | {} | {by} |",
+            best.name, runner_up.name
+        ));
+    }
+    rows
+}
+
+/// Scan and lookup times of an index, through [`NewlineIndex`]
+fn indexed(
+    name: &'static str,
+    build: Duration,
+    memory: usize,
+    input: &str,
+    index: &impl NewlineIndex,
+    queries: &[usize],
+) -> Representation {
+    let scan = median(|| {
+        let start = Instant::now();
+        let line_count = index.line_count(input.len());
+        black_box(
+            (0..line_count)
+                .map(|i| index.line(input, i).len())
+                .sum::<usize>(),
+        );
+        start.elapsed()
+    });
+    let query = median(|| {
+        let start = Instant::now();
+        black_box(
+            queries
+                .iter()
+                .map(|&i| index.line(input, i).len())
+                .sum::<usize>(),
+        );
+        start.elapsed()
+    });
+    Representation {
+        name,
+        build,
+        memory,
+        scan,
+        query: query.as_secs_f64() / queries.len().max(1) as f64,
+    }
+}
+
+fn median(mut run: impl FnMut() -> Duration) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS).map(|_| run()).collect();
+    times.sort_unstable();
+    times[RUNS / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_line() {
+        // no newline, so the flat offsets take no memory at all
+        let input = "one line";
+        let mut lines = Vec::new();
+        let representations = measure(input, &mut lines, &[0]);
+        assert_eq!(lines, ["one line"]);
+        let rows = recommendations(&representations);
+        assert_eq!(rows.len(), USE_CASES.len());
+        for row in &rows {
+            assert!(!row.contains("inf") && !row.contains("NaN"), "{row}");
+        }
+        assert!(
+            rows.last()
+                .unwrap()
+                .ends_with("| flat u32 | compressed | n/a |"),
+            "{}",
+            rows.last().unwrap()
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! `shuffle <input> [-o output] [--seed N] [--threads N]`
+//!
+//! Permutes the lines of a file. Only the index entries get shuffled; the output is then written
+//! by looking up each line's byte range, so every line costs one `line_range` query on a
+//! `ShardedLineIndex`. The lines are read through `ShuffledLines`, which prefetches the ones a few
+//! positions ahead.
+
+use crate::cli::{self, Args};
+use crate::compressed::NewlineIndex;
+use crate::rng::Rng;
+use crate::sharded::ShardedLineIndex;
+use crate::shuffled::{ShuffledLines, PREFETCH_DISTANCE};
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let seed = args.parsed("--seed")?.unwrap_or_else(Rng::seed_from_time);
+    let threads = match args.parsed("--threads")? {
+        Some(threads) => threads,
+        None => std::thread::available_parallelism()?.get(),
+    };
+    let output = args.value("-o")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = cli::read_input(&path)?;
+
+    let start = Instant::now();
+    let index = ShardedLineIndex::build(&input, threads);
+    let index_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut order: Vec<usize> = (0..index.line_count(input.len())).collect();
+    Rng::new(seed).shuffle(&mut order);
+    let shuffle_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut out = BufWriter::with_capacity(1 << 20, cli::output(output.as_deref())?);
+    for line in ShuffledLines::new(&input, &index, &order, PREFETCH_DISTANCE) {
+        out.write_all(line.as_bytes())?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    let write_time = start.elapsed();
+
+    eprintln!("seed {seed}, {} lines", index.line_count(input.len()));
+    eprintln!(
+        "index: {:>8.0} MB/s, shuffle: {:.3}s, write: {:>8.0} MB/s",
+        cli::thrpt(input.len(), index_time),
+        shuffle_time.as_secs_f64(),
+        cli::thrpt(input.len(), write_time),
+    );
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! `tail <input> [-n N]`
+//!
+//! Prints the last N lines (default 10). Only the end of the file is read: a block from the end
+//! is scanned backwards, and the block grows until it holds enough lines.
+
+use crate::cli::{self, Args};
+use crate::slice;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+
+const FIRST_BLOCK_SIZE: u64 = 64 * 1024;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let max_lines = args.parsed("-n")?.unwrap_or(10);
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let mut file = std::fs::File::open(&path).map_err(|err| format!("{path}: {err}"))?;
+    let len = file.metadata()?.len();
+    let mut block_size = FIRST_BLOCK_SIZE;
+    let mut buf = Vec::new();
+    loop {
+        let start = len.saturating_sub(block_size);
+        file.seek(SeekFrom::Start(start))?;
+        buf.clear();
+        (&file).take(len - start).read_to_end(&mut buf)?;
+        // the first line is probably cut off, unless this is the start of the file
+        let first_line_end = match buf.iter().position(|&b| b == b'\n') {
+            _ if start == 0 => 0,
+            Some(i) => i + 1,
+            None => buf.len(),
+        };
+        let text = std::str::from_utf8(&buf[first_line_end..])?;
+        let mut lines = Vec::new();
+        slice::split_last(text, max_lines, &mut lines);
+        if lines.len() == max_lines || start == 0 {
+            let mut out = BufWriter::new(cli::output(None)?);
+            for line in lines.iter().rev() {
+                out.write_all(line.as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+            out.flush()?;
+            return Ok(());
+        }
+        block_size *= 4;
+    }
+}
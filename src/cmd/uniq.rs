@@ -0,0 +1,109 @@
+//! `uniq <input> [-o output]`
+//!
+//! Collapses runs of equal adjacent lines into one, prefixed with the length of the run (like
+//! `uniq -c`). The input is read in blocks (see [`cli::for_each_block`]) and the lines are visited
+//! with [`slice::for_each_line`], so they're compared straight out of the read buffer without
+//! being collected first. Only the line whose run is still open at the end of a block is copied,
+//! since the next block may continue it.
+
+use crate::cli::{self, Args};
+use crate::slice;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+pub fn run(mut args: Args) -> cli::Result {
+    let output = args.value("-o")?;
+    let path = args.positional("input file")?;
+    args.finish()?;
+
+    let input = cli::input(&path)?;
+    let mut out = BufWriter::with_capacity(1 << 20, cli::output(output.as_deref())?);
+
+    let start = Instant::now();
+    let mut counter = RunCounter::default();
+    let total = cli::for_each_block(input, |text| Ok(counter.count(text, &mut out)?))?;
+    counter.finish(&mut out)?;
+    out.flush()?;
+    eprintln!(
+        "{} lines, {} distinct runs, {:.0} MB/s",
+        counter.lines,
+        counter.runs,
+        cli::thrpt(total, start.elapsed())
+    );
+    Ok(())
+}
+
+#[derive(Default)]
+struct RunCounter {
+    /// The line of the run that was still open at the end of the last block, and its length so
+    /// far (0 before the first line)
+    open_line: String,
+    open_count: usize,
+    lines: usize,
+    runs: usize,
+}
+
+impl RunCounter {
+    /// Counts the lines of `text`, which must end at a line boundary unless it's the end of the
+    /// input, writing out every run that ends in it
+    fn count(&mut self, text: &str, out: &mut impl Write) -> io::Result<()> {
+        let mut current = (self.open_line.as_str(), self.open_count);
+        let mut lines = 0;
+        let mut runs = 0;
+        let mut result = Ok(());
+        slice::for_each_line(text, |line| {
+            lines += 1;
+            if current.1 > 0 && current.0 == line {
+                current.1 += 1;
+                return;
+            }
+            if current.1 > 0 {
+                runs += 1;
+                if result.is_ok() {
+                    result = write_run(out, current);
+                }
+            }
+            current = (line, 1);
+        });
+        self.lines += lines;
+        self.runs += runs;
+        let (line, count) = current;
+        self.open_line = line.to_owned();
+        self.open_count = count;
+        result
+    }
+
+    fn finish(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.open_count > 0 {
+            self.runs += 1;
+            write_run(out, (&self.open_line, self.open_count))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_run(out: &mut impl Write, (line, count): (&str, usize)) -> io::Result<()> {
+    writeln!(out, "{count:>7} {line}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_counter() {
+        let input = "a\na\nb\n\n\na\nc\nc";
+        let expected = "      2 a\n      1 b\n      2 \n      1 a\n      2 c\n";
+        // every way of cutting the input into two blocks at a line boundary
+        for (cut, _) in input.match_indices('\n') {
+            let (first, second) = input.split_at(cut + 1);
+            let mut counter = RunCounter::default();
+            let mut out = Vec::new();
+            counter.count(first, &mut out).unwrap();
+            counter.count(second, &mut out).unwrap();
+            counter.finish(&mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected, "cut at {cut}");
+            assert_eq!((counter.lines, counter.runs), (8, 5));
+        }
+    }
+}
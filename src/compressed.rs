@@ -0,0 +1,1283 @@
+//! [`LineIndex`], a line index at about 2 bytes per line: the low 16 bits of each newline's
+//! offset, with the high bits stored once per 64 KiB block, and its kernels.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+#[derive(PartialEq, Eq, Default)]
+pub struct LineIndex {
+    /// Low 16 bits of each newline's index
+    /// One per line.
+    pub lows: Vec<u16>,
+    /// d[i] is the first index into 'lows' where the high bits are i
+    /// One per 64KB of input.
+    pub high_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn memory_usage(&self) -> MemoryStats {
+        MemoryStats {
+            lows_len: self.lows.len(),
+            lows_capacity: self.lows.capacity(),
+            high_starts_len: self.high_starts.len(),
+            high_starts_capacity: self.high_starts.capacity(),
+        }
+    }
+
+    /// Drops the spare capacity. Worth it for an index that outlives its build, since the
+    /// kernels reserve generously.
+    pub fn shrink_to_fit(&mut self) {
+        self.lows.shrink_to_fit();
+        self.high_starts.shrink_to_fit();
+    }
+
+    /// The newline offsets in order, block by block, without the search
+    /// [`NewlineIndex::newline`] makes for each
+    pub fn newlines(&self) -> impl Iterator<Item = usize> + '_ {
+        self.high_starts
+            .iter()
+            .enumerate()
+            .flat_map(move |(block, &start)| {
+                let end = self
+                    .high_starts
+                    .get(block + 1)
+                    .copied()
+                    .unwrap_or(self.lows.len());
+                self.lows[start..end]
+                    .iter()
+                    .map(move |&low| (block << 16) | low as usize)
+            })
+    }
+
+    /// The index of the lines a slice kernel found in `input`. Every line but an
+    /// unterminated last one has to be followed by its newline.
+    pub fn from_slices(input: &str, lines: &[&str]) -> Self {
+        let mut index = LineIndex {
+            lows: Vec::with_capacity(lines.len()),
+            high_starts: Vec::with_capacity(input.len().div_ceil(1 << 16)),
+        };
+        for line in lines {
+            let newline = line.as_ptr() as usize - input.as_ptr() as usize + line.len();
+            if newline == input.len() {
+                break;
+            }
+            debug_assert_eq!(input.as_bytes()[newline], b'\n');
+            while index.high_starts.len() <= newline >> 16 {
+                index.high_starts.push(index.lows.len());
+            }
+            index.lows.push(newline as u16);
+        }
+        // blocks after the last newline
+        while index.high_starts.len() < input.len().div_ceil(1 << 16) {
+            index.high_starts.push(index.lows.len());
+        }
+        index
+    }
+
+    /// Appends the lines to `out`, the same ones `slice::split` finds
+    pub fn to_slices<'input>(&self, input: &'input str, out: &mut Vec<&'input str>) {
+        out.reserve(self.lows.len() + 1);
+        let mut line_start = 0;
+        for (block, &first) in self.high_starts.iter().enumerate() {
+            let end = self
+                .high_starts
+                .get(block + 1)
+                .copied()
+                .unwrap_or(self.lows.len());
+            let high = block << 16;
+            for &low in &self.lows[first..end] {
+                let newline = high | low as usize;
+                out.push(&input[line_start..newline]);
+                line_start = newline + 1;
+            }
+        }
+        if line_start < input.len() {
+            out.push(&input[line_start..]);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryStats {
+    pub lows_len: usize,
+    pub lows_capacity: usize,
+    pub high_starts_len: usize,
+    pub high_starts_capacity: usize,
+}
+
+impl MemoryStats {
+    pub fn used_bytes(&self) -> usize {
+        self.lows_len * size_of::<u16>() + self.high_starts_len * size_of::<usize>()
+    }
+
+    pub fn reserved_bytes(&self) -> usize {
+        self.lows_capacity * size_of::<u16>() + self.high_starts_capacity * size_of::<usize>()
+    }
+
+    /// Used bytes per newline (or in total, if there are none)
+    pub fn bytes_per_line(&self) -> f64 {
+        self.used_bytes() as f64 / self.lows_len.max(1) as f64
+    }
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mib = |bytes| bytes as f64 / (1024. * 1024.);
+        write!(
+            f,
+            "{:.1} MiB used, {:.1} MiB reserved, {:.2} bytes/line",
+            mib(self.used_bytes()),
+            mib(self.reserved_bytes()),
+            self.bytes_per_line()
+        )
+    }
+}
+
+/// Line queries shared by the index types. Implementors only need to locate newlines.
+pub trait NewlineIndex {
+    /// Number of newlines
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Absolute byte offset of the i-th newline
+    fn newline(&self, i: usize) -> usize;
+
+    /// Number of lines, counted the same way as the slice kernels (a trailing empty line is
+    /// omitted)
+    fn line_count(&self, input_len: usize) -> usize {
+        let len = self.len();
+        let last_line_start = len.checked_sub(1).map_or(0, |i| self.newline(i) + 1);
+        len + (last_line_start != input_len) as usize
+    }
+
+    /// Byte range of the i-th line, not including its newline
+    fn line_range(&self, i: usize, input_len: usize) -> Range<usize> {
+        let start = if i == 0 { 0 } else { self.newline(i - 1) + 1 };
+        let end = if i < self.len() {
+            self.newline(i)
+        } else {
+            input_len
+        };
+        start..end
+    }
+
+    fn line<'input>(&self, input: &'input str, i: usize) -> &'input str {
+        &input[self.line_range(i, input.len())]
+    }
+
+    /// The line the byte at `pos` is in, which is the number of newlines before it (a
+    /// binary search over [`NewlineIndex::newline`])
+    #[cfg_attr(not(feature = "roaring"), allow(dead_code))]
+    fn line_of(&self, pos: usize) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.newline(mid) < pos {
+                true => low = mid + 1,
+                false => high = mid,
+            }
+        }
+        low
+    }
+
+    /// Whether the byte at `pos` is a newline, without looking at the input
+    #[cfg_attr(not(feature = "roaring"), allow(dead_code))]
+    fn is_newline(&self, pos: usize) -> bool {
+        self.line_of(pos + 1) != self.line_of(pos)
+    }
+}
+
+impl NewlineIndex for LineIndex {
+    fn len(&self) -> usize {
+        self.lows.len()
+    }
+
+    fn newline(&self, i: usize) -> usize {
+        // Blocks without newlines share a start with the next block, so take the last one.
+        let block = self.high_starts.partition_point(|&start| start <= i) - 1;
+        (block << 16) | self.lows[i] as usize
+    }
+
+    /// Only searches the block `pos` is in
+    fn line_of(&self, pos: usize) -> usize {
+        let block = pos >> 16;
+        let Some(&first) = self.high_starts.get(block) else {
+            return self.lows.len();
+        };
+        let end = self
+            .high_starts
+            .get(block + 1)
+            .copied()
+            .unwrap_or(self.lows.len());
+        first + self.lows[first..end].partition_point(|&low| (low as usize) < (pos & 0xffff))
+    }
+}
+
+/// Builds the index with the fastest kernel the current CPU supports
+///
+/// ```
+/// use split_bench::compressed::{self, LineIndex, NewlineIndex};
+///
+/// let input = "one\ntwo\nthree";
+/// let mut index = LineIndex::default();
+/// compressed::build(input, &mut index);
+/// assert_eq!(index.len(), 2);
+/// assert_eq!(index.line_count(input.len()), 3);
+/// assert_eq!(index.line(input, 1), "two");
+/// ```
+pub fn build(input: &str, out: &mut LineIndex) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use crate::splitters::*;
+        if let Some(avx512) = Avx512Splitter::new() {
+            avx512.build_index(input, out)
+        } else if let Some(avx2) = Avx2Splitter::new() {
+            avx2.build_index(input, out)
+        } else if let Some(sse42) = Sse42Splitter::new() {
+            sse42.build_index(input, out)
+        } else {
+            x86_64::sse2_unrollx4(input, out)
+        }
+    }
+    #[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+    riscv64::rvv(input, out);
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    wasm32::simd128(input, out);
+    #[cfg(target_arch = "loongarch64")]
+    if crate::slice::loongarch64::can_run_lasx() {
+        return unsafe { loongarch64::lasx(input, out) };
+    }
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        all(target_arch = "riscv64", target_feature = "v"),
+        all(target_arch = "wasm32", target_feature = "simd128")
+    )))]
+    swar(input, out)
+}
+
+/// [`build`] for bytes that may not be UTF-8, through the generic kernels of [`crate::sink`].
+/// Only newlines are stored, so the index of a `str` is the index of its bytes.
+pub fn build_bytes(input: &[u8], out: &mut LineIndex) {
+    crate::sink::split(input, out)
+}
+
+pub fn iter(input: &str, out: &mut LineIndex) {
+    for chunk in input.as_bytes().chunks(1 << 16) {
+        out.high_starts.push(out.lows.len());
+        for (idx, _) in chunk.iter().enumerate().filter(|e| *e.1 == b'\n') {
+            out.lows.push(idx as u16);
+        }
+    }
+}
+
+/// `slice::memchr`: `memchr::memchr_iter` over each 64 KiB block
+#[cfg(feature = "memchr")]
+pub fn memchr(input: &str, out: &mut LineIndex) {
+    for chunk_64k in input.as_bytes().chunks(1 << 16) {
+        out.high_starts.push(out.lows.len());
+        out.lows
+            .extend(memchr::memchr_iter(b'\n', chunk_64k).map(|pos| pos as u16));
+    }
+}
+
+/// `slice::swar`'s 8 bytes at a time, for targets without SIMD
+pub fn swar(input: &str, out: &mut LineIndex) {
+    swar_with::<{ crate::slice::SWAR_BIG_ENDIAN }>(input, out)
+}
+
+/// `slice::swar_with`: [`swar`] with words of either byte order
+pub fn swar_with<const BIG_ENDIAN: bool>(input: &str, out: &mut LineIndex) {
+    use crate::slice::{swar_load, swar_newlines, swar_pop_first};
+    for chunk_64k in input.as_bytes().chunks(1 << 16) {
+        out.high_starts.push(out.lows.len());
+        for (chunk_i, chunk) in chunk_64k.chunks_exact(8).enumerate() {
+            let mut mask = swar_newlines(swar_load::<BIG_ENDIAN>(chunk));
+            while mask != 0 {
+                let pos = chunk_i * 8 + swar_pop_first::<BIG_ENDIAN>(&mut mask);
+                out.lows.push(pos as u16);
+            }
+        }
+    }
+    tail(8, input, out);
+}
+
+/// Assumes high_start has already been written
+pub fn tail(chunk_size: usize, input: &str, out: &mut LineIndex) {
+    let base = input.len() & !(chunk_size - 1);
+    for (idx, _) in input.as_bytes()[base..]
+        .iter()
+        .enumerate()
+        .filter(|e| *e.1 == b'\n')
+    {
+        out.lows.push(base as u16 + idx as u16);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub mod aarch64 {
+    use crate::compressed::*;
+    use crate::slice::aarch64::{newline_mask, newline_nibbles};
+
+    pub fn neon(input: &str, out: &mut LineIndex) {
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            for (chunk_i, chunk) in chunk_64k.chunks_exact(16).enumerate() {
+                let mut mask = unsafe { newline_nibbles(chunk.as_ptr()) };
+                while mask != 0 {
+                    let low = chunk_i * 16 + mask.trailing_zeros() as usize / 4;
+                    out.lows.push(low as u16);
+                    mask &= mask - 1;
+                }
+            }
+        }
+        tail(16, input, out);
+    }
+
+    /// `slice::aarch64::newline_mask` per 64-byte chunk, written into spare capacity like
+    /// `x86_64::sse2_unrollx4`
+    pub fn neon_unrollx4(input: &str, out: &mut LineIndex) {
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                unsafe {
+                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                    while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                        let mut mask = newline_mask(chunk_64k.as_ptr().add(chunk_i * 64));
+                        while mask != 0 {
+                            let bit_pos = mask.trailing_zeros() as u16;
+                            out_arr
+                                .get_unchecked_mut(write_i)
+                                .write(chunk_i as u16 * 64 + bit_pos);
+                            write_i += 1;
+                            mask &= mask - 1;
+                        }
+                        chunk_i += 1;
+                    }
+                    out.lows.set_len(out.lows.len() + write_i);
+                }
+            }
+        }
+        tail(64, input, out);
+    }
+
+    /// `slice::aarch64::sve` per 64 KiB block, whose last vector stops at the block's end
+    #[cfg(feature = "nightly")]
+    #[target_feature(enable = "sve")]
+    pub unsafe fn sve(input: &str, out: &mut LineIndex) {
+        use crate::slice::aarch64::{sve_newline_mask, sve_vector_bytes};
+        let vector_bytes = sve_vector_bytes();
+        let words = vector_bytes.div_ceil(64);
+        let mut masks = [0u64; 4];
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_start = 0;
+            while chunk_start < chunk_64k.len() {
+                sve_newline_mask(chunk_64k, chunk_start, &mut masks);
+                for (word_i, &word) in masks.get_unchecked(..words).iter().enumerate() {
+                    let mut mask = word & (u64::MAX >> (64 - (vector_bytes - word_i * 64).min(64)));
+                    while mask != 0 {
+                        let low = chunk_start + word_i * 64 + mask.trailing_zeros() as usize;
+                        out.lows.push(low as u16);
+                        mask &= mask - 1;
+                    }
+                }
+                chunk_start += vector_bytes;
+            }
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub mod wasm32 {
+    use crate::compressed::*;
+    use crate::slice::wasm32::newline_mask;
+
+    /// `slice::wasm32::simd128_unroll`'s mask per 64-byte chunk, written into spare capacity
+    pub fn simd128(input: &str, out: &mut LineIndex) {
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                unsafe {
+                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                    while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                        let mut mask = newline_mask(chunk_64k.as_ptr().add(chunk_i * 64));
+                        while mask != 0 {
+                            let bit_pos = mask.trailing_zeros() as u16;
+                            out_arr
+                                .get_unchecked_mut(write_i)
+                                .write(chunk_i as u16 * 64 + bit_pos);
+                            write_i += 1;
+                            mask &= mask - 1;
+                        }
+                        chunk_i += 1;
+                    }
+                    out.lows.set_len(out.lows.len() + write_i);
+                }
+            }
+        }
+        tail(64, input, out);
+    }
+}
+
+#[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+pub mod riscv64 {
+    use crate::compressed::*;
+    use crate::slice::riscv64::{newline_offsets, STEP};
+
+    /// `slice::riscv64::rvv` with the offsets, plus the step's start in its 64 KiB block,
+    /// compressed straight into the index's spare capacity
+    pub fn rvv(input: &str, out: &mut LineIndex) {
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_start = 0;
+            while chunk_start < chunk_64k.len() {
+                let avl = (chunk_64k.len() - chunk_start).min(STEP);
+                out.lows.reserve(STEP);
+                unsafe {
+                    let (scanned, count) = newline_offsets(
+                        chunk_64k.as_ptr().add(chunk_start),
+                        avl,
+                        chunk_start as u16,
+                        out.lows.spare_capacity_mut().as_mut_ptr().cast(),
+                    );
+                    out.lows.set_len(out.lows.len() + count);
+                    chunk_start += scanned;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "loongarch64")]
+pub mod loongarch64 {
+    use crate::compressed::*;
+    use crate::slice::loongarch64::lasx_newline_mask;
+
+    /// `slice::loongarch64::lasx`'s mask per 64-byte chunk, written into spare capacity
+    #[target_feature(enable = "lasx")]
+    pub unsafe fn lasx(input: &str, out: &mut LineIndex) {
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let mut mask = lasx_newline_mask(chunk_64k.as_ptr().add(chunk_i * 64));
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(chunk_i as u16 * 64 + bit_pos);
+                        write_i += 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail(64, input, out);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use crate::compressed::*;
+    use core::arch::x86_64::*;
+
+    pub fn sse2(input: &str, out: &mut LineIndex) {
+        let nl_v = unsafe { _mm_loadu_si128([b'\n'; 16].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            for (chunk_idx, chunk) in chunk_64k.chunks_exact(16).enumerate() {
+                unsafe {
+                    let v = _mm_loadu_si128(chunk.as_ptr().cast());
+                    let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as u16;
+                        out.lows.push(chunk_idx as u16 * 16 + bit_pos);
+                        mask &= mask - 1;
+                    }
+                }
+            }
+        }
+        tail(16, input, out);
+    }
+
+    pub fn sse2_unroll(input: &str, out: &mut LineIndex) {
+        let nl_v = unsafe { _mm_loadu_si128([b'\n'; 16].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 16;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                unsafe {
+                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                    while write_i <= (256 - 16) && chunk_i < stop_chunk_i {
+                        let v = _mm_loadu_si128(chunk_64k.as_ptr().add(chunk_i * 16).cast());
+                        let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
+                        while mask != 0 {
+                            let bit_pos = mask.trailing_zeros() as u16;
+                            out_arr
+                                .get_unchecked_mut(write_i)
+                                .write(chunk_i as u16 * 16 + bit_pos);
+                            write_i += 1;
+                            mask &= mask - 1;
+                        }
+                        chunk_i += 1;
+                    }
+                    out.lows.set_len(out.lows.len() + write_i);
+                }
+            }
+        }
+        tail(16, input, out);
+    }
+
+    pub fn sse2_unrollx4(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
+        };
+        let nl_v = unsafe { load([b'\n'; 16].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                unsafe {
+                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                    while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                        let in_ptr = chunk_64k.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
+                        let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
+                        let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
+                        let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
+                        let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
+                        let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
+                        while mask != 0 {
+                            let bit_pos = mask.trailing_zeros() as u16;
+                            out_arr
+                                .get_unchecked_mut(write_i)
+                                .write(chunk_i as u16 * 64 + bit_pos);
+                            write_i += 1;
+                            mask &= mask - 1;
+                        }
+                        chunk_i += 1;
+                    }
+                    out.lows.set_len(out.lows.len() + write_i);
+                }
+            }
+        }
+        tail(64, input, out);
+    }
+
+    /// [`tail`] with a vector compare per 64 bytes instead of a loop over the bytes
+    #[inline]
+    #[target_feature(enable = "avx2,bmi1")]
+    unsafe fn tail_avx2(chunk_size: usize, input: &str, out: &mut LineIndex) {
+        let base = input.len() & !(chunk_size - 1);
+        for (piece_i, piece) in input.as_bytes()[base..].chunks(64).enumerate() {
+            let mut mask = crate::slice::x86_64::newline_mask_copied(piece);
+            while mask != 0 {
+                let low = base + piece_i * 64 + mask.trailing_zeros() as usize;
+                out.lows.push(low as u16);
+                mask &= mask - 1;
+            }
+        }
+    }
+
+    /// [`tail`] with masked loads, which don't touch the bytes masked out
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn tail_avx512(chunk_size: usize, input: &str, out: &mut LineIndex) {
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let base = input.len() & !(chunk_size - 1);
+        for (piece_i, piece) in input.as_bytes()[base..].chunks(64).enumerate() {
+            let load_mask = u64::MAX >> (64 - piece.len());
+            let v = _mm512_maskz_loadu_epi8(load_mask, piece.as_ptr().cast());
+            let mut mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+            while mask != 0 {
+                let low = base + piece_i * 64 + mask.trailing_zeros() as usize;
+                out.lows.push(low as u16);
+                mask &= mask - 1;
+            }
+        }
+    }
+
+    pub fn can_run_sse42() -> bool {
+        has_x86_features!("popcnt")
+    }
+
+    // enabling bmi1 isn't interesting bc there's a very narrow slice of CPUs with BMI1 but not
+    // AVX2, but a broad range of older CPUS with popcnt
+    #[target_feature(enable = "popcnt")]
+    pub unsafe fn sse42_unrollx4_interleavex2(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
+        };
+        const CHUNK_SIZE: usize = 128;
+        /// count_ones() without branching on the zero case. Result undefined if input is 0
+        /// Same encoding as tzcnt.
+        fn rep_bsf(input: u64) -> u64 {
+            let mut output;
+            unsafe {
+                core::arch::asm!("rep bsf {output}, {input}", input = in(reg) input, output = out(reg) output)
+            };
+            output
+        }
+        let nl_v = unsafe { load([b'\n'; 16].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                let iter_count = 32.min(stop_chunk_i - chunk_i);
+                out.lows.reserve(iter_count * CHUNK_SIZE);
+                let out_arr = out
+                    .lows
+                    .spare_capacity_mut()
+                    .get_unchecked_mut(..iter_count * CHUNK_SIZE);
+                for _ in 0..iter_count {
+                    let mut mask1 = {
+                        let in_ptr = chunk_64k
+                            .as_ptr()
+                            .byte_add(chunk_i * CHUNK_SIZE)
+                            .cast::<__m128i>();
+                        let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
+                        let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
+                        let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
+                        let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
+                        mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48)
+                    };
+
+                    let mut mask2 = {
+                        let in_ptr = chunk_64k
+                            .as_ptr()
+                            .byte_add(chunk_i * CHUNK_SIZE + 64)
+                            .cast::<__m128i>();
+                        let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
+                        let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
+                        let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
+                        let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
+                        mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48)
+                    };
+                    let mut write_i2 = write_i + mask1.count_ones() as usize;
+                    let mask2_count = mask2.count_ones() as usize;
+
+                    while mask1 != 0 {
+                        let bit_pos = mask1.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(chunk_i as u16 * CHUNK_SIZE as u16 + bit_pos);
+                        write_i += 1;
+                        mask1 &= mask1 - 1;
+
+                        let bit_pos = rep_bsf(mask2) as u16;
+                        out_arr.get_unchecked_mut(write_i2).write(
+                            (chunk_i as u16 * CHUNK_SIZE as u16)
+                                .wrapping_add(64)
+                                .wrapping_add(bit_pos),
+                        );
+                        write_i2 += 1;
+                        mask2 &= mask2.wrapping_sub(1);
+                    }
+                    write_i += mask2_count;
+                    while mask2 != 0 {
+                        let bit_pos = mask2.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i2)
+                            .write(chunk_i as u16 * CHUNK_SIZE as u16 + 64 + bit_pos);
+                        write_i2 += 1;
+                        mask2 &= mask2 - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail(128, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unroll(input: &str, out: &mut LineIndex) {
+        let nl_v = unsafe { _mm256_loadu_si256([b'\n'; 32].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 32;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 32) && chunk_i < stop_chunk_i {
+                    let v = _mm256_loadu_si256(chunk_64k.as_ptr().add(chunk_i * 32).cast());
+                    let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(chunk_i as u16 * 32 + bit_pos);
+                        write_i += 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx2(32, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unrollx2(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let nl_v = unsafe { _mm256_loadu_si256([b'\n'; 32].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let ptr = chunk_64k.as_ptr().add(chunk_i * 64);
+                    let v1 = load(ptr.cast());
+                    let v2 = load(ptr.byte_add(32).cast());
+                    let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                        | (movemask(eq(v1, nl_v)) as u32 as u64);
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(chunk_i as u16 * 64 + bit_pos);
+                        write_i += 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx2(64, input, out);
+    }
+
+    /// [`avx2_unrollx2`], software-pipelined like `slice::x86_64::avx2_pipelined`
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_pipelined(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mask_at = |chunk_i: usize| {
+                let ptr = chunk_64k.as_ptr().add(chunk_i * 64);
+                let v1 = load(ptr.cast());
+                let v2 = load(ptr.byte_add(32).cast());
+                ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                    | (movemask(eq(v1, nl_v)) as u32 as u64)
+            };
+            let stop_chunk_i = chunk_64k.len() / 64;
+            let mut chunk_i = 0;
+            let mut mask = if stop_chunk_i > 0 { mask_at(0) } else { 0 };
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    // the last chunk loads itself again rather than branching
+                    let next_mask = mask_at((chunk_i + 1).min(stop_chunk_i - 1));
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(chunk_i as u16 * 64 + bit_pos);
+                        write_i += 1;
+                        mask &= mask - 1;
+                    }
+                    mask = next_mask;
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx2(64, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unrollx2_interleavex2(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        const CHUNK_SIZE: usize = 128;
+        let nl_v = unsafe { _mm256_loadu_si256([b'\n'; 32].as_ptr().cast()) };
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
+            while chunk_i < stop_chunk_i {
+                // two iters of 64B, start 2nd at + popcount, stop when first exhausted,
+                // finish 2nd
+                let mut write_i = 0;
+                let iter_count = 32.min(stop_chunk_i - chunk_i);
+                out.lows.reserve(iter_count * CHUNK_SIZE);
+                let out_arr = out
+                    .lows
+                    .spare_capacity_mut()
+                    .get_unchecked_mut(..iter_count * CHUNK_SIZE);
+                for _ in 0..iter_count {
+                    let ptr = chunk_64k.as_ptr().add(chunk_i * CHUNK_SIZE);
+                    let v1 = load(ptr.cast());
+                    let v2 = load(ptr.byte_add(32).cast());
+                    let mut mask1 = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                        | (movemask(eq(v1, nl_v)) as u32 as u64);
+
+                    let v1 = load(ptr.byte_add(64).cast());
+                    let v2 = load(ptr.byte_add(96).cast());
+                    let mut mask2 = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                        | (movemask(eq(v1, nl_v)) as u32 as u64);
+                    let mut write_i2 = write_i + mask1.count_ones() as usize;
+                    let mask2_count = mask2.count_ones() as usize;
+                    while mask1 != 0 {
+                        let bit_pos = mask1.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(chunk_i as u16 * CHUNK_SIZE as u16 + bit_pos);
+                        write_i += 1;
+                        mask1 &= mask1 - 1;
+
+                        let bit_pos = _tzcnt_u64(mask2) as u16;
+                        // if this turns out to be a junk value, it will be ignored later (by
+                        // truncating the slice). So, overflowing is fine.
+                        out_arr.get_unchecked_mut(write_i2).write(
+                            (chunk_i as u16 * CHUNK_SIZE as u16)
+                                .wrapping_add(64)
+                                .wrapping_add(bit_pos),
+                        );
+                        write_i2 += 1;
+                        mask2 &= mask2.wrapping_sub(1);
+                    }
+                    write_i += mask2_count;
+                    while mask2 != 0 {
+                        let bit_pos = mask2.trailing_zeros() as u16;
+                        out_arr
+                            .get_unchecked_mut(write_i2)
+                            .write(chunk_i as u16 * CHUNK_SIZE as u16 + 64 + bit_pos);
+                        write_i2 += 1;
+                        mask2 &= mask2 - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx2(128, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_lut(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        /// Precomputed table of 8bit mask -> packed list of 2B indices
+        const LUT: [[u16; 8]; 256] = {
+            let mut t = [[0u16; 8]; 256];
+            let mut t_i = 0;
+            while t_i < 256 {
+                let mut e = t[t_i];
+                let mut bit_i = 0;
+                let mut packed_i = 0;
+                while bit_i < 8 {
+                    if t_i & (1 << bit_i) != 0 {
+                        e[packed_i] = bit_i;
+                        packed_i += 1;
+                    }
+                    bit_i += 1;
+                }
+                t[t_i] = e;
+                t_i += 1;
+            }
+            t
+        };
+        let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
+        let u16_8_v = _mm_set1_epi16(8);
+        let u16_32_v = _mm_set1_epi16(32);
+        const CHUNK_SIZE: usize = 32;
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
+            let mut offset_v = _mm_setzero_si128();
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                let iter_count = 32.min(stop_chunk_i - chunk_i);
+                out.lows.reserve(iter_count * CHUNK_SIZE);
+                let out_arr = out
+                    .lows
+                    .spare_capacity_mut()
+                    .get_unchecked_mut(..iter_count * CHUNK_SIZE);
+                for _ in 0..iter_count {
+                    let ptr = chunk_64k.as_ptr().add(chunk_i * CHUNK_SIZE);
+                    let v = load(ptr.cast());
+                    let mask = movemask(eq(nl_v, v));
+                    if mask == 0 {
+                        offset_v = _mm_add_epi16(offset_v, u16_32_v);
+                    } else {
+                        // for each 8bit of mask, lookup, shift, write, adv by popcnt.
+                        for byte in mask.to_le_bytes() {
+                            let mut packed_indices =
+                                _mm_loadu_si128(LUT.as_ptr().add(byte as usize).cast());
+                            packed_indices = _mm_add_epi16(packed_indices, offset_v);
+                            offset_v = _mm_add_epi16(offset_v, u16_8_v);
+                            _mm_storeu_si128(
+                                out_arr.as_mut_ptr().add(write_i).cast::<__m128i>(),
+                                packed_indices,
+                            );
+                            write_i += byte.count_ones() as usize;
+                        }
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx2(CHUNK_SIZE, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_big_lut(input: &str, out: &mut LineIndex) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        const U16_SIZE: usize = 1 << 16;
+        /// Precomputed table of 16 bit mask -> packed list of 2B indices
+        /// This is slow in const and makes RA a lot slower :(
+        const LUT: &[[u16; 16]; U16_SIZE] = &{
+            let mut t = [[0u16; 16]; U16_SIZE];
+            let mut t_i = 0;
+            while t_i < U16_SIZE {
+                let mut e = t[t_i];
+                let mut bit_i = 0;
+                let mut packed_i = 0;
+                while bit_i < 16 {
+                    if t_i & (1 << bit_i) != 0 {
+                        e[packed_i] = bit_i;
+                        packed_i += 1;
+                    }
+                    bit_i += 1;
+                }
+                t[t_i] = e;
+                t_i += 1;
+            }
+            t
+        };
+        let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
+        let u16_16_v = _mm256_set1_epi16(16);
+        let u16_32_v = _mm256_set1_epi16(32);
+        const CHUNK_SIZE: usize = 32;
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
+            let mut offset_v = _mm256_setzero_si256();
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                let iter_count = 32.min(stop_chunk_i - chunk_i);
+                out.lows.reserve(iter_count * CHUNK_SIZE);
+                let out_arr = out
+                    .lows
+                    .spare_capacity_mut()
+                    .get_unchecked_mut(..iter_count * CHUNK_SIZE);
+                for _ in 0..iter_count {
+                    let ptr = chunk_64k.as_ptr().add(chunk_i * CHUNK_SIZE);
+                    let v = load(ptr.cast());
+                    let mask = movemask(eq(nl_v, v));
+                    if mask == 0 {
+                        offset_v = _mm256_add_epi16(offset_v, u16_32_v);
+                    } else {
+                        // for each 16bit of mask, lookup, shift, write, adv by popcnt. The
+                        // whole LUT row has to be written, since all 16 bits can be set.
+                        for word in core::mem::transmute::<i32, [u16; 2]>(mask) {
+                            let mut packed_indices =
+                                _mm256_loadu_si256(LUT.as_ptr().add(word as usize).cast());
+                            packed_indices = _mm256_add_epi16(packed_indices, offset_v);
+                            offset_v = _mm256_add_epi16(offset_v, u16_16_v);
+                            _mm256_storeu_si256(
+                                out_arr.as_mut_ptr().add(write_i).cast::<__m256i>(),
+                                packed_indices,
+                            );
+                            write_i += word.count_ones() as usize;
+                        }
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx2(CHUNK_SIZE, input, out);
+    }
+
+    pub fn can_run_avx512_compress() -> bool {
+        has_x86_features!("popcnt", "avx512f", "avx512bw", "avx512vbmi2")
+    }
+
+    #[inline(never)]
+    #[target_feature(enable = "popcnt,avx512f,avx512bw,avx512vbmi2")]
+    pub unsafe fn avx512_compress(input: &str, out: &mut LineIndex) {
+        const IDX_ARR: [u8; 64] = {
+            let mut t = [0u8; 64];
+            let mut i = 0;
+            while i < t.len() {
+                t[i] = i as u8;
+                i += 1;
+            }
+            t
+        };
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let idx_v = _mm512_loadu_epi8(IDX_ARR.as_ptr().cast());
+        let i16_64_v = _mm512_set1_epi16(64);
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut offset_v = _mm512_setzero_si512();
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let v = _mm512_loadu_si512(chunk_64k.as_ptr().add(chunk_i * 64).cast());
+                    let mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+                    let num_lines = mask.count_ones();
+                    let idxs = _mm512_maskz_compress_epi8(mask, idx_v);
+                    // first half
+                    let low_idxs = _mm512_cvtepu8_epi16(_mm512_castsi512_si256(idxs));
+                    let low_idxs = _mm512_add_epi16(low_idxs, offset_v);
+                    _mm512_storeu_si512(out_arr.as_mut_ptr().add(write_i).cast(), low_idxs);
+                    // second half
+                    if num_lines > 32 {
+                        let high_idxs = _mm512_cvtepu8_epi16(_mm512_extracti64x4_epi64::<1>(idxs));
+                        let high_idxs = _mm512_add_epi16(high_idxs, offset_v);
+                        // if there are any results in high_idxs, then low must have been full, so
+                        // we can unconditionally write 64 bytes ahead of the previous addr
+                        _mm512_storeu_si512(
+                            out_arr.as_mut_ptr().add(write_i).byte_add(64).cast(),
+                            high_idxs,
+                        );
+                    }
+                    offset_v = _mm512_add_epi16(offset_v, i16_64_v);
+                    write_i += num_lines as usize;
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx512(64, input, out);
+    }
+
+    /// [`avx512_compress`] with both halves of the chunk compressed at u16 granularity
+    /// (`vpcompressw`), each from its 32 bits of the mask and a vector of its 32 offsets. Both
+    /// are stored unconditionally, the second one right after the newlines of the first, so
+    /// there is no `num_lines > 32` branch and no widening of compressed bytes.
+    ///
+    /// On an AVX-512 Xeon, that wins where chunks hover around 32 newlines and the branch
+    /// mispredicts (0-2 by 75%, `mixed` by 40%), and loses 10-18% on sparser stages, where
+    /// the branch predicts well and one byte compress beats two word ones.
+    #[inline(never)]
+    #[target_feature(enable = "popcnt,avx512f,avx512bw,avx512vbmi2")]
+    pub unsafe fn avx512_compress_words(input: &str, out: &mut LineIndex) {
+        const IDX_ARR: [u16; 64] = {
+            let mut t = [0u16; 64];
+            let mut i = 0;
+            while i < t.len() {
+                t[i] = i as u16;
+                i += 1;
+            }
+            t
+        };
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let low_idx_v = _mm512_loadu_epi16(IDX_ARR.as_ptr().cast());
+        let high_idx_v = _mm512_loadu_epi16(IDX_ARR.as_ptr().add(32).cast());
+        let i16_64_v = _mm512_set1_epi16(64);
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut offset_v = _mm512_setzero_si512();
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let v = _mm512_loadu_si512(chunk_64k.as_ptr().add(chunk_i * 64).cast());
+                    let mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+                    let (low_mask, high_mask) = (mask as u32, (mask >> 32) as u32);
+                    let low_idxs = _mm512_maskz_compress_epi16(
+                        low_mask,
+                        _mm512_add_epi16(low_idx_v, offset_v),
+                    );
+                    let high_idxs = _mm512_maskz_compress_epi16(
+                        high_mask,
+                        _mm512_add_epi16(high_idx_v, offset_v),
+                    );
+                    _mm512_storeu_si512(out_arr.as_mut_ptr().add(write_i).cast(), low_idxs);
+                    write_i += low_mask.count_ones() as usize;
+                    _mm512_storeu_si512(out_arr.as_mut_ptr().add(write_i).cast(), high_idxs);
+                    write_i += high_mask.count_ones() as usize;
+                    offset_v = _mm512_add_epi16(offset_v, i16_64_v);
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx512(64, input, out);
+    }
+
+    /// [`avx512_compress_words`] with the compressed offsets stored straight to the output
+    /// (`vpcompressw` with a memory operand, `_mm512_mask_compressstoreu_epi16`), which writes
+    /// only the selected words, instead of compressing into a register and storing all 64 bytes
+    /// of it. Zen 4 microcodes the memory form, so it's for the cores where the direct store wins.
+    #[inline(never)]
+    #[target_feature(enable = "popcnt,avx512f,avx512bw,avx512vbmi2")]
+    pub unsafe fn avx512_compressstore(input: &str, out: &mut LineIndex) {
+        const IDX_ARR: [u16; 64] = {
+            let mut t = [0u16; 64];
+            let mut i = 0;
+            while i < t.len() {
+                t[i] = i as u16;
+                i += 1;
+            }
+            t
+        };
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let low_idx_v = _mm512_loadu_epi16(IDX_ARR.as_ptr().cast());
+        let high_idx_v = _mm512_loadu_epi16(IDX_ARR.as_ptr().add(32).cast());
+        let i16_64_v = _mm512_set1_epi16(64);
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut offset_v = _mm512_setzero_si512();
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let v = _mm512_loadu_si512(chunk_64k.as_ptr().add(chunk_i * 64).cast());
+                    let mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+                    let (low_mask, high_mask) = (mask as u32, (mask >> 32) as u32);
+                    _mm512_mask_compressstoreu_epi16(
+                        out_arr.as_mut_ptr().add(write_i).cast(),
+                        low_mask,
+                        _mm512_add_epi16(low_idx_v, offset_v),
+                    );
+                    write_i += low_mask.count_ones() as usize;
+                    _mm512_mask_compressstoreu_epi16(
+                        out_arr.as_mut_ptr().add(write_i).cast(),
+                        high_mask,
+                        _mm512_add_epi16(high_idx_v, offset_v),
+                    );
+                    write_i += high_mask.count_ones() as usize;
+                    offset_v = _mm512_add_epi16(offset_v, i16_64_v);
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx512(64, input, out);
+    }
+
+    /// Chunks with at most this many newlines take the bit loop in [`avx512_hybrid`]
+    pub const HYBRID_SPARSE_MAX: u32 = 2;
+
+    /// [`avx512_compress`] for chunks with many newlines, the bit loop of [`avx2_unrollx2`]
+    /// for those with few, picked per 64-byte chunk by the mask's popcount. The bit loop's
+    /// cost grows with the newlines, and its exit is mispredicted once per chunk, while the
+    /// compress costs the same for any chunk.
+    ///
+    /// It doesn't pay off on an AVX-512 Xeon: the compress is cheap enough that any chunk
+    /// taking the bit loop instead is slower, and the branch between the two mispredicts on
+    /// the `mixed` stage. With up to 2 newlines taking the bit loop, it's ahead of
+    /// [`avx512_compress`] only on `single line` and `0-0` (by noise), and about half as fast on
+    /// `mixed` and the stages with 10 to 40 byte lines. Larger thresholds do worse.
+    #[inline(never)]
+    #[target_feature(enable = "popcnt,avx512f,avx512bw,avx512vbmi2")]
+    pub unsafe fn avx512_hybrid(input: &str, out: &mut LineIndex) {
+        const IDX_ARR: [u8; 64] = {
+            let mut t = [0u8; 64];
+            let mut i = 0;
+            while i < t.len() {
+                t[i] = i as u8;
+                i += 1;
+            }
+            t
+        };
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let idx_v = _mm512_loadu_epi8(IDX_ARR.as_ptr().cast());
+        for chunk_64k in input.as_bytes().chunks(1 << 16) {
+            out.high_starts.push(out.lows.len());
+            let mut chunk_i = 0;
+            let stop_chunk_i = chunk_64k.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.lows.reserve(256);
+                let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let v = _mm512_loadu_si512(chunk_64k.as_ptr().add(chunk_i * 64).cast());
+                    let mut mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+                    let num_lines = mask.count_ones();
+                    if num_lines <= HYBRID_SPARSE_MAX {
+                        while mask != 0 {
+                            let bit_pos = mask.trailing_zeros() as u16;
+                            out_arr
+                                .get_unchecked_mut(write_i)
+                                .write(chunk_i as u16 * 64 + bit_pos);
+                            write_i += 1;
+                            mask &= mask - 1;
+                        }
+                    } else {
+                        let offset_v = _mm512_set1_epi16((chunk_i * 64) as i16);
+                        let idxs = _mm512_maskz_compress_epi8(mask, idx_v);
+                        let low_idxs = _mm512_cvtepu8_epi16(_mm512_castsi512_si256(idxs));
+                        let low_idxs = _mm512_add_epi16(low_idxs, offset_v);
+                        _mm512_storeu_si512(out_arr.as_mut_ptr().add(write_i).cast(), low_idxs);
+                        if num_lines > 32 {
+                            let high_idxs =
+                                _mm512_cvtepu8_epi16(_mm512_extracti64x4_epi64::<1>(idxs));
+                            let high_idxs = _mm512_add_epi16(high_idxs, offset_v);
+                            _mm512_storeu_si512(
+                                out_arr.as_mut_ptr().add(write_i).byte_add(64).cast(),
+                                high_idxs,
+                            );
+                        }
+                        write_i += num_lines as usize;
+                    }
+                    chunk_i += 1;
+                }
+                out.lows.set_len(out.lows.len() + write_i);
+            }
+        }
+        tail_avx512(64, input, out);
+    }
+}
@@ -0,0 +1,81 @@
+//! A cheap guess at how many bytes an input's lines take, from the newlines in about 1% of its
+//! 64-byte blocks, spread evenly over it. The dispatcher uses it to pick between a kernel that's
+//! best on long lines and one that's best on short ones, see `Dispatcher::split_adaptive`.
+
+/// Bytes between sampled blocks, so about one block in 100 is read. A prime, so the samples of
+/// fixed-length lines don't all land on the same offset in their line.
+const SAMPLE_STRIDE: usize = 6397;
+
+/// Newlines found in the sampled bytes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LineDensity {
+    pub sampled_bytes: usize,
+    pub newlines: usize,
+}
+
+impl LineDensity {
+    /// Average line length (newline included), infinite if the samples had no newline
+    pub fn bytes_per_line(self) -> f64 {
+        self.sampled_bytes as f64 / self.newlines as f64
+    }
+}
+
+/// Counts the newlines of one 64-byte block every [`SAMPLE_STRIDE`] bytes. Inputs of less than
+/// a block are counted whole.
+pub fn estimate_line_density(input: &str) -> LineDensity {
+    let bytes = input.as_bytes();
+    if bytes.len() < 64 {
+        return LineDensity {
+            sampled_bytes: bytes.len(),
+            newlines: count_newlines(bytes),
+        };
+    }
+    let mut density = LineDensity {
+        sampled_bytes: 0,
+        newlines: 0,
+    };
+    // the last block can start anywhere, as long as it's whole
+    for start in (0..=bytes.len() - 64).step_by(SAMPLE_STRIDE) {
+        density.sampled_bytes += 64;
+        density.newlines += count_newlines(&bytes[start..start + 64]);
+    }
+    density
+}
+
+/// A loop LLVM vectorizes on its own, which is plenty for 1% of the input
+fn count_newlines(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b == b'\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_line_density() {
+        assert_eq!(
+            estimate_line_density("a\nb\n"),
+            LineDensity {
+                sampled_bytes: 4,
+                newlines: 2
+            }
+        );
+        assert!(estimate_line_density("").bytes_per_line().is_nan());
+        assert!(estimate_line_density(&"a".repeat(100_000))
+            .bytes_per_line()
+            .is_infinite());
+        // 64 lines of 100 bytes would be 6400 bytes, so a stride of that would only ever see a
+        // newline in every sample or in none
+        let long = "a".repeat(99) + "\n";
+        for (line, expected) in [("abc\n", 4.), (long.as_str(), 100.)] {
+            let input = line.repeat(100_000);
+            let density = estimate_line_density(&input);
+            assert_eq!(
+                density.sampled_bytes,
+                ((input.len() - 64) / SAMPLE_STRIDE + 1) * 64
+            );
+            let estimate = density.bytes_per_line();
+            assert!((estimate / expected - 1.).abs() < 0.05, "{estimate}");
+        }
+    }
+}
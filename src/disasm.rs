@@ -0,0 +1,215 @@
+//! `--emit-asm`: the disassembly of one kernel in this binary, printed next to its results, so a
+//! question about a table row can go straight to the instructions. It runs binutils' `nm` and
+//! `objdump` on the running executable, which takes an ELF build on Linux with its symbol table
+//! (release builds keep it unless `strip = "symbols"`).
+//!
+//! The kernels are behind function pointers, so the function is found by address. Most slice
+//! cases are closures calling a `#[target_feature]` kernel that can't be inlined into them, so a
+//! function without a loop that calls or jumps to only one other function is skipped for that one.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+/// Wrappers skipped at most, past the function pointer itself
+const MAX_WRAPPERS: usize = 3;
+
+pub struct Instruction {
+    pub address: u64,
+    /// Intel syntax, as `objdump` prints it
+    pub text: String,
+    /// Loops the instruction is in, each one being the span of a backward branch
+    pub loop_depth: usize,
+}
+
+pub struct Function {
+    /// Demangled
+    pub symbol: String,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Function {
+    fn start(&self) -> u64 {
+        self.instructions.first().map_or(0, |ins| ins.address)
+    }
+
+    fn end(&self) -> u64 {
+        self.instructions.last().map_or(0, |ins| ins.address + 1)
+    }
+
+    /// Distinct call and jump targets outside the function
+    fn callees(&self) -> Vec<u64> {
+        let mut callees = Vec::new();
+        for ins in &self.instructions {
+            if let Some(target) = branch_target(&ins.text) {
+                if !(self.start()..self.end()).contains(&target) && !callees.contains(&target) {
+                    callees.push(target);
+                }
+            }
+        }
+        callees
+    }
+}
+
+/// Disassembles the function `addr` points to, or the kernel it wraps
+pub fn disassemble(addr: usize) -> Result<Function, String> {
+    let exe = std::fs::read_link("/proc/self/exe")
+        .map_err(|err| format!("finding the executable: {err}"))?;
+    let symbols = symbols(&exe)?;
+    let mut function = function_at(&exe, &symbols, addr as u64 - load_bias(&exe)?)?;
+    for _ in 0..MAX_WRAPPERS {
+        let has_loop = function.instructions.iter().any(|ins| ins.loop_depth > 0);
+        match function.callees()[..] {
+            [callee] if !has_loop => function = function_at(&exe, &symbols, callee)?,
+            _ => break,
+        }
+    }
+    Ok(function)
+}
+
+/// What to subtract from a runtime address to get the address in the file: where the executable
+/// was loaded if it's position independent, else nothing
+fn load_bias(exe: &Path) -> Result<u64, String> {
+    let mut header = [0; 18];
+    File::open(exe)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|err| format!("reading {}: {err}", exe.display()))?;
+    // e_type, 3 being ET_DYN
+    if header[16..] != [3, 0] {
+        return Ok(0);
+    }
+    let maps = std::fs::read_to_string("/proc/self/maps")
+        .map_err(|err| format!("reading /proc/self/maps: {err}"))?;
+    // "start-end perms offset dev inode path", the first mapping of the file at offset 0
+    maps.lines()
+        .find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (range, offset, path) = (fields[0], fields[2], *fields.get(5)?);
+            let start = range.split_once('-')?.0;
+            (Path::new(path) == exe && u64::from_str_radix(offset, 16) == Ok(0))
+                .then(|| u64::from_str_radix(start, 16).ok())?
+        })
+        .ok_or_else(|| format!("{} isn't in /proc/self/maps", exe.display()))
+}
+
+/// (start, size, name) of every function symbol
+fn symbols(exe: &Path) -> Result<Vec<(u64, u64, String)>, String> {
+    let stdout = run(Command::new("nm")
+        .args(["--defined-only", "--print-size", "--demangle"])
+        .arg(exe))?;
+    // "start size type name", where the name can have spaces
+    Ok(stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ' ');
+            let start = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let size = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let kind = fields.next()?;
+            let name = fields.next()?;
+            matches!(kind, "t" | "T").then(|| (start, size, name.to_owned()))
+        })
+        .collect())
+}
+
+fn function_at(exe: &Path, symbols: &[(u64, u64, String)], addr: u64) -> Result<Function, String> {
+    let (start, size, symbol) = symbols
+        .iter()
+        .find(|(start, size, _)| (*start..start + size).contains(&addr))
+        .ok_or_else(|| format!("no function symbol at {addr:#x}, is the binary stripped?"))?;
+    let stdout = run(Command::new("objdump")
+        .args(["-d", "--no-show-raw-insn", "-M", "intel", "--demangle"])
+        .arg(format!("--start-address={start:#x}"))
+        .arg(format!("--stop-address={:#x}", start + size))
+        .arg(exe))?;
+    // "  1a2b0:\tvpcmpeqb ymm1,ymm0,YMMWORD PTR [rdi]"
+    let mut instructions: Vec<Instruction> = stdout
+        .lines()
+        .filter_map(|line| {
+            let (address, text) = line.split_once(":\t")?;
+            Some(Instruction {
+                address: u64::from_str_radix(address.trim(), 16).ok()?,
+                // branches within the function only need the offset
+                text: text
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .replace(&format!("<{symbol}+"), "<+"),
+                loop_depth: 0,
+            })
+        })
+        .collect();
+    let loops: Vec<(u64, u64)> = instructions
+        .iter()
+        .filter_map(|ins| {
+            let target = branch_target(&ins.text)?;
+            (ins.text.starts_with('j') && (*start..=ins.address).contains(&target))
+                .then_some((target, ins.address))
+        })
+        .collect();
+    for ins in &mut instructions {
+        ins.loop_depth = loops
+            .iter()
+            .filter(|(first, last)| (*first..=*last).contains(&ins.address))
+            .count();
+    }
+    Ok(Function {
+        symbol: symbol.clone(),
+        instructions,
+    })
+}
+
+/// The address a `j*` or `call` goes to, when it's a constant
+fn branch_target(text: &str) -> Option<u64> {
+    let (mnemonic, operand) = text.split_once(' ')?;
+    if !mnemonic.starts_with('j') && mnemonic != "call" {
+        return None;
+    }
+    // "1a2c0 <symbol+0x10>"
+    u64::from_str_radix(operand.split(' ').next()?, 16).ok()
+}
+
+fn run(command: &mut Command) -> Result<String, String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|err| format!("running {program} (from binutils): {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|err| format!("{program}'s output: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_target() {
+        assert_eq!(branch_target("jne 2975d0 <+0x80>"), Some(0x2975d0));
+        assert_eq!(
+            branch_target("call 2afe4c <std::sync::once_lock::OnceLock<T>::initialize>"),
+            Some(0x2afe4c)
+        );
+        assert_eq!(branch_target("jmp rax"), None);
+        assert_eq!(branch_target("mov rdx,rsi"), None);
+        assert_eq!(branch_target("ret"), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_disassemble() {
+        let estimate: fn(&str) -> _ = crate::density::estimate_line_density;
+        let function = disassemble(estimate as usize).unwrap();
+        assert!(
+            function.symbol.contains("estimate_line_density"),
+            "{}",
+            function.symbol
+        );
+        // the loop over the samples
+        assert!(function.instructions.iter().any(|ins| ins.loop_depth > 0));
+    }
+}
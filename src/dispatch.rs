@@ -0,0 +1,467 @@
+//! Which slice kernel [`slice::split`] runs. Having the features for a kernel doesn't make it the
+//! fastest one: on the 9th gen Intel in the README, `sse2_unrollx4` beats `avx2_unrollx2` on every
+//! stage but the single line, and Zen 1 executes each 256-bit op as two 128-bit halves. So the
+//! dispatcher walks an ordered preference list and takes the first kernel the CPU can run. The
+//! default list depends on the CPU family, and [`Dispatcher::builder`] puts other preferences in
+//! front of it.
+//!
+//! A second kernel is kept for inputs of long lines, which [`Dispatcher::split_adaptive`] runs
+//! when a sample of the input says its lines are at least [`SPARSE_BYTES_PER_LINE`] long.
+
+#[cfg(target_arch = "x86_64")]
+use crate::splitters::Avx2Splitter;
+use crate::{density, slice};
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ptr;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SliceKernel {
+    /// Not in any default order: one 512-bit compare per 64 bytes saves only the second compare
+    /// and mask merge of `avx2_unrollx2`, while the bit loop over the mask, which is most of the
+    /// work on dense lines, is the same, and some Intel cores lower their clock while running
+    /// 512-bit ops. Whether it wins is up to the CPU, which the slice table's
+    /// `avx512_unrollx2` row says, and `prefer` picks it.
+    Avx512Unrollx2,
+    Avx2Unrollx2,
+    Sse2Unrollx4,
+    NeonUnrollx4,
+    Neon,
+    /// Only in wasm builds with `simd128` enabled, see [`slice::wasm32`]
+    Simd128,
+    /// Only in riscv64 builds with the `v` target feature, see [`slice::riscv64`]
+    Rvv,
+    /// LoongArch's 256-bit vectors, detected at runtime, see [`slice::loongarch64`]
+    Lasx,
+    /// LoongArch's 128-bit vectors, which CPUs without LASX (the 2K series) still have
+    Lsx,
+    /// 8 bytes at a time in a `u64`, for the targets none of the above run on
+    Swar,
+    /// The portable fallback, always supported
+    StdReuse,
+}
+
+impl SliceKernel {
+    pub fn name(self) -> &'static str {
+        match self {
+            SliceKernel::Avx512Unrollx2 => "avx512_unrollx2",
+            SliceKernel::Avx2Unrollx2 => "avx2_unrollx2",
+            SliceKernel::Sse2Unrollx4 => "sse2_unrollx4",
+            SliceKernel::NeonUnrollx4 => "neon_unrollx4",
+            SliceKernel::Neon => "neon",
+            SliceKernel::Simd128 => "simd128",
+            SliceKernel::Rvv => "rvv",
+            SliceKernel::Lasx => "lasx",
+            SliceKernel::Lsx => "lsx",
+            SliceKernel::Swar => "swar",
+            SliceKernel::StdReuse => "std_reuse",
+        }
+    }
+
+    /// Bytes per iteration of the kernel's vector loop. Inputs shorter than that (and the end of
+    /// longer ones) go through its scalar tail. `None` for the scalar kernel.
+    pub fn chunk_len(self) -> Option<usize> {
+        match self {
+            SliceKernel::Avx512Unrollx2 => Some(128),
+            SliceKernel::Avx2Unrollx2 | SliceKernel::Sse2Unrollx4 => Some(64),
+            SliceKernel::NeonUnrollx4 => Some(64),
+            SliceKernel::Neon => Some(16),
+            SliceKernel::Simd128 => Some(64),
+            // at most, with vector registers of 512 bits or more
+            SliceKernel::Rvv => Some(256),
+            SliceKernel::Lasx | SliceKernel::Lsx => Some(64),
+            SliceKernel::Swar => Some(8),
+            SliceKernel::StdReuse => None,
+        }
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            SliceKernel::Avx512Unrollx2 => slice::x86_64::can_run_avx512(),
+            #[cfg(target_arch = "x86_64")]
+            SliceKernel::Avx2Unrollx2 => slice::x86_64::can_run_avx2(),
+            #[cfg(target_arch = "x86_64")]
+            SliceKernel::Sse2Unrollx4 => true,
+            #[cfg(target_arch = "aarch64")]
+            SliceKernel::NeonUnrollx4 | SliceKernel::Neon => true,
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            SliceKernel::Simd128 => true,
+            #[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+            SliceKernel::Rvv => true,
+            #[cfg(target_arch = "loongarch64")]
+            SliceKernel::Lasx => slice::loongarch64::can_run_lasx(),
+            #[cfg(target_arch = "loongarch64")]
+            SliceKernel::Lsx => slice::loongarch64::can_run_lsx(),
+            SliceKernel::Swar | SliceKernel::StdReuse => true,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for SliceKernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        [
+            SliceKernel::Avx512Unrollx2,
+            SliceKernel::Avx2Unrollx2,
+            SliceKernel::Sse2Unrollx4,
+            SliceKernel::NeonUnrollx4,
+            SliceKernel::Neon,
+            SliceKernel::Simd128,
+            SliceKernel::Rvv,
+            SliceKernel::Lasx,
+            SliceKernel::Lsx,
+            SliceKernel::Swar,
+            SliceKernel::StdReuse,
+        ]
+        .into_iter()
+        .find(|kernel| kernel.name() == s)
+        .ok_or_else(|| {
+            "expected one of avx512_unrollx2, avx2_unrollx2, sse2_unrollx4, neon_unrollx4, neon, simd128, rvv, lasx, lsx, swar, std_reuse"
+                .to_owned()
+        })
+    }
+}
+
+/// CPUs grouped by which slice kernel wins on them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+pub enum CpuFamily {
+    /// AMD family 17h before Zen 2 (Zen 1 and Zen+), with 128-bit AVX2 units
+    Zen1,
+    /// Intel without AVX-512, like the 9th gen in the README
+    IntelNoAvx512,
+    /// Anything with AVX-512, like the "CPU w/ AVX512" in the README
+    Avx512,
+    /// Anything else, including every aarch64 CPU and every wasm engine
+    Other,
+}
+
+impl CpuFamily {
+    #[cfg(target_arch = "x86_64")]
+    pub fn detect() -> Self {
+        use core::arch::x86_64::__cpuid;
+
+        if has_x86_features!("avx512f") {
+            return CpuFamily::Avx512;
+        }
+        let vendor = __cpuid(0);
+        let vendor = [vendor.ebx, vendor.edx, vendor.ecx].map(u32::to_le_bytes);
+        let eax = __cpuid(1).eax;
+        let mut family = (eax >> 8) & 0xf;
+        let mut model = (eax >> 4) & 0xf;
+        if family == 0xf {
+            family += (eax >> 20) & 0xff;
+            model |= ((eax >> 16) & 0xf) << 4;
+        }
+        match vendor.as_flattened() {
+            b"AuthenticAMD" if family == 0x17 && model < 0x30 => CpuFamily::Zen1,
+            b"GenuineIntel" => CpuFamily::IntelNoAvx512,
+            _ => CpuFamily::Other,
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn detect() -> Self {
+        CpuFamily::Other
+    }
+
+    /// The kernels in order of preference, from the README tables (and for Zen 1, from halving
+    /// the AVX2 throughput). [`SliceKernel::StdReuse`] comes after all of them.
+    pub fn default_order(self) -> &'static [SliceKernel] {
+        match self {
+            CpuFamily::Zen1 | CpuFamily::IntelNoAvx512 => {
+                &[SliceKernel::Sse2Unrollx4, SliceKernel::Avx2Unrollx2]
+            }
+            CpuFamily::Avx512 => &[SliceKernel::Avx2Unrollx2, SliceKernel::Sse2Unrollx4],
+            // the neon kernels only run on aarch64, simd128 on wasm, rvv on riscv64 and lasx and
+            // lsx on loongarch64, where the others can't, and swar runs everywhere, so it's what
+            // targets without any of them get.
+            // `neon_unrollx4` goes first as `sse2_unrollx4` does, without Arm results to say so
+            CpuFamily::Other => &[
+                SliceKernel::Avx2Unrollx2,
+                SliceKernel::Sse2Unrollx4,
+                SliceKernel::NeonUnrollx4,
+                SliceKernel::Neon,
+                SliceKernel::Simd128,
+                SliceKernel::Rvv,
+                SliceKernel::Lasx,
+                SliceKernel::Lsx,
+                SliceKernel::Swar,
+            ],
+        }
+    }
+}
+
+/// Average line length (newline included) from which [`Dispatcher::split_adaptive`] runs the
+/// sparse kernel. On the 9th gen Intel in the README, `avx2_unrollx2` only beats `sse2_unrollx4`
+/// on the single line stage, so it takes lines much longer than the 40-120 byte stage's.
+pub const SPARSE_BYTES_PER_LINE: f64 = 256.;
+
+pub struct Dispatcher {
+    family: CpuFamily,
+    /// The whole preference list, including the kernels this CPU can't run
+    order: Vec<SliceKernel>,
+    /// The first kernel in `order` the CPU supports
+    kernel: SliceKernel,
+    /// What [`Dispatcher::split_adaptive`] runs on long lines, `kernel` if it isn't supported
+    sparse_kernel: SliceKernel,
+    #[cfg(target_arch = "x86_64")]
+    avx2: Option<Avx2Splitter>,
+}
+
+impl Dispatcher {
+    pub fn builder() -> DispatcherBuilder {
+        DispatcherBuilder {
+            preferred: Vec::new(),
+            family: CpuFamily::detect(),
+            sparse: SliceKernel::Avx2Unrollx2,
+        }
+    }
+
+    pub fn split<'input>(&self, input: &'input str, out: &mut Vec<&'input str>) {
+        self.split_with(self.kernel, input, out)
+    }
+
+    /// [`Dispatcher::split`], or the sparse kernel if a sample of `input` (see
+    /// [`density::estimate_line_density`]) has lines of [`SPARSE_BYTES_PER_LINE`] or more
+    pub fn split_adaptive<'input>(&self, input: &'input str, out: &mut Vec<&'input str>) {
+        let kernel = match self.sparse_kernel != self.kernel
+            && density::estimate_line_density(input).bytes_per_line() >= SPARSE_BYTES_PER_LINE
+        {
+            true => self.sparse_kernel,
+            false => self.kernel,
+        };
+        self.split_with(kernel, input, out)
+    }
+
+    /// Always runs the sparse kernel, for comparing with [`Dispatcher::split_adaptive`]
+    pub fn split_sparse<'input>(&self, input: &'input str, out: &mut Vec<&'input str>) {
+        self.split_with(self.sparse_kernel, input, out)
+    }
+
+    /// Runs `kernel`, which must be `kernel` or `sparse_kernel`, so the CPU supports it
+    fn split_with<'input>(
+        &self,
+        kernel: SliceKernel,
+        input: &'input str,
+        out: &mut Vec<&'input str>,
+    ) {
+        match kernel {
+            #[cfg(target_arch = "x86_64")]
+            SliceKernel::Avx512Unrollx2 => unsafe { slice::x86_64::avx512_unrollx2(input, out) },
+            #[cfg(target_arch = "x86_64")]
+            SliceKernel::Avx2Unrollx2 => self.avx2.unwrap().split(input, out),
+            #[cfg(target_arch = "x86_64")]
+            SliceKernel::Sse2Unrollx4 => slice::x86_64::sse2_unrollx4(input, out),
+            #[cfg(target_arch = "aarch64")]
+            SliceKernel::NeonUnrollx4 => slice::aarch64::neon_unrollx4(input, out),
+            #[cfg(target_arch = "aarch64")]
+            SliceKernel::Neon => slice::aarch64::neon(input, out),
+            #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+            SliceKernel::Simd128 => slice::wasm32::simd128(input, out),
+            #[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+            SliceKernel::Rvv => slice::riscv64::rvv(input, out),
+            #[cfg(target_arch = "loongarch64")]
+            SliceKernel::Lasx => unsafe { slice::loongarch64::lasx(input, out) },
+            #[cfg(target_arch = "loongarch64")]
+            SliceKernel::Lsx => unsafe { slice::loongarch64::lsx(input, out) },
+            SliceKernel::Swar => slice::swar(input, out),
+            _ => slice::std_reuse(input, out),
+        }
+    }
+
+    /// The kernel [`Dispatcher::split`] runs
+    pub fn kernel(&self) -> SliceKernel {
+        self.kernel
+    }
+
+    /// The kernel [`Dispatcher::split_adaptive`] runs on long lines
+    pub fn sparse_kernel(&self) -> SliceKernel {
+        self.sparse_kernel
+    }
+
+    /// Makes this the dispatcher behind [`slice::split`]. Fails (handing it back) if
+    /// [`slice::split`] already ran or another one was installed.
+    pub fn install(self) -> Result<(), Self> {
+        set_global(self).map(|_| ())
+    }
+}
+
+impl fmt::Display for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?}, order", self.kernel.name(), self.family)?;
+        for kernel in &self.order {
+            write!(f, " {}", kernel.name())?;
+        }
+        write!(f, ", sparse {})", self.sparse_kernel.name())
+    }
+}
+
+pub struct DispatcherBuilder {
+    preferred: Vec<SliceKernel>,
+    family: CpuFamily,
+    sparse: SliceKernel,
+}
+
+impl DispatcherBuilder {
+    /// Tries `kernel` after the ones preferred so far, but before the family defaults
+    pub fn prefer(mut self, kernel: SliceKernel) -> Self {
+        self.preferred.push(kernel);
+        self
+    }
+
+    /// The kernel [`Dispatcher::split_adaptive`] runs on long lines (`avx2_unrollx2` by
+    /// default). One the CPU doesn't support leaves it to the main kernel.
+    pub fn sparse(mut self, kernel: SliceKernel) -> Self {
+        self.sparse = kernel;
+        self
+    }
+
+    pub fn build(self) -> Dispatcher {
+        let mut order = Vec::new();
+        let candidates = self.preferred.iter().chain(self.family.default_order());
+        for &kernel in candidates.chain(&[SliceKernel::StdReuse]) {
+            if !order.contains(&kernel) {
+                order.push(kernel);
+            }
+        }
+        let kernel = *order.iter().find(|kernel| kernel.is_supported()).unwrap();
+        let sparse_kernel = match self.sparse.is_supported() {
+            true => self.sparse,
+            false => kernel,
+        };
+        Dispatcher {
+            family: self.family,
+            order,
+            kernel,
+            sparse_kernel,
+            #[cfg(target_arch = "x86_64")]
+            avx2: Avx2Splitter::new(),
+        }
+    }
+}
+
+/// A leaked `Box<Dispatcher>`, null until the first [`global`] or [`Dispatcher::install`]. Not a
+/// `OnceLock`, which needs std: threads that race to set it each build a dispatcher, and all but
+/// one get theirs back.
+static GLOBAL: AtomicPtr<Dispatcher> = AtomicPtr::new(ptr::null_mut());
+
+fn set_global(dispatcher: Dispatcher) -> Result<&'static Dispatcher, Dispatcher> {
+    let new = Box::into_raw(Box::new(dispatcher));
+    match GLOBAL.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Ok(unsafe { &*new }),
+        // never shared, so still ours
+        Err(_) => Err(*unsafe { Box::from_raw(new) }),
+    }
+}
+
+/// The installed dispatcher, or the detected family's defaults if there is none
+pub fn global() -> &'static Dispatcher {
+    let current = GLOBAL.load(Ordering::Acquire);
+    if !current.is_null() {
+        // set once and never freed
+        return unsafe { &*current };
+    }
+    match set_global(Dispatcher::builder().build()) {
+        Ok(dispatcher) => dispatcher,
+        Err(_) => unsafe { &*GLOBAL.load(Ordering::Acquire) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order() {
+        let builder = |family| DispatcherBuilder {
+            preferred: Vec::new(),
+            family,
+            sparse: SliceKernel::Avx2Unrollx2,
+        };
+        let dispatcher = builder(CpuFamily::Avx512)
+            .prefer(SliceKernel::Sse2Unrollx4)
+            .prefer(SliceKernel::StdReuse)
+            .prefer(SliceKernel::Sse2Unrollx4)
+            .build();
+        assert_eq!(
+            dispatcher.order,
+            [
+                SliceKernel::Sse2Unrollx4,
+                SliceKernel::StdReuse,
+                SliceKernel::Avx2Unrollx2
+            ]
+        );
+        let dispatcher = builder(CpuFamily::Zen1).build();
+        assert_eq!(dispatcher.order[2], SliceKernel::StdReuse);
+        assert_eq!(
+            dispatcher.order.iter().find(|k| k.is_supported()),
+            Some(&dispatcher.kernel)
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let input = "ab\n\ncdefghijklmnopqrstuvwxyz0123456789\nx".repeat(10);
+        let expected: Vec<_> = input.lines().collect();
+        for kernel in [
+            "avx512_unrollx2",
+            "avx2_unrollx2",
+            "sse2_unrollx4",
+            "neon_unrollx4",
+            "neon",
+            "simd128",
+            "rvv",
+            "lasx",
+            "lsx",
+            "swar",
+            "std_reuse",
+        ] {
+            let kernel: SliceKernel = kernel.parse().unwrap();
+            let dispatcher = Dispatcher::builder().prefer(kernel).build();
+            let mut lines = Vec::new();
+            dispatcher.split(&input, &mut lines);
+            assert_eq!(lines, expected, "{dispatcher}");
+        }
+        assert!("avx2".parse::<SliceKernel>().is_err());
+    }
+
+    #[test]
+    fn test_split_adaptive() {
+        let dense = "ab\n\ncd\n".repeat(10_000);
+        let sparse = ("x".repeat(1000) + "\n").repeat(100);
+        for kernel in [
+            "avx512_unrollx2",
+            "avx2_unrollx2",
+            "sse2_unrollx4",
+            "neon_unrollx4",
+            "neon",
+            "simd128",
+            "rvv",
+            "lasx",
+            "lsx",
+            "swar",
+            "std_reuse",
+        ] {
+            let kernel: SliceKernel = kernel.parse().unwrap();
+            let dispatcher = Dispatcher::builder()
+                .prefer(SliceKernel::StdReuse)
+                .sparse(kernel)
+                .build();
+            assert!([kernel, SliceKernel::StdReuse].contains(&dispatcher.sparse_kernel()));
+            for input in [&dense, &sparse] {
+                let mut lines = Vec::new();
+                dispatcher.split_adaptive(input, &mut lines);
+                assert_eq!(lines, input.lines().collect::<Vec<_>>(), "{dispatcher}");
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! BOM sniffing, so UTF-16 files (common for logs exported on Windows) get split into lines
+//! instead of coming out as one garbled line. Transcoding needs the `utf16` feature.
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Utf8(std::str::Utf8Error),
+    /// UTF-16 input, but built without the `utf16` feature
+    #[cfg(not(feature = "utf16"))]
+    Utf16Unsupported,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Utf8(err) => err.fmt(f),
+            #[cfg(not(feature = "utf16"))]
+            DecodeError::Utf16Unsupported => {
+                f.write_str("input is UTF-16, rebuild with `--features utf16` to read it")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Detects the encoding from the BOM, if any. Returns the encoding and the length of the BOM.
+/// Input without a BOM is assumed to be UTF-8.
+pub fn sniff(input: &[u8]) -> (Encoding, usize) {
+    match input {
+        [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+        [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+        [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+        _ => (Encoding::Utf8, 0),
+    }
+}
+
+/// Returns `input` as UTF-8 without its BOM. UTF-8 input is borrowed as-is; UTF-16 input is
+/// transcoded into `scratch`.
+pub fn decode<'a>(input: &'a [u8], scratch: &'a mut String) -> Result<&'a str, DecodeError> {
+    let (encoding, bom_len) = sniff(input);
+    let input = &input[bom_len..];
+    match encoding {
+        Encoding::Utf8 => std::str::from_utf8(input).map_err(DecodeError::Utf8),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            transcode_utf16(encoding, input, scratch)?;
+            Ok(scratch.as_str())
+        }
+    }
+}
+
+#[cfg(feature = "utf16")]
+fn transcode_utf16(encoding: Encoding, input: &[u8], out: &mut String) -> Result<(), DecodeError> {
+    let encoding = match encoding {
+        Encoding::Utf16Be => encoding_rs::UTF_16BE,
+        _ => encoding_rs::UTF_16LE,
+    };
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    out.clear();
+    out.reserve(
+        decoder
+            .max_utf8_buffer_length(input.len())
+            .unwrap_or(input.len() * 3),
+    );
+    // malformed sequences become U+FFFD, like `String::from_utf8_lossy`
+    let _ = decoder.decode_to_string(input, out, true);
+    Ok(())
+}
+
+#[cfg(not(feature = "utf16"))]
+fn transcode_utf16(_: Encoding, _: &[u8], _: &mut String) -> Result<(), DecodeError> {
+    Err(DecodeError::Utf16Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        let mut out = if big_endian {
+            vec![0xfe, 0xff]
+        } else {
+            vec![0xff, 0xfe]
+        };
+        for unit in text.encode_utf16() {
+            out.extend(if big_endian {
+                unit.to_be_bytes()
+            } else {
+                unit.to_le_bytes()
+            });
+        }
+        out
+    }
+
+    #[test]
+    fn test_utf8() {
+        let mut scratch = String::new();
+        let text = decode(b"\xef\xbb\xbfa\nb\n", &mut scratch).unwrap();
+        assert_eq!(text, "a\nb\n");
+        assert!(scratch.is_empty());
+    }
+
+    #[cfg(feature = "utf16")]
+    #[test]
+    fn test_utf16() {
+        let text = "\u{e9}t\u{e9}\nline two\n\u{1f600}";
+        for big_endian in [false, true] {
+            let input = utf16(text, big_endian);
+            let mut scratch = String::new();
+            assert_eq!(decode(&input, &mut scratch).unwrap(), text);
+        }
+    }
+
+    #[cfg(not(feature = "utf16"))]
+    #[test]
+    fn test_utf16() {
+        let input = utf16("a\nb", false);
+        let mut scratch = String::new();
+        let result = decode(&input, &mut scratch);
+        assert!(matches!(result, Err(DecodeError::Utf16Unsupported)));
+    }
+}
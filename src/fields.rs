@@ -0,0 +1,282 @@
+//! A two-level index for TSV and CSV-like data: the record (newline) offsets, the field delimiter
+//! offsets, and for each record where its delimiters start, so field j of record i is two array
+//! reads away. One pass finds both delimiters, compared by the `fields` subcommand to a pass per
+//! delimiter. Quotes aren't parsed, so a quoted delimiter still splits. Only for inputs under
+//! 4 GiB.
+
+use std::ops::Range;
+
+/// The offsets of an input's delimiters
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct FieldIndex {
+    /// Newline offsets, as in `flat`
+    pub records: Vec<u32>,
+    /// Field delimiter offsets, across all records
+    pub fields: Vec<u32>,
+    /// Index in `fields` of each record's first delimiter, plus one past the last record's, so
+    /// record i's delimiters are `fields[field_starts[i]..field_starts[i + 1]]`
+    pub field_starts: Vec<u32>,
+}
+
+impl FieldIndex {
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.fields.clear();
+        self.field_starts.clear();
+        self.field_starts.push(0);
+    }
+
+    /// Counted like lines: a trailing newline doesn't start another record
+    pub fn record_count(&self) -> usize {
+        self.field_starts.len().saturating_sub(1)
+    }
+
+    /// Byte range of record i, not including its newline
+    pub fn record_range(&self, i: usize, input_len: usize) -> Range<usize> {
+        let start = if i == 0 {
+            0
+        } else {
+            self.records[i - 1] as usize + 1
+        };
+        let end = self
+            .records
+            .get(i)
+            .map_or(input_len, |&newline| newline as usize);
+        start..end
+    }
+
+    pub fn field_count(&self, record: usize) -> usize {
+        (self.field_starts[record + 1] - self.field_starts[record]) as usize + 1
+    }
+
+    /// Field j of record i, or `None` if it has fewer fields
+    pub fn field<'input>(&self, input: &'input str, i: usize, j: usize) -> Option<&'input str> {
+        let record = self.record_range(i, input.len());
+        let delimiters =
+            &self.fields[self.field_starts[i] as usize..self.field_starts[i + 1] as usize];
+        if j > delimiters.len() {
+            return None;
+        }
+        let start = match j {
+            0 => record.start,
+            j => delimiters[j - 1] as usize + 1,
+        };
+        let end = delimiters.get(j).map_or(record.end, |&d| d as usize);
+        Some(&input[start..end])
+    }
+
+    /// Closes the last record if it has no newline
+    fn finish(&mut self, input: &str) {
+        if !input.is_empty() && !input.ends_with('\n') {
+            self.field_starts.push(self.fields.len() as u32);
+        }
+    }
+}
+
+/// A kernel building the index of an input split by a delimiter, `unsafe` for the ones needing
+/// CPU features
+pub type FieldsFn = unsafe fn(&str, u8, &mut FieldIndex);
+
+/// Builds the index in one pass with the fastest kernel the current CPU supports
+pub fn build(input: &str, delimiter: u8, out: &mut FieldIndex) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, delimiter, out) };
+    }
+    scalar(input, delimiter, out)
+}
+
+pub fn scalar(input: &str, delimiter: u8, out: &mut FieldIndex) {
+    assert!(input.len() <= u32::MAX as usize);
+    assert_ne!(delimiter, b'\n');
+    out.clear();
+    for (pos, &byte) in input.as_bytes().iter().enumerate() {
+        if byte == b'\n' {
+            out.records.push(pos as u32);
+            out.field_starts.push(out.fields.len() as u32);
+        } else if byte == delimiter {
+            out.fields.push(pos as u32);
+        }
+    }
+    out.finish(input);
+}
+
+/// Links records to their delimiters after they were found separately, in one walk over both
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+pub fn link(out: &mut FieldIndex, input: &str) {
+    out.field_starts.clear();
+    out.field_starts.push(0);
+    let mut field = 0;
+    for &newline in &out.records {
+        while out.fields.get(field).is_some_and(|&d| d < newline) {
+            field += 1;
+        }
+        out.field_starts.push(field as u32);
+    }
+    out.finish(input);
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use std::arch::x86_64::*;
+
+    /// Both masks of each 64-byte chunk from one load. Newlines store their offset and the
+    /// delimiters before them in the chunk (a `popcnt`), delimiters their offset, in two
+    /// branch-free bit loops rather than one that branches on the kind of each bit.
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2(input: &str, delimiter: u8, out: &mut FieldIndex) {
+        assert!(input.len() <= u32::MAX as usize);
+        assert_ne!(delimiter, b'\n');
+        out.clear();
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let delimiter_v = _mm256_set1_epi8(delimiter as i8);
+        let mask_of = |v1, v2, byte_v| {
+            ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, byte_v)) as u32 as u64) << 32)
+                | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, byte_v)) as u32 as u64)
+        };
+        let stop_chunk_i = input.len() / 64;
+        let mut chunk_i = 0;
+        while chunk_i < stop_chunk_i {
+            let (mut records_i, mut fields_i) = (0, 0);
+            let fields_before = out.fields.len() as u32;
+            out.records.reserve(256);
+            out.field_starts.reserve(256);
+            out.fields.reserve(256);
+            let records = out.records.spare_capacity_mut().get_unchecked_mut(..256);
+            let field_starts = out
+                .field_starts
+                .spare_capacity_mut()
+                .get_unchecked_mut(..256);
+            let fields = out.fields.spare_capacity_mut().get_unchecked_mut(..256);
+            while records_i <= (256 - 64) && fields_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().add(chunk_i * 64);
+                let v1 = _mm256_loadu_si256(ptr.cast());
+                let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+                let mut nl_mask = mask_of(v1, v2, nl_v);
+                let mut delimiter_mask = mask_of(v1, v2, delimiter_v);
+                let base = chunk_i as u32 * 64;
+                let chunk_fields = fields_before + fields_i as u32;
+                while nl_mask != 0 {
+                    let pos = nl_mask.trailing_zeros();
+                    let before = (delimiter_mask & ((1 << pos) - 1)).count_ones();
+                    records.get_unchecked_mut(records_i).write(base + pos);
+                    field_starts
+                        .get_unchecked_mut(records_i)
+                        .write(chunk_fields + before);
+                    records_i += 1;
+                    nl_mask &= nl_mask - 1;
+                }
+                while delimiter_mask != 0 {
+                    fields
+                        .get_unchecked_mut(fields_i)
+                        .write(base + delimiter_mask.trailing_zeros());
+                    fields_i += 1;
+                    delimiter_mask &= delimiter_mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.records.set_len(out.records.len() + records_i);
+            out.field_starts.set_len(out.field_starts.len() + records_i);
+            out.fields.set_len(out.fields.len() + fields_i);
+        }
+        let base = stop_chunk_i * 64;
+        for (pos, &byte) in input.as_bytes()[base..].iter().enumerate() {
+            if byte == b'\n' {
+                out.records.push((base + pos) as u32);
+                out.field_starts.push(out.fields.len() as u32);
+            } else if byte == delimiter {
+                out.fields.push((base + pos) as u32);
+            }
+        }
+        out.finish(input);
+    }
+
+    /// `flat::x86_64::avx2` for any byte, for the pass-per-delimiter baseline
+    #[target_feature(enable = "avx2,bmi1")]
+    pub unsafe fn offsets(input: &str, byte: u8, out: &mut Vec<u32>) {
+        assert!(input.len() <= u32::MAX as usize);
+        let byte_v = _mm256_set1_epi8(byte as i8);
+        let stop_chunk_i = input.len() / 64;
+        let mut chunk_i = 0;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().add(chunk_i * 64);
+                let v1 = _mm256_loadu_si256(ptr.cast());
+                let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+                let mut mask =
+                    ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, byte_v)) as u32 as u64) << 32)
+                        | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, byte_v)) as u32 as u64);
+                while mask != 0 {
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(chunk_i as u32 * 64 + mask.trailing_zeros());
+                    write_i += 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        let base = stop_chunk_i * 64;
+        out.extend(
+            input.as_bytes()[base..]
+                .iter()
+                .enumerate()
+                .filter(|e| *e.1 == byte)
+                .map(|(idx, _)| (base + idx) as u32),
+        );
+    }
+
+    /// A pass per delimiter, then [`link`]
+    #[target_feature(enable = "avx2,bmi1")]
+    pub unsafe fn two_passes(input: &str, delimiter: u8, out: &mut FieldIndex) {
+        out.records.clear();
+        out.fields.clear();
+        offsets(input, b'\n', &mut out.records);
+        offsets(input, delimiter, &mut out.fields);
+        link(out, input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let long = "a\tbc\t\tdefghijklmnop\n\n\t\nqrstuvwxyz\t0123456789\n".repeat(8) + "x\ty";
+        for len in (0..=200).chain([long.len() - 1, long.len()]) {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let mut expected = FieldIndex::default();
+                scalar(input, b'\t', &mut expected);
+                let lines = crate::slice::std(input);
+                assert_eq!(expected.record_count(), lines.len());
+                for (i, line) in lines.iter().enumerate() {
+                    let fields: Vec<&str> = line.split('\t').collect();
+                    assert_eq!(expected.field_count(i), fields.len());
+                    for (j, field) in fields.iter().enumerate() {
+                        assert_eq!(expected.field(input, i, j), Some(*field));
+                    }
+                    assert_eq!(expected.field(input, i, fields.len()), None);
+                }
+                let mut linked = expected.clone();
+                link(&mut linked, input);
+                assert_eq!(linked, expected, "link, len {len}");
+                #[cfg(target_arch = "x86_64")]
+                if crate::slice::x86_64::can_run_avx2() {
+                    let kernels: &[(&str, FieldsFn)] =
+                        &[("avx2", x86_64::avx2), ("two passes", x86_64::two_passes)];
+                    for (name, kernel) in kernels {
+                        let mut out = FieldIndex::default();
+                        unsafe { kernel(input, b'\t', &mut out) };
+                        assert_eq!(out, expected, "{name}, len {len}");
+                    }
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Newline offsets as a flat `Vec<u32>`: twice the size of a `LineIndex`, but every lookup is a
+//! plain array access. Only for inputs under 4 GiB.
+
+use crate::compressed::{LineIndex, NewlineIndex};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Builds the offsets with the fastest kernel the current CPU supports
+pub fn build(input: &str, out: &mut Vec<u32>) {
+    #[cfg(target_arch = "x86_64")]
+    if x86_64::can_run_avx512_compressd() {
+        return unsafe { x86_64::avx512_compressd(input, out) };
+    } else if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, out) };
+    }
+    iter(input, out)
+}
+
+impl NewlineIndex for Vec<u32> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn newline(&self, i: usize) -> usize {
+        self[i] as usize
+    }
+}
+
+pub fn iter(input: &str, out: &mut Vec<u32>) {
+    assert!(input.len() <= u32::MAX as usize);
+    tail(0, input, out);
+}
+
+/// Expands an existing `LineIndex`, i.e. going through the u16 block scheme
+pub fn from_line_index(index: &LineIndex, out: &mut Vec<u32>) {
+    out.reserve(index.lows.len());
+    for (block, &start) in index.high_starts.iter().enumerate() {
+        let end = index
+            .high_starts
+            .get(block + 1)
+            .copied()
+            .unwrap_or(index.lows.len());
+        let high = (block as u32) << 16;
+        out.extend(index.lows[start..end].iter().map(|&low| high | low as u32));
+    }
+}
+
+/// Scalar loop over the bytes from `start` on
+pub fn tail(start: usize, input: &str, out: &mut Vec<u32>) {
+    for (idx, _) in input.as_bytes()[start..]
+        .iter()
+        .enumerate()
+        .filter(|e| *e.1 == b'\n')
+    {
+        out.push((start + idx) as u32);
+    }
+}
+
+/// Updates `index`, the newline offsets of an input before its bytes in `old` were replaced by
+/// `new_len` others, to those of `input`, the result. Only the new bytes are scanned, the offsets
+/// after them are moved by the change in length.
+pub fn splice(index: &mut Vec<u32>, input: &str, old: Range<usize>, new_len: usize) {
+    assert!(input.len() <= u32::MAX as usize);
+    let first = index.partition_point(|&nl| (nl as usize) < old.start);
+    let last = index.partition_point(|&nl| (nl as usize) < old.end);
+    let new_end = old.start + new_len;
+    for nl in &mut index[last..] {
+        *nl = (*nl as usize - old.end + new_end) as u32;
+    }
+    let found = input.as_bytes()[old.start..new_end]
+        .iter()
+        .enumerate()
+        .filter(|e| *e.1 == b'\n')
+        .map(|(idx, _)| (old.start + idx) as u32);
+    index.splice(first..last, found);
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use core::arch::x86_64::*;
+
+    /// The scan of `compressed::x86_64::avx2_unrollx2`, storing absolute offsets
+    #[target_feature(enable = "avx2,bmi1")]
+    pub unsafe fn avx2(input: &str, out: &mut Vec<u32>) {
+        assert!(input.len() <= u32::MAX as usize);
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().add(chunk_i * 64);
+                let v1 = _mm256_loadu_si256(ptr.cast());
+                let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+                let mut mask = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64)
+                    << 32)
+                    | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+                while mask != 0 {
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(chunk_i as u32 * 64 + mask.trailing_zeros());
+                    write_i += 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail(stop_chunk_i * 64, input, out);
+    }
+
+    pub fn can_run_avx512_compressd() -> bool {
+        has_x86_features!("popcnt", "avx512f", "avx512bw")
+    }
+
+    /// Each 16-byte quarter of a 64-byte chunk compresses a vector of its absolute offsets by
+    /// its 16 bits of the newline mask (`vpcompressd`). The compress goes to a register and is
+    /// then stored whole, because `vpcompressd` with a memory operand is microcoded on Zen 4.
+    #[target_feature(enable = "popcnt,avx512f,avx512bw")]
+    pub unsafe fn avx512_compressd(input: &str, out: &mut Vec<u32>) {
+        assert!(input.len() <= u32::MAX as usize);
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let u32_16_v = _mm512_set1_epi32(16);
+        // absolute offsets of the bytes in the current quarter
+        let mut offset_v = _mm512_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let v = _mm512_loadu_si512(input.as_ptr().add(chunk_i * 64).cast());
+                let mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+                for quarter in 0..4 {
+                    let quarter_mask = (mask >> (quarter * 16)) as u16;
+                    let offsets = _mm512_maskz_compress_epi32(quarter_mask, offset_v);
+                    // always in bounds: at most 48 were written for this chunk so far
+                    _mm512_storeu_si512(out_arr.as_mut_ptr().add(write_i).cast(), offsets);
+                    write_i += quarter_mask.count_ones() as usize;
+                    offset_v = _mm512_add_epi32(offset_v, u32_16_v);
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail(stop_chunk_i * 64, input, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(f: impl Fn(&str, &mut Vec<u32>)) {
+        let long = "ab\n\n\n0123456789\n".repeat(20_000);
+        for len in [0, 1, 3, 63, 64, 65, 1000, 65536 + 7, long.len()] {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let expected: Vec<u32> = input.match_indices('\n').map(|(i, _)| i as u32).collect();
+                let mut out = vec![7];
+                f(input, &mut out);
+                assert_eq!(out[0], 7, "len {len}");
+                assert_eq!(out[1..], expected, "len {len}");
+            });
+        }
+    }
+
+    #[test]
+    fn test_iter() {
+        check(iter);
+    }
+
+    #[test]
+    fn test_build() {
+        check(build);
+    }
+
+    #[test]
+    fn test_splice() {
+        // (input, replaced range, replacement)
+        let cases: &[(&str, Range<usize>, &str)] = &[
+            ("", 0..0, ""),
+            ("", 0..0, "\n\n"),
+            ("a\nb\nc", 1..2, ""),
+            ("a\nb\nc", 1..4, "\n\n\nx"),
+            ("a\nb\nc", 5..5, "\n"),
+            ("\n\n\n", 0..3, "abc"),
+            ("ab\ncd\n", 1..1, "\n"),
+        ];
+        for (before, old, new) in cases {
+            let mut index = Vec::new();
+            iter(before, &mut index);
+            let mut after = before.to_string();
+            after.replace_range(old.clone(), new);
+            splice(&mut index, &after, old.clone(), new.len());
+            let mut expected = Vec::new();
+            iter(&after, &mut expected);
+            assert_eq!(index, expected, "{before:?}, {old:?} -> {new:?}");
+        }
+    }
+
+    #[test]
+    fn test_from_line_index() {
+        check(|input, out| {
+            let mut index = LineIndex::default();
+            crate::compressed::build(input, &mut index);
+            from_line_index(&index, out);
+        });
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2() {
+        if crate::slice::x86_64::can_run_avx2() {
+            check(|input, out| unsafe { x86_64::avx2(input, out) });
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx512_compressd() {
+        if x86_64::can_run_avx512_compressd() {
+            check(|input, out| unsafe { x86_64::avx512_compressd(input, out) });
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Experimental: building a `LineIndex` with a compute shader (`--features gpu`).
+//!
+//! One workgroup handles one 64 KiB block, so the block structure of `LineIndex` falls out
+//! naturally. Each of its 256 threads counts the newlines in 256 bytes, the counts are
+//! prefix-summed in workgroup memory, and then the block reserves its output range with a single
+//! `atomicAdd` on a global counter. Blocks therefore land in the output in whatever order they
+//! finish, and the host puts them back in order while copying into the index.
+//!
+//! The bench reports the end-to-end number, plus a compute-only row. The gap between the two is
+//! the cost of moving data across the bus, which is the whole question for this experiment.
+
+use crate::compressed::LineIndex;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const BLOCK_SIZE: usize = 1 << 16;
+/// Input handled per dispatch. The worst case output is one u32 per input byte, which has to
+/// stay under the default 128 MiB storage buffer binding limit.
+const BATCH_SIZE: usize = 256 * BLOCK_SIZE;
+
+const SHADER: &str = r#"
+@group(0) @binding(0) var<storage, read> input: array<u32>;
+@group(0) @binding(1) var<storage, read_write> positions: array<u32>;
+// per block: (start in `positions`, count)
+@group(0) @binding(2) var<storage, read_write> blocks: array<vec2<u32>>;
+@group(0) @binding(3) var<storage, read_write> total: atomic<u32>;
+// x: length of this batch in bytes
+@group(0) @binding(4) var<uniform> params: vec4<u32>;
+
+var<workgroup> counts: array<u32, 256>;
+var<workgroup> block_base: u32;
+
+fn newline_bits(word: u32) -> u32 {
+    var bits = 0u;
+    for (var b = 0u; b < 4u; b++) {
+        if ((word >> (b * 8u)) & 0xffu) == 10u {
+            bits |= 1u << b;
+        }
+    }
+    return bits;
+}
+
+@compute @workgroup_size(256)
+fn main(@builtin(workgroup_id) wg: vec3<u32>, @builtin(local_invocation_index) t: u32) {
+    let seg_start = wg.x * 65536u + t * 256u;
+    let len = params.x;
+
+    var count = 0u;
+    for (var w = 0u; w < 64u; w++) {
+        let byte = seg_start + w * 4u;
+        if byte >= len {
+            break;
+        }
+        count += countOneBits(newline_bits(input[byte / 4u]));
+    }
+    counts[t] = count;
+    workgroupBarrier();
+
+    if t == 0u {
+        var sum = 0u;
+        for (var i = 0u; i < 256u; i++) {
+            let c = counts[i];
+            counts[i] = sum;
+            sum += c;
+        }
+        block_base = atomicAdd(&total, sum);
+        blocks[wg.x] = vec2<u32>(block_base, sum);
+    }
+    workgroupBarrier();
+
+    var out_i = block_base + counts[t];
+    for (var w = 0u; w < 64u; w++) {
+        let byte = seg_start + w * 4u;
+        if byte >= len {
+            break;
+        }
+        var bits = newline_bits(input[byte / 4u]);
+        while bits != 0u {
+            let b = firstTrailingBit(bits);
+            positions[out_i] = t * 256u + w * 4u + b;
+            out_i++;
+            bits &= bits - 1u;
+        }
+    }
+}
+"#;
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+/// `None` if there's no usable adapter
+fn gpu() -> Option<&'static Gpu> {
+    static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+    GPU.get_or_init(|| {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&Default::default())).ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("split"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("split"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Some(Gpu {
+            device,
+            queue,
+            pipeline,
+        })
+    })
+    .as_ref()
+}
+
+pub fn can_run() -> bool {
+    gpu().is_some()
+}
+
+/// Where the time went in the most recent [`build`]
+#[derive(Clone, Copy, Default)]
+pub struct Timings {
+    pub upload: Duration,
+    pub compute: Duration,
+    /// Reading results back, and putting the blocks in order
+    pub download: Duration,
+}
+
+static LAST_TIMINGS: Mutex<Timings> = Mutex::new(Timings {
+    upload: Duration::ZERO,
+    compute: Duration::ZERO,
+    download: Duration::ZERO,
+});
+
+pub fn last_timings() -> Timings {
+    *LAST_TIMINGS.lock().unwrap()
+}
+
+/// Panics if [`can_run`] is false
+pub fn build(input: &str, out: &mut LineIndex) {
+    use wgpu::BufferUsages as Usage;
+    let gpu = gpu().expect("no GPU adapter");
+    let (device, queue) = (&gpu.device, &gpu.queue);
+    let buffer = |label, size: usize, usage| {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as u64,
+            usage,
+            mapped_at_creation: false,
+        })
+    };
+    let max_blocks = BATCH_SIZE / BLOCK_SIZE;
+    let input_buf = buffer("input", BATCH_SIZE, Usage::STORAGE | Usage::COPY_DST);
+    let positions_buf = buffer(
+        "positions",
+        BATCH_SIZE * 4,
+        Usage::STORAGE | Usage::COPY_SRC,
+    );
+    let blocks_buf = buffer("blocks", max_blocks * 8, Usage::STORAGE | Usage::COPY_SRC);
+    let total_buf = buffer("total", 4, Usage::STORAGE | Usage::COPY_DST);
+    let params_buf = buffer("params", 16, Usage::UNIFORM | Usage::COPY_DST);
+    let readback_blocks = buffer(
+        "readback blocks",
+        max_blocks * 8,
+        Usage::MAP_READ | Usage::COPY_DST,
+    );
+    let readback_positions = buffer(
+        "readback positions",
+        BATCH_SIZE * 4,
+        Usage::MAP_READ | Usage::COPY_DST,
+    );
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &gpu.pipeline.get_bind_group_layout(0),
+        entries: &[
+            &input_buf,
+            &positions_buf,
+            &blocks_buf,
+            &total_buf,
+            &params_buf,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, buf)| wgpu::BindGroupEntry {
+            binding: i as u32,
+            resource: buf.as_entire_binding(),
+        })
+        .collect::<Vec<_>>(),
+    });
+    let wait = || device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    let read = |buf: &wgpu::Buffer, size: usize, dst: &mut Vec<u32>| {
+        let slice = buf.slice(..size as u64);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        wait();
+        let view = slice.get_mapped_range().unwrap();
+        dst.clear();
+        dst.extend(
+            view.chunks_exact(4)
+                .map(|w| u32::from_le_bytes(w.try_into().unwrap())),
+        );
+        drop(view);
+        buf.unmap();
+    };
+
+    let mut timings = Timings::default();
+    let (mut blocks, mut positions) = (Vec::new(), Vec::new());
+    for batch in input.as_bytes().chunks(BATCH_SIZE) {
+        let start = Instant::now();
+        // writes must be a multiple of 4 bytes, so pad the last word with zeros
+        let aligned = batch.len() & !3;
+        queue.write_buffer(&input_buf, 0, &batch[..aligned]);
+        if aligned != batch.len() {
+            let mut last = [0u8; 4];
+            last[..batch.len() - aligned].copy_from_slice(&batch[aligned..]);
+            queue.write_buffer(&input_buf, aligned as u64, &last);
+        }
+        queue.write_buffer(&total_buf, 0, &0u32.to_le_bytes());
+        let params = [batch.len() as u32, 0, 0, 0];
+        queue.write_buffer(&params_buf, 0, &params.map(u32::to_le_bytes).concat());
+        queue.submit([]);
+        wait();
+        timings.upload += start.elapsed();
+
+        let start = Instant::now();
+        let block_count = batch.len().div_ceil(BLOCK_SIZE);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&gpu.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(block_count as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&blocks_buf, 0, &readback_blocks, 0, block_count as u64 * 8);
+        queue.submit([encoder.finish()]);
+        wait();
+        timings.compute += start.elapsed();
+
+        let start = Instant::now();
+        read(&readback_blocks, block_count * 8, &mut blocks);
+        let total: u32 = blocks.chunks_exact(2).map(|b| b[1]).sum();
+        if total != 0 {
+            let mut encoder = device.create_command_encoder(&Default::default());
+            encoder.copy_buffer_to_buffer(
+                &positions_buf,
+                0,
+                &readback_positions,
+                0,
+                total as u64 * 4,
+            );
+            queue.submit([encoder.finish()]);
+            read(&readback_positions, total as usize * 4, &mut positions);
+        }
+        for block in blocks.chunks_exact(2) {
+            let (base, count) = (block[0] as usize, block[1] as usize);
+            out.high_starts.push(out.lows.len());
+            out.lows
+                .extend(positions[base..base + count].iter().map(|&p| p as u16));
+        }
+        timings.download += start.elapsed();
+    }
+    *LAST_TIMINGS.lock().unwrap() = timings;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        if !can_run() {
+            return;
+        }
+        let long = "ab\n\n0123456789012345678901234567890123456\n".repeat(50_000);
+        for input in ["", "\n", "a\nb", &long] {
+            let (mut expected, mut out) = (LineIndex::default(), LineIndex::default());
+            crate::compressed::iter(input, &mut expected);
+            build(input, &mut out);
+            assert!(expected == out, "input len {}", input.len());
+        }
+    }
+}
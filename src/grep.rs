@@ -0,0 +1,135 @@
+//! Plugging the kernels into the ripgrep ecosystem (`--features grep`).
+//!
+//! `grep-searcher` finds line boundaries itself: on a slice, it runs the matcher over the whole
+//! buffer and then looks for the newlines around each match. [`search_slice`] is the same search
+//! with the kernels as the line-boundary engine instead: every line comes from
+//! [`slice::for_each_line`] and is handed to the matcher on its own. It takes the same
+//! `Matcher` and the same closure sink (`sinks::UTF8`) as `Searcher::search_slice`, so a tool can
+//! switch engines without touching either. The two approaches favor different inputs: one
+//! matcher call per line is cheap when lines are short, while grep's single pass wins when
+//! matches are rare.
+//!
+//! `grep-searcher`'s `Sink` trait itself can't be driven from outside the crate (its
+//! `SinkMatch` can't be constructed), hence the closure sink.
+
+use crate::slice;
+use grep_matcher::{Match, Matcher, NoCaptures, NoError};
+use grep_searcher::sinks::UTF8;
+use std::io;
+
+/// Calls `sink` with the line number (starting at 1) and contents of every line of `haystack`
+/// that `matcher` matches, until it returns `Ok(false)` or an error. Like with `grep-searcher`,
+/// lines are passed with their terminator.
+pub fn search_slice<M, F>(matcher: &M, haystack: &str, mut sink: UTF8<F>) -> io::Result<()>
+where
+    M: Matcher,
+    F: FnMut(u64, &str) -> Result<bool, io::Error>,
+{
+    let mut line_number = 0;
+    // `for_each_line` can't stop early, so once this is set the rest of the lines are skipped
+    let mut result = Ok(true);
+    slice::for_each_line(haystack, |line| {
+        line_number += 1;
+        if !matches!(result, Ok(true)) {
+            return;
+        }
+        result = match matcher.is_match(line.as_bytes()) {
+            Ok(false) => Ok(true),
+            Ok(true) => {
+                // extend the line over its newline, if it has one
+                let start = line.as_ptr() as usize - haystack.as_ptr() as usize;
+                let end = (start + line.len() + 1).min(haystack.len());
+                (sink.0)(line_number, &haystack[start..end])
+            }
+            Err(err) => Err(io::Error::other(err.to_string())),
+        };
+    });
+    result.map(|_| ())
+}
+
+/// A plain substring matcher, so both engines pay the same for matching in the bench
+pub struct Literal(pub &'static str);
+
+impl Matcher for Literal {
+    type Captures = NoCaptures;
+    type Error = NoError;
+
+    fn find_at(&self, haystack: &[u8], at: usize) -> Result<Option<Match>, NoError> {
+        let needle = self.0.as_bytes();
+        let Some(&first) = needle.first() else {
+            return Ok(Some(Match::new(at, at)));
+        };
+        let mut pos = at;
+        while let Some(i) = crate::roofline::memchr(first, &haystack[pos..]) {
+            let start = pos + i;
+            if haystack[start..].starts_with(needle) {
+                return Ok(Some(Match::new(start, start + needle.len())));
+            }
+            pos = start + 1;
+        }
+        Ok(None)
+    }
+
+    fn new_captures(&self) -> Result<NoCaptures, NoError> {
+        Ok(NoCaptures::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grep_searcher::Searcher;
+
+    /// (line number, line) for every match
+    fn with_both(matcher: &Literal, haystack: &str) -> [Vec<(u64, String)>; 2] {
+        let mut expected = Vec::new();
+        Searcher::new()
+            .search_slice(
+                matcher,
+                haystack.as_bytes(),
+                UTF8(|n, line| {
+                    expected.push((n, line.to_owned()));
+                    Ok(true)
+                }),
+            )
+            .unwrap();
+        let mut found = Vec::new();
+        search_slice(
+            matcher,
+            haystack,
+            UTF8(|n, line| {
+                found.push((n, line.to_owned()));
+                Ok(true)
+            }),
+        )
+        .unwrap();
+        [expected, found]
+    }
+
+    #[test]
+    fn test_search_slice() {
+        let long = "abc\n\nxabcx\nab\nc\n".repeat(100) + "abc";
+        for haystack in ["", "abc", "abc\n", "\n\nabc\nab", "ab\nc", &long] {
+            for needle in ["abc", "b", "zzz", "a"] {
+                let [expected, found] = with_both(&Literal(needle), haystack);
+                assert_eq!(found, expected, "needle `{needle}` in `{haystack}`");
+            }
+        }
+        assert_eq!(with_both(&Literal("abc"), &long)[1].len(), 201);
+    }
+
+    #[test]
+    fn test_search_slice_stops() {
+        let mut seen = 0;
+        search_slice(
+            &Literal("a"),
+            "a\na\na\n",
+            UTF8(|_, _| {
+                seen += 1;
+                Ok(seen < 2)
+            }),
+        )
+        .unwrap();
+        assert_eq!(seen, 2);
+    }
+}
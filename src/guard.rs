@@ -0,0 +1,132 @@
+//! Inputs that end right before an unmapped page (`--guard-pages`, and the kernel tests). A
+//! kernel that loads a full vector past `input.len()` usually gets away with it, since the bytes
+//! after a `Vec`'s contents are mapped, and only fails when the input happens to end at the end
+//! of a page. Here the input always does, so the over-read faults on its first run. Reads before
+//! the start of the input aren't caught.
+
+use std::io;
+
+/// A read-only copy of a `str` whose last byte is the last byte of a mapped page, followed by a
+/// `PROT_NONE` one
+pub struct GuardedInput {
+    map: *mut u8,
+    // only read by `munmap`
+    #[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+    map_len: usize,
+    start: usize,
+    len: usize,
+}
+
+impl GuardedInput {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn new(input: &str) -> io::Result<Self> {
+        const PROT_NONE: i32 = 0;
+        const PROT_READ: i32 = 1;
+        const PROT_WRITE: i32 = 2;
+        const MAP_PRIVATE: i32 = 2;
+        #[cfg(target_os = "linux")]
+        const MAP_ANONYMOUS: i32 = 0x20;
+        #[cfg(target_os = "macos")]
+        const MAP_ANONYMOUS: i32 = 0x1000;
+        extern "C" {
+            fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, off: i64)
+                -> *mut u8;
+            fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+            fn getpagesize() -> i32;
+        }
+        let os_err = |call: &str| {
+            let err = io::Error::last_os_error();
+            io::Error::new(err.kind(), format!("{call}: {err}"))
+        };
+
+        let page_size = unsafe { getpagesize() } as usize;
+        let data_len = input.len().next_multiple_of(page_size);
+        let map_len = data_len + page_size;
+        let map = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if map as isize == -1 {
+            return Err(os_err("mmap"));
+        }
+        // owns the mapping from here on, so an error below unmaps it
+        let guarded = GuardedInput {
+            map,
+            map_len,
+            start: data_len - input.len(),
+            len: input.len(),
+        };
+        unsafe {
+            std::ptr::copy_nonoverlapping(input.as_ptr(), map.add(guarded.start), input.len());
+            if mprotect(map.add(data_len), page_size, PROT_NONE) != 0 {
+                return Err(os_err("mprotect(PROT_NONE)"));
+            }
+            // a kernel writing into its input would be a bug too
+            if data_len > 0 && mprotect(map, data_len, PROT_READ) != 0 {
+                return Err(os_err("mprotect(PROT_READ)"));
+            }
+        }
+        Ok(guarded)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn new(_: &str) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "guard pages are only implemented for Linux and macOS",
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe {
+            let bytes = std::slice::from_raw_parts(self.map.add(self.start), self.len);
+            std::str::from_utf8_unchecked(bytes)
+        }
+    }
+}
+
+impl Drop for GuardedInput {
+    fn drop(&mut self) {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        {
+            extern "C" {
+                fn munmap(addr: *mut u8, len: usize) -> i32;
+            }
+            unsafe { munmap(self.map, self.map_len) };
+        }
+    }
+}
+
+/// Runs `f` on a guarded copy of `input`, or on `input` itself where guard pages aren't
+/// implemented
+pub fn with_guard_page<R>(input: &str, f: impl FnOnce(&str) -> R) -> R {
+    match GuardedInput::new(input) {
+        Ok(guarded) => f(guarded.as_str()),
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => f(input),
+        Err(err) => panic!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guarded_input() {
+        for len in [0, 1, 4095, 4096, 4097, 100_000] {
+            let input = "ab\n".repeat(len).chars().take(len).collect::<String>();
+            with_guard_page(&input, |guarded| {
+                assert_eq!(guarded, input);
+                // ends where a page does
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                assert_eq!((guarded.as_ptr() as usize + len) % 4096, 0);
+            });
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! `--history FILE`: every bench run appends its results to a JSON Lines file, one flat object
+//! per measurement, so runs can be compared over time (see the `history` subcommand). JSON Lines
+//! rather than a database keeps the dependencies at zero, and a file that's appended to is easy
+//! to keep in CI artifacts or grep through. Only the flat objects written here are read back.
+
+use crate::cli;
+use std::io::{BufWriter, Write};
+
+/// What a run was measured under, repeated in each of its records
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    /// Unix time of the start of the run, in seconds. Also identifies the run.
+    pub time: u64,
+    pub cpu: String,
+    pub seed: u64,
+    pub lines: usize,
+    pub deterministic: bool,
+    /// See `pretouch`
+    pub pretouch: String,
+    /// See `alloc`
+    pub allocator: String,
+}
+
+impl Run {
+    pub fn new(seed: u64, lines: usize, deterministic: bool, pretouch: &str) -> Self {
+        Run {
+            time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            cpu: cpu_model(),
+            seed,
+            lines,
+            deterministic,
+            pretouch: pretouch.to_owned(),
+            allocator: crate::alloc::NAME.to_owned(),
+        }
+    }
+}
+
+/// One cell of one of the bench tables
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub run: Run,
+    pub table: String,
+    pub algo: String,
+    pub stage: String,
+    /// MB/s, even in `--deterministic` runs
+    pub thrpt: f64,
+}
+
+pub fn append(path: &str, records: &[Record]) -> cli::Result {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("{path}: {err}"))?;
+    let mut out = BufWriter::new(file);
+    let mut line = String::new();
+    for record in records {
+        line.clear();
+        write_record(record, &mut line);
+        out.write_all(line.as_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+pub fn load(path: &str) -> cli::Result<Vec<Record>> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            parse_record(line)
+                .ok_or_else(|| format!("{path}:{}: not a history record", i + 1).into())
+        })
+        .collect()
+}
+
+fn write_record(record: &Record, out: &mut String) {
+    let Record {
+        run,
+        table,
+        algo,
+        stage,
+        thrpt,
+    } = record;
+    out.push_str(&format!("{{\"time\":{},\"cpu\":", run.time));
+    write_str(&run.cpu, out);
+    out.push_str(&format!(
+        ",\"seed\":{},\"lines\":{},\"deterministic\":{},\"pretouch\":",
+        run.seed, run.lines, run.deterministic
+    ));
+    write_str(&run.pretouch, out);
+    out.push_str(",\"allocator\":");
+    write_str(&run.allocator, out);
+    out.push_str(",\"table\":");
+    write_str(table, out);
+    out.push_str(",\"algo\":");
+    write_str(algo, out);
+    out.push_str(",\"stage\":");
+    write_str(stage, out);
+    out.push_str(&format!(",\"thrpt\":{thrpt}}}\n"));
+}
+
+fn write_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn parse_record(line: &str) -> Option<Record> {
+    let fields = parse_object(line)?;
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    Some(Record {
+        run: Run {
+            time: field("time")?.parse().ok()?,
+            cpu: field("cpu")?.to_owned(),
+            seed: field("seed")?.parse().ok()?,
+            lines: field("lines")?.parse().ok()?,
+            deterministic: field("deterministic")?.parse().ok()?,
+            // older records were all written with the default
+            pretouch: field("pretouch").unwrap_or("write").to_owned(),
+            allocator: field("allocator").unwrap_or("system").to_owned(),
+        },
+        table: field("table")?.to_owned(),
+        algo: field("algo")?.to_owned(),
+        stage: field("stage")?.to_owned(),
+        thrpt: field("thrpt")?.parse().ok()?,
+    })
+}
+
+/// A flat object of strings, numbers and bools. Strings come back unescaped, and the other
+/// values as their source text.
+fn parse_object(line: &str) -> Option<Vec<(String, String)>> {
+    let mut rest = line.trim().strip_prefix('{')?.trim_start();
+    let mut fields = Vec::new();
+    if let Some(after) = rest.strip_prefix('}') {
+        return after.trim().is_empty().then_some(fields);
+    }
+    loop {
+        let (key, after) = parse_str(rest)?;
+        rest = after.trim_start().strip_prefix(':')?.trim_start();
+        let value = if rest.starts_with('"') {
+            let (value, after) = parse_str(rest)?;
+            rest = after;
+            value
+        } else {
+            let end = rest.find([',', '}']).unwrap_or(rest.len());
+            let (value, after) = rest.split_at(end);
+            rest = after;
+            value.trim_end().to_owned()
+        };
+        fields.push((key, value));
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else {
+            let after = rest.strip_prefix('}')?;
+            return after.trim().is_empty().then_some(fields);
+        }
+    }
+}
+
+/// Parses the string literal at the start of `input`, returning it and what follows it
+fn parse_str(input: &str) -> Option<(String, &str)> {
+    let mut chars = input.strip_prefix('"')?.char_indices();
+    let mut out = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &input[1 + i + 1..])),
+            '\\' => out.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                }
+                c => c,
+            }),
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+/// So records from different machines can be told apart
+fn cpu_model() -> String {
+    std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|info| {
+            info.lines()
+                .find_map(|line| line.strip_prefix("model name")?.split_once(':'))
+                .map(|(_, model)| model.trim().to_owned())
+        })
+        .or_else(cpuid_brand)
+        .unwrap_or_else(|| std::env::consts::ARCH.to_owned())
+}
+
+/// The brand string `/proc/cpuinfo` shows, straight from the CPU, for systems without it
+#[cfg(target_arch = "x86_64")]
+fn cpuid_brand() -> Option<String> {
+    use std::arch::x86_64::__cpuid;
+    if __cpuid(0x8000_0000).eax < 0x8000_0004 {
+        return None;
+    }
+    let mut brand = Vec::with_capacity(48);
+    for leaf in 0x8000_0002..=0x8000_0004 {
+        let regs = __cpuid(leaf);
+        for reg in [regs.eax, regs.ebx, regs.ecx, regs.edx] {
+            brand.extend_from_slice(&reg.to_le_bytes());
+        }
+    }
+    let brand = String::from_utf8_lossy(&brand);
+    let brand = brand.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!brand.is_empty()).then(|| brand.to_owned())
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cpuid_brand() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_cpuid_brand() {
+        let from_proc = std::fs::read_to_string("/proc/cpuinfo").unwrap();
+        if let Some(brand) = cpuid_brand() {
+            assert!(from_proc.contains(&brand), "{brand}");
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let record = Record {
+            run: Run {
+                time: 1_700_000_000,
+                cpu: "Some \"CPU\" \\ @ 3.0GHz\u{1}".to_owned(),
+                seed: u64::MAX,
+                lines: 10_000_000,
+                deterministic: true,
+                pretouch: "madvise".to_owned(),
+                allocator: "mimalloc".to_owned(),
+            },
+            table: "compressed".to_owned(),
+            algo: "avx2 intrlv".to_owned(),
+            stage: "0-40".to_owned(),
+            thrpt: 12345.678,
+        };
+        let mut line = String::new();
+        write_record(&record, &mut line);
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(parse_record(&line), Some(record));
+        assert_eq!(parse_record("{}"), None);
+        assert_eq!(parse_record(&line[..line.len() - 3]), None);
+    }
+
+    #[test]
+    fn test_parse_object() {
+        assert_eq!(
+            parse_object(r#" { "a" : 1 , "b":"x\"y", "c": true } "#),
+            Some(vec![
+                ("a".to_owned(), "1".to_owned()),
+                ("b".to_owned(), "x\"y".to_owned()),
+                ("c".to_owned(), "true".to_owned()),
+            ])
+        );
+        assert_eq!(parse_object("{}"), Some(vec![]));
+        assert_eq!(parse_object("{\"a\":1} x"), None);
+        assert_eq!(parse_object("[1]"), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Splitting for JSON Lines input. Alongside each line, records whether it could be a JSON
+//! object/array (non-empty and starting with `{` or `[`), so junk lines can be dropped without
+//! a second pass over the input.
+//!
+//! The check is a single byte load at each line start. That byte was just scanned, so it's
+//! almost always in L1.
+
+use alloc::vec::Vec;
+
+pub fn looks_like_json(line: &str) -> bool {
+    matches!(line.as_bytes().first(), Some(b'{' | b'['))
+}
+
+/// Reference implementation: split, then check each line
+pub fn two_pass<'input>(input: &'input str, lines: &mut Vec<&'input str>, flags: &mut Vec<bool>) {
+    crate::slice::split(input, lines);
+    flags.extend(lines.iter().map(|line| looks_like_json(line)));
+}
+
+/// `line_start` must be in bounds. Empty lines start at their own newline, so they're
+/// never flagged.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn flag_at(input: &str, line_start: usize) -> bool {
+    matches!(input.as_bytes().get_unchecked(line_start), b'{' | b'[')
+}
+
+#[cfg(target_arch = "x86_64")]
+fn tail<'input>(
+    mut line_start: usize,
+    chunk_size: usize,
+    input: &'input str,
+    lines: &mut Vec<&'input str>,
+    flags: &mut Vec<bool>,
+) {
+    for i in (input.len() & !(chunk_size - 1))..input.len() {
+        if input.as_bytes()[i] != b'\n' {
+            continue;
+        }
+        lines.push(&input[line_start..i]);
+        flags.push(unsafe { flag_at(input, line_start) });
+        line_start = i + 1;
+    }
+    if line_start != input.len() {
+        lines.push(&input[line_start..]);
+        flags.push(unsafe { flag_at(input, line_start) });
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::{flag_at, tail};
+    use alloc::vec::Vec;
+    use core::arch::x86_64::*;
+
+    pub fn sse2_unrollx4<'input>(
+        input: &'input str,
+        lines: &mut Vec<&'input str>,
+        flags: &mut Vec<bool>,
+    ) {
+        use core::arch::x86_64::{
+            _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        unsafe {
+            let nl_v = load([b'\n'; 16].as_ptr().cast());
+            let mut chunk_i = 0;
+            let stop_chunk_i = input.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                lines.reserve(256);
+                flags.reserve(256);
+                let lines_arr = lines.spare_capacity_mut().get_unchecked_mut(..256);
+                let flags_arr = flags.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let in_ptr = input.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
+                    let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
+                    let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
+                    let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
+                    let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
+                    let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
+                    while mask != 0 {
+                        let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                        lines_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        flags_arr
+                            .get_unchecked_mut(write_i)
+                            .write(flag_at(input, line_start));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                lines.set_len(lines.len() + write_i);
+                flags.set_len(flags.len() + write_i);
+            }
+        }
+        tail(line_start, 64, input, lines, flags);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unrollx2<'input>(
+        input: &'input str,
+        lines: &mut Vec<&'input str>,
+        flags: &mut Vec<bool>,
+    ) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            lines.reserve(256);
+            flags.reserve(256);
+            let lines_arr = lines.spare_capacity_mut().get_unchecked_mut(..256);
+            let flags_arr = flags.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().byte_add(chunk_i * 64);
+                let v1 = load(ptr.cast());
+                let v2 = load(ptr.byte_add(32).cast());
+                let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                    | (movemask(eq(v1, nl_v)) as u32 as u64);
+                while mask != 0 {
+                    let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                    lines_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..line_end));
+                    flags_arr
+                        .get_unchecked_mut(write_i)
+                        .write(flag_at(input, line_start));
+                    write_i += 1;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            lines.set_len(lines.len() + write_i);
+            flags.set_len(flags.len() + write_i);
+        }
+        tail(line_start, 64, input, lines, flags);
+    }
+}
+
+/// Splits and flags with the fastest kernel the current CPU supports
+pub fn split<'input>(input: &'input str, lines: &mut Vec<&'input str>, flags: &mut Vec<bool>) {
+    #[cfg(target_arch = "x86_64")]
+    if let Some(avx2) = crate::splitters::Avx2Splitter::new() {
+        avx2.split_jsonl(input, lines, flags)
+    } else {
+        x86_64::sse2_unrollx4(input, lines, flags)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    two_pass(input, lines, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TEST_CASES: &[&str] = &[
+        "",
+        "{",
+        "\n",
+        "[]\nxyz\n\n{}",
+        "{\"a\": 1}\n  {\"indented\": true}\nnull\n[1, 2]\n",
+    ];
+
+    fn check(split_fn: impl for<'a> Fn(&'a str, &mut Vec<&'a str>, &mut Vec<bool>)) {
+        for input in TEST_CASES {
+            // repeat to get past the tail handling
+            crate::guard::with_guard_page(&input.repeat(40), |input| {
+                let (mut lines, mut flags) = (Vec::new(), Vec::new());
+                let (mut expected_lines, mut expected_flags) = (Vec::new(), Vec::new());
+                split_fn(input, &mut lines, &mut flags);
+                two_pass(input, &mut expected_lines, &mut expected_flags);
+                assert_eq!(lines, expected_lines, "input: `{input}`");
+                assert_eq!(flags, expected_flags, "input: `{input}`");
+            });
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        check(split);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_sse2_unrollx4() {
+        check(x86_64::sse2_unrollx4);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_unrollx2() {
+        if let Some(avx2) = crate::splitters::Avx2Splitter::new() {
+            check(|a, b, c| avx2.split_jsonl(a, b, c));
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Inputs in large pages (`--large-pages`), so the timed runs take fewer TLB misses on their way
+//! through a stage's input. On Windows they're `VirtualAlloc(MEM_LARGE_PAGES)` pages, which need
+//! the `SeLockMemoryPrivilege` ("Lock pages in memory") granted to the user; on Linux the mapping
+//! is only advised to use transparent huge pages (`MADV_HUGEPAGE`), which the kernel may not do.
+//! Elsewhere, or without the privilege, [`LargePageInput::new`] fails and the bench keeps the
+//! inputs where they are.
+
+use std::io;
+
+/// A read-only copy of a `str`, in large pages
+pub struct LargePageInput {
+    map: *mut u8,
+    // only read by `munmap`
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    map_len: usize,
+    start: usize,
+    len: usize,
+}
+
+impl LargePageInput {
+    #[cfg(target_os = "linux")]
+    pub fn new(input: &str) -> io::Result<Self> {
+        const PROT_READ: i32 = 1;
+        const PROT_WRITE: i32 = 2;
+        const MAP_PRIVATE: i32 = 2;
+        const MAP_ANONYMOUS: i32 = 0x20;
+        const MADV_HUGEPAGE: i32 = 14;
+        /// x86_64's and aarch64's (with 4 KiB pages) transparent huge pages
+        const HUGE_PAGE: usize = 2 << 20;
+        extern "C" {
+            fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, off: i64)
+                -> *mut u8;
+            fn madvise(addr: *mut u8, len: usize, advice: i32) -> i32;
+            fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+        }
+        let os_err = |call: &str| {
+            let err = io::Error::last_os_error();
+            io::Error::new(err.kind(), format!("{call}: {err}"))
+        };
+
+        // a huge page has to be aligned to its size, and mmap only aligns to a small one
+        let data_len = input.len().max(1).next_multiple_of(HUGE_PAGE);
+        let map_len = data_len + HUGE_PAGE;
+        let map = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if map as isize == -1 {
+            return Err(os_err("mmap"));
+        }
+        // owns the mapping from here on, so an error below unmaps it
+        let large = LargePageInput {
+            map,
+            map_len,
+            start: (map as usize).next_multiple_of(HUGE_PAGE) - map as usize,
+            len: input.len(),
+        };
+        unsafe {
+            let data = map.add(large.start);
+            // before the first write, so the faults map huge pages
+            if madvise(data, data_len, MADV_HUGEPAGE) != 0 {
+                return Err(os_err("madvise(MADV_HUGEPAGE)"));
+            }
+            std::ptr::copy_nonoverlapping(input.as_ptr(), data, input.len());
+            if mprotect(data, data_len, PROT_READ) != 0 {
+                return Err(os_err("mprotect(PROT_READ)"));
+            }
+        }
+        Ok(large)
+    }
+
+    #[cfg(windows)]
+    pub fn new(input: &str) -> io::Result<Self> {
+        use std::ffi::c_void;
+        const MEM_COMMIT: u32 = 0x1000;
+        const MEM_RESERVE: u32 = 0x2000;
+        const MEM_LARGE_PAGES: u32 = 0x2000_0000;
+        const PAGE_READWRITE: u32 = 0x04;
+        const PAGE_READONLY: u32 = 0x02;
+        extern "system" {
+            fn GetLargePageMinimum() -> usize;
+            fn VirtualAlloc(addr: *mut c_void, size: usize, kind: u32, protect: u32)
+                -> *mut c_void;
+            fn VirtualProtect(addr: *mut c_void, size: usize, protect: u32, old: *mut u32) -> i32;
+        }
+        let os_err = |call: &str| {
+            let err = io::Error::last_os_error();
+            io::Error::new(err.kind(), format!("{call}: {err}"))
+        };
+
+        let page_size = unsafe { GetLargePageMinimum() };
+        if page_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "this CPU has no large pages",
+            ));
+        }
+        enable_lock_memory_privilege()?;
+        // large pages are committed up front, so the size has to be whole pages
+        let map_len = input.len().max(1).next_multiple_of(page_size);
+        let map = unsafe {
+            VirtualAlloc(
+                std::ptr::null_mut(),
+                map_len,
+                MEM_RESERVE | MEM_COMMIT | MEM_LARGE_PAGES,
+                PAGE_READWRITE,
+            )
+        };
+        if map.is_null() {
+            // usually too little contiguous physical memory left
+            return Err(os_err("VirtualAlloc(MEM_LARGE_PAGES)"));
+        }
+        // owns the allocation from here on, so an error below frees it
+        let large = LargePageInput {
+            map: map.cast(),
+            map_len,
+            start: 0,
+            len: input.len(),
+        };
+        unsafe {
+            std::ptr::copy_nonoverlapping(input.as_ptr(), large.map, input.len());
+            let mut old = 0;
+            if VirtualProtect(map, map_len, PAGE_READONLY, &mut old) == 0 {
+                return Err(os_err("VirtualProtect(PAGE_READONLY)"));
+            }
+        }
+        Ok(large)
+    }
+
+    #[cfg(not(any(target_os = "linux", windows)))]
+    pub fn new(_: &str) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "large pages are only implemented for Linux and Windows",
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe {
+            let bytes = std::slice::from_raw_parts(self.map.add(self.start), self.len);
+            std::str::from_utf8_unchecked(bytes)
+        }
+    }
+}
+
+impl Drop for LargePageInput {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        {
+            extern "C" {
+                fn munmap(addr: *mut u8, len: usize) -> i32;
+            }
+            unsafe { munmap(self.map, self.map_len) };
+        }
+        #[cfg(windows)]
+        {
+            const MEM_RELEASE: u32 = 0x8000;
+            extern "system" {
+                fn VirtualFree(addr: *mut std::ffi::c_void, size: usize, kind: u32) -> i32;
+            }
+            // the size has to be 0 when releasing
+            unsafe { VirtualFree(self.map.cast(), 0, MEM_RELEASE) };
+        }
+    }
+}
+
+/// Enables `SeLockMemoryPrivilege` in the process token, which `MEM_LARGE_PAGES` needs. Holding
+/// the privilege isn't enough, it has to be enabled, and only an account it was granted to (in
+/// the local security policy, then logging in again) can enable it.
+#[cfg(windows)]
+fn enable_lock_memory_privilege() -> io::Result<()> {
+    use std::ffi::c_void;
+    const TOKEN_ADJUST_PRIVILEGES: u32 = 0x20;
+    const TOKEN_QUERY: u32 = 0x08;
+    const SE_PRIVILEGE_ENABLED: u32 = 0x02;
+    const ERROR_NOT_ALL_ASSIGNED: i32 = 1300;
+    #[repr(C)]
+    struct Luid {
+        low: u32,
+        high: i32,
+    }
+    /// `TOKEN_PRIVILEGES` with room for the one privilege
+    #[repr(C)]
+    struct TokenPrivileges {
+        count: u32,
+        luid: Luid,
+        attributes: u32,
+    }
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: *mut c_void, access: u32, token: *mut *mut c_void) -> i32;
+        fn LookupPrivilegeValueW(system: *const u16, name: *const u16, luid: *mut Luid) -> i32;
+        fn AdjustTokenPrivileges(
+            token: *mut c_void,
+            disable_all: i32,
+            new: *const TokenPrivileges,
+            prev_len: u32,
+            prev: *mut TokenPrivileges,
+            ret_len: *mut u32,
+        ) -> i32;
+    }
+    let os_err = |call: &str| {
+        let err = io::Error::last_os_error();
+        io::Error::new(err.kind(), format!("{call}: {err}"))
+    };
+
+    let name: Vec<u16> = "SeLockMemoryPrivilege\0".encode_utf16().collect();
+    let mut privileges = TokenPrivileges {
+        count: 1,
+        luid: Luid { low: 0, high: 0 },
+        attributes: SE_PRIVILEGE_ENABLED,
+    };
+    if unsafe { LookupPrivilegeValueW(std::ptr::null(), name.as_ptr(), &mut privileges.luid) } == 0
+    {
+        return Err(os_err("LookupPrivilegeValue(SeLockMemoryPrivilege)"));
+    }
+    let mut token = std::ptr::null_mut();
+    let access = TOKEN_ADJUST_PRIVILEGES | TOKEN_QUERY;
+    if unsafe { OpenProcessToken(GetCurrentProcess(), access, &mut token) } == 0 {
+        return Err(os_err("OpenProcessToken"));
+    }
+    let adjusted = unsafe {
+        AdjustTokenPrivileges(
+            token,
+            0,
+            &privileges,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    // succeeds without enabling a privilege the token doesn't have, which only the error says
+    let err = io::Error::last_os_error();
+    unsafe { CloseHandle(token) };
+    if adjusted == 0 {
+        return Err(io::Error::new(
+            err.kind(),
+            format!("AdjustTokenPrivileges: {err}"),
+        ));
+    }
+    if err.raw_os_error() == Some(ERROR_NOT_ALL_ASSIGNED) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the user doesn't hold SeLockMemoryPrivilege (\"Lock pages in memory\")",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_page_input() {
+        for input in ["", "a\nb\n", &"line\n".repeat(1 << 20)] {
+            match LargePageInput::new(input) {
+                Ok(large) => assert_eq!(large.as_str(), input),
+                // no large pages here, or not allowed to use them
+                Err(err) => eprintln!("skipped: {err}"),
+            }
+        }
+    }
+}
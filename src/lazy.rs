@@ -0,0 +1,108 @@
+//! Lines that are only materialized where they're used. The `LineIndex` is built up front with
+//! the fastest kernel (2 bytes per line), and a 64 KiB block's `&str`s (16 bytes per line) are
+//! made the first time one of its lines is asked for. A consumer that stops after the first
+//! screenful, or samples a few lines, never pays for the slices it doesn't look at, while one that
+//! reads everything pays about what `slice::split` would, plus the index.
+
+use crate::compressed::{self, LineIndex, NewlineIndex};
+use std::cell::OnceCell;
+
+pub struct LazyLines<'input> {
+    input: &'input str,
+    index: LineIndex,
+    /// Lines ending in each block (so the last line of the input, if it has no newline, is in
+    /// none of them)
+    blocks: Vec<OnceCell<Vec<&'input str>>>,
+    line_count: usize,
+}
+
+impl<'input> LazyLines<'input> {
+    pub fn new(input: &'input str) -> Self {
+        let mut index = LineIndex::default();
+        compressed::build(input, &mut index);
+        let blocks = (0..index.high_starts.len())
+            .map(|_| OnceCell::new())
+            .collect();
+        let line_count = index.line_count(input.len());
+        LazyLines {
+            input,
+            index,
+            blocks,
+            line_count,
+        }
+    }
+
+    /// Same as the number of lines `slice::split` finds
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    pub fn get(&self, i: usize) -> Option<&'input str> {
+        if i >= self.index.len() {
+            // the unterminated last line, if there is one
+            return (i < self.line_count).then(|| self.index.line(self.input, i));
+        }
+        // blocks without newlines share a start with the next block, so take the last one
+        let block = self.index.high_starts.partition_point(|&start| start <= i) - 1;
+        let lines = self.blocks[block].get_or_init(|| self.materialize(block));
+        Some(lines[i - self.index.high_starts[block]])
+    }
+
+    /// Number of blocks whose lines were made so far
+    pub fn materialized_blocks(&self) -> usize {
+        self.blocks
+            .iter()
+            .filter(|block| block.get().is_some())
+            .count()
+    }
+
+    fn materialize(&self, block: usize) -> Vec<&'input str> {
+        let starts = &self.index.high_starts;
+        let first = starts[block];
+        let end = starts
+            .get(block + 1)
+            .copied()
+            .unwrap_or(self.index.lows.len());
+        let mut line_start = match first {
+            0 => 0,
+            _ => self.index.newline(first - 1) + 1,
+        };
+        let high = block << 16;
+        self.index.lows[first..end]
+            .iter()
+            .map(|&low| {
+                let newline = high | low as usize;
+                let line = &self.input[line_start..newline];
+                line_start = newline + 1;
+                line
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_lines() {
+        // empty blocks in between, and a last line without a newline
+        let long = "ab\n\n0123456789\n".repeat(10_000) + &"x".repeat(200_000) + "\nyz\n\nlast";
+        for input in ["", "\n", "a", "a\n\nb\n", &long] {
+            let expected: Vec<&str> = input.split_terminator('\n').collect();
+            let lines = LazyLines::new(input);
+            assert_eq!(lines.line_count(), expected.len());
+            assert_eq!(lines.materialized_blocks(), 0);
+            for (i, line) in expected.iter().enumerate() {
+                assert_eq!(lines.get(i), Some(*line), "line {i}");
+            }
+            assert_eq!(lines.get(expected.len()), None);
+        }
+
+        let lines = LazyLines::new(&long);
+        assert_eq!(lines.get(30_001), Some("yz"));
+        assert_eq!(lines.materialized_blocks(), 1);
+        assert_eq!(lines.get(30_003), Some("last"));
+        assert_eq!(lines.materialized_blocks(), 1);
+    }
+}
@@ -0,0 +1,62 @@
+//! The line-splitting kernels, for use outside the bench: [`slice::split`] for a `Vec<&str>` of
+//! lines (or [`simd_lines::SimdLines`] to iterate over them), [`compressed::build`] for a
+//! [`compressed::LineIndex`], each running the fastest kernel
+//! the CPU has (see [`dispatch`]). The kernels themselves are public too, in each module's
+//! `x86_64` and `aarch64` submodules, with the safe ones callable on any CPU that has SSE2 and the
+//! `unsafe` ones only after checking for their features. The `split-bench` binary benchmarks
+//! them through this crate.
+//!
+//! Without the default `std` feature the crate is `#![no_std]` and only needs `alloc`. There's no
+//! runtime feature detection then, so a kernel counts as supported if its features are enabled at
+//! compile time (`-C target-feature`), and [`guard`] isn't built.
+
+// every `unsafe` kernel has the same contract, the one above: the CPU has its target features
+#![allow(clippy::missing_safety_doc)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(portable_simd))]
+
+extern crate alloc;
+
+/// `is_x86_feature_detected!` for each feature, or without std, whether they're all enabled at
+/// compile time
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+macro_rules! has_x86_features {
+    ($($feature:tt),+) => {
+        $(std::is_x86_feature_detected!($feature))&&+
+    };
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+macro_rules! has_x86_features {
+    ($($feature:tt),+) => {
+        cfg!(all($(target_feature = $feature),+))
+    };
+}
+
+pub mod arena;
+#[cfg(feature = "std")]
+pub mod blocks;
+pub mod chunks;
+pub mod compressed;
+pub mod density;
+pub mod dispatch;
+pub mod flat;
+#[cfg(any(feature = "std", test))]
+pub mod guard;
+pub mod jsonl;
+pub mod pool;
+#[cfg(feature = "python")]
+mod python;
+pub mod ranges;
+#[cfg(feature = "std")]
+pub mod sharded;
+pub mod simd_lines;
+pub mod sink;
+pub mod slice;
+pub mod span;
+#[cfg(target_arch = "x86_64")]
+pub mod splitters;
+pub mod substring;
+pub mod uninit;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod wasm;
@@ -0,0 +1,214 @@
+//! `.lidx` files: a `LineIndex` saved to disk, so a big input only has to be scanned once. The
+//! layout is the in-memory one, little-endian:
+//!
+//! | field | size |
+//! | :-- | :-- |
+//! | magic `LIDX` | 4 |
+//! | version (1) | u32 |
+//! | input length | u64 |
+//! | number of lows | u64 |
+//! | number of high starts (one per 64 KiB block) | u64 |
+//! | lows | u16 each |
+//! | high starts | u64 each |
+//!
+//! There's also a summary of an index's line density, for sanity-checking those files.
+
+use crate::compressed::{LineIndex, NewlineIndex};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+pub const MAGIC: &[u8; 4] = b"LIDX";
+const VERSION: u32 = 1;
+const BLOCK_SIZE: usize = 1 << 16;
+
+pub fn write(index: &LineIndex, input_len: usize, out: &mut impl Write) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    for n in [input_len, index.lows.len(), index.high_starts.len()] {
+        out.write_all(&(n as u64).to_le_bytes())?;
+    }
+    let lows: Vec<u8> = index
+        .lows
+        .iter()
+        .flat_map(|low| low.to_le_bytes())
+        .collect();
+    out.write_all(&lows)?;
+    let high_starts: Vec<u8> = index
+        .high_starts
+        .iter()
+        .flat_map(|&start| (start as u64).to_le_bytes())
+        .collect();
+    out.write_all(&high_starts)
+}
+
+/// Reads an index and the length of its input, checking that the two are consistent
+pub fn read(input: &mut impl Read) -> io::Result<(LineIndex, usize)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let mut header = [0; 4 + 4 + 3 * 8];
+    input.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid("not a .lidx file"));
+    }
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported .lidx version {version}")));
+    }
+    let [input_len, lows_len, high_starts_len] =
+        [8, 16, 24].map(|at| u64::from_le_bytes(header[at..at + 8].try_into().unwrap()) as usize);
+    if high_starts_len != input_len.div_ceil(BLOCK_SIZE) || lows_len > input_len {
+        return Err(invalid("header doesn't match the input length"));
+    }
+
+    let mut bytes = vec![0; lows_len * 2];
+    input.read_exact(&mut bytes)?;
+    let lows = bytes
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    let mut bytes = vec![0; high_starts_len * 8];
+    input.read_exact(&mut bytes)?;
+    let high_starts: Vec<usize> = bytes
+        .chunks_exact(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+        .collect();
+    if high_starts.first().is_some_and(|&start| start != 0)
+        || high_starts.windows(2).any(|w| w[0] > w[1])
+        || high_starts.last().is_some_and(|&start| start > lows_len)
+    {
+        return Err(invalid("block starts are out of order"));
+    }
+    if input.read(&mut [0])? != 0 {
+        return Err(invalid("trailing data"));
+    }
+    let index = LineIndex { lows, high_starts };
+    if index.len() > 0 && index.newline(index.len() - 1) >= input_len {
+        return Err(invalid("newline past the end of the input"));
+    }
+    Ok((index, input_len))
+}
+
+/// How the newlines are spread over the 64 KiB blocks
+pub struct Summary {
+    pub input_len: usize,
+    pub newlines: usize,
+    pub lines: usize,
+    /// (block, newlines) of the block with the most newlines, the first one on ties
+    pub densest: Option<(usize, usize)>,
+    /// (block, newlines) of the block with the fewest newlines, the first one on ties
+    pub sparsest: Option<(usize, usize)>,
+    /// Bucket 0 counts blocks without newlines, bucket i blocks with `2^(i-1)..2^i`
+    pub blocks_per_bucket: [usize; 18],
+}
+
+impl Summary {
+    pub fn new(index: &LineIndex, input_len: usize) -> Self {
+        let blocks = index.high_starts.len();
+        let per_block = (0..blocks).map(|block| {
+            let end = index
+                .high_starts
+                .get(block + 1)
+                .copied()
+                .unwrap_or(index.lows.len());
+            end - index.high_starts[block]
+        });
+        let mut summary = Summary {
+            input_len,
+            newlines: index.len(),
+            lines: index.line_count(input_len),
+            densest: None,
+            sparsest: None,
+            blocks_per_bucket: [0; 18],
+        };
+        for (block, count) in per_block.enumerate() {
+            if summary.densest.is_none_or(|(_, max)| count > max) {
+                summary.densest = Some((block, count));
+            }
+            if summary.sparsest.is_none_or(|(_, min)| count < min) {
+                summary.sparsest = Some((block, count));
+            }
+            summary.blocks_per_bucket[(usize::BITS - count.leading_zeros()) as usize] += 1;
+        }
+        summary
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blocks = self.input_len.div_ceil(BLOCK_SIZE);
+        writeln!(
+            f,
+            "input     : {} bytes, {blocks} blocks of 64 KiB",
+            self.input_len
+        )?;
+        writeln!(f, "lines     : {} ({} newlines)", self.lines, self.newlines)?;
+        if let (Some(densest), Some(sparsest)) = (self.densest, self.sparsest) {
+            writeln!(f, "densest   : block {}, {} newlines", densest.0, densest.1)?;
+            writeln!(
+                f,
+                "sparsest  : block {}, {} newlines",
+                sparsest.0, sparsest.1
+            )?;
+        }
+        writeln!(f, "newlines per block:")?;
+        for (bucket, &count) in self.blocks_per_bucket.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let range = match bucket {
+                0 => "0".to_owned(),
+                1 => "1".to_owned(),
+                _ => format!("{}-{}", 1 << (bucket - 1), (1 << bucket) - 1),
+            };
+            writeln!(f, "{range:>12}: {count} blocks")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressed;
+
+    #[test]
+    fn test_roundtrip() {
+        let long = "ab\n".repeat(30_000) + &"c".repeat(70_000) + "\n\n";
+        for input in ["", "\n", "a", "a\nb\n", &long] {
+            let mut index = LineIndex::default();
+            compressed::build(input, &mut index);
+            let mut file = Vec::new();
+            write(&index, input.len(), &mut file).unwrap();
+            let (read_index, input_len) = read(&mut file.as_slice()).unwrap();
+            assert!(read_index == index);
+            assert_eq!(input_len, input.len());
+
+            // truncated, or with extra bytes
+            assert!(read(&mut &file[..file.len() - 1]).is_err());
+            file.push(0);
+            assert!(read(&mut file.as_slice()).is_err());
+        }
+        assert!(read(&mut &b"LIDY\x01\0\0\0"[..]).is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        // block 0: 30k newlines, block 1: 1k, block 2: none, block 3: 2
+        let input = "a\n".repeat(30_000)
+            + &"b".repeat(BLOCK_SIZE - 60_000)
+            + &"\nb".repeat(1_000)
+            + &"b".repeat(BLOCK_SIZE - 2_000)
+            + &"b".repeat(BLOCK_SIZE)
+            + "\n\n";
+        let mut index = LineIndex::default();
+        compressed::build(&input, &mut index);
+        let summary = Summary::new(&index, input.len());
+        assert_eq!(summary.newlines, 31_002);
+        assert_eq!(summary.lines, 31_002);
+        assert_eq!(summary.densest, Some((0, 30_000)));
+        assert_eq!(summary.sparsest, Some((2, 0)));
+        let text = summary.to_string();
+        for bucket in ["0", "2-3", "512-1023", "16384-32767"] {
+            assert!(text.contains(&format!("{bucket:>12}: 1 blocks")), "{text}");
+        }
+    }
+}
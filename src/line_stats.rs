@@ -0,0 +1,130 @@
+//! Line length statistics: the longest line and a histogram with power-of-two buckets, which is
+//! enough for approximate percentiles. Computed either in the same pass as the newline scan, or
+//! afterwards from the slices, which is what the bench compares.
+
+/// Lengths don't count the newline
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LineLengthStats {
+    pub lines: usize,
+    pub max: usize,
+    /// Bucket 0 counts empty lines, bucket i lengths in `2^(i-1)..2^i`
+    pub buckets: [usize; usize::BITS as usize + 1],
+}
+
+impl Default for LineLengthStats {
+    fn default() -> Self {
+        LineLengthStats {
+            lines: 0,
+            max: 0,
+            buckets: [0; usize::BITS as usize + 1],
+        }
+    }
+}
+
+impl LineLengthStats {
+    #[inline]
+    fn add(&mut self, len: usize) {
+        self.lines += 1;
+        self.max = self.max.max(len);
+        self.buckets[(usize::BITS - len.leading_zeros()) as usize] += 1;
+    }
+
+    /// An upper bound for the length at percentile `p` (in `0.0..=1.0`): the end of its bucket
+    pub fn percentile(&self, p: f64) -> usize {
+        let target = ((p * self.lines as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+        for (i, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return (1usize.checked_shl(i as u32).unwrap_or(0)).wrapping_sub(1);
+            }
+        }
+        0
+    }
+}
+
+/// Lines are the same as [`crate::slice::split`]'s
+pub fn line_length_stats(input: &str) -> LineLengthStats {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input) };
+    }
+    scalar(input)
+}
+
+pub fn scalar(input: &str) -> LineLengthStats {
+    let mut stats = LineLengthStats::default();
+    for line in input.split_terminator('\n') {
+        stats.add(line.len());
+    }
+    stats
+}
+
+/// The unfused way: a second pass over already split lines
+pub fn from_lines(lines: &[&str]) -> LineLengthStats {
+    let mut stats = LineLengthStats::default();
+    for line in lines {
+        stats.add(line.len());
+    }
+    stats
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use std::arch::x86_64::*;
+
+    /// The newline scan of `slice::x86_64::avx2_unrollx2`, adding each line length to the stats
+    /// instead of writing out a slice
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2(input: &str) -> LineLengthStats {
+        let mut stats = LineLengthStats::default();
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mut line_start = 0;
+        for chunk_i in 0..input.len() / 64 {
+            let ptr = input.as_ptr().add(chunk_i * 64);
+            let v1 = _mm256_loadu_si256(ptr.cast());
+            let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+            let mut mask = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64)
+                << 32)
+                | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                stats.add(line_end - line_start);
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        for line in input[line_start..].split_terminator('\n') {
+            stats.add(line.len());
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_length_stats() {
+        let long = "ab\n\n0123456789\nx\n".repeat(1000) + &"y".repeat(300);
+        for input in ["", "\n", "a", "a\n\nbcd", &long] {
+            let expected = from_lines(&input.split_terminator('\n').collect::<Vec<_>>());
+            assert_eq!(scalar(input), expected);
+            assert_eq!(line_length_stats(input), expected);
+        }
+        let stats = line_length_stats(&long);
+        assert_eq!(stats.lines, 4001);
+        assert_eq!(stats.max, 300);
+        assert_eq!(&stats.buckets[..5], &[1000, 1000, 1000, 0, 1000]);
+        assert_eq!(stats.buckets[9], 1);
+        assert_eq!(stats.percentile(0.0), 0);
+        assert_eq!(stats.percentile(0.2), 0);
+        assert_eq!(stats.percentile(0.5), 3);
+        assert_eq!(stats.percentile(0.75), 15);
+        assert_eq!(stats.percentile(0.99), 15);
+        assert_eq!(stats.percentile(1.0), 511);
+        assert_eq!(LineLengthStats::default().percentile(0.5), 0);
+    }
+}
@@ -1,1113 +1,985 @@
-mod slice {
-    pub fn std(input: &str) -> Vec<&str> {
-        input.lines().collect()
-    }
+mod affinity;
+mod alloc;
+#[cfg(all(feature = "asm", target_arch = "x86_64"))]
+mod asm;
+mod bench;
+mod classify;
+mod cli;
+mod cmd;
+mod disasm;
+mod encoding;
+mod fields;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "grep")]
+mod grep;
+mod history;
+mod large_pages;
+mod lazy;
+mod lidx;
+mod line_stats;
+#[cfg(feature = "roaring")]
+mod newline_set;
+mod paragraph;
+mod pipeline;
+mod prefixed;
+mod pretouch;
+mod regressions;
+mod rng;
+mod roofline;
+mod shared;
+mod shuffled;
+mod stride;
+mod trimmed;
+mod verify;
+mod watchdog;
 
-    pub fn std_reuse<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-        for line in input.lines() {
-            out.push(line);
-        }
-    }
+use rng::Rng;
+use std::time::Duration;
 
-    #[cfg(target_arch = "x86_64")]
-    pub mod x86_64 {
-        use std::arch::x86_64::*;
-
-        pub fn sse2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            // scan 16-byte chunks, then handle tail
-            let mut line_start = 0;
-            unsafe {
-                let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
-                for (chunk_i, chunk) in input.as_bytes().chunks_exact(16).enumerate() {
-                    let v = _mm_loadu_si128(chunk.as_ptr().cast());
-                    let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
-                    while mask != 0 {
-                        /*
-                        abcdefNhijklNmoN
-                        (reversed, so first char is lowest bit)
-                        1001000001000000
-                         */
-                        let bit_pos = mask.trailing_zeros() as usize;
-                        let line_end = chunk_i * 16 + bit_pos;
-                        out.push(&input[line_start..line_end]);
-                        line_start = line_end + 1;
-                        mask &= mask - 1;
-                    }
-                }
-            }
-            tail(line_start, 16, input, out);
-        }
+use split_bench::{
+    arena, compressed, density, dispatch, flat, guard, jsonl, pool, ranges, sharded, simd_lines,
+    sink, slice, span, substring, uninit,
+};
 
-        fn tail<'input>(
-            mut line_start: usize,
-            chunk_size: usize,
-            input: &'input str,
-            out: &mut Vec<&'input str>,
-        ) {
-            // handle last bytes
-            for i in (input.len() & !(chunk_size - 1))..input.len() {
-                if input.as_bytes()[i] != b'\n' {
-                    continue;
-                }
-                out.push(unsafe { input.get_unchecked(line_start..i) });
-                line_start = i + 1;
+/// `lines` lines of M to N bytes each (not counting the newline). Line lengths are drawn from
+/// `rng`, so a seed reproduces the input, whatever the number of threads: the lines are generated
+/// in parallel, in chunks of [`PREP_CHUNK_LINES`] lines that each have their own `Rng`. A first
+/// pass adds up the length of every chunk, so each one knows where it goes, and a second one
+/// generates the same lengths again and writes the lines.
+fn prep_vec_range<const M: usize, const N: usize>(
+    vec: &mut Vec<u8>,
+    lines: usize,
+    rng: &mut Rng,
+) -> Prepared {
+    assert!(M <= N);
+    prep_vec_lines(vec, lines, rng, || |rng: &mut Rng| M + rng.below(N - M + 1))
+}
+
+/// `lines` lines in runs of random length, alternating between 0 to 2 bytes per line (runs of up
+/// to 512 lines) and 40 to 120 bytes (up to 16 lines), so both take about the same share of the
+/// input and the density changes every few hundred bytes. A kernel that's only fast on one of
+/// them, or mispredicts where they switch, shows it here.
+fn prep_vec_mixed(vec: &mut Vec<u8>, lines: usize, rng: &mut Rng) -> Prepared {
+    prep_vec_lines(vec, lines, rng, || {
+        let (mut dense, mut left) = (false, 0);
+        move |rng: &mut Rng| {
+            if left == 0 {
+                dense = !dense;
+                left = 1 + rng.below(if dense { 512 } else { 16 });
             }
-            // handle last line. omit if empty
-            if line_start != input.len() {
-                out.push(unsafe { input.get_unchecked(line_start..) });
+            left -= 1;
+            match dense {
+                true => rng.below(3),
+                false => 40 + rng.below(81),
             }
         }
+    })
+}
 
-        pub fn sse2_unsafe<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            // scan 16-byte chunks, then handle tail
-            let mut line_start = 0;
-            unsafe {
-                let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
-                for (chunk_i, chunk) in input.as_bytes().chunks_exact(16).enumerate() {
-                    let v = _mm_loadu_si128(chunk.as_ptr().cast());
-                    let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
-                    while mask != 0 {
-                        let bit_pos = mask.trailing_zeros() as usize;
-                        let line_end = chunk_i * 16 + bit_pos;
-                        out.push(input.get_unchecked(line_start..line_end));
-                        line_start = line_end + 1;
-                        mask &= mask - 1;
-                    }
+/// `lines` lines of 40 to 120 bytes, like `40-120`, with tabs cutting them into fields of 1 to
+/// 16 bytes, for the delimiter table. Each chunk of the input gets its own `Rng`, as in
+/// [`prep_vec_lines`].
+fn prep_vec_tsv(vec: &mut Vec<u8>, lines: usize, rng: &mut Rng) -> Prepared {
+    let prepared = prep_vec_range::<40, 120>(vec, lines, rng);
+    let base_seed = rng.next_u64();
+    in_parallel(
+        vec.chunks_mut(PREP_CHUNK_LINES * 64).enumerate(),
+        |(chunk, part)| {
+            let mut rng = Rng::new(Rng::new(base_seed ^ chunk as u64).next_u64());
+            let mut pos = rng.below(16);
+            while pos < part.len() {
+                if part[pos] == b'a' {
+                    part[pos] = b'\t';
                 }
+                pos += 1 + rng.below(16);
             }
-            tail(line_start, 16, input, out);
-        }
+        },
+    );
+    prepared
+}
 
-        pub fn sse2_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            // Key idea is to pull the allocation out of the innermost loop
-
-            let mut line_start = 0;
-            unsafe {
-                let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
-                let mut chunk_i = 0;
-                let stop_chunk_i = input.len() / 16;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.reserve(256);
-                    let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
-                    while write_i < (256 - 16) && chunk_i < stop_chunk_i {
-                        let v = _mm_loadu_si128(input.as_ptr().byte_add(chunk_i * 16).cast());
-                        let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
-                        while mask != 0 {
-                            let bit_pos = mask.trailing_zeros() as usize;
-                            let line_end = chunk_i * 16 + bit_pos;
-                            out_arr
-                                .get_unchecked_mut(write_i)
-                                .write(input.get_unchecked(line_start..line_end));
-                            write_i += 1;
-                            line_start = line_end + 1;
-                            mask &= mask - 1;
-                        }
-                        chunk_i += 1;
-                    }
-                    out.set_len(out.len() + write_i);
-                }
-            }
-            tail(line_start, 16, input, out);
-        }
+/// `lines` lines, whose lengths come from the generator `line_lens` makes for each chunk of
+/// [`PREP_CHUNK_LINES`], see [`prep_vec_range`]
+fn prep_vec_lines<G: FnMut(&mut Rng) -> usize>(
+    vec: &mut Vec<u8>,
+    lines: usize,
+    rng: &mut Rng,
+    line_lens: impl Fn() -> G + Sync,
+) -> Prepared {
+    let base_seed = rng.next_u64();
+    let chunk_rng = |chunk: usize| Rng::new(Rng::new(base_seed ^ chunk as u64).next_u64());
+    let chunk_lines = |chunk: usize| PREP_CHUNK_LINES.min(lines - chunk * PREP_CHUNK_LINES);
+    let mut chunk_lens = vec![0; lines.div_ceil(PREP_CHUNK_LINES)];
+    in_parallel(chunk_lens.iter_mut().enumerate(), |(chunk, chunk_len)| {
+        let (mut rng, mut line_len) = (chunk_rng(chunk), line_lens());
+        *chunk_len = (0..chunk_lines(chunk))
+            .map(|_| line_len(&mut rng) + 1)
+            .sum();
+    });
+    let len = chunk_lens.iter().sum();
 
-        pub fn sse2_unrollx4<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            let mut line_start = 0;
-            unsafe {
-                let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
-                let mut chunk_i = 0;
-                let stop_chunk_i = input.len() / 64;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.reserve(256);
-                    let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
-                    while write_i < (256 - 64) && chunk_i < stop_chunk_i {
-                        use std::arch::x86_64::{
-                            _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load,
-                            _mm_movemask_epi8 as movemask,
-                        };
-                        let in_ptr = input.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
-                        let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
-                        let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
-                        let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
-                        let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
-                        let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
-                        while mask != 0 {
-                            let bit_pos = mask.trailing_zeros() as usize;
-                            let line_end = chunk_i * 64 + bit_pos;
-                            out_arr
-                                .get_unchecked_mut(write_i)
-                                .write(input.get_unchecked(line_start..line_end));
-                            write_i += 1;
-                            line_start = line_end + 1;
-                            mask &= mask - 1;
-                        }
-                        chunk_i += 1;
-                    }
-                    out.set_len(out.len() + write_i);
-                }
-            }
-            tail(line_start, 64, input, out);
+    vec.clear();
+    vec.reserve(len);
+    let mut rest = &mut vec.spare_capacity_mut()[..len];
+    let parts = chunk_lens.iter().map(|&chunk_len| {
+        let (part, after) = std::mem::take(&mut rest).split_at_mut(chunk_len);
+        rest = after;
+        part
+    });
+    in_parallel(parts.enumerate(), |(chunk, part)| {
+        let (mut rng, mut next_len) = (chunk_rng(chunk), line_lens());
+        let mut pos = 0;
+        for _ in 0..chunk_lines(chunk) {
+            let line_len = next_len(&mut rng);
+            part[pos..pos + line_len].fill(std::mem::MaybeUninit::new(b'a'));
+            part[pos + line_len].write(b'\n');
+            pos += line_len + 1;
         }
+    });
+    // every byte was written, the chunks' lengths add up to `len`
+    unsafe { vec.set_len(len) };
+    Prepared {
+        len,
+        newlines: lines,
+    }
+}
 
-        pub fn can_run_avx2() -> bool {
-            is_x86_feature_detected!("avx2")
-                && is_x86_feature_detected!("bmi1")
-                && is_x86_feature_detected!("popcnt")
-        }
+/// `count` inputs of 1 to 63 bytes, back to back, with a newline for about one byte in 8.
+/// Returns the range of each.
+fn prep_small_inputs(
+    vec: &mut Vec<u8>,
+    count: usize,
+    rng: &mut Rng,
+) -> Vec<std::ops::Range<usize>> {
+    vec.clear();
+    (0..count)
+        .map(|_| {
+            let start = vec.len();
+            let len = 1 + rng.below(SMALL_INPUT_MAX_LEN);
+            vec.extend((0..len).map(|_| match rng.below(8) {
+                0 => b'\n',
+                _ => b'a',
+            }));
+            start..vec.len()
+        })
+        .collect()
+}
 
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            // scan 32-byte chunks, then handle tail
-            let mut line_start = 0;
-            let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
-            for (chunk_i, chunk) in input.as_bytes().chunks_exact(32).enumerate() {
-                let v = _mm256_loadu_si256(chunk.as_ptr().cast());
-                let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
-                while mask != 0 {
-                    let bit_pos = mask.trailing_zeros() as usize;
-                    let line_end = chunk_i * 32 + bit_pos;
-                    out.push(&input[line_start..line_end]);
-                    line_start = line_end + 1;
-                    mask &= mask - 1;
-                }
-            }
-            tail(line_start, 32, input, out);
-        }
+/// `len` copies of `byte`, written in parallel
+fn prep_vec_fill(vec: &mut Vec<u8>, len: usize, byte: u8) -> Prepared {
+    vec.clear();
+    vec.reserve(len);
+    let parts = vec.spare_capacity_mut()[..len].chunks_mut(PREP_CHUNK_LINES * 64);
+    in_parallel(parts, |part| part.fill(std::mem::MaybeUninit::new(byte)));
+    unsafe { vec.set_len(len) };
+    Prepared {
+        len,
+        newlines: if byte == b'\n' { len } else { 0 },
+    }
+}
 
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_unsafe<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            // scan 32-byte chunks, then handle tail
-            let mut line_start = 0;
-            let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
-            for (chunk_i, chunk) in input.as_bytes().chunks_exact(32).enumerate() {
-                let v = _mm256_loadu_si256(chunk.as_ptr().cast());
-                let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
-                while mask != 0 {
-                    let bit_pos = mask.trailing_zeros() as usize;
-                    let line_end = chunk_i * 32 + bit_pos;
-                    out.push(input.get_unchecked(line_start..line_end));
-                    line_start = line_end + 1;
-                    mask &= mask - 1;
-                }
-            }
-            tail(line_start, 32, input, out);
+/// The substring table's own stages, each split on its delimiter: `\r\n` line ends, Markdown-like
+/// paragraphs of 1 to 8 lines, and MIME-like parts of 1 to 16 lines between `--` lines, whose
+/// 2-byte lines are near misses that only the middle bytes tell apart. A [`RecordFn`] appends one
+/// record and its delimiter.
+const SUBSTRING_STAGES: &[(&str, &str, RecordFn)] = &[
+    ("crlf 0-80", "\r\n", |vec, rng| {
+        vec.resize(vec.len() + rng.below(81), b'a');
+        vec.extend(b"\r\n");
+    }),
+    ("paragraphs", "\n\n", |vec, rng| {
+        for _ in 0..=rng.below(8) {
+            vec.resize(vec.len() + 20 + rng.below(61), b'a');
+            vec.push(b'\n');
         }
-
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            // Key idea is to pull the allocation out of the innermost loop
-            let mut line_start = 0;
-            let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
-            let mut chunk_i = 0;
-            let stop_chunk_i = input.len() / 32;
-            while chunk_i < stop_chunk_i {
-                let mut write_i = 0;
-                // this is the only function call in the loop. Vector registers have to be reloaded
-                // after a function call. That's why we go through the trouble of removing it from the
-                // inner loop.
-                out.reserve(256);
-                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
-                // at most 32 items will be added per chunk
-                while write_i <= (256 - 32) && chunk_i < stop_chunk_i {
-                    let v = _mm256_loadu_si256(input.as_ptr().byte_add(chunk_i * 32).cast());
-                    let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
-                    while mask != 0 {
-                        let bit_pos = mask.trailing_zeros() as usize;
-                        let line_end = chunk_i * 32 + bit_pos;
-                        out_arr
-                            .get_unchecked_mut(write_i)
-                            .write(input.get_unchecked(line_start..line_end));
-                        write_i += 1;
-                        line_start = line_end + 1;
-                        mask &= mask - 1;
-                    }
-                    chunk_i += 1;
-                }
-                out.set_len(out.len() + write_i);
-            }
-            tail(line_start, 32, input, out);
+        vec.push(b'\n');
+    }),
+    ("mime", "\n--\n", |vec, rng| {
+        for _ in 0..=rng.below(16) {
+            vec.resize(vec.len() + rng.below(41), b'a');
+            vec.push(b'\n');
         }
+        vec.extend(b"--\n");
+    }),
+];
 
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_unrollx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
-            use std::arch::x86_64::{
-                _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load,
-                _mm256_movemask_epi8 as movemask,
-            };
-            let mut line_start = 0;
-            let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
-            let mut chunk_i = 0;
-            let stop_chunk_i = input.len() / 64;
-            while chunk_i < stop_chunk_i {
-                let mut write_i = 0;
-                // this is the only function call in the loop. Vector registers have to be reloaded
-                // after a function call. That's why we go through the trouble of removing it from the
-                // inner loop.
-                out.reserve(256);
-                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
-                // at most 64 items will be added per chunk
-                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
-                    let ptr = input.as_ptr().byte_add(chunk_i * 64);
-                    let v1 = load(ptr.cast());
-                    let v2 = load(ptr.byte_add(32).cast());
-                    let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
-                        | (movemask(eq(v1, nl_v)) as u32 as u64);
-                    while mask != 0 {
-                        let bit_pos = mask.trailing_zeros() as usize;
-                        let line_end = chunk_i * 64 + bit_pos;
-                        out_arr
-                            .get_unchecked_mut(write_i)
-                            .write(input.get_unchecked(line_start..line_end));
-                        write_i += 1;
-                        line_start = line_end + 1;
-                        mask &= mask - 1;
-                    }
-                    chunk_i += 1;
-                }
-                out.set_len(out.len() + write_i);
-            }
-            tail(line_start, 64, input, out);
+/// The NUL table's own stages, records ended by `\0`: paths like `find -print0` writes, one in 16
+/// with a newline in it, and `git log -z`-like records of 1 to 8 lines
+const NUL_STAGES: &[(&str, RecordFn)] = &[
+    ("paths 10-80", |vec, rng| {
+        let start = vec.len();
+        vec.resize(start + 10 + rng.below(71), b'a');
+        if rng.below(16) == 0 {
+            let newline = start + rng.below(vec.len() - start);
+            vec[newline] = b'\n';
+        }
+        vec.push(b'\0');
+    }),
+    ("multiline", |vec, rng| {
+        for _ in 0..=rng.below(8) {
+            vec.resize(vec.len() + rng.below(81), b'a');
+            vec.push(b'\n');
         }
+        vec.push(b'\0');
+    }),
+];
+
+/// Records until the input is about as large as a `0-80` stage of `lines` lines
+fn prep_record_stage(vec: &mut Vec<u8>, lines: usize, rng: &mut Rng, record: RecordFn) {
+    vec.clear();
+    while vec.len() < lines * 41 {
+        record(vec, rng);
     }
 }
 
-mod compressed {
-    #[derive(PartialEq, Eq)]
-    pub struct LineIndex {
-        /// Low 16 bits of each newline's index
-        /// One per line.
-        pub lows: Vec<u16>,
-        /// d[i] is the first index into 'lows' where the high bits are i
-        /// One per 64KB of input.
-        pub high_starts: Vec<usize>,
-    }
+/// What a [`PrepFn`] generated
+#[derive(Clone, Copy)]
+struct Prepared {
+    len: usize,
+    /// Known from how the input was made, so every kernel's output can be checked against it
+    /// without running a reference kernel, even where the outputs aren't compared
+    newlines: usize,
+}
 
-    pub fn iter(input: &str, out: &mut LineIndex) {
-        for chunk in input.as_bytes().chunks(1 << 16) {
-            out.high_starts.push(out.lows.len());
-            for (idx, _) in chunk.iter().enumerate().filter(|e| *e.1 == b'\n') {
-                out.lows.push(idx as u16);
-            }
-        }
+impl Prepared {
+    /// Lines `slice::split` finds in `input`: one per newline, plus an unterminated last one
+    fn lines(self, input: &str) -> usize {
+        self.newlines + usize::from(!input.is_empty() && !input.ends_with('\n'))
     }
+}
 
-    /// Assumes high_start has already been written
-    pub fn tail(chunk_size: usize, input: &str, out: &mut LineIndex) {
-        let base = input.len() & !(chunk_size - 1);
-        for (idx, _) in input.as_bytes()[base..]
-            .iter()
-            .enumerate()
-            .filter(|e| *e.1 == b'\n')
-        {
-            out.lows.push(base as u16 + idx as u16);
-        }
-    }
+/// Lines per independently seeded chunk of a generated stage
+const PREP_CHUNK_LINES: usize = 1 << 16;
 
-    #[cfg(target_arch = "x86_64")]
-    pub mod x86_64 {
-        use crate::compressed::*;
-        use std::arch::x86_64::*;
-
-        pub fn sse2(input: &str, out: &mut LineIndex) {
-            let nl_v = unsafe { _mm_loadu_si128([b'\n'; 16].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                for (chunk_idx, chunk) in chunk_64k.chunks_exact(16).enumerate() {
-                    unsafe {
-                        let v = _mm_loadu_si128(chunk.as_ptr().cast());
-                        let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
-                        while mask != 0 {
-                            let bit_pos = mask.trailing_zeros() as u16;
-                            out.lows.push(chunk_idx as u16 * 16 + bit_pos);
-                            mask &= mask - 1;
-                        }
-                    }
-                }
-            }
-            tail(16, input, out);
-        }
-
-        pub fn sse2_unroll(input: &str, out: &mut LineIndex) {
-            let nl_v = unsafe { _mm_loadu_si128([b'\n'; 16].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / 16;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.lows.reserve(256);
-                    unsafe {
-                        let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
-                        while write_i <= (256 - 16) && chunk_i < stop_chunk_i {
-                            let v = _mm_loadu_si128(chunk_64k.as_ptr().add(chunk_i * 16).cast());
-                            let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
-                            while mask != 0 {
-                                let bit_pos = mask.trailing_zeros() as u16;
-                                out_arr
-                                    .get_unchecked_mut(write_i)
-                                    .write(chunk_i as u16 * 16 + bit_pos);
-                                write_i += 1;
-                                mask &= mask - 1;
-                            }
-                            chunk_i += 1;
-                        }
-                        out.lows.set_len(out.lows.len() + write_i);
-                    }
-                }
-            }
-            tail(16, input, out);
+/// Calls `f` with every item, spread over one thread per core
+fn in_parallel<T: Send>(items: impl Iterator<Item = T>, f: impl Fn(T) + Sync) {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut groups: Vec<Vec<T>> = (0..threads).map(|_| Vec::new()).collect();
+    for (i, item) in items.enumerate() {
+        groups[i % threads].push(item);
+    }
+    std::thread::scope(|scope| {
+        for group in groups {
+            let f = &f;
+            scope.spawn(move || group.into_iter().for_each(f));
         }
+    });
+}
 
-        pub fn sse2_unrollx4(input: &str, out: &mut LineIndex) {
-            use std::arch::x86_64::{
-                _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
-            };
-            let nl_v = unsafe { load([b'\n'; 16].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / 64;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.lows.reserve(256);
-                    unsafe {
-                        let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
-                        while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
-                            let in_ptr =
-                                chunk_64k.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
-                            let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
-                            let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
-                            let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
-                            let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
-                            let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
-                            while mask != 0 {
-                                let bit_pos = mask.trailing_zeros() as u16;
-                                out_arr
-                                    .get_unchecked_mut(write_i)
-                                    .write(chunk_i as u16 * 64 + bit_pos);
-                                write_i += 1;
-                                mask &= mask - 1;
-                            }
-                            chunk_i += 1;
-                        }
-                        out.lows.set_len(out.lows.len() + write_i);
-                    }
-                }
-            }
-            tail(64, input, out);
-        }
+/// [`LAZY_SAMPLES`] line numbers spread evenly over `line_count` lines (fewer if there aren't
+/// that many)
+fn sampled_lines(line_count: usize) -> impl Iterator<Item = usize> {
+    (0..LAZY_SAMPLES.min(line_count)).map(move |k| k * line_count / LAZY_SAMPLES.min(line_count))
+}
 
-        pub fn can_run_sse42() -> bool {
-            is_x86_feature_detected!("popcnt")
-        }
+/// Times the first call of a kernel (`run` times one call), then returns that and the median of
+/// the `warm_runs` calls after it
+fn first_call_times(warm_runs: usize, mut run: impl FnMut() -> Duration) -> (Duration, Duration) {
+    let cold = run();
+    let mut warm: Vec<Duration> = (0..warm_runs).map(|_| run()).collect();
+    warm.sort_unstable();
+    (cold, warm[warm.len() / 2])
+}
 
-        // enabling bmi1 isn't interesting bc there's a very narrow slice of CPUs with BMI1 but not
-        // AVX2, but a broad range of older CPUS with popcnt
-        #[target_feature(enable = "popcnt")]
-        pub unsafe fn sse42_unrollx4_interleavex2(input: &str, out: &mut LineIndex) {
-            use std::arch::x86_64::{
-                _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
-            };
-            const CHUNK_SIZE: usize = 128;
-            /// count_ones() without branching on the zero case. Result undefined if input is 0
-            /// Same encoding as tzcnt.
-            fn rep_bsf(input: u64) -> u64 {
-                let mut output;
-                unsafe {
-                    std::arch::asm!("rep bsf {output}, {input}", input = in(reg) input, output = out(reg) output)
-                };
-                output
-            }
-            let nl_v = unsafe { load([b'\n'; 16].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    let iter_count = 32.min(stop_chunk_i - chunk_i);
-                    out.lows.reserve(iter_count * CHUNK_SIZE);
-                    let out_arr = out
-                        .lows
-                        .spare_capacity_mut()
-                        .get_unchecked_mut(..iter_count * CHUNK_SIZE);
-                    for _ in 0..iter_count {
-                        let mut mask1 = {
-                            let in_ptr = chunk_64k
-                                .as_ptr()
-                                .byte_add(chunk_i * CHUNK_SIZE)
-                                .cast::<__m128i>();
-                            let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
-                            let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
-                            let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
-                            let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
-                            mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48)
-                        };
-
-                        let mut mask2 = {
-                            let in_ptr = chunk_64k
-                                .as_ptr()
-                                .byte_add(chunk_i * CHUNK_SIZE + 64)
-                                .cast::<__m128i>();
-                            let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
-                            let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
-                            let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
-                            let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
-                            mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48)
-                        };
-                        let mut write_i2 = write_i + mask1.count_ones() as usize;
-                        let mask2_count = mask2.count_ones() as usize;
-
-                        while mask1 != 0 {
-                            let bit_pos = mask1.trailing_zeros() as u16;
-                            out_arr
-                                .get_unchecked_mut(write_i)
-                                .write(chunk_i as u16 * CHUNK_SIZE as u16 + bit_pos);
-                            write_i += 1;
-                            mask1 &= mask1 - 1;
-
-                            let bit_pos = rep_bsf(mask2) as u16;
-                            out_arr.get_unchecked_mut(write_i2).write(
-                                (chunk_i as u16 * CHUNK_SIZE as u16)
-                                    .wrapping_add(64)
-                                    .wrapping_add(bit_pos),
-                            );
-                            write_i2 += 1;
-                            mask2 &= mask2.wrapping_sub(1);
-                        }
-                        write_i += mask2_count;
-                        while mask2 != 0 {
-                            let bit_pos = mask2.trailing_zeros() as u16;
-                            out_arr
-                                .get_unchecked_mut(write_i2)
-                                .write(chunk_i as u16 * CHUNK_SIZE as u16 + 64 + bit_pos);
-                            write_i2 += 1;
-                            mask2 &= mask2 - 1;
-                        }
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
-            }
-            tail(128, input, out);
-        }
+/// How many times each kernel runs per stage, and when those runs disagree too much to trust
+#[derive(Clone, Copy)]
+struct Sampling {
+    iterations: usize,
+    /// Relative standard deviation above which a batch of iterations is rerun
+    noise_threshold: f64,
+    max_reruns: usize,
+}
 
-        pub fn can_run_avx2() -> bool {
-            // in practice, avx2 also implies bmi1 and popcnt
-            is_x86_feature_detected!("avx2")
-                && is_x86_feature_detected!("bmi1")
-                && is_x86_feature_detected!("popcnt")
-        }
+struct Measurement {
+    /// Median MB/s of the kept batch
+    thrpt: f64,
+    /// Relative standard deviation of the kept batch
+    rel_stddev: f64,
+    reruns: usize,
+    noisy: bool,
+}
 
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_unroll(input: &str, out: &mut LineIndex) {
-            let nl_v = unsafe { _mm256_loadu_si256([b'\n'; 32].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / 32;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.lows.reserve(256);
-                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
-                    while write_i <= (256 - 32) && chunk_i < stop_chunk_i {
-                        let v = _mm256_loadu_si256(chunk_64k.as_ptr().add(chunk_i * 32).cast());
-                        let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
-                        while mask != 0 {
-                            let bit_pos = mask.trailing_zeros() as u16;
-                            out_arr
-                                .get_unchecked_mut(write_i)
-                                .write(chunk_i as u16 * 32 + bit_pos);
-                            write_i += 1;
-                            mask &= mask - 1;
-                        }
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
-            }
-            tail(32, input, out);
-        }
+/// Runs `run` (which times one iteration) `sampling.iterations` times and takes the median MB/s.
+/// If the runs vary by more than the threshold (e.g. another process woke up), the whole batch is
+/// rerun, up to `max_reruns` times. If no batch is quiet enough, the least noisy one is kept and
+/// the result is flagged, rather than reported as if it could be trusted.
+fn measure_thrpt(sampling: Sampling, len: usize, run: &mut dyn FnMut() -> Duration) -> Measurement {
+    match measure_thrpt_within(sampling, len, None, run) {
+        Ok(measurement) => measurement,
+        Err(_) => unreachable!("no limit to go over"),
+    }
+}
 
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_unrollx2(input: &str, out: &mut LineIndex) {
-            use std::arch::x86_64::{
-                _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load,
-                _mm256_movemask_epi8 as movemask,
-            };
-            let nl_v = unsafe { _mm256_loadu_si256([b'\n'; 32].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / 64;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.lows.reserve(256);
-                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
-                    while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
-                        let ptr = chunk_64k.as_ptr().add(chunk_i * 64);
-                        let v1 = load(ptr.cast());
-                        let v2 = load(ptr.byte_add(32).cast());
-                        let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
-                            | (movemask(eq(v1, nl_v)) as u32 as u64);
-                        while mask != 0 {
-                            let bit_pos = mask.trailing_zeros() as u16;
-                            out_arr
-                                .get_unchecked_mut(write_i)
-                                .write(chunk_i as u16 * 64 + bit_pos);
-                            write_i += 1;
-                            mask &= mask - 1;
-                        }
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
+/// [`measure_thrpt`], giving up on the first run that takes longer than `limit` and returning how
+/// long it took instead
+fn measure_thrpt_within(
+    sampling: Sampling,
+    len: usize,
+    limit: Option<Duration>,
+    run: &mut dyn FnMut() -> Duration,
+) -> Result<Measurement, Duration> {
+    let mut kept: Option<Measurement> = None;
+    for reruns in 0..=sampling.max_reruns {
+        let mut thrpts = Vec::with_capacity(sampling.iterations);
+        for _ in 0..sampling.iterations {
+            let duration = run();
+            if limit.is_some_and(|limit| duration > limit) {
+                return Err(duration);
             }
-            tail(64, input, out);
+            thrpts.push(cli::thrpt(len, duration));
         }
-
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_unrollx2_interleavex2(input: &str, out: &mut LineIndex) {
-            use std::arch::x86_64::{
-                _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load,
-                _mm256_movemask_epi8 as movemask,
-            };
-            const CHUNK_SIZE: usize = 128;
-            let nl_v = unsafe { _mm256_loadu_si256([b'\n'; 32].as_ptr().cast()) };
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
-                while chunk_i < stop_chunk_i {
-                    // two iters of 64B, start 2nd at + popcount, stop when first exhausted,
-                    // finish 2nd
-                    let mut write_i = 0;
-                    let iter_count = 32.min(stop_chunk_i - chunk_i);
-                    out.lows.reserve(iter_count * CHUNK_SIZE);
-                    let out_arr = out
-                        .lows
-                        .spare_capacity_mut()
-                        .get_unchecked_mut(..iter_count * CHUNK_SIZE);
-                    for _ in 0..iter_count {
-                        let ptr = chunk_64k.as_ptr().add(chunk_i * CHUNK_SIZE);
-                        let v1 = load(ptr.cast());
-                        let v2 = load(ptr.byte_add(32).cast());
-                        let mut mask1 = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
-                            | (movemask(eq(v1, nl_v)) as u32 as u64);
-
-                        let v1 = load(ptr.byte_add(64).cast());
-                        let v2 = load(ptr.byte_add(96).cast());
-                        let mut mask2 = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
-                            | (movemask(eq(v1, nl_v)) as u32 as u64);
-                        let mut write_i2 = write_i + mask1.count_ones() as usize;
-                        let mask2_count = mask2.count_ones() as usize;
-                        while mask1 != 0 {
-                            let bit_pos = mask1.trailing_zeros() as u16;
-                            out_arr
-                                .get_unchecked_mut(write_i)
-                                .write(chunk_i as u16 * CHUNK_SIZE as u16 + bit_pos);
-                            write_i += 1;
-                            mask1 &= mask1 - 1;
-
-                            let bit_pos = _tzcnt_u64(mask2) as u16;
-                            // if this turns out to be a junk value, it will be ignored later (by
-                            // truncating the slice). So, overflowing is fine.
-                            out_arr.get_unchecked_mut(write_i2).write(
-                                (chunk_i as u16 * CHUNK_SIZE as u16)
-                                    .wrapping_add(64)
-                                    .wrapping_add(bit_pos),
-                            );
-                            write_i2 += 1;
-                            mask2 &= mask2.wrapping_sub(1);
-                        }
-                        write_i += mask2_count;
-                        while mask2 != 0 {
-                            let bit_pos = mask2.trailing_zeros() as u16;
-                            out_arr
-                                .get_unchecked_mut(write_i2)
-                                .write(chunk_i as u16 * CHUNK_SIZE as u16 + 64 + bit_pos);
-                            write_i2 += 1;
-                            mask2 &= mask2 - 1;
-                        }
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
-            }
-            tail(128, input, out);
+        thrpts.sort_by(f64::total_cmp);
+        let rel_stddev = rel_stddev(&thrpts);
+        let measurement = Measurement {
+            thrpt: thrpts[thrpts.len() / 2],
+            rel_stddev,
+            reruns,
+            noisy: rel_stddev > sampling.noise_threshold,
+        };
+        if !measurement.noisy {
+            return Ok(measurement);
         }
-
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_lut(input: &str, out: &mut LineIndex) {
-            use std::arch::x86_64::{
-                _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load,
-                _mm256_movemask_epi8 as movemask,
-            };
-            /// Precomputed table of 8bit mask -> packed list of 2B indices
-            const LUT: [[u16; 8]; 256] = {
-                let mut t = [[0u16; 8]; 256];
-                let mut t_i = 0;
-                while t_i < 256 {
-                    let mut e = t[t_i];
-                    let mut bit_i = 0;
-                    let mut packed_i = 0;
-                    while bit_i < 8 {
-                        if t_i & (1 << bit_i) != 0 {
-                            e[packed_i] = bit_i;
-                            packed_i += 1;
-                        }
-                        bit_i += 1;
-                    }
-                    t[t_i] = e;
-                    t_i += 1;
-                }
-                t
-            };
-            let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
-            let u16_8_v = _mm_set1_epi16(8);
-            let u16_32_v = _mm_set1_epi16(32);
-            const CHUNK_SIZE: usize = 32;
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
-                let mut offset_v = _mm_setzero_si128();
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    let iter_count = 32.min(stop_chunk_i - chunk_i);
-                    out.lows.reserve(iter_count * CHUNK_SIZE);
-                    let out_arr = out
-                        .lows
-                        .spare_capacity_mut()
-                        .get_unchecked_mut(..iter_count * CHUNK_SIZE);
-                    for _ in 0..iter_count {
-                        let ptr = chunk_64k.as_ptr().add(chunk_i * CHUNK_SIZE);
-                        let v = load(ptr.cast());
-                        let mask = movemask(eq(nl_v, v));
-                        if mask == 0 {
-                            offset_v = _mm_add_epi16(offset_v, u16_32_v);
-                        } else {
-                            // for each 8bit of mask, lookup, shift, write, adv by popcnt.
-                            for byte in mask.to_le_bytes() {
-                                let mut packed_indices =
-                                    _mm_loadu_si128(LUT.as_ptr().add(byte as usize).cast());
-                                packed_indices = _mm_add_epi16(packed_indices, offset_v);
-                                offset_v = _mm_add_epi16(offset_v, u16_8_v);
-                                _mm_storeu_si128(
-                                    out_arr.as_mut_ptr().add(write_i).cast::<__m128i>(),
-                                    packed_indices,
-                                );
-                                write_i += byte.count_ones() as usize;
-                            }
-                        }
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
-            }
-            tail(64, input, out);
+        if kept
+            .as_ref()
+            .is_none_or(|kept| measurement.rel_stddev < kept.rel_stddev)
+        {
+            kept = Some(measurement);
         }
+    }
+    let mut kept = kept.unwrap();
+    kept.reruns = sampling.max_reruns;
+    Ok(kept)
+}
 
-        #[target_feature(enable = "avx2,bmi1,popcnt")]
-        pub unsafe fn avx2_big_lut(input: &str, out: &mut LineIndex) {
-            use std::arch::x86_64::{
-                _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load,
-                _mm256_movemask_epi8 as movemask,
-            };
-            const U16_SIZE: usize = 1 << 16;
-            /// Precomputed table of 16 bit mask -> packed list of 2B indices
-            /// This is slow in const and makes RA a lot slower :(
-            const LUT: &[[u16; 16]; U16_SIZE] = &{
-                let mut t = [[0u16; 16]; U16_SIZE];
-                let mut t_i = 0;
-                while t_i < U16_SIZE {
-                    let mut e = t[t_i];
-                    let mut bit_i = 0;
-                    let mut packed_i = 0;
-                    while bit_i < 16 {
-                        if t_i & (1 << bit_i) != 0 {
-                            e[packed_i] = bit_i;
-                            packed_i += 1;
-                        }
-                        bit_i += 1;
-                    }
-                    t[t_i] = e;
-                    t_i += 1;
-                }
-                t
-            };
-            let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
-            let u16_16_v = _mm_set1_epi16(16);
-            let u16_32_v = _mm_set1_epi16(32);
-            const CHUNK_SIZE: usize = 32;
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / CHUNK_SIZE;
-                let mut offset_v = _mm_setzero_si128();
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    let iter_count = 32.min(stop_chunk_i - chunk_i);
-                    out.lows.reserve(iter_count * CHUNK_SIZE);
-                    let out_arr = out
-                        .lows
-                        .spare_capacity_mut()
-                        .get_unchecked_mut(..iter_count * CHUNK_SIZE);
-                    for _ in 0..iter_count {
-                        let ptr = chunk_64k.as_ptr().add(chunk_i * CHUNK_SIZE);
-                        let v = load(ptr.cast());
-                        let mask = movemask(eq(nl_v, v));
-                        if mask == 0 {
-                            offset_v = _mm_add_epi16(offset_v, u16_32_v);
-                        } else {
-                            // for each 8bit of mask, lookup, shift, write, adv by popcnt.
-                            for word in std::mem::transmute::<i32, [u16; 2]>(mask) {
-                                let mut packed_indices =
-                                    _mm_loadu_si128(LUT.as_ptr().add(word as usize).cast());
-                                packed_indices = _mm_add_epi16(packed_indices, offset_v);
-                                offset_v = _mm_add_epi16(offset_v, u16_16_v);
-                                _mm_storeu_si128(
-                                    out_arr.as_mut_ptr().add(write_i).cast::<__m128i>(),
-                                    packed_indices,
-                                );
-                                write_i += word.count_ones() as usize;
-                            }
-                        }
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
-            }
-            tail(64, input, out);
-        }
+/// Standard deviation over mean (the coefficient of variation)
+fn rel_stddev(values: &[f64]) -> f64 {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance =
+        values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64;
+    variance.sqrt() / mean
+}
 
-        pub fn can_run_avx512_compress() -> bool {
-            is_x86_feature_detected!("popcnt")
-                && is_x86_feature_detected!("avx512f")
-                && is_x86_feature_detected!("avx512bw")
-                && is_x86_feature_detected!("avx512vbmi2")
-        }
+/// Byte ranges of `input` with at most `max_lines` lines each, so all of it with no maximum
+fn segments(input: &str, max_lines: Option<usize>) -> Vec<std::ops::Range<usize>> {
+    let max_lines = max_lines.unwrap_or(usize::MAX);
+    let cuts = input
+        .match_indices('\n')
+        .skip(max_lines - 1)
+        .step_by(max_lines)
+        .map(|(newline, _)| newline + 1)
+        .filter(|&cut| cut < input.len());
+    let bounds: Vec<usize> = std::iter::once(0)
+        .chain(cuts)
+        .chain(std::iter::once(input.len()))
+        .collect();
+    bounds.windows(2).map(|pair| pair[0]..pair[1]).collect()
+}
 
-        #[inline(never)]
-        #[target_feature(enable = "popcnt,avx512f,avx512bw,avx512vbmi2")]
-        pub unsafe fn avx512_compress(input: &str, out: &mut LineIndex) {
-            const IDX_ARR: [u8; 64] = {
-                let mut t = [0u8; 64];
-                let mut i = 0;
-                while i < t.len() {
-                    t[i] = i as u8;
-                    i += 1;
-                }
-                t
-            };
-            let nl_v = _mm512_set1_epi8(b'\n' as i8);
-            let idx_v = _mm512_loadu_epi8(IDX_ARR.as_ptr().cast());
-            let i16_64_v = _mm512_set1_epi16(64);
-            for chunk_64k in input.as_bytes().chunks(1 << 16) {
-                out.high_starts.push(out.lows.len());
-                let mut offset_v = _mm512_setzero_si512();
-                let mut chunk_i = 0;
-                let stop_chunk_i = chunk_64k.len() / 64;
-                while chunk_i < stop_chunk_i {
-                    let mut write_i = 0;
-                    out.lows.reserve(256);
-                    let out_arr = out.lows.spare_capacity_mut().get_unchecked_mut(..256);
-                    while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
-                        let v = _mm512_loadu_si512(chunk_64k.as_ptr().add(chunk_i * 64).cast());
-                        let mask = _mm512_cmpeq_epi8_mask(v, nl_v);
-                        let num_lines = mask.count_ones();
-                        let idxs = _mm512_maskz_compress_epi8(mask, idx_v);
-                        // first half
-                        let low_idxs = _mm512_cvtepu8_epi16(_mm512_castsi512_si256(idxs));
-                        let low_idxs = _mm512_add_epi16(low_idxs, offset_v);
-                        _mm512_storeu_si512(out_arr.as_mut_ptr().add(write_i).cast(), low_idxs);
-                        // second half
-                        if num_lines > 32 {
-                            let high_idxs =
-                                _mm512_cvtepu8_epi16(_mm512_extracti64x4_epi64::<1>(idxs));
-                            let high_idxs = _mm512_add_epi16(high_idxs, offset_v);
-                            // if there are any results in high_idxs, then low must have been full, so
-                            // we can unconditionally write 64 bytes ahead of the previous addr
-                            _mm512_storeu_si512(
-                                out_arr.as_mut_ptr().add(write_i).byte_add(64).cast(),
-                                high_idxs,
-                            );
-                        }
-                        offset_v = _mm512_add_epi16(offset_v, i16_64_v);
-                        write_i += num_lines as usize;
-                        chunk_i += 1;
-                    }
-                    out.lows.set_len(out.lows.len() + write_i);
-                }
-            }
-            tail(64, input, out);
-        }
+/// Runs `split` on each segment of `input`, keeping only the last one's lines in `out`. Returns
+/// how many lines it found in all of them.
+fn split_segments<'input>(
+    split: SliceSplitFn,
+    input: &'input str,
+    segments: &[std::ops::Range<usize>],
+    out: &mut Vec<&'input str>,
+) -> usize {
+    let mut lines = 0;
+    for segment in segments {
+        out.clear();
+        split(&input[segment.clone()], out);
+        lines += out.len();
     }
+    lines
 }
 
-fn reset_vector<'b, T: ?Sized>(mut vec: Vec<&T>) -> Vec<&'b T> {
-    vec.clear();
-    let cap = vec.capacity();
-    let ptr = vec.as_mut_ptr();
-    std::mem::forget(vec);
-    unsafe { Vec::from_raw_parts(ptr.cast(), 0, cap) }
-}
+/// The shortest a `--timeout` limit gets, so a stage `std` splits in a few milliseconds doesn't
+/// time out a kernel over a page fault burst
+const TIMEOUT_FLOOR: Duration = Duration::from_millis(10);
+/// A run still going after this many times its limit, and at least [`HANG_FLOOR`], is taken as
+/// hung, see `watchdog`
+const HANG_LIMITS: u32 = 10;
+const HANG_FLOOR: Duration = Duration::from_secs(10);
 
-/// M: min bytes per line, N: max bytes per line
-fn prep_vec_range<const M: usize, const N: usize>(vec: &mut Vec<u8>) -> usize {
-    use std::collections::HashSet; // Used to shuffle a sequence of ints
-    assert!(M <= N);
-    vec.fill(b'a');
-    let mut idx = 0;
-    (0..vec.len().min(256 * 1024 * 1024) * 2 / (N + M))
-        .collect::<HashSet<usize>>()
-        .iter()
-        .copied()
-        .map(|i| M + (i % (N - M + 1)))
-        .for_each(|i| {
-            idx += i;
-            vec[idx] = b'\n';
-        });
-    vec.len().min(256 * 1024 * 1024)
-}
+/// Inputs in the small inputs table, and their largest size, just under a 64-byte chunk
+const SMALL_INPUTS: usize = 2_000_000;
+const SMALL_INPUT_MAX_LEN: usize = 63;
+/// Size of the first call input, roughly one request's worth
+const FIRST_CALL_LINES: usize = 10_000;
+const FIRST_CALL_WARM_RUNS: usize = 5;
 
 type SliceSplitFn = for<'a, 'b> fn(&'a str, &'b mut Vec<&'a str>);
 type CompressSplitFn = unsafe fn(&str, &mut compressed::LineIndex);
-type FeatCheckFn = fn() -> bool;
+type FlatSplitFn = unsafe fn(&str, &mut Vec<u32>);
+type RangesSplitFn = unsafe fn(&str, &mut Vec<std::ops::Range<u32>>);
+type ClassifyFn = unsafe fn(&[u8]) -> [usize; 4];
+#[cfg(feature = "grep")]
+type GrepFn = fn(&grep::Literal, &str) -> std::io::Result<GrepCounts>;
+/// Matching lines, and their total length
+#[cfg(feature = "grep")]
+type GrepCounts = (u64, usize);
+/// Matches the lines of 30 bytes or more, since the inputs are all `a`s
+#[cfg(feature = "grep")]
+const GREP_NEEDLE: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+/// Touches some of the lines (getting scratch space for them), returns their total length
+type LazyFn = for<'a> fn(&'a str, &mut Vec<&'a str>) -> usize;
+/// Rows of the LineIndex conversions table
+const CONVERSIONS: [&str; 3] = ["split", "to_slices", "from_slices"];
+/// Rows of the line ranges table before its kernels: `slice::split` and `compressed::build`, the
+/// outputs the ranges sit between, for scale
+const RANGE_SCALE: [&str; 2] = ["slices", "LineIndex"];
+/// Rows of the arena output table: `slice::split` into a reused and a new `Vec`, and
+/// `arena::split_in` into a reset and a new `arena::Bump`
+const ARENA_CASES: [&str; 4] = ["Vec", "fresh Vec", "arena", "fresh arena"];
+/// The prefix of the prefixed lines table. The generated lines are all `a`s, so it keeps the lines
+/// of 4 bytes or more, and is compared entirely in registers.
+const BENCH_PREFIX: &str = "aaaa";
+/// Rows of the random line access table
+const RANDOM_ACCESS: [&str; 3] = ["in order", "shuffled", "shuffled, prefetch"];
+/// Rows of the adaptive dispatch table, followed by the sampling pass on its own
+const ADAPTIVE_CASES: [(&str, SliceSplitFn); 3] = [
+    ("dispatch", slice::split),
+    ("sparse kernel", |input, out| {
+        dispatch::global().split_sparse(input, out)
+    }),
+    ("adaptive", slice::split_adaptive),
+];
+/// Ways to find the line around a byte offset without an index
+const SPAN_CASES: [(&str, SpanFn); 2] = [
+    ("std", span::std_line_range),
+    ("simd", span::find_line_range),
+];
+/// Threads reading a [`shared::SharedLines`] at once in the concurrent lookups table, each taking
+/// its share of the shuffled lines
+const CONCURRENT_READERS: [(&str, usize); 4] = [
+    ("1 reader", 1),
+    ("2 readers", 2),
+    ("4 readers", 4),
+    ("16 readers", 16),
+];
+/// Rows of the newline set table, each LineIndex row followed by the roaring one it's compared to
+#[cfg(feature = "roaring")]
+const NEWLINE_SET_ROWS: [&str; 8] = [
+    "LineIndex build (MB/s)",
+    "roaring build (MB/s)",
+    "LineIndex line_of (M/s)",
+    "roaring line_of (M/s)",
+    "LineIndex is_newline (M/s)",
+    "roaring is_newline (M/s)",
+    "LineIndex memory (B/line)",
+    "roaring memory (B/line)",
+];
+/// Random offsets looked up per stage by the newline set table
+#[cfg(feature = "roaring")]
+const NEWLINE_SET_QUERIES: usize = 1_000_000;
+/// Lines the sampling consumers of the lazy lines table look at, spread evenly over the input
+const LAZY_SAMPLES: usize = 1000;
+type ParagraphFn = for<'a> unsafe fn(&'a str, &mut Vec<&'a str>);
+/// Gets scratch space for the lines, which only the unfused cases use
+type LineStatsFn = for<'a> unsafe fn(&'a str, &mut Vec<&'a str>) -> line_stats::LineLengthStats;
+/// Features a bench case needs, see [`has_feature`]
+/// The range of the line around a byte offset, see [`span`]
+type SpanFn = fn(&str, usize) -> std::ops::Range<usize>;
 
-fn main() {
-    use std::hint::black_box;
-    use std::time::Instant;
-
-    let benchmark_stages: &[(&str, fn(&mut Vec<u8>) -> usize)] = &[
-        ("single line", |vec| vec.len()),
-        ("0-1", prep_vec_range::<0, 1>),
-        ("0-2", prep_vec_range::<0, 2>),
-        ("1-20", prep_vec_range::<1, 20>),
-        ("5-20", prep_vec_range::<5, 20>),
-        ("10-30", prep_vec_range::<10, 30>),
-        ("0-40", prep_vec_range::<0, 40>),
-        ("0-80", prep_vec_range::<0, 80>),
-        ("40-120", prep_vec_range::<40, 120>),
-        ("0-0", |vec| {
-            vec.fill(b'\n');
-            // Slices takes 16GB w/ 1 billion
-            vec.len().min(64 * 1024 * 1024)
-        }),
-    ];
-    let slice_bench_cases: &[(&str, FeatCheckFn, SliceSplitFn)] = &[
-        ("std_reuse", || true, slice::std_reuse),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2", || true, slice::x86_64::sse2),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2_unsafe", || true, slice::x86_64::sse2_unsafe),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2_unroll", || true, slice::x86_64::sse2_unroll),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2_unrollx4", || true, slice::x86_64::sse2_unrollx4),
-        #[cfg(target_arch = "x86_64")]
-        ("avx2", slice::x86_64::can_run_avx2, |a, b| unsafe {
-            slice::x86_64::avx2(a, b)
-        }),
-        #[cfg(target_arch = "x86_64")]
-        ("avx2_unsafe", slice::x86_64::can_run_avx2, |a, b| unsafe {
-            slice::x86_64::avx2_unsafe(a, b)
-        }),
-        #[cfg(target_arch = "x86_64")]
-        ("avx2_unroll", slice::x86_64::can_run_avx2, |a, b| unsafe {
-            slice::x86_64::avx2_unroll(a, b)
-        }),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx2_unrollx2",
-            slice::x86_64::can_run_avx2,
-            |a, b| unsafe { slice::x86_64::avx2_unrollx2(a, b) },
-        ),
-    ];
-    let slice_bench_cases = &slice_bench_cases.iter().filter(|i| i.1()).collect::<Vec<_>>();
-    let compressed_bench_cases: &[(&str, FeatCheckFn, CompressSplitFn)] = &[
-        ("iter", || true, compressed::iter),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2", || true, compressed::x86_64::sse2),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2 unroll", || true, compressed::x86_64::sse2_unroll),
-        #[cfg(target_arch = "x86_64")]
-        ("sse2 unrollx4", || true, compressed::x86_64::sse2_unrollx4),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "sse4 intrlv",
-            compressed::x86_64::can_run_sse42,
-            compressed::x86_64::sse42_unrollx4_interleavex2,
-        ),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx2 unroll",
-            compressed::x86_64::can_run_avx2,
-            compressed::x86_64::avx2_unroll,
-        ),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx2 unrollx2",
-            compressed::x86_64::can_run_avx2,
-            compressed::x86_64::avx2_unrollx2,
-        ),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx2 intrlv",
-            compressed::x86_64::can_run_avx2,
-            compressed::x86_64::avx2_unrollx2_interleavex2,
-        ),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx2 lut",
-            compressed::x86_64::can_run_avx2,
-            compressed::x86_64::avx2_lut,
-        ),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx2 big lut",
-            compressed::x86_64::can_run_avx2,
-            compressed::x86_64::avx2_big_lut,
-        ),
-        #[cfg(target_arch = "x86_64")]
-        (
-            "avx512",
-            compressed::x86_64::can_run_avx512_compress,
-            compressed::x86_64::avx512_compress,
-        ),
-    ];
-    let compressed_bench_cases = &compressed_bench_cases.iter().filter(|i| i.1()).collect::<Vec<_>>();
+type Features = &'static [&'static str];
+/// Fills the vec with the given number of lines (unless the stage has a fixed shape)
+type PrepFn = fn(&mut Vec<u8>, usize, &mut Rng) -> Prepared;
+/// Appends a record of a substring or NUL stage, see [`SUBSTRING_STAGES`]
+type RecordFn = fn(&mut Vec<u8>, &mut Rng);
+type ByteSplitFn = for<'input> fn(&'input [u8], &mut Vec<&'input [u8]>);
+type SubstringFn = for<'input> fn(&'input str, &str, &mut Vec<&'input str>);
 
-    // this can be done with Vecs, but this is fine
-    let mut slice_thrpts = Vec::new();
-    let mut compressed_thrpts = Vec::new();
+#[cfg(target_arch = "x86_64")]
+const AVX2: Features = &["avx2", "bmi1", "popcnt"];
+#[cfg(target_arch = "x86_64")]
+const AVX2_BMI2: Features = &["avx2", "bmi1", "bmi2", "popcnt"];
+#[cfg(target_arch = "x86_64")]
+const SSE42: Features = &["popcnt"];
+#[cfg(target_arch = "x86_64")]
+const AVX512_COMPRESS: Features = &["popcnt", "avx512f", "avx512bw", "avx512vbmi2"];
+#[cfg(target_arch = "x86_64")]
+const AVX512_COMPRESSD: Features = &["popcnt", "avx512f", "avx512bw"];
+#[cfg(target_arch = "x86_64")]
+const AVX512: Features = &["avx512f", "avx512bw", "bmi1", "popcnt"];
+#[cfg(all(feature = "nightly", target_arch = "aarch64"))]
+const SVE: Features = &["sve"];
+#[cfg(target_arch = "loongarch64")]
+const LSX: Features = &["lsx"];
+#[cfg(target_arch = "loongarch64")]
+const LASX: Features = &["lasx"];
 
-    let mut b = vec![b'a'; 1024 * 1024 * 1024];
+/// The slice cases that follow `str::lines`, dropping the `\r` of a `\r\n`. The others keep it,
+/// like `str::split_terminator('\n')`, and the `crlf` table's cases all drop it. The generated
+/// stages have no `\r`, so both give the same lines there; the regression pack checks each case
+/// against its own.
+const STR_LINES_CASES: &[&str] = &["std_reuse"];
 
-    // pre-fill the vec (beyond just reserving) so that the first fn doesn't pay for all the page
-    // misses (some OSs may give CoW zero pages for `Vec::with_capacity(...)` )
-    let mut pool_out_slice_buf = black_box(vec![""; 64 * 1024 * 1024]);
-    let mut out_compressed_buf = compressed::LineIndex {
-        lows: Vec::with_capacity(64 * 1024 * 1024),
-        high_starts: Vec::with_capacity(16),
-    };
-    let mut test_compressed_buf = compressed::LineIndex {
-        lows: Vec::new(),
-        high_starts: Vec::new(),
-    };
+// the tables with cases that need CPU features are consts, so that `features` can list them
+const SLICE_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("std_reuse", &[], slice::std_reuse),
+    ("dispatch", &[], slice::split),
+    #[cfg(feature = "memchr")]
+    ("memchr", &[], slice::memchr),
+    ("swar", &[], slice::swar),
+    #[cfg(target_arch = "aarch64")]
+    ("neon", &[], slice::aarch64::neon),
+    #[cfg(target_arch = "aarch64")]
+    ("neon_unroll", &[], slice::aarch64::neon_unroll),
+    #[cfg(target_arch = "aarch64")]
+    ("neon_unrollx4", &[], slice::aarch64::neon_unrollx4),
+    #[cfg(all(feature = "nightly", target_arch = "aarch64"))]
+    ("sve", SVE, |a, b| unsafe { slice::aarch64::sve(a, b) }),
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    ("simd128", &[], slice::wasm32::simd128),
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    ("simd128_unroll", &[], slice::wasm32::simd128_unroll),
+    #[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+    ("rvv", &[], slice::riscv64::rvv),
+    #[cfg(target_arch = "loongarch64")]
+    ("lsx", LSX, |a, b| unsafe { slice::loongarch64::lsx(a, b) }),
+    #[cfg(target_arch = "loongarch64")]
+    ("lasx", LASX, |a, b| unsafe {
+        slice::loongarch64::lasx(a, b)
+    }),
+    #[cfg(feature = "nightly")]
+    ("portable", &[], slice::portable::simd),
+    #[cfg(all(feature = "nightly", target_arch = "x86_64"))]
+    ("portable_avx2", AVX2, |a, b| unsafe {
+        slice::portable::simd_avx2(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2", &[], slice::x86_64::sse2),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2_unsafe", &[], slice::x86_64::sse2_unsafe),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2_unroll", &[], slice::x86_64::sse2_unroll),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2_unrollx4", &[], slice::x86_64::sse2_unrollx4),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2", AVX2, |a, b| unsafe { slice::x86_64::avx2(a, b) }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2_unsafe", AVX2, |a, b| unsafe {
+        slice::x86_64::avx2_unsafe(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2_unroll", AVX2, |a, b| unsafe {
+        slice::x86_64::avx2_unroll(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2_unrollx2", AVX2, |a, b| unsafe {
+        slice::x86_64::avx2_unrollx2(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2_pipelined", AVX2, |a, b| unsafe {
+        slice::x86_64::avx2_pipelined(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2_lut", AVX2, |a, b| unsafe {
+        slice::x86_64::avx2_lut(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2_bmi2", AVX2_BMI2, |a, b| unsafe {
+        slice::x86_64::avx2_bmi2(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx512", AVX512, |a, b| unsafe {
+        slice::x86_64::avx512(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx512_unroll", AVX512, |a, b| unsafe {
+        slice::x86_64::avx512_unroll(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx512_unrollx2", AVX512, |a, b| unsafe {
+        slice::x86_64::avx512_unrollx2(a, b)
+    }),
+    // avx2 where it can run
+    ("uninit", &[], uninit::split_into_vec),
+    // dispatch unless every line has the same length
+    ("fixed stride", &[], stride::split),
+    ("SimdLines", &[], simd_lines::split),
+    // the generic kernels of `sink`, with `Vec<&str>` as the sink
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| sink::x86_64::sse2(a, b)),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2(a, b)
+    }),
+];
+
+const COMPRESSED_BENCH_CASES: &[(&str, Features, CompressSplitFn)] = &[
+    ("iter", &[], compressed::iter),
+    #[cfg(feature = "memchr")]
+    ("memchr", &[], compressed::memchr),
+    ("swar", &[], compressed::swar),
+    #[cfg(target_arch = "aarch64")]
+    ("neon", &[], compressed::aarch64::neon),
+    #[cfg(target_arch = "aarch64")]
+    ("neon unrollx4", &[], compressed::aarch64::neon_unrollx4),
+    #[cfg(all(feature = "nightly", target_arch = "aarch64"))]
+    ("sve", SVE, |a, b| unsafe { compressed::aarch64::sve(a, b) }),
+    #[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+    ("rvv", &[], compressed::riscv64::rvv),
+    #[cfg(target_arch = "loongarch64")]
+    ("lasx", LASX, |a, b| unsafe {
+        compressed::loongarch64::lasx(a, b)
+    }),
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    ("simd128", &[], compressed::wasm32::simd128),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2", &[], compressed::x86_64::sse2),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2 unroll", &[], compressed::x86_64::sse2_unroll),
+    #[cfg(target_arch = "x86_64")]
+    ("sse2 unrollx4", &[], compressed::x86_64::sse2_unrollx4),
+    #[cfg(target_arch = "x86_64")]
+    (
+        "sse4 intrlv",
+        SSE42,
+        compressed::x86_64::sse42_unrollx4_interleavex2,
+    ),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 unroll", AVX2, compressed::x86_64::avx2_unroll),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 unrollx2", AVX2, compressed::x86_64::avx2_unrollx2),
+    #[cfg(all(feature = "asm", target_arch = "x86_64"))]
+    ("avx2 unrollx2 asm", AVX2, asm::compressed_avx2_unrollx2),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 pipelined", AVX2, compressed::x86_64::avx2_pipelined),
+    #[cfg(target_arch = "x86_64")]
+    (
+        "avx2 intrlv",
+        AVX2,
+        compressed::x86_64::avx2_unrollx2_interleavex2,
+    ),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 lut", AVX2, compressed::x86_64::avx2_lut),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 big lut", AVX2, compressed::x86_64::avx2_big_lut),
+    #[cfg(target_arch = "x86_64")]
+    (
+        "avx512",
+        AVX512_COMPRESS,
+        compressed::x86_64::avx512_compress,
+    ),
+    #[cfg(target_arch = "x86_64")]
+    (
+        "avx512 words",
+        AVX512_COMPRESS,
+        compressed::x86_64::avx512_compress_words,
+    ),
+    #[cfg(target_arch = "x86_64")]
+    (
+        "avx512 cstore",
+        AVX512_COMPRESS,
+        compressed::x86_64::avx512_compressstore,
+    ),
+    #[cfg(target_arch = "x86_64")]
+    (
+        "avx512 hybrid",
+        AVX512_COMPRESS,
+        compressed::x86_64::avx512_hybrid,
+    ),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| sink::x86_64::sse2(a.as_bytes(), b)),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2(a.as_bytes(), b)
+    }),
+    #[cfg(feature = "gpu")]
+    ("gpu", &["gpu"], gpu::build),
+];
 
-    for (stage_label, prep_fn) in benchmark_stages {
-        println!("\n\t\t{stage_label}");
-        let mut cur_slice_thrpts = Vec::new();
-        let mut cur_compressed_thrpts = Vec::new();
+const FLAT_BENCH_CASES: &[(&str, Features, FlatSplitFn)] = &[
+    ("iter", &[], flat::iter),
+    // includes allocating the LineIndex, which a caller that only wants u32s would pay too
+    ("via LineIndex", &[], |input, out| {
+        let mut index = compressed::LineIndex::default();
+        compressed::build(input, &mut index);
+        flat::from_line_index(&index, out);
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2", AVX2, |input, out| unsafe {
+        flat::x86_64::avx2(input, out)
+    }),
+    #[cfg(all(feature = "asm", target_arch = "x86_64"))]
+    ("avx2 asm", AVX2, |input, out| unsafe {
+        asm::flat_avx2(input, out)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx512", AVX512_COMPRESSD, |input, out| unsafe {
+        flat::x86_64::avx512_compressd(input, out)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |input, out| unsafe {
+        sink::x86_64::avx2(input.as_bytes(), out)
+    }),
+];
 
-        let len = prep_fn(&mut b);
-        let input = std::str::from_utf8(&b[..len]).unwrap();
-        let mut out_slice_buf = pool_out_slice_buf;
+const RANGES_BENCH_CASES: &[(&str, Features, RangesSplitFn)] = &[
+    ("iter", &[], ranges::iter),
+    // avx2 where it can run
+    ("dispatch", &[], ranges::build),
+    // includes allocating the `Vec<&str>`, which a caller that only wants ranges would pay too
+    ("via slices", &[], |input, out| {
+        let mut lines = Vec::new();
+        slice::split(input, &mut lines);
+        ranges::from_slices(input, &lines, out);
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2", AVX2, |input, out| unsafe {
+        ranges::x86_64::avx2(input, out)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |input, out| {
+        sink::x86_64::sse2(input.as_bytes(), out)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |input, out| unsafe {
+        sink::x86_64::avx2(input.as_bytes(), out)
+    }),
+];
 
-        println!("\tslices");
-        {
-            let start = Instant::now();
-            black_box(slice::std(input));
-            let duration = start.elapsed().as_secs_f64();
-            let thrpt = len as f64 / duration / 1_000_000.;
-            println!("{fn_label:<13}: {thrpt:>8.0}", fn_label = "std");
-            cur_slice_thrpts.push(thrpt);
-        }
-        for (fn_label, feat_checker, fnc) in slice_bench_cases {
-            if !feat_checker() {
-                println!("skipping {fn_label} because of missing CPU features");
-                continue;
-            }
-            out_slice_buf.clear();
-            let start = Instant::now();
-            fnc(input, &mut out_slice_buf);
-            let duration = start.elapsed().as_secs_f64();
-            black_box(&mut out_slice_buf);
-            let thrpt = len as f64 / duration / 1_000_000.;
-            println!("{fn_label:<13}: {thrpt:>8.0}");
-            cur_slice_thrpts.push(thrpt);
-        }
-        // run first test case again to show that it's not sensitive to order (e.g. cache)
-        {
-            let start = Instant::now();
-            black_box(slice::std(input));
-            let duration = start.elapsed().as_secs_f64();
-            let thrpt = len as f64 / duration / 1_000_000.;
-            println!("{fn_label:<13}: {thrpt:>8.0}", fn_label = "std");
-            cur_slice_thrpts.push(thrpt);
-        }
+/// Counts `\n`, `\r`, `\0` and `\x1e` separately, see `classify`
+const CLASSIFY_BENCH_CASES: &[(&str, Features, ClassifyFn)] = &[
+    ("scalar", &[], classify::count_scalar),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 cmpeq", &["avx2"], classify::x86_64::count_avx2_cmpeq),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2 lut", &["avx2"], classify::x86_64::count_avx2_lut),
+];
+
+const LINE_STATS_BENCH_CASES: &[(&str, Features, LineStatsFn)] = &[
+    ("split + pass", &[], |input, lines| {
+        slice::split(input, lines);
+        line_stats::from_lines(lines)
+    }),
+    ("fused scalar", &[], |input, _| line_stats::scalar(input)),
+    #[cfg(target_arch = "x86_64")]
+    ("fused avx2", AVX2, |input, _| unsafe {
+        line_stats::x86_64::avx2(input)
+    }),
+];
 
-        println!("\tcompressed");
-        test_compressed_buf.lows.clear();
-        test_compressed_buf.high_starts.clear();
-        compressed::iter(input, &mut test_compressed_buf);
-        for (fn_label, feat_checker, fnc) in compressed_bench_cases {
-            if !feat_checker() {
-                println!("skipping {fn_label} because of missing CPU features");
-                continue;
+const PARAGRAPH_BENCH_CASES: &[(&str, Features, ParagraphFn)] = &[
+    ("scalar", &[], paragraph::scalar),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2", AVX2, paragraph::x86_64::avx2),
+    // the index is built in the call, for a caller that doesn't keep one, see `paragraph::from_newlines`
+    ("flat index", &[], |input, out| {
+        let mut offsets = Vec::new();
+        flat::build(input, &mut offsets);
+        paragraph::from_newlines(input, offsets.iter().map(|&n| n as usize), out)
+    }),
+    ("LineIndex", &[], |input, out| {
+        let mut index = compressed::LineIndex::default();
+        compressed::build(input, &mut index);
+        paragraph::from_newlines(input, index.newlines(), out)
+    }),
+];
+
+const CRLF_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("std", &[], |input, out| out.extend(input.lines())),
+    ("split + trim", &[], |input, out| {
+        let first = out.len();
+        slice::split(input, out);
+        for line in &mut out[first..] {
+            let end = line.as_ptr() as usize - input.as_ptr() as usize + line.len();
+            // a last line without a newline keeps its `\r`, as in `str::lines`
+            if end < input.len() {
+                *line = line.strip_suffix('\r').unwrap_or(line);
             }
-            out_compressed_buf.lows.clear();
-            out_compressed_buf.high_starts.clear();
-            let start = Instant::now();
-            unsafe { fnc(input, &mut out_compressed_buf) };
-            let duration = start.elapsed().as_secs_f64();
-            black_box(&mut out_compressed_buf);
-            let thrpt = len as f64 / duration / 1_000_000.;
-            println!("{fn_label:<13}: {thrpt:>8.0}");
-            cur_compressed_thrpts.push(thrpt);
-            assert!(
-                out_compressed_buf == test_compressed_buf,
-                "(compressed) {fn_label} failed during {stage_label}"
-            );
         }
+    }),
+    // avx2 where it can run
+    ("dispatch", &[], slice::split_crlf),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| {
+        sink::x86_64::sse2(a, &mut sink::Crlf(b))
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2(a, &mut sink::Crlf(b))
+    }),
+];
 
-        pool_out_slice_buf = reset_vector(out_slice_buf);
-
-        slice_thrpts.push(cur_slice_thrpts);
-        compressed_thrpts.push(cur_compressed_thrpts);
-    }
+/// Fields between tabs rather than lines, see `slice::split_on`
+const DELIM_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("std", &[], |input, out| {
+        out.extend(input.split_terminator('\t'))
+    }),
+    // avx2 where it can run
+    ("dispatch", &[], slice::split_on::<b'\t'>),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| {
+        sink::x86_64::sse2_on::<b'\t', _>(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2_on::<b'\t', _>(a, b)
+    }),
+];
 
-    // now, print the markdown tables
+/// Records ended by any of `\n`, `\r` and `\0`, with `\r\n` as one, see `sink::Delims`
+const RECORD_DELIMS: sink::Delims = sink::Delims::new(b"\n\r\0").with_crlf();
 
-    // Headers
-    println!("\n## Slice\n");
-    let print_table_header = || {
-        print!("| algo |");
-        for (stage_label, ..) in benchmark_stages {
-            print!(" {stage_label} |");
+const DELIM_SET_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("std", &[], |input, out| {
+        let mut rest = input;
+        while let Some(end) = rest.find(['\n', '\r', '\0']) {
+            out.push(&rest[..end]);
+            let len = if rest[end..].starts_with("\r\n") {
+                2
+            } else {
+                1
+            };
+            rest = &rest[end + len..];
         }
-        println!();
-        print!("| :-- |");
-        for _ in benchmark_stages {
-            print!(" --: |");
+        if !rest.is_empty() {
+            out.push(rest);
         }
-        println!();
-    };
-    print_table_header();
-    // | Algo | thrpts... |
-    print!("| std |");
-    for thrpt in slice_thrpts.iter().map(|vec| vec[0]) {
-        print!(" {thrpt:.0} |");
-    }
-    println!();
-    for (idx, (algo_name, ..)) in slice_bench_cases.iter().enumerate() {
-        print!("| {algo_name} |");
-        for thrpt in slice_thrpts.iter().map(|vec| vec[idx + 1]) {
-            print!(" {thrpt:.0} |")
+    }),
+    ("scalar", &[], |a, b| {
+        sink::scalar_any(a, &RECORD_DELIMS, &mut sink::Crlf(b))
+    }),
+    // avx2 where it can run
+    ("dispatch", &[], |a, b| {
+        sink::split_any(a, &RECORD_DELIMS, &mut sink::Crlf(b))
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| {
+        sink::x86_64::sse2_any(a, &RECORD_DELIMS, &mut sink::Crlf(b))
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2_any(a, &RECORD_DELIMS, &mut sink::Crlf(b))
+    }),
+];
+
+const SUBSTRING_BENCH_CASES: &[(&str, Features, SubstringFn)] = &[
+    ("std", &[], substring::scalar),
+    ("split + join", &[], substring::split_then_join),
+    // avx2 where it can run
+    ("dispatch", &[], substring::split),
+    #[cfg(target_arch = "x86_64")]
+    ("avx2", AVX2, |a, b, c| unsafe {
+        substring::x86_64::avx2(a, b, c)
+    }),
+];
+
+/// Records ended by `\0`, split as bytes, see `slice::split_nul`
+const NUL_BENCH_CASES: &[(&str, Features, ByteSplitFn)] = &[
+    ("std", &[], |input, out| {
+        out.extend(input.split(|&b| b == b'\0'));
+        // like `split_terminator`, no empty record after the last `\0`
+        if out.last() == Some(&&b""[..]) {
+            out.pop();
         }
-        println!();
-    }
+    }),
+    // avx2 where it can run
+    ("dispatch", &[], slice::split_nul),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| {
+        sink::x86_64::sse2_on::<b'\0', _>(a, b)
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2_on::<b'\0', _>(a, b)
+    }),
+];
 
-    println!("\n## Compressed format\n");
-    print_table_header();
-    for (idx, (algo_name, ..)) in compressed_bench_cases.iter().enumerate() {
-        print!("| {algo_name} |");
-        for thrpt in compressed_thrpts.iter().map(|vec| vec[idx]) {
-            print!(" {thrpt:.0} |")
+const INCLUSIVE_BENCH_CASES: &[(&str, Features, SliceSplitFn)] = &[
+    ("std", &[], |input, out| {
+        out.extend(input.split_inclusive('\n'))
+    }),
+    ("split + widen", &[], |input, out| {
+        let first = out.len();
+        slice::split(input, out);
+        for line in &mut out[first..] {
+            let end = line.as_ptr() as usize - input.as_ptr() as usize + line.len();
+            if end < input.len() {
+                // the byte after a line is its newline, so this stays on char boundaries
+                *line = unsafe { input.get_unchecked(end - line.len()..end + 1) };
+            }
         }
-        println!();
+    }),
+    // avx2 where it can run
+    ("dispatch", &[], slice::split_inclusive),
+    #[cfg(target_arch = "x86_64")]
+    ("sink sse2", &[], |a, b| {
+        sink::x86_64::sse2(a, &mut sink::Inclusive(b))
+    }),
+    #[cfg(target_arch = "x86_64")]
+    ("sink avx2", AVX2, |a, b| unsafe {
+        sink::x86_64::avx2(a, &mut sink::Inclusive(b))
+    }),
+];
+
+/// `name` is an `is_x86_feature_detected!` name, or "gpu" for a usable GPU adapter
+fn has_feature(name: &str) -> bool {
+    match name {
+        #[cfg(feature = "gpu")]
+        "gpu" => gpu::can_run(),
+        #[cfg(target_arch = "x86_64")]
+        "avx2" => is_x86_feature_detected!("avx2"),
+        #[cfg(target_arch = "x86_64")]
+        "bmi1" => is_x86_feature_detected!("bmi1"),
+        #[cfg(target_arch = "x86_64")]
+        "bmi2" => is_x86_feature_detected!("bmi2"),
+        #[cfg(target_arch = "x86_64")]
+        "popcnt" => is_x86_feature_detected!("popcnt"),
+        #[cfg(target_arch = "x86_64")]
+        "avx512f" => is_x86_feature_detected!("avx512f"),
+        #[cfg(target_arch = "x86_64")]
+        "avx512bw" => is_x86_feature_detected!("avx512bw"),
+        #[cfg(target_arch = "x86_64")]
+        "avx512vbmi2" => is_x86_feature_detected!("avx512vbmi2"),
+        #[cfg(target_arch = "aarch64")]
+        "sve" => std::arch::is_aarch64_feature_detected!("sve"),
+        #[cfg(target_arch = "loongarch64")]
+        "lsx" => std::arch::is_loongarch_feature_detected!("lsx"),
+        #[cfg(target_arch = "loongarch64")]
+        "lasx" => std::arch::is_loongarch_feature_detected!("lasx"),
+        _ => unreachable!("unknown feature `{name}`"),
+    }
+}
+
+fn missing_features(features: Features) -> Vec<&'static str> {
+    features
+        .iter()
+        .copied()
+        .filter(|f| !has_feature(f))
+        .collect()
+}
+
+fn main() {
+    let mut args = cli::Args::from_env();
+    let result = match args.subcommand().as_deref() {
+        None => bench::run(args),
+        Some("allocators") => cmd::allocators::run(args),
+        Some("allocators-run") => cmd::allocators::run_child(args),
+        Some("batch") => cmd::batch::run(args),
+        Some("features") => cmd::features::run(args),
+        Some("fuzz-splice") => cmd::fuzz_splice::run(args),
+        Some("fields") => cmd::fields::run(args),
+        Some("history") => cmd::history::run(args),
+        Some("index") => cmd::index::run(args),
+        Some("jsonl") => cmd::jsonl::run(args),
+        Some("langs") => cmd::langs::run(args),
+        Some("matrix") => cmd::matrix::run(args),
+        Some("matrix-run") => cmd::matrix::run_child(args),
+        Some("nl") => cmd::nl::run(args),
+        Some("normalize") => cmd::normalize::run(args),
+        Some("regress") => cmd::regress::run(args),
+        Some("report") => cmd::report::run(args),
+        Some("shuffle") => cmd::shuffle::run(args),
+        Some("tail") => cmd::tail::run(args),
+        Some("uniq") => cmd::uniq::run(args),
+        Some(other) => Err(format!("unknown subcommand `{other}`").into()),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        std::process::exit(1);
     }
 }
 
@@ -1138,6 +1010,93 @@ mod tests {
         ),
     ];
 
+    #[test]
+    fn test_prep_vec_range() {
+        let lines = crate::PREP_CHUNK_LINES * 2 + 5;
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        let prepared = crate::prep_vec_range::<3, 9>(&mut first, lines, &mut crate::Rng::new(1));
+        assert_eq!(prepared.len, first.len());
+        assert_eq!(prepared.newlines, lines);
+        let line_lens: Vec<usize> = std(std::str::from_utf8(&first).unwrap())
+            .iter()
+            .map(|line| line.len())
+            .collect();
+        assert_eq!(line_lens.len(), lines);
+        assert!(line_lens.iter().all(|len| (3..=9).contains(len)));
+        assert!(first.ends_with(b"\n"));
+        crate::prep_vec_range::<3, 9>(&mut second, lines, &mut crate::Rng::new(1));
+        assert_eq!(first, second);
+        // the chunks don't repeat each other
+        let chunk = crate::PREP_CHUNK_LINES;
+        assert_ne!(line_lens[..100], line_lens[chunk..chunk + 100]);
+    }
+
+    #[test]
+    fn test_prep_vec_mixed() {
+        let lines = crate::PREP_CHUNK_LINES + 5;
+        let mut vec = Vec::new();
+        let prepared = crate::prep_vec_mixed(&mut vec, lines, &mut crate::Rng::new(1));
+        assert_eq!(prepared.len, vec.len());
+        let line_lens: Vec<usize> = std(std::str::from_utf8(&vec).unwrap())
+            .iter()
+            .map(|line| line.len())
+            .collect();
+        assert_eq!(line_lens.len(), lines);
+        assert!(line_lens.iter().all(|len| matches!(len, 0..=2 | 40..=120)));
+        // the runs are short, and both kinds take a good share of the input
+        assert!(
+            line_lens
+                .windows(2)
+                .filter(|w| (w[0] > 2) != (w[1] > 2))
+                .count()
+                > 200
+        );
+        let sparse_bytes: usize = line_lens.iter().filter(|&&len| len > 2).sum();
+        assert!((0.4..0.9).contains(&(sparse_bytes as f64 / vec.len() as f64)));
+    }
+
+    #[test]
+    fn test_prep_small_inputs() {
+        let mut vec = Vec::new();
+        let pieces = crate::prep_small_inputs(&mut vec, 1000, &mut crate::Rng::new(1));
+        assert_eq!(pieces.len(), 1000);
+        assert_eq!(pieces.last().unwrap().end, vec.len());
+        assert!(pieces.windows(2).all(|w| w[0].end == w[1].start));
+        assert!(pieces
+            .iter()
+            .all(|piece| (1..=crate::SMALL_INPUT_MAX_LEN).contains(&piece.len())));
+        assert!(vec.contains(&b'\n'));
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn test_segments() {
+        let input = "a\nb\n\nc\nd";
+        assert_eq!(crate::segments(input, None), [0..input.len()]);
+        assert_eq!(crate::segments(input, Some(2)), [0..4, 4..7, 7..8]);
+        assert_eq!(crate::segments(input, Some(4)), [0..7, 7..8]);
+        assert_eq!(crate::segments(input, Some(5)), [0..8]);
+        // a cut at the end doesn't make an empty segment
+        assert_eq!(crate::segments("a\nb\n", Some(1)), [0..2, 2..4]);
+        assert_eq!(crate::segments("", Some(1)), [0..0]);
+
+        let mut out = Vec::new();
+        let found = crate::split_segments(split, input, &crate::segments(input, Some(2)), &mut out);
+        assert_eq!(found, 5);
+        assert_eq!(out, ["d"]);
+    }
+
+    /// Checks a kernel on [`TEST_CASES`], each ending right before a guard page
+    fn check(split_fn: impl for<'a> Fn(&'a str, &mut Vec<&'a str>)) {
+        for (input, expected) in TEST_CASES {
+            crate::guard::with_guard_page(input, |input| {
+                let mut buf = Vec::new();
+                split_fn(input, &mut buf);
+                assert_eq!(expected, &buf, "input: `{input}`");
+            });
+        }
+    }
+
     #[test]
     fn test_std() {
         for (input, expected) in TEST_CASES {
@@ -1146,47 +1105,331 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_split() {
+        check(split);
+    }
+
+    #[test]
+    fn test_for_each_line() {
+        let long = TEST_CASES
+            .iter()
+            .map(|case| case.0)
+            .collect::<String>()
+            .repeat(5);
+        let cases = TEST_CASES.iter().map(|case| case.0).chain([long.as_str()]);
+        for input in cases {
+            crate::guard::with_guard_page(input, |input| {
+                let mut buf = Vec::new();
+                for_each_line(input, |line| buf.push(line));
+                assert_eq!(std(input), buf, "input: `{input}`");
+                #[cfg(target_arch = "x86_64")]
+                {
+                    buf.clear();
+                    x86_64::sse2_for_each_line(input, |line| buf.push(line));
+                    assert_eq!(std(input), buf, "sse2, input: `{input}`");
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_split_lines_within() {
+        let input = "ab\n\ncde\nfg";
+        let expected: &[(usize, &[&str], &str)] = &[
+            (0, &[], input),
+            (2, &[], input),
+            (3, &["ab"], "\ncde\nfg"),
+            (7, &["ab", ""], "cde\nfg"),
+            (8, &["ab", "", "cde"], "fg"),
+            (100, &["ab", "", "cde"], "fg"),
+        ];
+        for &(max_bytes, lines, rest) in expected {
+            assert_eq!(
+                split_lines_within(input, max_bytes),
+                (lines.to_vec(), rest),
+                "max_bytes: {max_bytes}"
+            );
+        }
+        assert_eq!(split_lines_within("", 10), (vec![], ""));
+    }
+
+    /// Checks a reverse kernel against the forward output, reversed, with and without a limit.
+    /// Like the forward kernels, it must keep a `\r` before a newline.
+    fn check_rev(rev_fn: impl for<'a> Fn(&'a str, usize, &mut Vec<&'a str>)) {
+        let long = TEST_CASES
+            .iter()
+            .map(|case| case.0)
+            .collect::<String>()
+            .repeat(5);
+        let crlf = "a\r\n\r\nbc\r\n".repeat(10);
+        let cases =
+            TEST_CASES
+                .iter()
+                .map(|case| case.0)
+                .chain([long.as_str(), crlf.as_str(), "a\r\nb\r"]);
+        for input in cases {
+            crate::guard::with_guard_page(input, |input| {
+                let expected = input.split_terminator('\n').collect::<Vec<_>>();
+                let mut buf = Vec::new();
+                for max_lines in [0, 1, 2, 7, usize::MAX] {
+                    buf.clear();
+                    rev_fn(input, max_lines, &mut buf);
+                    let expected = expected
+                        .iter()
+                        .rev()
+                        .take(max_lines)
+                        .copied()
+                        .collect::<Vec<_>>();
+                    assert_eq!(expected, buf, "input: `{input}`, max_lines: {max_lines}");
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_split_last() {
+        check_rev(split_last);
+    }
+
     #[test]
     fn test_std_reuse() {
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            std_reuse(input, &mut buf);
-            assert_eq!(expected, &buf, "input: `{input}`");
+        check(std_reuse);
+    }
+
+    #[test]
+    fn test_swar() {
+        check(swar);
+        check(swar_with::<false>);
+        check(swar_with::<true>);
+        // non-newline bytes on both sides of a newline's, each differing from it in one bit
+        let near = "\u{0}\u{8}\u{b}\u{e}\n\u{1a}\n*\u{4a}\u{2}".repeat(7);
+        for len in 0..=near.len() {
+            for kernel in [swar, swar_with::<false>, swar_with::<true>] {
+                let mut buf = Vec::new();
+                kernel(&near[..len], &mut buf);
+                assert_eq!(std(&near[..len]), buf, "len {len}");
+            }
+            let mut expected = crate::compressed::LineIndex::default();
+            crate::compressed::iter(&near[..len], &mut expected);
+            for kernel in [
+                crate::compressed::swar,
+                crate::compressed::swar_with::<false>,
+                crate::compressed::swar_with::<true>,
+            ] {
+                let mut index = crate::compressed::LineIndex::default();
+                kernel(&near[..len], &mut index);
+                assert!(index == expected, "len {len}");
+            }
+        }
+    }
+
+    #[cfg(feature = "memchr")]
+    #[test]
+    fn test_memchr() {
+        check(memchr);
+        // past a 64 KiB block, ending with and without a newline
+        let long = "ab\n\n\ncdefg\n".repeat(10_000);
+        for len in [
+            0,
+            1,
+            3,
+            65536 - 1,
+            65536,
+            65536 + 1,
+            long.len() - 1,
+            long.len(),
+        ] {
+            let mut buf = Vec::new();
+            memchr(&long[..len], &mut buf);
+            assert_eq!(std(&long[..len]), buf, "len {len}");
+            let (mut expected, mut index) =
+                (crate::compressed::LineIndex::default(), Default::default());
+            crate::compressed::iter(&long[..len], &mut expected);
+            crate::compressed::memchr(&long[..len], &mut index);
+            assert!(index == expected, "len {len}");
+        }
+    }
+
+    #[test]
+    fn test_swar_byte_order() {
+        // newlines at bytes 1, 2 and 7, which both orders find in that order
+        let chunk = b"a\n\nbcde\n";
+        let found = |mut mask: u64, pop_first: fn(&mut u64) -> usize| {
+            let mut found = Vec::new();
+            while mask != 0 {
+                found.push(pop_first(&mut mask));
+            }
+            found
+        };
+        let little = swar_newlines(swar_load::<false>(chunk));
+        let big = swar_newlines(swar_load::<true>(chunk));
+        assert_eq!(big, little.swap_bytes());
+        assert_eq!(found(little, swar_pop_first::<false>), [1, 2, 7]);
+        assert_eq!(found(big, swar_pop_first::<true>), [1, 2, 7]);
+        // the other order's scan mirrors the indices
+        assert_eq!(found(big, swar_pop_first::<false>), [0, 5, 6]);
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn test_neon() {
+        check(aarch64::neon);
+        check(aarch64::neon_unroll);
+        check(aarch64::neon_unrollx4);
+        // every tail length, over more than one batch of 256 lines
+        let long = "ab\n\n\ncdefg\n".repeat(40);
+        for len in 0..=long.len() {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                for kernel in [aarch64::neon_unroll, aarch64::neon_unrollx4] {
+                    let mut buf = Vec::new();
+                    kernel(input, &mut buf);
+                    assert_eq!(std(input), buf, "len {len}");
+                }
+            });
+        }
+        let input = "ab\n\n\n0123456789\n".repeat(10_000) + "tail";
+        let expected = {
+            let mut index = crate::compressed::LineIndex::default();
+            crate::compressed::iter(&input, &mut index);
+            index
+        };
+        for kernel in [
+            crate::compressed::aarch64::neon,
+            crate::compressed::aarch64::neon_unrollx4,
+        ] {
+            let mut index = crate::compressed::LineIndex::default();
+            kernel(&input, &mut index);
+            assert!(index == expected);
+        }
+    }
+
+    #[cfg(all(feature = "nightly", target_arch = "aarch64"))]
+    #[test]
+    fn test_sve() {
+        if !aarch64::can_run_sve() {
+            return;
+        }
+        check(|a, b| unsafe { aarch64::sve(a, b) });
+        // every length up to a few vectors of the longest kind, and past a 64 KiB block
+        let long = "ab\n\n\ncdefg\n".repeat(10_000);
+        let lens = (0..=600).chain([65536 - 1, 65536, 65536 + 1, long.len()]);
+        for len in lens {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let mut buf = Vec::new();
+                unsafe { aarch64::sve(input, &mut buf) };
+                assert_eq!(std(input), buf, "len {len}");
+                let (mut expected, mut index) =
+                    (crate::compressed::LineIndex::default(), Default::default());
+                crate::compressed::iter(input, &mut expected);
+                unsafe { crate::compressed::aarch64::sve(input, &mut index) };
+                assert!(index == expected, "len {len}");
+            });
+        }
+    }
+
+    #[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+    #[test]
+    fn test_rvv() {
+        check(riscv64::rvv);
+        // every length up to a few steps, and past a 64 KiB block
+        let long = "ab\n\n\ncdefg\n".repeat(10_000);
+        let lens = (0..=600).chain([65536 - 1, 65536, 65536 + 1, long.len()]);
+        for len in lens {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let mut buf = Vec::new();
+                riscv64::rvv(input, &mut buf);
+                assert_eq!(std(input), buf, "len {len}");
+                let (mut expected, mut index) =
+                    (crate::compressed::LineIndex::default(), Default::default());
+                crate::compressed::iter(input, &mut expected);
+                crate::compressed::riscv64::rvv(input, &mut index);
+                assert!(index == expected, "len {len}");
+            });
+        }
+    }
+
+    #[cfg(target_arch = "loongarch64")]
+    #[test]
+    fn test_loongarch64() {
+        // against swar rather than std: the scalar kernels are what's been run on LoongArch
+        let long = "ab\n\n\ncdefg\n".repeat(10_000);
+        let lens = (0..=300).chain([65536 - 1, 65536, 65536 + 1, long.len()]);
+        if loongarch64::can_run_lsx() {
+            check(|a, b| unsafe { loongarch64::lsx(a, b) });
+            for len in lens.clone() {
+                crate::guard::with_guard_page(&long[..len], |input| {
+                    let (mut expected, mut buf) = (Vec::new(), Vec::new());
+                    swar(input, &mut expected);
+                    unsafe { loongarch64::lsx(input, &mut buf) };
+                    assert_eq!(expected, buf, "len {len}");
+                });
+            }
+        }
+        if loongarch64::can_run_lasx() {
+            check(|a, b| unsafe { loongarch64::lasx(a, b) });
+            for len in lens {
+                crate::guard::with_guard_page(&long[..len], |input| {
+                    let (mut expected, mut buf) = (Vec::new(), Vec::new());
+                    swar(input, &mut expected);
+                    unsafe { loongarch64::lasx(input, &mut buf) };
+                    assert_eq!(expected, buf, "len {len}");
+                    let (mut expected, mut index) =
+                        (crate::compressed::LineIndex::default(), Default::default());
+                    crate::compressed::swar(input, &mut expected);
+                    unsafe { crate::compressed::loongarch64::lasx(input, &mut index) };
+                    assert!(index == expected, "len {len}");
+                });
+            }
+        }
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    #[test]
+    fn test_simd128() {
+        check(wasm32::simd128);
+        check(wasm32::simd128_unroll);
+        // every tail length, over more than one batch of 256 lines
+        let long = "ab\n\n\ncdefg\n".repeat(40);
+        for len in 0..=long.len() {
+            let mut buf = Vec::new();
+            wasm32::simd128_unroll(&long[..len], &mut buf);
+            assert_eq!(std(&long[..len]), buf, "len {len}");
+        }
+        let input = "ab\n\n\n0123456789\n".repeat(10_000) + "tail";
+        let (mut expected, mut index) =
+            (crate::compressed::LineIndex::default(), Default::default());
+        crate::compressed::iter(&input, &mut expected);
+        crate::compressed::wasm32::simd128(&input, &mut index);
+        assert!(index == expected);
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn test_portable() {
+        check(portable::simd);
+        #[cfg(target_arch = "x86_64")]
+        if x86_64::can_run_avx2() {
+            check(|a, b| unsafe { portable::simd_avx2(a, b) });
         }
     }
 
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_sse2() {
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            x86_64::sse2(input, &mut buf);
-            assert_eq!(expected, &buf, "input: `{input}`");
-        }
+        check(x86_64::sse2);
     }
 
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_sse2_unroll() {
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            x86_64::sse2_unroll(input, &mut buf);
-            assert_eq!(expected, &buf, "input: `{input}`");
-        }
+        check(x86_64::sse2_unroll);
     }
 
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_sse2_unrollx4() {
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            x86_64::sse2_unrollx4(input, &mut buf);
-            assert_eq!(expected, &buf, "input: `{input}`");
-        }
+        check(x86_64::sse2_unrollx4);
     }
 
     #[cfg(target_arch = "x86_64")]
@@ -1195,26 +1438,49 @@ mod tests {
         if !x86_64::can_run_avx2() {
             return;
         }
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            unsafe { x86_64::avx2(input, &mut buf) };
-            assert_eq!(expected, &buf, "input: `{input}`");
+        check(|a, b| unsafe { x86_64::avx2(a, b) });
+        check(|a, b| unsafe { x86_64::avx2_lut(a, b) });
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_bmi2() {
+        if !x86_64::can_run_avx2_bmi2() {
+            return;
+        }
+        check(|a, b| unsafe { x86_64::avx2_bmi2(a, b) });
+        let long = "ab\n\n\ncdefg\n".repeat(40);
+        for len in 0..=long.len() {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let mut buf = Vec::new();
+                unsafe { x86_64::avx2_bmi2(input, &mut buf) };
+                assert_eq!(std(input), buf, "len {len}");
+            });
         }
     }
 
     #[cfg(target_arch = "x86_64")]
     #[test]
-    fn test_avx2_unroll() {
+    fn test_sse2_rev() {
+        check_rev(x86_64::sse2_rev);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_rev() {
         if !x86_64::can_run_avx2() {
             return;
         }
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            unsafe { x86_64::avx2_unroll(input, &mut buf) };
-            assert_eq!(expected, &buf, "input: `{input}`");
+        check_rev(|a, b, c| unsafe { x86_64::avx2_rev(a, b, c) });
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_unroll() {
+        if !x86_64::can_run_avx2() {
+            return;
         }
+        check(|a, b| unsafe { x86_64::avx2_unroll(a, b) });
     }
     #[cfg(target_arch = "x86_64")]
     #[test]
@@ -1222,11 +1488,250 @@ mod tests {
         if !x86_64::can_run_avx2() {
             return;
         }
-        let mut buf = Vec::new();
-        for (input, expected) in TEST_CASES {
-            buf.clear();
-            unsafe { x86_64::avx2_unrollx2(input, &mut buf) };
-            assert_eq!(expected, &buf, "input: `{input}`");
+        check(|a, b| unsafe { x86_64::avx2_unrollx2(a, b) });
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_tails() {
+        if !x86_64::can_run_avx2() {
+            return;
+        }
+        let kernels: &[(&str, crate::SliceSplitFn)] = &[
+            ("avx2", |a, b| unsafe { x86_64::avx2(a, b) }),
+            ("avx2_unsafe", |a, b| unsafe { x86_64::avx2_unsafe(a, b) }),
+            ("avx2_unroll", |a, b| unsafe { x86_64::avx2_unroll(a, b) }),
+            ("avx2_unrollx2", |a, b| unsafe {
+                x86_64::avx2_unrollx2(a, b)
+            }),
+            ("avx2_pipelined", |a, b| unsafe {
+                x86_64::avx2_pipelined(a, b)
+            }),
+            ("avx2_lut", |a, b| unsafe { x86_64::avx2_lut(a, b) }),
+        ];
+        // every tail length, with newlines at its first and last byte for some of them
+        let long = "ab\n\n\ncdefg\n".repeat(40);
+        for len in 0..=long.len() {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                for (name, kernel) in kernels {
+                    let mut buf = Vec::new();
+                    kernel(input, &mut buf);
+                    assert_eq!(std(input), buf, "{name}, len {len}");
+                }
+            });
         }
     }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx512() {
+        if !x86_64::can_run_avx512() {
+            return;
+        }
+        check(|a, b| unsafe { x86_64::avx512(a, b) });
+        check(|a, b| unsafe { x86_64::avx512_unroll(a, b) });
+        check(|a, b| unsafe { x86_64::avx512_unrollx2(a, b) });
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx512_tails() {
+        if !x86_64::can_run_avx512() {
+            return;
+        }
+        let kernels: &[(&str, crate::SliceSplitFn)] = &[
+            ("avx512", |a, b| unsafe { x86_64::avx512(a, b) }),
+            ("avx512_unroll", |a, b| unsafe {
+                x86_64::avx512_unroll(a, b)
+            }),
+            ("avx512_unrollx2", |a, b| unsafe {
+                x86_64::avx512_unrollx2(a, b)
+            }),
+        ];
+        // every tail length of the 128-byte loop, over more than one batch of 256 lines
+        let long = "ab\n\n\ncdefg\n".repeat(80);
+        for len in 0..=long.len() {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                for (name, kernel) in kernels {
+                    let mut buf = Vec::new();
+                    kernel(input, &mut buf);
+                    assert_eq!(std(input), buf, "{name}, len {len}");
+                }
+            });
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2_pipelined() {
+        if !x86_64::can_run_avx2() {
+            return;
+        }
+        let long = "12345678901234567\n\n1\n".repeat(100);
+        let cases = TEST_CASES.iter().map(|(input, _)| *input).chain([
+            &long[..64],
+            &long[..65],
+            &long[..1000],
+            &long[..],
+        ]);
+        for input in cases {
+            crate::guard::with_guard_page(input, |input| {
+                let mut buf = Vec::new();
+                unsafe { x86_64::avx2_pipelined(input, &mut buf) };
+                assert_eq!(std(input), buf, "input: `{input}`");
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod compressed_tests {
+    use crate::compressed::*;
+
+    #[test]
+    fn test_line_range() {
+        let long = "abc\n".repeat(40_000) + "\n\ntail";
+        let inputs = ["", "a", "\n", "a\n", "\n\nb", &long];
+        for input in inputs {
+            let mut index = LineIndex::default();
+            iter(input, &mut index);
+            let expected = crate::slice::std(input);
+            assert_eq!(index.line_count(input.len()), expected.len());
+            for (i, line) in expected.iter().enumerate() {
+                assert_eq!(index.line(input, i), *line);
+            }
+            for (i, (pos, _)) in input.match_indices('\n').enumerate() {
+                assert_eq!(index.newline(i), pos);
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let input = "abc\n".repeat(40_000);
+        let mut index = LineIndex::default();
+        index.lows.reserve(1 << 20);
+        iter(&input, &mut index);
+        let stats = index.memory_usage();
+        assert_eq!((stats.lows_len, stats.high_starts_len), (40_000, 3));
+        assert_eq!(stats.used_bytes(), 40_000 * 2 + 3 * size_of::<usize>());
+        assert!(stats.reserved_bytes() >= (1 << 20) * 2);
+
+        index.shrink_to_fit();
+        let stats = index.memory_usage();
+        assert_eq!(stats.lows_capacity, stats.lows_len);
+        assert_eq!(stats.high_starts_capacity, stats.high_starts_len);
+        assert_eq!(stats.reserved_bytes(), stats.used_bytes());
+        assert_eq!(LineIndex::default().memory_usage().bytes_per_line(), 0.);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_kernels_partial_chunk() {
+        use split_bench::splitters::*;
+        // lengths that aren't a multiple of any kernel's chunk size, with newlines in the tail
+        let bases = [
+            "a\n\nbc\n".repeat(30_000),
+            // more than 8 newlines in 16 bytes
+            "\n\n\na\n".repeat(30_000),
+            // switching between sparse and dense chunks
+            ("a".repeat(90) + "\n" + &"\n\n\na\n".repeat(10)).repeat(1_000),
+        ];
+        let kernels: &[(&str, crate::CompressSplitFn)] = &[
+            ("swar", swar),
+            ("sse2", x86_64::sse2),
+            ("sse2_unroll", x86_64::sse2_unroll),
+            ("sse2_unrollx4", x86_64::sse2_unrollx4),
+            ("sse42", |a, b| {
+                Sse42Splitter::new().unwrap().build_index(a, b)
+            }),
+            ("avx2_unroll", |a, b| unsafe { x86_64::avx2_unroll(a, b) }),
+            ("avx2_unrollx2", |a, b| unsafe {
+                x86_64::avx2_unrollx2(a, b)
+            }),
+            ("avx2_pipelined", |a, b| unsafe {
+                x86_64::avx2_pipelined(a, b)
+            }),
+            ("avx2_interleave", |a, b| unsafe {
+                x86_64::avx2_unrollx2_interleavex2(a, b)
+            }),
+            ("avx2_lut", |a, b| unsafe { x86_64::avx2_lut(a, b) }),
+            ("avx2_big_lut", |a, b| unsafe { x86_64::avx2_big_lut(a, b) }),
+            ("avx512", |a, b| unsafe { x86_64::avx512_compress(a, b) }),
+            ("avx512 words", |a, b| unsafe {
+                x86_64::avx512_compress_words(a, b)
+            }),
+            ("avx512 cstore", |a, b| unsafe {
+                x86_64::avx512_compressstore(a, b)
+            }),
+            ("avx512 hybrid", |a, b| unsafe {
+                x86_64::avx512_hybrid(a, b)
+            }),
+        ];
+        let runnable = |name: &str| match name {
+            "sse42" => Sse42Splitter::new().is_some(),
+            name if name.starts_with("avx512") => Avx512Splitter::new().is_some(),
+            name if name.starts_with("avx2") => Avx2Splitter::new().is_some(),
+            _ => true,
+        };
+        // every length up to a few chunks, so every tail length, plus tails right after a 64 KiB
+        // block starts
+        let lens = (0..=260).chain([65536, 65536 + 1, 65536 + 63, 65536 + 127, 65536 * 2 - 1]);
+        for (base, len) in bases
+            .iter()
+            .flat_map(|base| lens.clone().chain([base.len()]).map(move |len| (base, len)))
+        {
+            crate::guard::with_guard_page(&base[..len], |input| {
+                let mut expected = LineIndex::default();
+                iter(input, &mut expected);
+                for (name, kernel) in kernels.iter().filter(|k| runnable(k.0)) {
+                    // poison the spare capacity, so a slot the kernel skips can't hold the right
+                    // value by accident
+                    let mut out = LineIndex::default();
+                    out.lows.resize(input.len() + 256, u16::MAX);
+                    out.lows.clear();
+                    unsafe { kernel(input, &mut out) };
+                    assert!(expected == out, "{name}, len {len}");
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_slices_conversions() {
+        // a block without newlines in the middle, and one at the end
+        let long =
+            "abc\n".repeat(40_000) + &"x".repeat(1 << 17) + "\n\ntail" + &"y".repeat(1 << 16);
+        for input in [
+            "",
+            "a",
+            "\n",
+            "a\n",
+            "\n\nb",
+            &long,
+            &long[..long.len() - (1 << 16)],
+        ] {
+            let mut expected = LineIndex::default();
+            iter(input, &mut expected);
+            let lines = crate::slice::std(input);
+            assert!(
+                LineIndex::from_slices(input, &lines) == expected,
+                "{:?}",
+                &input[..input.len().min(10)]
+            );
+            let mut out = vec!["before"];
+            expected.to_slices(input, &mut out);
+            assert_eq!(out[0], "before");
+            assert_eq!(out[1..], lines);
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        let input = "12345678901234567\n".repeat(10_000);
+        let (mut expected, mut out) = (LineIndex::default(), LineIndex::default());
+        iter(&input, &mut expected);
+        build(&input, &mut out);
+        assert!(expected == out);
+    }
 }
@@ -0,0 +1,76 @@
+//! Newline positions as a Roaring bitmap (the `roaring` crate), for programs that already keep
+//! their positional sets that way. Roaring stores each 64 Ki range of values as a sorted `u16`
+//! array while it has at most 4096 of them, which is a `LineIndex` block, and as a 8 KiB bitset
+//! past that, so dense inputs take less memory. It also keeps the counts it needs for `rank`.
+//! Only for inputs under 4 GiB.
+
+use crate::compressed::NewlineIndex;
+use roaring::RoaringBitmap;
+
+pub struct NewlineSet(pub RoaringBitmap);
+
+impl NewlineSet {
+    /// Finds the newlines with the fastest flat u32 kernel, then adds them in order
+    pub fn build(input: &str) -> Self {
+        let mut offsets = Vec::new();
+        crate::flat::build(input, &mut offsets);
+        NewlineSet(RoaringBitmap::from_sorted_iter(offsets).unwrap())
+    }
+
+    /// Bytes the bitmap takes serialized, close to what it takes in memory
+    pub fn memory_bytes(&self) -> usize {
+        self.0.serialized_size()
+    }
+}
+
+impl NewlineIndex for NewlineSet {
+    fn len(&self) -> usize {
+        self.0.len() as usize
+    }
+
+    fn newline(&self, i: usize) -> usize {
+        self.0.select(i as u32).unwrap() as usize
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        match pos {
+            0 => 0,
+            pos => self.0.rank(pos as u32 - 1) as usize,
+        }
+    }
+
+    fn is_newline(&self, pos: usize) -> bool {
+        self.0.contains(pos as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressed::LineIndex;
+
+    #[test]
+    fn test_queries() {
+        // a sparse block, a dense one (a bitset in the bitmap) and one without newlines
+        let input = "abc\n".repeat(10_000) + &"\n".repeat(1 << 16) + &"x".repeat(1 << 16) + "\nz";
+        let set = NewlineSet::build(&input);
+        let mut index = LineIndex::default();
+        crate::compressed::iter(&input, &mut index);
+        assert_eq!(set.len(), index.len());
+        assert_eq!(set.line_count(input.len()), index.line_count(input.len()));
+        for pos in (0..=input.len())
+            .step_by(997)
+            .chain([input.len() - 2, input.len()])
+        {
+            assert_eq!(set.line_of(pos), index.line_of(pos), "{pos}");
+            assert_eq!(
+                set.is_newline(pos),
+                input.as_bytes().get(pos) == Some(&b'\n')
+            );
+            assert_eq!(index.is_newline(pos), set.is_newline(pos), "{pos}");
+        }
+        for i in (0..set.len()).step_by(101) {
+            assert_eq!(set.newline(i), index.newline(i));
+        }
+    }
+}
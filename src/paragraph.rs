@@ -0,0 +1,195 @@
+//! awk's paragraph mode (`RS=""`): records are separated by one or more blank lines, that is by
+//! runs of two or more newlines. Newlines at the start and end of the input don't delimit
+//! anything, and a paragraph keeps the single newlines inside it.
+//!
+//! The kernels find separators as newlines that directly follow another newline, so a run of N
+//! newlines shows up as N-1 bits. The bit for the first byte of a chunk comes from the last byte of
+//! the previous one, carried over. [`from_newlines`] finds them in a newline index instead, as
+//! consecutive offsets, for a caller that already has one.
+
+/// Lines are only `\n`-terminated; a blank line holding a `\r` is part of a paragraph
+pub fn split<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, out) };
+    }
+    scalar(input, out)
+}
+
+/// Where the current paragraph started, and what to do with each separator bit
+struct Paragraphs {
+    start: usize,
+}
+
+impl Paragraphs {
+    fn new(input: &str) -> Self {
+        Paragraphs {
+            start: input.len() - input.trim_start_matches('\n').len(),
+        }
+    }
+
+    /// `i` is a newline right after another newline
+    #[inline]
+    fn separator<'input>(&mut self, i: usize, input: &'input str, out: &mut Vec<&'input str>) {
+        // later newlines of a run (and those of the leading run) end an empty paragraph
+        if i - 1 > self.start {
+            out.push(&input[self.start..i - 1]);
+        }
+        self.start = self.start.max(i + 1);
+    }
+
+    fn finish<'input>(self, input: &'input str, out: &mut Vec<&'input str>) {
+        let last = input[self.start.min(input.len())..].trim_end_matches('\n');
+        if !last.is_empty() {
+            out.push(last);
+        }
+    }
+}
+
+pub fn scalar<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    let mut paragraphs = Paragraphs::new(input);
+    scalar_from(1, &mut paragraphs, input, out);
+    paragraphs.finish(input, out);
+}
+
+/// Looks for separators at `from..`, with `from >= 1`
+fn scalar_from<'input>(
+    from: usize,
+    paragraphs: &mut Paragraphs,
+    input: &'input str,
+    out: &mut Vec<&'input str>,
+) {
+    let bytes = input.as_bytes();
+    for i in from..bytes.len() {
+        if bytes[i] == b'\n' && bytes[i - 1] == b'\n' {
+            paragraphs.separator(i, input, out);
+        }
+    }
+}
+
+/// The paragraphs of `input`, from its newline offsets in order (a flat `Vec<u32>`, or
+/// [`LineIndex::newlines`](crate::compressed::LineIndex::newlines)), without scanning it
+/// again: a separator is a newline right after the previous one
+pub fn from_newlines<'input>(
+    input: &'input str,
+    newlines: impl IntoIterator<Item = usize>,
+    out: &mut Vec<&'input str>,
+) {
+    let mut paragraphs = Paragraphs::new(input);
+    let mut prev = None;
+    for newline in newlines {
+        if newline > 0 && prev == Some(newline - 1) {
+            paragraphs.separator(newline, input, out);
+        }
+        prev = Some(newline);
+    }
+    paragraphs.finish(input, out);
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2,bmi1")]
+    pub unsafe fn avx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut paragraphs = Paragraphs::new(input);
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        // whether the byte before the chunk is a newline
+        let mut carry = 0;
+        for chunk_i in 0..input.len() / 64 {
+            let ptr = input.as_ptr().add(chunk_i * 64);
+            let v1 = _mm256_loadu_si256(ptr.cast());
+            let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+            let newlines = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64)
+                << 32)
+                | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+            let mut mask = newlines & ((newlines << 1) | carry);
+            carry = newlines >> 63;
+            while mask != 0 {
+                paragraphs.separator(chunk_i * 64 + mask.trailing_zeros() as usize, input, out);
+                mask &= mask - 1;
+            }
+        }
+        scalar_from((input.len() & !63).max(1), &mut paragraphs, input, out);
+        paragraphs.finish(input, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The paragraphs as awk sees them: records between blank lines, without empty ones
+    fn reference(input: &str) -> Vec<&str> {
+        let mut paragraphs = Vec::new();
+        let mut start = None;
+        let mut offset = 0;
+        for line in input.split_inclusive('\n') {
+            let end = offset + line.len();
+            if line == "\n" {
+                if let Some(start) = start.take() {
+                    paragraphs.push(&input[start..offset - 1]);
+                }
+            } else {
+                start.get_or_insert(offset);
+            }
+            offset = end;
+        }
+        if let Some(start) = start {
+            paragraphs.push(input[start..].trim_end_matches('\n'));
+        }
+        paragraphs
+    }
+
+    #[test]
+    fn test_split() {
+        let cases: &[(&str, &[&str])] = &[
+            ("", &[]),
+            ("\n\n\n", &[]),
+            ("a", &["a"]),
+            ("a\n", &["a"]),
+            ("a\nb\n\nc", &["a\nb", "c"]),
+            ("\n\na\n\n\n\nb\n\n", &["a", "b"]),
+            ("\na\n \nb", &["a\n \nb"]),
+        ];
+        for &(input, expected) in cases {
+            assert_eq!(reference(input), expected, "input: {input:?}");
+            let mut out = Vec::new();
+            scalar(input, &mut out);
+            assert_eq!(out, expected, "input: {input:?}");
+            out.clear();
+            split(input, &mut out);
+            assert_eq!(out, expected, "input: {input:?}");
+            out.clear();
+            from_newlines(input, input.match_indices('\n').map(|m| m.0), &mut out);
+            assert_eq!(out, expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_split_block_boundaries() {
+        // separator runs of 1 to 4 newlines, starting just before, at and after a boundary
+        for boundary in [64, 128, 1 << 16] {
+            for run in 1..=4 {
+                for start in boundary - 4..boundary + 2 {
+                    let mut input = "x".repeat(start);
+                    input.replace_range(start / 2..start / 2 + 1, "\n");
+                    input += &"\n".repeat(run);
+                    input += &"y\n".repeat(40);
+                    crate::guard::with_guard_page(&input, |input| {
+                        let mut out = Vec::new();
+                        split(input, &mut out);
+                        assert_eq!(out, reference(input), "run {run} at {start}");
+                        assert_eq!(out.len(), if run == 1 { 1 } else { 2 });
+                        let mut index = crate::compressed::LineIndex::default();
+                        crate::compressed::build(input, &mut index);
+                        let mut from_index = Vec::new();
+                        from_newlines(input, index.newlines(), &mut from_index);
+                        assert_eq!(from_index, out, "run {run} at {start}");
+                    });
+                }
+            }
+        }
+    }
+}
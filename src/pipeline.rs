@@ -0,0 +1,88 @@
+//! The bench normally measures a producer alone, with the whole machine to itself. In a real
+//! pipeline something reads the lines while the next batch is being split, competing for the
+//! shared LLC and memory bandwidth. This runs a splitter in that setting: the input is split in
+//! batches, and a consumer thread reads every line of each batch while the next one is produced.
+
+use crate::SliceSplitFn;
+use std::hint::black_box;
+use std::sync::mpsc;
+
+/// Batches in flight between the producer and consumer
+const QUEUE_DEPTH: usize = 4;
+
+/// Splits `input` with `split` in batches of roughly `batch_size` bytes (cut after a newline),
+/// while a consumer thread reads the lines. Returns what the consumer saw, so the work can't be
+/// optimized away and the caller can check it.
+pub fn split_with_consumer(input: &str, batch_size: usize, split: SliceSplitFn) -> ConsumerStats {
+    let (full_tx, full_rx) = mpsc::sync_channel::<Vec<&str>>(QUEUE_DEPTH);
+    let (empty_tx, empty_rx) = mpsc::channel::<Vec<&str>>();
+    std::thread::scope(|scope| {
+        let consumer = scope.spawn(move || {
+            let mut stats = ConsumerStats::default();
+            for lines in full_rx {
+                for line in &lines {
+                    // touch the line itself, not just the slice
+                    stats.lines += 1;
+                    stats.bytes += line.len();
+                    stats.first_bytes += line.as_bytes().first().copied().unwrap_or(0) as usize;
+                }
+                // the producer may have hung up already
+                let _ = empty_tx.send(black_box(lines));
+            }
+            stats
+        });
+
+        let mut rest = input;
+        while !rest.is_empty() {
+            let search_from = batch_size.min(rest.len());
+            let batch_len = match crate::roofline::memchr(b'\n', &rest.as_bytes()[search_from..]) {
+                Some(i) => search_from + i + 1,
+                None => rest.len(),
+            };
+            let (batch, next) = rest.split_at(batch_len);
+            rest = next;
+            // reuse a batch the consumer is done with, if there is one
+            let mut lines = empty_rx.try_recv().unwrap_or_default();
+            lines.clear();
+            split(batch, &mut lines);
+            full_tx.send(lines).unwrap();
+        }
+        drop(full_tx);
+        consumer.join().unwrap()
+    })
+}
+
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct ConsumerStats {
+    pub lines: usize,
+    pub bytes: usize,
+    pub first_bytes: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_with_consumer() {
+        let long = "ab\n\n0123456789\n".repeat(10_000);
+        for input in ["", "\n", "a", "a\nb", &long] {
+            let lines: Vec<&str> = input.lines().collect();
+            let expected = ConsumerStats {
+                lines: lines.len(),
+                bytes: lines.iter().map(|line| line.len()).sum(),
+                first_bytes: lines
+                    .iter()
+                    .map(|line| *line.as_bytes().first().unwrap_or(&0) as usize)
+                    .sum(),
+            };
+            for batch_size in [1, 7, 1000, 1 << 20] {
+                assert_eq!(
+                    split_with_consumer(input, batch_size, crate::slice::split),
+                    expected,
+                    "batch size {batch_size}"
+                );
+            }
+        }
+    }
+}
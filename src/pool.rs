@@ -0,0 +1,78 @@
+//! Reusing a `Vec<&str>`'s allocation for lines of different inputs. The borrow checker ties a
+//! `Vec<&'input str>` to its input even once it's empty, so keeping one around for the next input
+//! (the next block of a stream, the next stage of the bench) takes relabeling the empty buffer's
+//! lifetime, which [`LinePool`] does without any unsafe code.
+
+use alloc::vec::Vec;
+
+/// Holds one empty line buffer between uses
+///
+/// ```
+/// use split_bench::{pool::LinePool, slice};
+///
+/// let mut pool = LinePool::default();
+/// for block in [String::from("a\nb\n"), String::from("c\n")] {
+///     // each block's lines borrow the block, the allocation outlives them all
+///     let mut lines = pool.take();
+///     slice::split(&block, &mut lines);
+///     assert!(!lines.is_empty());
+///     pool.put(lines);
+/// }
+/// assert!(pool.take().capacity() >= 2);
+/// ```
+#[derive(Default)]
+pub struct LinePool {
+    /// Always empty. `'static` stands for "no input", nothing is ever stored with it.
+    buf: Vec<&'static str>,
+}
+
+impl LinePool {
+    /// The pooled buffer, empty, for lines of any input. Without a [`LinePool::put`] since the
+    /// last `take`, it has no capacity.
+    pub fn take<'input>(&mut self) -> Vec<&'input str> {
+        relabel(core::mem::take(&mut self.buf))
+    }
+
+    /// Keeps `lines`' allocation for the next [`LinePool::take`], dropping its contents (and
+    /// whatever buffer the pool held)
+    pub fn put(&mut self, lines: Vec<&str>) {
+        self.buf = relabel(lines);
+    }
+}
+
+/// `vec`, emptied, as a buffer for another lifetime. Collecting a `Vec`'s `into_iter` into a `Vec`
+/// of an element with the same size and alignment reuses its allocation, and an empty iterator
+/// never calls the closure.
+fn relabel<'b>(mut vec: Vec<&str>) -> Vec<&'b str> {
+    vec.clear();
+    vec.into_iter()
+        .map(|_| -> &'b str { unreachable!() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_pool() {
+        let mut pool = LinePool::default();
+        assert_eq!(pool.take().capacity(), 0);
+        let ptr = {
+            let input = "a\nb\n".repeat(1000);
+            let mut lines = pool.take();
+            crate::slice::split(&input, &mut lines);
+            let ptr = lines.as_ptr() as usize;
+            pool.put(lines);
+            ptr
+        };
+        // the input is gone, the allocation isn't
+        let input = String::from("c\n");
+        let mut lines = pool.take();
+        assert!(lines.is_empty());
+        assert!(lines.capacity() >= 2000);
+        assert_eq!(lines.as_ptr() as usize, ptr);
+        crate::slice::split(&input, &mut lines);
+        assert_eq!(lines, ["c"]);
+    }
+}
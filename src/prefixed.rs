@@ -0,0 +1,195 @@
+//! Only the lines starting with a prefix, like keeping a log's `E` lines. Either filtered while
+//! splitting, or as a pass over the output of `slice::split`, which is what the bench compares. The
+//! fused kernel compares the first bytes of every line to the prefix in the vector registers, with
+//! one unaligned load per prefix byte, so a line that doesn't match is skipped without reading it
+//! again.
+
+pub fn split<'input>(input: &'input str, prefix: &str, out: &mut Vec<&'input str>) {
+    // a prefix with a newline never matches, but in registers it could match across lines
+    #[cfg(target_arch = "x86_64")]
+    if !prefix.is_empty() && !prefix.contains('\n') && crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, prefix, out) };
+    }
+    scalar(input, prefix, out)
+}
+
+/// Splits into `out`, then compacts the new lines that start with `prefix` in place
+pub fn split_then_filter<'input>(input: &'input str, prefix: &str, out: &mut Vec<&'input str>) {
+    let first = out.len();
+    crate::slice::split(input, out);
+    let mut kept = first;
+    for i in first..out.len() {
+        if out[i].starts_with(prefix) {
+            out[kept] = out[i];
+            kept += 1;
+        }
+    }
+    out.truncate(kept);
+}
+
+pub fn scalar<'input>(input: &'input str, prefix: &str, out: &mut Vec<&'input str>) {
+    out.extend(
+        input
+            .split_terminator('\n')
+            .filter(|line| line.starts_with(prefix)),
+    );
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use std::arch::x86_64::*;
+
+    /// Prefix bytes compared in registers. Lines matching those are compared to the rest of a
+    /// longer prefix one by one.
+    const REGISTER_PREFIX: usize = 4;
+
+    /// `slice::x86_64::avx2_unrollx2`, only writing the lines starting with `prefix`, which must
+    /// not be empty or contain a newline
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2<'input>(input: &'input str, prefix: &str, out: &mut Vec<&'input str>) {
+        debug_assert!(!prefix.is_empty() && !prefix.contains('\n'));
+        // one instance per length, so the loop over the prefix bytes is unrolled
+        match prefix.len() {
+            1 => avx2_n::<1>(input, prefix, out),
+            2 => avx2_n::<2>(input, prefix, out),
+            3 => avx2_n::<3>(input, prefix, out),
+            _ => avx2_n::<REGISTER_PREFIX>(input, prefix, out),
+        }
+    }
+
+    /// 64 bits, one per byte from `ptr` on, set for the bytes equal to `v`'s
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn eq_mask(ptr: *const u8, v: __m256i) -> u64 {
+        let lo = _mm256_cmpeq_epi8(_mm256_loadu_si256(ptr.cast()), v);
+        let hi = _mm256_cmpeq_epi8(_mm256_loadu_si256(ptr.add(32).cast()), v);
+        ((_mm256_movemask_epi8(hi) as u32 as u64) << 32) | (_mm256_movemask_epi8(lo) as u32 as u64)
+    }
+
+    /// Compares the first `N` bytes of `prefix` in registers
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    unsafe fn avx2_n<'input, const N: usize>(
+        input: &'input str,
+        prefix: &str,
+        out: &mut Vec<&'input str>,
+    ) {
+        let bytes = input.as_bytes();
+        let compare_rest = prefix.len() > N;
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let prefix_v: [__m256i; N] =
+            std::array::from_fn(|k| _mm256_set1_epi8(prefix.as_bytes()[k] as i8));
+        let mut line_start = 0;
+        // whether the line that starts at `line_start` matched in registers
+        let mut matched = false;
+        // whether a line starts at the first byte of the next chunk
+        let mut carry = true;
+        let mut chunk_i = 0;
+        // the loads for the last prefix byte reach `N - 1` bytes past the chunk
+        let stop_chunk_i = input.len().saturating_sub(N - 1) / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().add(chunk_i * 64);
+                let mut mask = eq_mask(ptr, nl_v);
+                // bit i: the bytes from i on start with the prefix
+                let mut hits = u64::MAX;
+                for (k, v) in prefix_v.iter().enumerate() {
+                    hits &= eq_mask(ptr.add(k), *v);
+                }
+                if carry {
+                    matched = hits & 1 != 0;
+                }
+                carry = mask >> 63 != 0;
+                while mask != 0 {
+                    let bit_pos = mask.trailing_zeros();
+                    let line_end = chunk_i * 64 + bit_pos as usize;
+                    let line = input.get_unchecked(line_start..line_end);
+                    // always written, only kept if it matches
+                    out_arr.get_unchecked_mut(write_i).write(line);
+                    write_i += (matched
+                        && (!compare_rest || line.as_bytes().starts_with(prefix.as_bytes())))
+                        as usize;
+                    line_start = line_end + 1;
+                    // a newline in the last byte leaves this to `carry`
+                    matched = (hits >> bit_pos) >> 1 & 1 != 0;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        // the newlines after the last whole chunk, then the last line if it has none
+        let tail_start = stop_chunk_i * 64;
+        for (i, &byte) in bytes[tail_start..].iter().enumerate() {
+            if byte == b'\n' {
+                let line = &input[line_start..tail_start + i];
+                if line.starts_with(prefix) {
+                    out.push(line);
+                }
+                line_start = tail_start + i + 1;
+            }
+        }
+        if line_start < input.len() && input[line_start..].starts_with(prefix) {
+            out.push(&input[line_start..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type PrefixFn = for<'a> fn(&'a str, &str, &mut Vec<&'a str>);
+
+    #[test]
+    fn test_split() {
+        let cases: &[(&str, &str, &[&str])] = &[
+            ("", "E", &[]),
+            ("E", "E", &["E"]),
+            ("E\nW\nEE\n\nE", "E", &["E", "EE", "E"]),
+            ("ERR x\nERROR\nER\n", "ERR", &["ERR x", "ERROR"]),
+            (
+                "ERROR: a\nERRORS\nERROR:\n",
+                "ERROR:",
+                &["ERROR: a", "ERROR:"],
+            ),
+            ("a\n\nb\n", "", &["a", "", "b"]),
+            ("a\nb\n", "a\nb", &[]),
+            ("é\ne\n", "é", &["é"]),
+        ];
+        for &(input, prefix, expected) in cases {
+            let kernels: [(&str, PrefixFn); 3] = [
+                ("split", split),
+                ("split_then_filter", split_then_filter),
+                ("scalar", scalar),
+            ];
+            for (name, kernel) in kernels {
+                let mut out = vec!["before"];
+                kernel(input, prefix, &mut out);
+                assert_eq!(out[1..], *expected, "{name}, input: {input:?}, {prefix:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_chunk_boundaries() {
+        // matches, near misses and empty lines on both sides of 64-byte boundaries, and prefixes
+        // compared in registers only and in registers then byte by byte
+        let long = "ERROR a\nERR\nWARN b\n\nERRORS\nE\n".repeat(5_000) + "ERROR last";
+        for prefix in ["E", "ERR", "ERRO", "ERROR", "ERROR "] {
+            for len in [0, 1, 3, 63, 64, 65, 67, 127, 1000, long.len()] {
+                crate::guard::with_guard_page(&long[..len], |input| {
+                    let mut expected = Vec::new();
+                    scalar(input, prefix, &mut expected);
+                    let (mut fused, mut filtered) = (Vec::new(), Vec::new());
+                    split(input, prefix, &mut fused);
+                    split_then_filter(input, prefix, &mut filtered);
+                    assert_eq!(fused, expected, "len {len}, {prefix:?}");
+                    assert_eq!(filtered, expected, "len {len}, {prefix:?}");
+                });
+            }
+        }
+    }
+}
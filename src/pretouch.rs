@@ -0,0 +1,100 @@
+//! How the bench's output buffers get their pages (`--pretouch`). A kernel writing into memory
+//! that was never touched pays a page fault every 4 KiB, which can cost as much as the splitting
+//! itself. A long-running service reuses warm buffers, while a one-shot tool gets fresh ones, so
+//! both are worth measuring.
+
+use std::io;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pretouch {
+    /// Every timed run gets a fresh allocation that was never touched
+    None,
+    /// The buffers are filled once up front and then reused
+    Write,
+    /// The kernel populates the pages up front (`MADV_POPULATE_WRITE`, Linux 5.14+), without
+    /// the writes, and the buffers are reused
+    Madvise,
+}
+
+impl FromStr for Pretouch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "none" => Ok(Pretouch::None),
+            "write" => Ok(Pretouch::Write),
+            "madvise" => Ok(Pretouch::Madvise),
+            _ => Err("expected one of none, write, madvise".to_owned()),
+        }
+    }
+}
+
+impl Pretouch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pretouch::None => "none",
+            Pretouch::Write => "write",
+            Pretouch::Madvise => "madvise",
+        }
+    }
+
+    pub fn alloc<T: Clone + Default>(self, capacity: usize) -> io::Result<Vec<T>> {
+        Ok(match self {
+            Pretouch::None => Vec::with_capacity(capacity),
+            Pretouch::Write => {
+                let mut vec = vec![T::default(); capacity];
+                vec.clear();
+                vec
+            }
+            Pretouch::Madvise => {
+                let mut vec = Vec::with_capacity(capacity);
+                populate(vec.spare_capacity_mut())?;
+                vec
+            }
+        })
+    }
+
+    /// Empties `vec` for the next timed run. Under [`Pretouch::None`] that means swapping in a
+    /// fresh allocation of the same capacity, so the run pays for its page faults again.
+    pub fn reset<T>(self, vec: &mut Vec<T>) {
+        if self == Pretouch::None {
+            *vec = Vec::with_capacity(vec.capacity());
+        } else {
+            vec.clear();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn populate<T>(buf: &mut [T]) -> io::Result<()> {
+    const MADV_POPULATE_WRITE: i32 = 23;
+    extern "C" {
+        fn madvise(addr: *mut u8, len: usize, advice: i32) -> i32;
+        fn getpagesize() -> i32;
+    }
+    if buf.is_empty() {
+        return Ok(());
+    }
+    // the range has to start on a page boundary. The page the buffer starts in is mapped anyway.
+    let page_size = unsafe { getpagesize() } as usize;
+    let start = buf.as_mut_ptr() as usize;
+    let aligned_start = start & !(page_size - 1);
+    let len = start + size_of_val(buf) - aligned_start;
+    if unsafe { madvise(aligned_start as *mut u8, len, MADV_POPULATE_WRITE) } != 0 {
+        let err = io::Error::last_os_error();
+        return Err(io::Error::new(
+            err.kind(),
+            format!("madvise(MADV_POPULATE_WRITE): {err}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn populate<T>(_: &mut [T]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--pretouch madvise is only implemented for Linux",
+    ))
+}
@@ -0,0 +1,113 @@
+//! The `split_bench` Python module (`--features python`, built with `maturin develop --release`).
+//! Lines come back as `memoryview` slices of the `bytes` that was split, so no line is copied
+//! into a `bytes` or `str` of its own, and each view keeps the input alive. The kernels run
+//! without the GIL, which `bytes` doesn't need since it can't change.
+
+use crate::compressed::{self, NewlineIndex};
+use alloc::vec::Vec;
+use pyo3::exceptions::PyIndexError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList, PyMemoryView, PySlice};
+
+/// Views of `data[start..end]` for each range, through one view of the whole of `data`
+fn views<'py>(
+    data: &Bound<'py, PyBytes>,
+    ranges: impl IntoIterator<Item = (usize, usize)>,
+) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    let view = PyMemoryView::from(data.as_any())?;
+    ranges
+        .into_iter()
+        .map(|(start, end)| view.get_item(PySlice::new(data.py(), start as isize, end as isize, 1)))
+        .collect()
+}
+
+/// Runs `f` with the cycle collector paused. Views are tracked by it, so creating one per line
+/// would run a collection every few hundred lines, each going over every live object (the
+/// views of the last input too), which made `split_lines` 4x slower. A view can't be part of
+/// a cycle, and no Python code runs in between, so nothing can tell.
+fn without_gc<T>(py: Python<'_>, f: impl FnOnce() -> PyResult<T>) -> PyResult<T> {
+    let gc = py.import("gc")?;
+    let enabled = gc.call_method0("isenabled")?.is_truthy()?;
+    if enabled {
+        gc.call_method0("disable")?;
+    }
+    let result = f();
+    if enabled {
+        gc.call_method0("enable")?;
+    }
+    result
+}
+
+/// The lines of `data`, split like `slice::split` (no empty line after a trailing newline, and
+/// a `\r` before a newline kept)
+#[pyfunction]
+fn split_lines<'py>(data: &Bound<'py, PyBytes>) -> PyResult<Bound<'py, PyList>> {
+    let bytes = data.as_bytes();
+    let mut lines = Vec::new();
+    data.py()
+        .detach(|| crate::slice::split_bytes(bytes, &mut lines));
+    let start = |line: &[u8]| line.as_ptr() as usize - bytes.as_ptr() as usize;
+    let ranges = lines
+        .iter()
+        .map(|line| (start(line), start(line) + line.len()));
+    without_gc(data.py(), || PyList::new(data.py(), views(data, ranges)?))
+}
+
+/// A [`compressed::LineIndex`] of `data`, which it keeps alive to hand out its lines
+#[pyclass(frozen)]
+struct LineIndex {
+    data: Py<PyBytes>,
+    index: compressed::LineIndex,
+}
+
+#[pymethods]
+impl LineIndex {
+    /// Number of lines
+    fn __len__(&self, py: Python<'_>) -> usize {
+        self.index.line_count(self.data.bind(py).as_bytes().len())
+    }
+
+    /// Line `i` as a view of the input, negative counting from the end
+    fn __getitem__<'py>(&self, py: Python<'py>, i: isize) -> PyResult<Bound<'py, PyAny>> {
+        let len = self.__len__(py);
+        let i = match i < 0 {
+            true => i + len as isize,
+            false => i,
+        };
+        if i < 0 || i as usize >= len {
+            return Err(PyIndexError::new_err("line index out of range"));
+        }
+        let data = self.data.bind(py);
+        let range = self.index.line_range(i as usize, data.as_bytes().len());
+        Ok(views(data, [(range.start, range.end)])?.remove(0))
+    }
+
+    /// The line the byte at `pos` is in
+    fn line_of(&self, pos: usize) -> usize {
+        self.index.line_of(pos)
+    }
+
+    /// Bytes taken by the index itself, not counting the input
+    fn used_bytes(&self) -> usize {
+        self.index.memory_usage().used_bytes()
+    }
+}
+
+#[pyfunction]
+fn line_index(data: &Bound<'_, PyBytes>) -> LineIndex {
+    let bytes = data.as_bytes();
+    let mut index = compressed::LineIndex::default();
+    data.py()
+        .detach(|| compressed::build_bytes(bytes, &mut index));
+    LineIndex {
+        data: data.clone().unbind(),
+        index,
+    }
+}
+
+#[pymodule]
+fn split_bench(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(split_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(line_index, m)?)?;
+    m.add_class::<LineIndex>()
+}
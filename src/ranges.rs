@@ -0,0 +1,160 @@
+//! Lines as `Range<u32>`s of the input, the newline left out: 8 bytes a line where a `&str` is 16,
+//! so a kernel writes half as much, and the ranges don't borrow the input. The lines are the same
+//! as `slice::split`'s. Only for inputs under 4 GiB. [`line`] and [`to_slices`] turn ranges back
+//! into `&str`s of the input they were split from.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Splits with the fastest kernel the current CPU supports
+pub fn build(input: &str, out: &mut Vec<Range<u32>>) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, out) };
+    }
+    crate::sink::split(input.as_bytes(), out)
+}
+
+pub fn iter(input: &str, out: &mut Vec<Range<u32>>) {
+    assert!(input.len() <= u32::MAX as usize);
+    tail(0, 0, input, out);
+}
+
+/// Scalar loop over the bytes from `start` on, the current line starting at `line_start`, and
+/// the last line if it has no newline
+pub fn tail(start: usize, line_start: usize, input: &str, out: &mut Vec<Range<u32>>) {
+    let mut line_start = line_start as u32;
+    for (idx, _) in input.as_bytes()[start..]
+        .iter()
+        .enumerate()
+        .filter(|e| *e.1 == b'\n')
+    {
+        let newline = (start + idx) as u32;
+        out.push(line_start..newline);
+        line_start = newline + 1;
+    }
+    if line_start as usize != input.len() {
+        out.push(line_start..input.len() as u32);
+    }
+}
+
+/// The line at `range` of `input`. Panics if it's out of bounds or not on char boundaries, which
+/// it can only be if it came from another input.
+#[inline]
+pub fn line<'input>(input: &'input str, range: &Range<u32>) -> &'input str {
+    &input[range.start as usize..range.end as usize]
+}
+
+/// Pushes the lines at `ranges` of `input` onto `out`, checked as in [`line`]
+pub fn to_slices<'input>(input: &'input str, ranges: &[Range<u32>], out: &mut Vec<&'input str>) {
+    out.extend(ranges.iter().map(|range| line(input, range)));
+}
+
+/// Pushes the ranges of `lines`, which must all be slices of `input`, onto `out`
+pub fn from_slices(input: &str, lines: &[&str], out: &mut Vec<Range<u32>>) {
+    assert!(input.len() <= u32::MAX as usize);
+    out.extend(lines.iter().map(|line| {
+        let start = (line.as_ptr() as usize).wrapping_sub(input.as_ptr() as usize);
+        assert!(
+            start <= input.len() && line.len() <= input.len() - start,
+            "a line that isn't a slice of the input"
+        );
+        start as u32..(start + line.len()) as u32
+    }));
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use core::arch::x86_64::*;
+
+    /// The scan of `flat::x86_64::avx2`, storing the range that ends at each newline
+    #[target_feature(enable = "avx2,bmi1")]
+    pub unsafe fn avx2(input: &str, out: &mut Vec<Range<u32>>) {
+        assert!(input.len() <= u32::MAX as usize);
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mut line_start = 0;
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().add(chunk_i * 64);
+                let v1 = _mm256_loadu_si256(ptr.cast());
+                let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+                let mut mask = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64)
+                    << 32)
+                    | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+                while mask != 0 {
+                    let newline = chunk_i as u32 * 64 + mask.trailing_zeros();
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(line_start..newline);
+                    line_start = newline + 1;
+                    write_i += 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail(stop_chunk_i * 64, line_start as usize, input, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(f: impl Fn(&str, &mut Vec<Range<u32>>)) {
+        let long = "ab\n\n\n0123456789\n".repeat(20_000);
+        for len in [0, 1, 3, 4, 63, 64, 65, 1000, 65536 + 7, long.len()] {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let lines: Vec<&str> = input.split_terminator('\n').collect();
+                let mut out = Vec::new();
+                out.push(7..9);
+                f(input, &mut out);
+                assert_eq!(out[0], 7..9, "len {len}");
+                let mut found = Vec::new();
+                to_slices(input, &out[1..], &mut found);
+                assert_eq!(found, lines, "len {len}");
+            });
+        }
+    }
+
+    #[test]
+    fn test_iter() {
+        check(iter);
+    }
+
+    #[test]
+    fn test_build() {
+        check(build);
+    }
+
+    #[test]
+    fn test_from_slices() {
+        check(|input, out| {
+            let mut lines = Vec::new();
+            crate::slice::split(input, &mut lines);
+            from_slices(input, &lines, out);
+        });
+    }
+
+    #[test]
+    #[should_panic = "isn't a slice of the input"]
+    fn test_from_slices_outside() {
+        let input = "ab\ncd";
+        from_slices(&input[..2], &["cd"], &mut Vec::new());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_avx2() {
+        if crate::slice::x86_64::can_run_avx2() {
+            check(|input, out| unsafe { x86_64::avx2(input, out) });
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! The regression pack: inputs that once broke a kernel (minimized fuzz finds, bug reports), kept
+//! as files in `tests/regressions/` and run through every slice, compressed, flat and ranges
+//! kernel the CPU has by `test_regressions`, so an edge case fixed once guards every kernel added
+//! later. The `regress` subcommand checks, minimizes and saves new ones.
+
+use crate::compressed::LineIndex;
+use crate::{compressed, flat, ranges};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+pub fn dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/regressions"))
+}
+
+/// Runs every runnable bench case on `input`, placed right before a guard page, and describes
+/// the first one that disagrees with its reference or panics
+pub fn check(input: &str) -> Result<(), String> {
+    crate::guard::with_guard_page(input, |input| {
+        // the kernels keep a `\r` before the newline, `str::lines` drops it
+        let expected_lines: Vec<&str> = input.split_terminator('\n').collect();
+        let expected_str_lines: Vec<&str> = input.lines().collect();
+        let expected_inclusive: Vec<&str> = input.split_inclusive('\n').collect();
+        let expected_fields: Vec<&str> = input.split_terminator('\t').collect();
+        let mut expected_records = Vec::new();
+        (crate::DELIM_SET_BENCH_CASES[0].2)(input, &mut expected_records);
+        let mut expected_index = LineIndex::default();
+        compressed::iter(input, &mut expected_index);
+        let mut expected_offsets = Vec::new();
+        flat::iter(input, &mut expected_offsets);
+        let runnable = |features| crate::missing_features(features).is_empty();
+        let agrees = |run: &mut dyn FnMut() -> bool| {
+            // a kernel's own assert is a failure too, not the end of the check
+            panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or(false)
+        };
+        let slice_tables = [
+            ("slice", crate::SLICE_BENCH_CASES),
+            ("crlf", crate::CRLF_BENCH_CASES),
+            ("inclusive", crate::INCLUSIVE_BENCH_CASES),
+            ("delimiter", crate::DELIM_BENCH_CASES),
+            ("delimiter set", crate::DELIM_SET_BENCH_CASES),
+        ];
+        for (table, cases) in slice_tables {
+            for (name, features, kernel) in cases {
+                let expected = match table {
+                    "slice" if !crate::STR_LINES_CASES.contains(name) => &expected_lines,
+                    "inclusive" => &expected_inclusive,
+                    "delimiter" => &expected_fields,
+                    "delimiter set" => &expected_records,
+                    _ => &expected_str_lines,
+                };
+                if runnable(features)
+                    && !agrees(&mut || {
+                        let mut lines = Vec::new();
+                        kernel(input, &mut lines);
+                        lines == *expected
+                    })
+                {
+                    return Err(format!("{table} {name}"));
+                }
+            }
+        }
+        for (_, delim, _) in crate::SUBSTRING_STAGES {
+            let expected: Vec<&str> = input.split_terminator(delim).collect();
+            for (name, features, kernel) in crate::SUBSTRING_BENCH_CASES {
+                if runnable(features)
+                    && !agrees(&mut || {
+                        let mut fields = Vec::new();
+                        kernel(input, delim, &mut fields);
+                        fields == expected
+                    })
+                {
+                    return Err(format!("substring {name} on {delim:?}"));
+                }
+            }
+        }
+        let mut expected_nul = Vec::new();
+        (crate::NUL_BENCH_CASES[0].2)(input.as_bytes(), &mut expected_nul);
+        for (name, features, kernel) in crate::NUL_BENCH_CASES {
+            if runnable(features)
+                && !agrees(&mut || {
+                    let mut records = Vec::new();
+                    kernel(input.as_bytes(), &mut records);
+                    records == expected_nul
+                })
+            {
+                return Err(format!("nul {name}"));
+            }
+        }
+        for (name, features, kernel) in crate::COMPRESSED_BENCH_CASES {
+            if runnable(features)
+                && !agrees(&mut || {
+                    let mut index = LineIndex::default();
+                    unsafe { kernel(input, &mut index) };
+                    index == expected_index
+                })
+            {
+                return Err(format!("compressed {name}"));
+            }
+        }
+        for (name, features, kernel) in crate::FLAT_BENCH_CASES {
+            if runnable(features)
+                && !agrees(&mut || {
+                    let mut offsets = Vec::new();
+                    unsafe { kernel(input, &mut offsets) };
+                    offsets == expected_offsets
+                })
+            {
+                return Err(format!("flat u32 {name}"));
+            }
+        }
+        for (name, features, kernel) in crate::RANGES_BENCH_CASES {
+            if runnable(features)
+                && !agrees(&mut || {
+                    let mut ranges = Vec::new();
+                    unsafe { kernel(input, &mut ranges) };
+                    let mut lines = Vec::new();
+                    ranges::to_slices(input, &ranges, &mut lines);
+                    lines == expected_lines
+                })
+            {
+                return Err(format!("ranges {name}"));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Shrinks an input `fails` holds for, dropping ever smaller runs of chars while it still holds
+/// (delta debugging, by halves)
+pub fn minimize(input: &str, fails: impl Fn(&str) -> bool) -> String {
+    let mut chars: Vec<char> = input.chars().collect();
+    let mut run = chars.len().div_ceil(2);
+    while run > 0 {
+        let mut start = 0;
+        while start < chars.len() {
+            let end = (start + run).min(chars.len());
+            let candidate: String = chars[..start].iter().chain(&chars[end..]).collect();
+            if fails(&candidate) {
+                chars.drain(start..end);
+            } else {
+                start = end;
+            }
+        }
+        run /= 2;
+    }
+    chars.into_iter().collect()
+}
+
+/// Writes `input` to the pack, named after a hash of it so saving it twice keeps one file
+pub fn save(input: &str) -> std::io::Result<PathBuf> {
+    // FNV-1a
+    let hash = input.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+    });
+    let dir = dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{hash:016x}.txt"));
+    std::fs::write(&path, input)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regressions() {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let input = std::fs::read_to_string(&path).unwrap();
+            if let Err(kernel) = check(&input) {
+                panic!("{kernel} fails on {}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn test_minimize() {
+        let input = "abc\ndef\n\n\nghi\u{e9}jkl\n".repeat(20);
+        // fails while it has two newlines in a row and an é
+        let minimized = minimize(&input, |input| {
+            input.contains("\n\n") && input.contains('é')
+        });
+        assert_eq!(minimized.chars().count(), 3);
+    }
+}
@@ -0,0 +1,102 @@
+//! Bandwidth ceilings for the bench, so each kernel's number can be read as "how close to
+//! memory-bound is it". `memcpy` reads and writes every byte; `memchr` only reads, and is what a
+//! splitter would cost if finding newlines were free.
+
+/// Copies `src` through `dst` (reused in `dst.len()` sized pieces), so the copy doesn't need a
+/// second buffer as big as the input
+pub fn memcpy(src: &[u8], dst: &mut [u8]) {
+    for chunk in src.chunks(dst.len()) {
+        dst[..chunk.len()].copy_from_slice(chunk);
+    }
+}
+
+/// Position of the first `needle`, using the widest vectors the current CPU supports
+pub fn memchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    #[cfg(target_arch = "x86_64")]
+    if x86_64::can_run_avx2() {
+        unsafe { x86_64::avx2(needle, haystack) }
+    } else {
+        x86_64::sse2(needle, haystack)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    haystack.iter().position(|&b| b == needle)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use std::arch::x86_64::*;
+
+    pub use crate::slice::x86_64::can_run_avx2;
+
+    /// 64 bytes per iteration, the mask is only built once a block has a match
+    pub fn sse2(needle: u8, haystack: &[u8]) -> Option<usize> {
+        unsafe {
+            let needle_v = _mm_set1_epi8(needle as i8);
+            let blocks = haystack.chunks_exact(64);
+            let rest_start = haystack.len() - blocks.remainder().len();
+            for (block_i, block) in blocks.enumerate() {
+                let ptr = block.as_ptr().cast::<__m128i>();
+                let eq0 = _mm_cmpeq_epi8(_mm_loadu_si128(ptr), needle_v);
+                let eq1 = _mm_cmpeq_epi8(_mm_loadu_si128(ptr.add(1)), needle_v);
+                let eq2 = _mm_cmpeq_epi8(_mm_loadu_si128(ptr.add(2)), needle_v);
+                let eq3 = _mm_cmpeq_epi8(_mm_loadu_si128(ptr.add(3)), needle_v);
+                let any = _mm_or_si128(_mm_or_si128(eq0, eq1), _mm_or_si128(eq2, eq3));
+                if _mm_movemask_epi8(any) != 0 {
+                    let mask = _mm_movemask_epi8(eq0) as u16 as u64
+                        | (_mm_movemask_epi8(eq1) as u16 as u64) << 16
+                        | (_mm_movemask_epi8(eq2) as u16 as u64) << 32
+                        | (_mm_movemask_epi8(eq3) as u16 as u64) << 48;
+                    return Some(block_i * 64 + mask.trailing_zeros() as usize);
+                }
+            }
+            haystack[rest_start..]
+                .iter()
+                .position(|&b| b == needle)
+                .map(|i| rest_start + i)
+        }
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2(needle: u8, haystack: &[u8]) -> Option<usize> {
+        let needle_v = _mm256_set1_epi8(needle as i8);
+        let blocks = haystack.chunks_exact(64);
+        let rest_start = haystack.len() - blocks.remainder().len();
+        for (block_i, block) in blocks.enumerate() {
+            let ptr = block.as_ptr().cast::<__m256i>();
+            let eq0 = _mm256_cmpeq_epi8(unsafe { _mm256_loadu_si256(ptr) }, needle_v);
+            let eq1 = _mm256_cmpeq_epi8(unsafe { _mm256_loadu_si256(ptr.add(1)) }, needle_v);
+            if _mm256_movemask_epi8(_mm256_or_si256(eq0, eq1)) != 0 {
+                let mask = _mm256_movemask_epi8(eq0) as u32 as u64
+                    | (_mm256_movemask_epi8(eq1) as u32 as u64) << 32;
+                return Some(block_i * 64 + mask.trailing_zeros() as usize);
+            }
+        }
+        haystack[rest_start..]
+            .iter()
+            .position(|&b| b == needle)
+            .map(|i| rest_start + i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memchr() {
+        let mut haystack = vec![b'a'; 300];
+        assert_eq!(memchr(b'\n', &haystack), None);
+        for i in [0, 15, 16, 63, 64, 200, 255, 256, 299] {
+            haystack[i] = b'\n';
+            assert_eq!(memchr(b'\n', &haystack), Some(i), "{i}");
+            #[cfg(target_arch = "x86_64")]
+            {
+                assert_eq!(x86_64::sse2(b'\n', &haystack), Some(i));
+                if x86_64::can_run_avx2() {
+                    assert_eq!(unsafe { x86_64::avx2(b'\n', &haystack) }, Some(i));
+                }
+            }
+            haystack[i] = b'a';
+        }
+    }
+}
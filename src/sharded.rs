@@ -0,0 +1,202 @@
+//! A `LineIndex` split into shards, each covering a contiguous byte range and built by its own
+//! thread. Shards keep their own (shard-relative) offsets, so building in parallel never needs a
+//! final merge; the small directory here is enough to answer queries across shards.
+//!
+//! [`ShardedLines`] does the same for slices.
+
+use crate::chunks;
+use crate::compressed::{self, LineIndex, NewlineIndex};
+use crate::slice;
+use std::ops::Index;
+
+#[derive(Default)]
+pub struct ShardedLineIndex {
+    pub shards: Vec<LineIndex>,
+    /// Byte offset where each shard starts
+    pub byte_starts: Vec<usize>,
+    /// Number of newlines in all shards before each shard
+    pub newline_starts: Vec<usize>,
+}
+
+impl ShardedLineIndex {
+    /// Builds `shard_count` shards of roughly equal size, one thread each
+    pub fn build(input: &str, shard_count: usize) -> Self {
+        let shard_len = input.len().div_ceil(shard_count.max(1)).max(1);
+        let mut byte_starts = vec![0];
+        while let Some(&last) = byte_starts.last() {
+            let mut next = last + shard_len;
+            if next >= input.len() {
+                break;
+            }
+            while !input.is_char_boundary(next) {
+                next += 1;
+            }
+            byte_starts.push(next);
+        }
+        let shards: Vec<LineIndex> = std::thread::scope(|scope| {
+            let handles: Vec<_> = byte_starts
+                .iter()
+                .enumerate()
+                .map(|(i, &start)| {
+                    let end = byte_starts.get(i + 1).copied().unwrap_or(input.len());
+                    let shard_input = &input[start..end];
+                    scope.spawn(move || {
+                        let mut shard = LineIndex::default();
+                        compressed::build(shard_input, &mut shard);
+                        shard
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let newline_starts = shards
+            .iter()
+            .scan(0, |count, shard| {
+                let start = *count;
+                *count += shard.lows.len();
+                Some(start)
+            })
+            .collect();
+        ShardedLineIndex {
+            shards,
+            byte_starts,
+            newline_starts,
+        }
+    }
+}
+
+impl NewlineIndex for ShardedLineIndex {
+    fn len(&self) -> usize {
+        self.newline_starts
+            .last()
+            .map_or(0, |&start| start + self.shards.last().unwrap().lows.len())
+    }
+
+    fn newline(&self, i: usize) -> usize {
+        // same trick as `LineIndex::newline`: empty shards share a start with the next one
+        let shard = self.newline_starts.partition_point(|&start| start <= i) - 1;
+        self.byte_starts[shard] + self.shards[shard].newline(i - self.newline_starts[shard])
+    }
+}
+
+/// Slices from one thread per shard, left in the per-shard Vecs they were split into. Reads like
+/// one `Vec<&str>` (indexing and iteration), without the final concatenation, which copies every
+/// slice once more.
+///
+/// ```
+/// use split_bench::sharded::ShardedLines;
+///
+/// let input = "line\n".repeat(1000);
+/// let mut lines = ShardedLines::default();
+/// lines.split(&input, 4);
+/// assert_eq!(lines.line_count(), 1000);
+/// assert_eq!(lines[999], "line");
+/// assert!(lines.iter().all(|line| line == "line"));
+/// ```
+#[derive(Default)]
+pub struct ShardedLines<'input> {
+    shards: Vec<Vec<&'input str>>,
+    /// Number of lines in all shards before each shard
+    line_starts: Vec<usize>,
+}
+
+impl<'input> ShardedLines<'input> {
+    /// Splits `input` into about `shard_count` shards, one thread each, reusing the shards' Vecs.
+    /// Shards are cut just after a newline (see `chunks`), so no line straddles two of them and
+    /// the boundaries need no fix-ups.
+    pub fn split(&mut self, input: &'input str, shard_count: usize) {
+        let shard_len = input.len().div_ceil(shard_count.max(1)).max(1);
+        let shard_inputs = chunks::chunk_at_line_boundaries(input, shard_len);
+        // every shard is non-empty, so it has at least one line
+        self.shards.resize_with(shard_inputs.len(), Vec::new);
+        std::thread::scope(|scope| {
+            for (shard, shard_input) in self.shards.iter_mut().zip(shard_inputs) {
+                scope.spawn(move || {
+                    shard.clear();
+                    slice::split(shard_input, shard);
+                });
+            }
+        });
+        self.line_starts.clear();
+        let mut count = 0;
+        for shard in &self.shards {
+            self.line_starts.push(count);
+            count += shard.len();
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts
+            .last()
+            .map_or(0, |&start| start + self.shards.last().unwrap().len())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'input str> + '_ {
+        self.shards.iter().flatten().copied()
+    }
+
+    /// The copying alternative: appends every line to `out`
+    pub fn concat(&self, out: &mut Vec<&'input str>) {
+        out.reserve(self.line_count());
+        for shard in &self.shards {
+            out.extend_from_slice(shard);
+        }
+    }
+}
+
+impl<'input> Index<usize> for ShardedLines<'input> {
+    type Output = &'input str;
+
+    fn index(&self, i: usize) -> &&'input str {
+        assert!(i < self.line_count(), "line {i} out of bounds");
+        let shard = self.line_starts.partition_point(|&start| start <= i) - 1;
+        &self.shards[shard][i - self.line_starts[shard]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_line_index() {
+        let long = "\u{e9}\u{e9}\u{e9}\n\n0123456789".repeat(30_000);
+        for input in ["", "\n", "ab", "a\nb\n", &long] {
+            let mut expected = LineIndex::default();
+            compressed::iter(input, &mut expected);
+            for shard_count in [1, 2, 3, 7, 64] {
+                let sharded = ShardedLineIndex::build(input, shard_count);
+                assert_eq!(sharded.len(), expected.lows.len());
+                assert_eq!(
+                    sharded.line_count(input.len()),
+                    expected.line_count(input.len())
+                );
+                for i in 0..sharded.line_count(input.len()) {
+                    assert_eq!(sharded.line(input, i), expected.line(input, i));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sharded_lines() {
+        let long = "\u{e9}\u{e9}\u{e9}\n\n0123456789".repeat(30_000);
+        let mut sharded = ShardedLines::default();
+        for input in ["", "\n", "ab", "a\nb\n", &long] {
+            let mut expected = Vec::new();
+            slice::split(input, &mut expected);
+            // shrinking and growing the shard count reuses the same value
+            for shard_count in [1, 64, 2, 3, 7] {
+                sharded.split(input, shard_count);
+                assert_eq!(sharded.line_count(), expected.len());
+                assert!(sharded.iter().eq(expected.iter().copied()));
+                for (i, line) in expected.iter().enumerate() {
+                    assert_eq!(sharded[i], *line);
+                }
+                let mut concat = vec!["x"];
+                sharded.concat(&mut concat);
+                assert_eq!(concat[1..], expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! A `LineIndex` and its input behind one `Arc`, for services that answer line queries from many
+//! request handlers. Cloning a [`SharedLines`] bumps a reference count, and every clone reads the
+//! same index, which only holds plain `Vec`s, so lookups need no lock.
+
+use crate::compressed::{self, LineIndex, NewlineIndex};
+use std::sync::Arc;
+
+pub struct SharedLines<S> {
+    inner: Arc<Shared<S>>,
+}
+
+struct Shared<S> {
+    input: S,
+    index: LineIndex,
+    line_count: usize,
+}
+
+impl<S: AsRef<str>> SharedLines<S> {
+    /// Indexes `input` with the fastest kernel the CPU supports
+    pub fn new(input: S) -> Self {
+        let mut index = LineIndex::default();
+        compressed::build(input.as_ref(), &mut index);
+        index.shrink_to_fit();
+        let line_count = index.line_count(input.as_ref().len());
+        SharedLines {
+            inner: Arc::new(Shared {
+                input,
+                index,
+                line_count,
+            }),
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.inner.line_count
+    }
+
+    pub fn line(&self, i: usize) -> Option<&str> {
+        let Shared {
+            input,
+            index,
+            line_count,
+        } = &*self.inner;
+        (i < *line_count).then(|| index.line(input.as_ref(), i))
+    }
+}
+
+// not derived, which would require `S: Clone`
+impl<S> Clone for SharedLines<S> {
+    fn clone(&self) -> Self {
+        SharedLines {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_lines() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<LineIndex>();
+        assert_send_sync::<SharedLines<String>>();
+        assert_send_sync::<SharedLines<&str>>();
+
+        let input: String = (0..100_000).map(|i| format!("line {i}\n")).collect();
+        let lines = SharedLines::new(input);
+        assert_eq!(lines.line_count(), 100_000);
+        assert_eq!(lines.line(100_000), None);
+        let readers: Vec<_> = (0..4)
+            .map(|reader| {
+                let lines = lines.clone();
+                std::thread::spawn(move || {
+                    for i in (reader..lines.line_count()).step_by(4) {
+                        assert_eq!(lines.line(i).unwrap(), format!("line {i}"));
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(SharedLines::new("").line(0), None);
+        assert_eq!(SharedLines::new("a\n\nb").line(1), Some(""));
+    }
+}
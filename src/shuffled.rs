@@ -0,0 +1,120 @@
+//! Lines in a given order, like a training-data shuffler reads them. Each line is a cache miss
+//! (often a TLB miss too) the CPU can't see coming, so [`ShuffledLines`] looks the lines up a few
+//! positions ahead of the one it yields and prefetches their first and last bytes, to have several
+//! misses in flight instead of one.
+
+use crate::compressed::NewlineIndex;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// How far ahead [`ShuffledLines`] prefetches, when it does
+pub const PREFETCH_DISTANCE: usize = 16;
+
+pub struct ShuffledLines<'a, I> {
+    input: &'a str,
+    index: &'a I,
+    /// Line numbers, in the order to yield them
+    order: &'a [usize],
+    distance: usize,
+    /// Position in `order` of the next line to look up
+    next: usize,
+    /// Ranges of the lines looked up but not yet yielded
+    ahead: VecDeque<Range<usize>>,
+}
+
+impl<'a, I: NewlineIndex> ShuffledLines<'a, I> {
+    /// Yields `input`'s lines in `order`, prefetching `distance` lines ahead (0 doesn't prefetch)
+    pub fn new(input: &'a str, index: &'a I, order: &'a [usize], distance: usize) -> Self {
+        let mut lines = ShuffledLines {
+            input,
+            index,
+            order,
+            distance,
+            next: 0,
+            ahead: VecDeque::with_capacity(distance + 1),
+        };
+        for _ in 0..distance {
+            lines.look_up_next();
+        }
+        lines
+    }
+
+    fn look_up_next(&mut self) {
+        let Some(&line) = self.order.get(self.next) else {
+            return;
+        };
+        self.next += 1;
+        let range = self.index.line_range(line, self.input.len());
+        if self.distance > 0 && !range.is_empty() {
+            prefetch(self.input.as_ptr().wrapping_add(range.start));
+            prefetch(self.input.as_ptr().wrapping_add(range.end - 1));
+        }
+        self.ahead.push_back(range);
+    }
+}
+
+impl<'a, I: NewlineIndex> Iterator for ShuffledLines<'a, I> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.look_up_next();
+        let range = self.ahead.pop_front()?;
+        Some(&self.input[range])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.order.len() - self.next + self.ahead.len();
+        (left, Some(left))
+    }
+}
+
+/// Asks for the cache line at `ptr`, without waiting for it
+#[inline(always)]
+fn prefetch(ptr: *const u8) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::*;
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast())
+    };
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        std::arch::asm!(
+            "prfm pldl1keep, [{ptr}]",
+            ptr = in(reg) ptr,
+            options(nostack, readonly, preserves_flags)
+        )
+    };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = ptr;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressed::LineIndex;
+    use crate::rng::Rng;
+
+    #[test]
+    fn test_shuffled_lines() {
+        let input: String = (0..1000)
+            .map(|i| format!("line {i}\n\n"))
+            .collect::<String>()
+            + "last";
+        let mut index = LineIndex::default();
+        crate::compressed::iter(&input, &mut index);
+        let lines: Vec<&str> = input.split('\n').collect();
+        let mut order: Vec<usize> = (0..lines.len()).collect();
+        Rng::new(1).shuffle(&mut order);
+        let expected: Vec<&str> = order.iter().map(|&i| lines[i]).collect();
+        for distance in [0, 1, PREFETCH_DISTANCE, 5000] {
+            let shuffled = ShuffledLines::new(&input, &index, &order, distance);
+            assert_eq!(shuffled.size_hint().0, order.len());
+            assert_eq!(
+                shuffled.collect::<Vec<_>>(),
+                expected,
+                "distance {distance}"
+            );
+        }
+        assert_eq!(ShuffledLines::new(&input, &index, &[], 4).next(), None);
+    }
+}
@@ -0,0 +1,254 @@
+//! [`SimdLines`], an iterator over the same lines as `slice::split` that finds them a 64-byte
+//! chunk at a time, like the kernels, without a `Vec` to collect them in. It holds the newline
+//! mask of the current chunk and yields one line per set bit, so a consumer that stops early
+//! scans no further than it read. It's double-ended, scanning a second chunk back from the end
+//! for `next_back` (the last lines of a log, say), and the two meet in the middle. The chunk
+//! compares are SSE2, part of the x86_64 baseline, so they inline into `next` with no feature
+//! check, and simd128 in wasm builds that enable it; other targets build the mask a byte at a
+//! time.
+
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+
+const CHUNK: usize = 64;
+
+/// ```
+/// use split_bench::simd_lines::SimdLines;
+///
+/// let mut lines = SimdLines::new("one\ntwo\nthree\nfour\n");
+/// assert_eq!(lines.next(), Some("one"));
+/// // from the other end, without scanning the lines in between
+/// assert_eq!(lines.next_back(), Some("four"));
+/// assert_eq!(lines.remainder(), "two\nthree");
+/// assert_eq!(lines.collect::<Vec<_>>(), ["two", "three"]);
+/// ```
+pub struct SimdLines<'input> {
+    input: &'input str,
+    /// Start of the first line not yielded yet
+    line_start: usize,
+    /// End of the last line not yielded yet, before its newline if it has one. The lines left are
+    /// those of `input[line_start..line_end]`, split on every newline.
+    line_end: usize,
+    /// Set once the last line left was yielded, from either end
+    finished: bool,
+    /// Offset of the chunk `mask` was found in
+    chunk_start: usize,
+    /// Newlines of that chunk not yielded yet, bit i for byte `chunk_start + i`
+    mask: u64,
+    /// Offset of the next chunk to scan
+    next_chunk: usize,
+    /// The same for `next_back`, which scans backwards from the end of the input, so its chunks
+    /// end where the previous one started
+    back_chunk_start: usize,
+    back_mask: u64,
+}
+
+impl<'input> SimdLines<'input> {
+    pub fn new(input: &'input str) -> Self {
+        // a trailing newline doesn't start another line
+        let line_end = input.len() - input.ends_with('\n') as usize;
+        SimdLines {
+            input,
+            line_start: 0,
+            line_end,
+            finished: input.is_empty(),
+            chunk_start: 0,
+            mask: 0,
+            next_chunk: 0,
+            back_chunk_start: line_end,
+            back_mask: 0,
+        }
+    }
+
+    /// The lines not yielded yet, without the last one's newline
+    pub fn remainder(&self) -> &'input str {
+        match self.finished {
+            true => "",
+            false => &self.input[self.line_start..self.line_end],
+        }
+    }
+
+    /// The last line left, once there's no newline between its ends, then `None`. Neither end
+    /// scans again after it: their masks are cleared and their next chunks are past the other end.
+    fn finish(&mut self) -> Option<&'input str> {
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        let line = &self.input[self.line_start..self.line_end];
+        (self.mask, self.back_mask) = (0, 0);
+        self.next_chunk = self.input.len();
+        self.back_chunk_start = 0;
+        Some(line)
+    }
+}
+
+impl<'input> Iterator for SimdLines<'input> {
+    type Item = &'input str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'input str> {
+        loop {
+            if self.mask != 0 {
+                let newline = self.chunk_start + self.mask.trailing_zeros() as usize;
+                self.mask &= self.mask - 1;
+                if newline >= self.line_end {
+                    // already passed by `next_back`
+                    return self.finish();
+                }
+                // both ends are next to a newline or at the ends of the input, so on char
+                // boundaries
+                let line = unsafe { self.input.get_unchecked(self.line_start..newline) };
+                self.line_start = newline + 1;
+                return Some(line);
+            }
+            if self.next_chunk >= self.line_end {
+                return self.finish();
+            }
+            self.mask = newline_mask(&self.input.as_bytes()[self.next_chunk..]);
+            self.chunk_start = self.next_chunk;
+            self.next_chunk += CHUNK;
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.finished {
+            true => (0, Some(0)),
+            // at least the line without a newline at the end, and a line per newline at most
+            false => (1, Some(self.line_end - self.line_start + 1)),
+        }
+    }
+}
+
+impl<'input> DoubleEndedIterator for SimdLines<'input> {
+    /// Takes the highest bit of each chunk's mask first, the last newline in it, and scans the
+    /// chunk ending where the previous one started once they're all taken. The first chunk of
+    /// the input, the last one scanned, is shorter if the length isn't a multiple of 64.
+    #[inline]
+    fn next_back(&mut self) -> Option<&'input str> {
+        loop {
+            if self.back_mask != 0 {
+                let bit_pos = 63 - self.back_mask.leading_zeros() as usize;
+                self.back_mask ^= 1 << bit_pos;
+                let newline = self.back_chunk_start + bit_pos;
+                if newline < self.line_start {
+                    // already passed by `next`
+                    return self.finish();
+                }
+                let line = unsafe { self.input.get_unchecked(newline + 1..self.line_end) };
+                self.line_end = newline;
+                return Some(line);
+            }
+            if self.back_chunk_start <= self.line_start {
+                return self.finish();
+            }
+            let chunk_end = self.back_chunk_start;
+            self.back_chunk_start = chunk_end.saturating_sub(CHUNK);
+            self.back_mask = newline_mask(&self.input.as_bytes()[self.back_chunk_start..chunk_end]);
+        }
+    }
+}
+
+impl FusedIterator for SimdLines<'_> {}
+
+/// Pushes the lines of [`SimdLines`] onto `out`, for the slice table
+pub fn split<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    out.extend(SimdLines::new(input))
+}
+
+/// Newline mask of the first 64 bytes of `rest`, or all of it if it's shorter
+#[inline(always)]
+fn newline_mask(rest: &[u8]) -> u64 {
+    if rest.len() < CHUNK {
+        // zero padding never matches
+        let mut buf = [0u8; CHUNK];
+        buf[..rest.len()].copy_from_slice(rest);
+        return chunk_mask(&buf);
+    }
+    chunk_mask(rest[..CHUNK].try_into().unwrap())
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn chunk_mask(chunk: &[u8; CHUNK]) -> u64 {
+    use core::arch::x86_64::*;
+    unsafe {
+        let nl_v = _mm_set1_epi8(b'\n' as i8);
+        let quarter = |i: usize| {
+            let v = _mm_loadu_si128(chunk.as_ptr().add(i * 16).cast());
+            _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16 as u64
+        };
+        quarter(0) | quarter(1) << 16 | quarter(2) << 32 | quarter(3) << 48
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+#[inline(always)]
+fn chunk_mask(chunk: &[u8; CHUNK]) -> u64 {
+    use core::arch::wasm32::*;
+    let nl_v = u8x16_splat(b'\n');
+    let quarter = |i: usize| unsafe {
+        let v = v128_load(chunk.as_ptr().add(i * 16).cast());
+        i8x16_bitmask(i8x16_eq(v, nl_v)) as u64
+    };
+    quarter(0) | quarter(1) << 16 | quarter(2) << 32 | quarter(3) << 48
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(target_arch = "wasm32", target_feature = "simd128")
+)))]
+#[inline(always)]
+fn chunk_mask(chunk: &[u8; CHUNK]) -> u64 {
+    chunk
+        .iter()
+        .enumerate()
+        .fold(0, |mask, (i, &b)| mask | ((b == b'\n') as u64) << i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simd_lines() {
+        let long = "a\n\nbc\u{e9}\n".repeat(40) + &"\n".repeat(64) + "de";
+        for len in (0..=200).chain([long.len() - 1, long.len()]) {
+            let Some(input) = long.get(..len) else {
+                continue;
+            };
+            crate::guard::with_guard_page(input, |input| {
+                let expected = input.split_terminator('\n').collect::<Vec<_>>();
+                assert_eq!(SimdLines::new(input).collect::<Vec<_>>(), expected);
+                let mut lines = SimdLines::new(input);
+                for (i, line) in expected.iter().enumerate() {
+                    let (low, high) = lines.size_hint();
+                    assert!(low <= expected.len() - i && high >= Some(expected.len() - i));
+                    assert_eq!(lines.next(), Some(*line));
+                }
+                assert_eq!(lines.remainder(), "");
+                assert_eq!(lines.next(), None);
+                assert_eq!(lines.next_back(), None);
+
+                let reversed = SimdLines::new(input).rev().collect::<Vec<_>>();
+                assert!(reversed.iter().eq(expected.iter().rev()), "len {len}");
+                // the first `front` lines from the front and the rest from the back, then both
+                // ends in turn
+                for front in 0..=expected.len() {
+                    let mut lines = SimdLines::new(input);
+                    let mut back = lines.by_ref().take(front).collect::<Vec<_>>();
+                    back.extend(lines.rev().collect::<Vec<_>>().into_iter().rev());
+                    assert_eq!(back, expected, "len {len}, {front} from the front");
+                }
+                let mut lines = SimdLines::new(input);
+                let (mut head, mut tail) = (Vec::new(), Vec::new());
+                while let Some(line) = lines.next() {
+                    head.push(line);
+                    tail.extend(lines.next_back());
+                }
+                head.extend(tail.into_iter().rev());
+                assert_eq!(head, expected, "len {len}, alternating");
+            });
+        }
+    }
+}
@@ -0,0 +1,738 @@
+//! One kernel family for every output. The `slice`, `compressed` and `flat` kernels each repeat
+//! the same scan and differ only in what they store per newline; here the scan is written once,
+//! generic over a [`LineSink`] that stores it, and monomorphized per sink, so `Vec<&str>`,
+//! `LineIndex`, flat `Vec<u32>` offsets, `Vec<Range<u32>>` lines and a bare [`LineCount`] all
+//! get the same SIMD loop. A sink pushes one newline at a time, where the hand-written kernels
+//! write into spare capacity without a length update per line, so the sink rows of the tables
+//! show what that costs. Only `&str` lines need UTF-8 input; the other sinks, and `Vec<&[u8]>`,
+//! take any bytes.
+
+use crate::compressed::LineIndex;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// What a kernel scans, `str` or `[u8]`
+pub trait Input {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl Input for str {
+    #[inline(always)]
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl Input for [u8] {
+    #[inline(always)]
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Where a kernel puts what it finds. The kernel tracks where the current line starts, so a
+/// sink that only stores newline offsets can ignore it.
+pub trait LineSink<'input> {
+    /// `str` for a sink of `&str` lines, which has to be given valid UTF-8, `[u8]` for the others,
+    /// which take any bytes, so a log or binary data can be split without validating it first
+    type Input: Input + ?Sized;
+
+    /// The line `input[line_start..newline]`, followed by a newline
+    fn line(&mut self, input: &'input Self::Input, line_start: usize, newline: usize);
+
+    /// Called once the whole input was scanned, with the start of the line after the last
+    /// newline, which is empty if the input ends with a newline
+    fn finish(&mut self, input: &'input Self::Input, line_start: usize);
+}
+
+impl<'input> LineSink<'input> for Vec<&'input str> {
+    type Input = str;
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input str, line_start: usize, newline: usize) {
+        // both ends are next to a newline or at the start, so on char boundaries
+        self.push(unsafe { input.get_unchecked(line_start..newline) });
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input str, line_start: usize) {
+        if line_start != input.len() {
+            self.push(unsafe { input.get_unchecked(line_start..) });
+        }
+    }
+}
+
+impl<'input> LineSink<'input> for Vec<&'input [u8]> {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input [u8], line_start: usize, newline: usize) {
+        self.push(unsafe { input.get_unchecked(line_start..newline) });
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input [u8], line_start: usize) {
+        if line_start != input.len() {
+            self.push(unsafe { input.get_unchecked(line_start..) });
+        }
+    }
+}
+
+/// Lines with their newline kept, like `str::split_inclusive('\n')`, so they concatenate back
+/// to the input byte for byte. Pushes `&str` or `&[u8]` lines onto the `Vec` it borrows.
+pub struct Inclusive<'out, T>(pub &'out mut Vec<T>);
+
+impl<'input> LineSink<'input> for Inclusive<'_, &'input str> {
+    type Input = str;
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input str, line_start: usize, newline: usize) {
+        // a newline is one byte, so the end is still a char boundary
+        self.0
+            .push(unsafe { input.get_unchecked(line_start..newline + 1) });
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input str, line_start: usize) {
+        self.0.finish(input, line_start)
+    }
+}
+
+impl<'input> LineSink<'input> for Inclusive<'_, &'input [u8]> {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input [u8], line_start: usize, newline: usize) {
+        self.0
+            .push(unsafe { input.get_unchecked(line_start..newline + 1) });
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input [u8], line_start: usize) {
+        self.0.finish(input, line_start)
+    }
+}
+
+/// Lines without the `\r` of a `\r\n`, like `str::lines`. A `\r` not followed by a newline
+/// stays, at the end of the input too. Pushes `&str` or `&[u8]` lines onto the `Vec` it borrows.
+pub struct Crlf<'out, T>(pub &'out mut Vec<T>);
+
+/// `newline`, or the `\r` before it
+#[inline(always)]
+fn crlf_line_end(bytes: &[u8], line_start: usize, newline: usize) -> usize {
+    newline - (newline > line_start && bytes[newline - 1] == b'\r') as usize
+}
+
+impl<'input> LineSink<'input> for Crlf<'_, &'input str> {
+    type Input = str;
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input str, line_start: usize, newline: usize) {
+        let end = crlf_line_end(input.as_bytes(), line_start, newline);
+        // `\r` is one byte, so the end is still a char boundary
+        self.0.push(unsafe { input.get_unchecked(line_start..end) });
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input str, line_start: usize) {
+        self.0.finish(input, line_start)
+    }
+}
+
+impl<'input> LineSink<'input> for Crlf<'_, &'input [u8]> {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, input: &'input [u8], line_start: usize, newline: usize) {
+        let end = crlf_line_end(input, line_start, newline);
+        self.0.push(unsafe { input.get_unchecked(line_start..end) });
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &'input [u8], line_start: usize) {
+        self.0.finish(input, line_start)
+    }
+}
+
+impl LineSink<'_> for LineIndex {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, _: &[u8], _: usize, newline: usize) {
+        // a block starts where its first newline would go, blocks without any included
+        while self.high_starts.len() <= newline >> 16 {
+            self.high_starts.push(self.lows.len());
+        }
+        self.lows.push(newline as u16);
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &[u8], _: usize) {
+        while self.high_starts.len() < input.len().div_ceil(1 << 16) {
+            self.high_starts.push(self.lows.len());
+        }
+    }
+}
+
+impl LineSink<'_> for Vec<u32> {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, _: &[u8], _: usize, newline: usize) {
+        self.push(newline as u32);
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &[u8], _: usize) {
+        assert!(input.len() <= u32::MAX as usize);
+    }
+}
+
+impl LineSink<'_> for Vec<Range<u32>> {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, _: &[u8], line_start: usize, newline: usize) {
+        self.push(line_start as u32..newline as u32);
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &[u8], line_start: usize) {
+        assert!(input.len() <= u32::MAX as usize);
+        if line_start != input.len() {
+            self.push(line_start as u32..input.len() as u32);
+        }
+    }
+}
+
+/// Counts the lines, the way the slice kernels would find them
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LineCount(pub usize);
+
+impl LineSink<'_> for LineCount {
+    type Input = [u8];
+
+    #[inline(always)]
+    fn line(&mut self, _: &[u8], _: usize, _: usize) {
+        self.0 += 1;
+    }
+
+    #[inline(always)]
+    fn finish(&mut self, input: &[u8], line_start: usize) {
+        self.0 += (line_start != input.len()) as usize;
+    }
+}
+
+/// Scans with the fastest kernel the current CPU supports
+pub fn split<'input, S: LineSink<'input>>(input: &'input S::Input, sink: &mut S) {
+    split_on::<b'\n', S>(input, sink)
+}
+
+/// [`split`] on another byte, like a tab, `;` or `\0`, which the sink then sees as the newline.
+/// It has to be ASCII, checked at compile time, so it can't be part of a char and `&str` lines
+/// still end on char boundaries.
+pub fn split_on<'input, const DELIM: u8, S: LineSink<'input>>(
+    input: &'input S::Input,
+    sink: &mut S,
+) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        unsafe { x86_64::avx2_on::<DELIM, S>(input, sink) }
+    } else {
+        x86_64::sse2_on::<DELIM, S>(input, sink)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    scalar_on::<DELIM, S>(input, sink)
+}
+
+pub fn scalar<'input, S: LineSink<'input>>(input: &'input S::Input, sink: &mut S) {
+    scalar_on::<b'\n', S>(input, sink)
+}
+
+pub fn scalar_on<'input, const DELIM: u8, S: LineSink<'input>>(
+    input: &'input S::Input,
+    sink: &mut S,
+) {
+    const { assert!(DELIM.is_ascii(), "the delimiter must be ASCII") };
+    let mut line_start = 0;
+    for (newline, _) in input
+        .as_bytes()
+        .iter()
+        .enumerate()
+        .filter(|e| *e.1 == DELIM)
+    {
+        sink.line(input, line_start, newline);
+        line_start = newline + 1;
+    }
+    sink.finish(input, line_start)
+}
+
+/// A set of 1 to 4 ASCII delimiters, any of which ends a line, for [`split_any`]. With
+/// [`Delims::with_crlf`], a `\r` right before a `\n` isn't one, so `\r\n`, `\r` and `\n` each
+/// end one line, as in old Mac and mixed-ending files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Delims {
+    /// The unused slots repeat the first, so the kernels always make 4 compares
+    bytes: [u8; 4],
+    crlf: bool,
+}
+
+impl Delims {
+    /// `\n`, `\r` and `\r\n`
+    pub const LINE_ENDINGS: Delims = Delims::new(b"\n\r").with_crlf();
+
+    pub const fn new(delims: &[u8]) -> Self {
+        assert!(!delims.is_empty() && delims.len() <= 4, "1 to 4 delimiters");
+        let mut bytes = [delims[0]; 4];
+        let mut i = 0;
+        while i < delims.len() {
+            assert!(delims[i].is_ascii(), "the delimiters must be ASCII");
+            bytes[i] = delims[i];
+            i += 1;
+        }
+        Delims { bytes, crlf: false }
+    }
+
+    /// Makes `\r\n` one delimiter, the `\n`, which the line ends at; a [`Crlf`] sink drops the
+    /// `\r` from it. Both have to be in the set.
+    pub const fn with_crlf(self) -> Self {
+        assert!(
+            self.contains(b'\r') && self.contains(b'\n'),
+            "`\\r\\n` needs both `\\r` and `\\n`"
+        );
+        Delims { crlf: true, ..self }
+    }
+
+    #[inline(always)]
+    pub const fn contains(&self, byte: u8) -> bool {
+        let [a, b, c, d] = self.bytes;
+        byte == a || byte == b || byte == c || byte == d
+    }
+
+    /// `mask` without the `\r`s of `\r\n`s, given the chunk's `\r`s and `\n`s, and whether the
+    /// byte after it is a `\n`
+    #[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+    #[inline(always)]
+    fn merge_crlf(&self, mask: u64, cr: u64, nl: u64, next_nl: bool) -> u64 {
+        match self.crlf {
+            true => mask & !(cr & (nl >> 1 | (next_nl as u64) << 63)),
+            false => mask,
+        }
+    }
+}
+
+/// Scans for any of `delims` with the fastest kernel the current CPU supports
+pub fn split_any<'input, S: LineSink<'input>>(
+    input: &'input S::Input,
+    delims: &Delims,
+    sink: &mut S,
+) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        unsafe { x86_64::avx2_any(input, delims, sink) }
+    } else {
+        x86_64::sse2_any(input, delims, sink)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    scalar_any(input, delims, sink)
+}
+
+pub fn scalar_any<'input, S: LineSink<'input>>(
+    input: &'input S::Input,
+    delims: &Delims,
+    sink: &mut S,
+) {
+    let bytes = input.as_bytes();
+    let mut line_start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let crlf = delims.crlf && b == b'\r' && bytes.get(i + 1) == Some(&b'\n');
+        if delims.contains(b) && !crlf {
+            sink.line(input, line_start, i);
+            line_start = i + 1;
+        }
+    }
+    sink.finish(input, line_start)
+}
+
+/// The chunk loop of the `_any` kernels: `masks` gives the delimiters, `\r`s and `\n`s of 64
+/// bytes, the tail's through a copy padded with a byte that isn't ASCII
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+#[inline(always)]
+fn scan_any<'input, S: LineSink<'input>>(
+    input: &'input S::Input,
+    delims: &Delims,
+    sink: &mut S,
+    masks: impl Fn(&[u8]) -> [u64; 3],
+) {
+    let bytes = input.as_bytes();
+    let mut line_start = 0;
+    let mut chunks = bytes.chunks_exact(64);
+    for (chunk_i, chunk) in chunks.by_ref().enumerate() {
+        let [any, cr, nl] = masks(chunk);
+        let next_nl = bytes.get((chunk_i + 1) * 64) == Some(&b'\n');
+        let mask = delims.merge_crlf(any, cr, nl, next_nl);
+        line_start = drain(input, sink, line_start, chunk_i * 64, mask);
+    }
+    let rest = chunks.remainder();
+    let mut buf = [0x80; 64];
+    buf[..rest.len()].copy_from_slice(rest);
+    let [any, cr, nl] = masks(&buf);
+    let mask = delims.merge_crlf(any, cr, nl, false);
+    let line_start = drain(input, sink, line_start, bytes.len() - rest.len(), mask);
+    sink.finish(input, line_start)
+}
+
+/// Hands the newlines of a 64-byte chunk at `base` to `sink`, returning the next line start
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+#[inline(always)]
+fn drain<'input, S: LineSink<'input>>(
+    input: &'input S::Input,
+    sink: &mut S,
+    mut line_start: usize,
+    base: usize,
+    mut mask: u64,
+) -> usize {
+    while mask != 0 {
+        let newline = base + mask.trailing_zeros() as usize;
+        sink.line(input, line_start, newline);
+        line_start = newline + 1;
+        mask &= mask - 1;
+    }
+    line_start
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use core::arch::x86_64::*;
+
+    /// Four 16-byte compares per 64-byte chunk, the tail included, through a padded copy
+    pub fn sse2<'input, S: LineSink<'input>>(input: &'input S::Input, sink: &mut S) {
+        sse2_on::<b'\n', S>(input, sink)
+    }
+
+    pub fn sse2_on<'input, const DELIM: u8, S: LineSink<'input>>(
+        input: &'input S::Input,
+        sink: &mut S,
+    ) {
+        const { assert!(DELIM.is_ascii(), "the delimiter must be ASCII") };
+        #[inline(always)]
+        fn mask<const DELIM: u8>(chunk: &[u8]) -> u64 {
+            unsafe {
+                let nl_v = _mm_set1_epi8(DELIM as i8);
+                let quarter = |i: usize| {
+                    let v = _mm_loadu_si128(chunk.as_ptr().add(i * 16).cast());
+                    _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16 as u64
+                };
+                quarter(0) | quarter(1) << 16 | quarter(2) << 32 | quarter(3) << 48
+            }
+        }
+        let mut line_start = 0;
+        let bytes = input.as_bytes();
+        let mut chunks = bytes.chunks_exact(64);
+        for (chunk_i, chunk) in chunks.by_ref().enumerate() {
+            line_start = drain(input, sink, line_start, chunk_i * 64, mask::<DELIM>(chunk));
+        }
+        let rest = chunks.remainder();
+        let mut buf = [!DELIM; 64];
+        buf[..rest.len()].copy_from_slice(rest);
+        let line_start = drain(
+            input,
+            sink,
+            line_start,
+            bytes.len() - rest.len(),
+            mask::<DELIM>(&buf),
+        );
+        sink.finish(input, line_start)
+    }
+
+    /// Four compares ORed together per 16 bytes, and the `\r` and `\n` ones kept apart too for
+    /// `\r\n`
+    pub fn sse2_any<'input, S: LineSink<'input>>(
+        input: &'input S::Input,
+        delims: &Delims,
+        sink: &mut S,
+    ) {
+        let delim_vs = delims.bytes.map(|b| unsafe { _mm_set1_epi8(b as i8) });
+        let (cr_v, nl_v) = unsafe { (_mm_set1_epi8(b'\r' as i8), _mm_set1_epi8(b'\n' as i8)) };
+        scan_any(input, delims, sink, |chunk| {
+            let mut masks = [0; 3];
+            for i in 0..4 {
+                unsafe {
+                    let v = _mm_loadu_si128(chunk.as_ptr().add(i * 16).cast());
+                    let eq = |d| _mm_cmpeq_epi8(v, d);
+                    let any = _mm_or_si128(
+                        _mm_or_si128(eq(delim_vs[0]), eq(delim_vs[1])),
+                        _mm_or_si128(eq(delim_vs[2]), eq(delim_vs[3])),
+                    );
+                    let bits = |v| (_mm_movemask_epi8(v) as u16 as u64) << (i * 16);
+                    masks[0] |= bits(any);
+                    if delims.crlf {
+                        masks[1] |= bits(eq(cr_v));
+                        masks[2] |= bits(eq(nl_v));
+                    }
+                }
+            }
+            masks
+        })
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_any<'input, S: LineSink<'input>>(
+        input: &'input S::Input,
+        delims: &Delims,
+        sink: &mut S,
+    ) {
+        let delim_vs = delims.bytes.map(|b| _mm256_set1_epi8(b as i8));
+        let (cr_v, nl_v) = (_mm256_set1_epi8(b'\r' as i8), _mm256_set1_epi8(b'\n' as i8));
+        scan_any(input, delims, sink, |chunk| {
+            let mut masks = [0; 3];
+            for i in 0..2 {
+                let v = unsafe { _mm256_loadu_si256(chunk.as_ptr().add(i * 32).cast()) };
+                let eq = |d| _mm256_cmpeq_epi8(v, d);
+                let any = _mm256_or_si256(
+                    _mm256_or_si256(eq(delim_vs[0]), eq(delim_vs[1])),
+                    _mm256_or_si256(eq(delim_vs[2]), eq(delim_vs[3])),
+                );
+                let bits = |v| (_mm256_movemask_epi8(v) as u32 as u64) << (i * 32);
+                masks[0] |= bits(any);
+                if delims.crlf {
+                    masks[1] |= bits(eq(cr_v));
+                    masks[2] |= bits(eq(nl_v));
+                }
+            }
+            masks
+        })
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2<'input, S: LineSink<'input>>(input: &'input S::Input, sink: &mut S) {
+        avx2_on::<b'\n', S>(input, sink)
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_on<'input, const DELIM: u8, S: LineSink<'input>>(
+        input: &'input S::Input,
+        sink: &mut S,
+    ) {
+        const { assert!(DELIM.is_ascii(), "the delimiter must be ASCII") };
+        let bytes = input.as_bytes();
+        let nl_v = _mm256_set1_epi8(DELIM as i8);
+        let mut line_start = 0;
+        let stop_chunk_i = bytes.len() / 64;
+        for chunk_i in 0..stop_chunk_i {
+            let ptr = bytes.as_ptr().add(chunk_i * 64);
+            let v1 = _mm256_loadu_si256(ptr.cast());
+            let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+            let mask = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64) << 32)
+                | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+            line_start = drain(input, sink, line_start, chunk_i * 64, mask);
+        }
+        let base = stop_chunk_i * 64;
+        let mask = crate::slice::x86_64::delim_mask_copied::<DELIM>(&bytes[base..]);
+        let line_start = drain(input, sink, line_start, base, mask);
+        sink.finish(input, line_start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Kernels are generic, so they're picked by name for each sink
+    fn run<'input, S: LineSink<'input>>(kernel: &str, input: &'input S::Input, sink: &mut S) {
+        run_on::<b'\n', S>(kernel, input, sink)
+    }
+
+    fn run_on<'input, const DELIM: u8, S: LineSink<'input>>(
+        kernel: &str,
+        input: &'input S::Input,
+        sink: &mut S,
+    ) {
+        match kernel {
+            "scalar" => scalar_on::<DELIM, S>(input, sink),
+            #[cfg(target_arch = "x86_64")]
+            "sse2" => x86_64::sse2_on::<DELIM, S>(input, sink),
+            #[cfg(target_arch = "x86_64")]
+            "avx2" => unsafe { x86_64::avx2_on::<DELIM, S>(input, sink) },
+            _ => unreachable!(),
+        }
+    }
+
+    fn run_any<'input, S: LineSink<'input>>(
+        kernel: &str,
+        input: &'input S::Input,
+        delims: &Delims,
+        sink: &mut S,
+    ) {
+        match kernel {
+            "scalar" => scalar_any(input, delims, sink),
+            #[cfg(target_arch = "x86_64")]
+            "sse2" => x86_64::sse2_any(input, delims, sink),
+            #[cfg(target_arch = "x86_64")]
+            "avx2" => unsafe { x86_64::avx2_any(input, delims, sink) },
+            _ => unreachable!(),
+        }
+    }
+
+    fn kernels() -> Vec<&'static str> {
+        #[cfg_attr(not(target_arch = "x86_64"), allow(unused_mut))]
+        let mut kernels = vec!["scalar"];
+        #[cfg(target_arch = "x86_64")]
+        {
+            kernels.push("sse2");
+            if crate::slice::x86_64::can_run_avx2() {
+                kernels.push("avx2");
+            }
+        }
+        kernels
+    }
+
+    #[test]
+    fn test_sinks() {
+        let kernels = kernels();
+        // past a 64 KiB block, with empty blocks
+        let long = "a\n\nbc\u{e9}\r\n\r\r\n".repeat(9_000) + &"x".repeat(140_000) + "\nde\r";
+        let mut inputs: Vec<&str> = (0..=130).filter_map(|len| long.get(..len)).collect();
+        inputs.extend([&long[..long.len() - 2], &long[..long.len() - 1], &long]);
+        for input in inputs {
+            crate::guard::with_guard_page(input, |input| {
+                let expected_lines = input.split_terminator('\n').collect::<Vec<_>>();
+                let expected_inclusive = input.split_inclusive('\n').collect::<Vec<_>>();
+                let expected_crlf = input.lines().collect::<Vec<_>>();
+                let mut expected_index = LineIndex::default();
+                crate::compressed::iter(input, &mut expected_index);
+                let mut expected_offsets = Vec::new();
+                crate::flat::iter(input, &mut expected_offsets);
+                for kernel in &kernels {
+                    let len = input.len();
+                    let mut lines: Vec<&str> = Vec::new();
+                    run(kernel, input, &mut lines);
+                    assert_eq!(lines, expected_lines, "{kernel}, len {len}");
+                    let mut index = LineIndex::default();
+                    run(kernel, input.as_bytes(), &mut index);
+                    assert!(index == expected_index, "{kernel}, len {len}");
+                    let mut offsets: Vec<u32> = Vec::new();
+                    run(kernel, input.as_bytes(), &mut offsets);
+                    assert_eq!(offsets, expected_offsets, "{kernel}, len {len}");
+                    let mut ranges: Vec<Range<u32>> = Vec::new();
+                    run(kernel, input.as_bytes(), &mut ranges);
+                    let mut ranged_lines = Vec::new();
+                    crate::ranges::to_slices(input, &ranges, &mut ranged_lines);
+                    assert_eq!(ranged_lines, expected_lines, "{kernel}, len {len}");
+                    let mut count = LineCount::default();
+                    run(kernel, input.as_bytes(), &mut count);
+                    assert_eq!(count.0, expected_lines.len(), "{kernel}, len {len}");
+                    let mut inclusive: Vec<&str> = Vec::new();
+                    run(kernel, input, &mut Inclusive(&mut inclusive));
+                    assert_eq!(inclusive, expected_inclusive, "{kernel}, len {len}");
+                    assert_eq!(inclusive.concat(), input);
+                    let mut crlf: Vec<&str> = Vec::new();
+                    run(kernel, input, &mut Crlf(&mut crlf));
+                    assert_eq!(crlf, expected_crlf, "{kernel}, len {len}");
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        // not UTF-8: stray continuation bytes, and a multibyte char cut by a newline
+        let long = b"\xff\n\n\x80a\xe2\x82\n\xac".repeat(100);
+        for len in 0..=long.len() {
+            let input = &long[..len];
+            let mut expected: Vec<&[u8]> = input.split(|&b| b == b'\n').collect();
+            if expected.last() == Some(&&b""[..]) {
+                expected.pop();
+            }
+            for kernel in kernels() {
+                let mut lines: Vec<&[u8]> = Vec::new();
+                run(kernel, input, &mut lines);
+                assert_eq!(lines, expected, "{kernel}, len {len}");
+                let mut inclusive: Vec<&[u8]> = Vec::new();
+                run(kernel, input, &mut Inclusive(&mut inclusive));
+                let expected_inclusive: Vec<&[u8]> =
+                    input.split_inclusive(|&b| b == b'\n').collect();
+                assert_eq!(inclusive, expected_inclusive, "{kernel}, len {len}");
+                assert_eq!(inclusive.concat(), input);
+            }
+        }
+    }
+
+    /// Splits on `DELIM` with every kernel and sink, where the input also has newlines to ignore
+    fn check_delim<const DELIM: u8>() {
+        let delim = DELIM as char;
+        let long = format!("a{delim}{delim}b\nc\u{e9}{delim}\r\n").repeat(40);
+        for len in 0..=long.len() {
+            let Some(input) = long.get(..len) else {
+                continue;
+            };
+            crate::guard::with_guard_page(input, |input| {
+                let expected: Vec<&str> = input.split_terminator(delim).collect();
+                let offsets: Vec<u32> = input.match_indices(delim).map(|m| m.0 as u32).collect();
+                for kernel in kernels() {
+                    let mut fields: Vec<&str> = Vec::new();
+                    run_on::<DELIM, _>(kernel, input, &mut fields);
+                    assert_eq!(fields, expected, "{kernel} on {delim:?}, len {len}");
+                    let mut found: Vec<u32> = Vec::new();
+                    run_on::<DELIM, _>(kernel, input.as_bytes(), &mut found);
+                    assert_eq!(found, offsets, "{kernel} on {delim:?}, len {len}");
+                }
+            });
+        }
+        let mut fields = Vec::new();
+        crate::slice::split_on::<DELIM>(&long, &mut fields);
+        assert_eq!(fields, long.split_terminator(delim).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_delimiters() {
+        check_delim::<b'\t'>();
+        check_delim::<b';'>();
+        // the padding of a tail must not match
+        check_delim::<b'\0'>();
+    }
+
+    #[test]
+    fn test_delim_sets() {
+        let input = "a\r\nb\rc\n\n\0d\r";
+        let mut lines = Vec::new();
+        crate::slice::split_universal(input, &mut lines);
+        assert_eq!(lines, ["a", "b", "c", "", "\0d"]);
+        let records = Delims::new(b"\n\r\0").with_crlf();
+        lines.clear();
+        split_any(input, &records, &mut Crlf(&mut lines));
+        assert_eq!(lines, ["a", "b", "c", "", "", "d"]);
+
+        // `\r\n`s across chunk boundaries, at every offset
+        let long = "ab\r\n\r\0\n\r\rc;\r".repeat(20) + "\r";
+        let sets = [
+            Delims::LINE_ENDINGS,
+            Delims::new(b"\n\r\0"),
+            records,
+            Delims::new(b";"),
+        ];
+        for start in 0..64 {
+            let input = &long[start..];
+            crate::guard::with_guard_page(input, |input| {
+                for delims in &sets {
+                    let mut expected: Vec<&str> = Vec::new();
+                    scalar_any(input, delims, &mut Crlf(&mut expected));
+                    let mut expected_offsets: Vec<u32> = Vec::new();
+                    scalar_any(input.as_bytes(), delims, &mut expected_offsets);
+                    for kernel in kernels() {
+                        let mut lines: Vec<&str> = Vec::new();
+                        run_any(kernel, input, delims, &mut Crlf(&mut lines));
+                        assert_eq!(lines, expected, "{kernel}, {delims:?}, from {start}");
+                        let mut offsets: Vec<u32> = Vec::new();
+                        run_any(kernel, input.as_bytes(), delims, &mut offsets);
+                        assert_eq!(
+                            offsets, expected_offsets,
+                            "{kernel}, {delims:?}, from {start}"
+                        );
+                    }
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,1509 @@
+//! Splitting into `Vec<&str>`, one slice per line, without the newline. `std` and `std_reuse` are
+//! `str::lines` (which also drops a `\r` before the newline, unlike the kernels, see
+//! [`split_crlf`]); [`split`] runs the fastest kernel for the current CPU, and [`split_on`] splits on
+//! another byte.
+
+use alloc::vec::Vec;
+
+pub fn std(input: &str) -> Vec<&str> {
+    input.lines().collect()
+}
+
+pub fn std_reuse<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    for line in input.lines() {
+        out.push(line);
+    }
+}
+
+/// `memchr::memchr_iter` over the newlines, as most production code finds lines. Unlike
+/// `std_reuse`, it keeps the `\r` of a `\r\n`, like the kernels.
+#[cfg(feature = "memchr")]
+pub fn memchr<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    let mut line_start = 0;
+    for line_end in memchr::memchr_iter(b'\n', input.as_bytes()) {
+        out.push(unsafe { input.get_unchecked(line_start..line_end) });
+        line_start = line_end + 1;
+    }
+    if line_start != input.len() {
+        out.push(unsafe { input.get_unchecked(line_start..) });
+    }
+}
+
+/// Splits with the kernel [`crate::dispatch::global`] picked for the current CPU
+///
+/// ```
+/// let mut lines = Vec::new();
+/// split_bench::slice::split("one\ntwo\n\nfour", &mut lines);
+/// // a trailing newline doesn't start another line, an empty line in the middle counts
+/// assert_eq!(lines, ["one", "two", "", "four"]);
+/// ```
+pub fn split<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    crate::dispatch::global().split(input, out)
+}
+
+/// [`split`], or the dispatcher's kernel for long lines if a sample of `input` says it has
+/// them, see [`crate::dispatch::Dispatcher::split_adaptive`]
+pub fn split_adaptive<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    crate::dispatch::global().split_adaptive(input, out)
+}
+
+/// [`split`] for bytes that may not be UTF-8 (a log with a stray byte, binary records), so they
+/// don't have to be validated first. Runs the generic kernels of [`crate::sink`].
+pub fn split_bytes<'input>(input: &'input [u8], out: &mut Vec<&'input [u8]>) {
+    crate::sink::split(input, out)
+}
+
+/// Lines with their newline, like `str::split_inclusive('\n')`, so they concatenate back to the
+/// input (`\r`s included). Runs the generic kernels of [`crate::sink`].
+pub fn split_inclusive<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    crate::sink::split(input, &mut crate::sink::Inclusive(out))
+}
+
+/// [`split_inclusive`] for bytes that may not be UTF-8
+pub fn split_inclusive_bytes<'input>(input: &'input [u8], out: &mut Vec<&'input [u8]>) {
+    crate::sink::split(input, &mut crate::sink::Inclusive(out))
+}
+
+/// Lines like `str::lines`, which drops the `\r` of a `\r\n` where the other kernels keep it,
+/// so a Windows-style file gives the same lines as `std`. Runs the generic kernels of
+/// [`crate::sink`].
+pub fn split_crlf<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    crate::sink::split(input, &mut crate::sink::Crlf(out))
+}
+
+/// Fields separated by `DELIM` (an ASCII byte like `b'\t'`, `b';'` or `b'\0'`), like
+/// `str::split_terminator`, so a trailing delimiter doesn't add an empty field. Runs the
+/// generic kernels of [`crate::sink`].
+pub fn split_on<'input, const DELIM: u8>(input: &'input str, out: &mut Vec<&'input str>) {
+    crate::sink::split_on::<DELIM, _>(input, out)
+}
+
+/// Lines ended by `\n`, `\r` or `\r\n`, without them, for old Mac and mixed-ending files, like
+/// Python's universal newlines. Runs the generic kernels of [`crate::sink`].
+pub fn split_universal<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    crate::sink::split_any(
+        input,
+        &crate::sink::Delims::LINE_ENDINGS,
+        &mut crate::sink::Crlf(out),
+    )
+}
+
+/// Records ended by `\0`, as `find -print0` and `git log -z` write them, newlines included.
+/// [`split_bytes_on`] with `b'\0'`, which the kernels' padding can't match.
+pub fn split_nul<'input>(input: &'input [u8], out: &mut Vec<&'input [u8]>) {
+    split_bytes_on::<b'\0'>(input, out)
+}
+
+/// [`split_on`] for bytes that may not be UTF-8
+pub fn split_bytes_on<'input, const DELIM: u8>(input: &'input [u8], out: &mut Vec<&'input [u8]>) {
+    crate::sink::split_on::<DELIM, _>(input, out)
+}
+
+/// Splits the complete lines (the ones ending in `\n`) that fit in the first `max_bytes` of
+/// `input`, newlines included, and returns them with the rest of the input, which starts at the
+/// first line that didn't fit. A trailing line without a newline is never complete, since more
+/// of it may be on the way. No lines means the first one is longer than `max_bytes`.
+pub fn split_lines_within(input: &str, max_bytes: usize) -> (Vec<&str>, &str) {
+    let budget = &input.as_bytes()[..max_bytes.min(input.len())];
+    let end = budget
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let (complete, rest) = input.split_at(end);
+    let mut lines = Vec::new();
+    split(complete, &mut lines);
+    (lines, rest)
+}
+
+/// Calls `f` with each line in order (the same lines [`split`] produces), without collecting
+/// them, for consumers that only look at each line once
+pub fn for_each_line<'input>(input: &'input str, f: impl FnMut(&'input str)) {
+    #[cfg(target_arch = "x86_64")]
+    if let Some(avx2) = crate::splitters::Avx2Splitter::new() {
+        avx2.for_each_line(input, f)
+    } else {
+        x86_64::sse2_for_each_line(input, f)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    input.split_terminator('\n').for_each(f)
+}
+
+/// Pushes up to `max_lines` lines onto `out`, last line first, using the fastest reverse
+/// kernel the current CPU supports
+pub fn split_last<'input>(input: &'input str, max_lines: usize, out: &mut Vec<&'input str>) {
+    #[cfg(target_arch = "x86_64")]
+    if let Some(avx2) = crate::splitters::Avx2Splitter::new() {
+        avx2.split_last(input, max_lines, out)
+    } else {
+        x86_64::sse2_rev(input, max_lines, out)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    out.extend(input.split_terminator('\n').rev().take(max_lines))
+}
+
+/// The newlines in `word`, as the top bit of their byte: with no SIMD, a `u64` compares 8 bytes
+/// at once (SWAR). Each byte is xored with `\n`, so a newline becomes 0, and a byte is 0 if
+/// adding 0x7f to its low 7 bits doesn't carry into its top bit and the top bit isn't set either.
+/// Unlike the shorter `(x - 0x01..) & !x & 0x80..`, no borrow crosses bytes, so only newlines are
+/// flagged.
+#[inline]
+pub fn swar_newlines(word: u64) -> u64 {
+    const LOW_7: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+    let x = word ^ 0x0a0a_0a0a_0a0a_0a0a;
+    !(((x & LOW_7) + LOW_7) | x | LOW_7)
+}
+
+/// Whether [`swar`] loads its words big-endian: in the target's own byte order, so that neither
+/// kind of target swaps bytes
+pub const SWAR_BIG_ENDIAN: bool = cfg!(target_endian = "big");
+
+/// The 8 bytes of `chunk` as a word, the first byte highest if `BIG_ENDIAN` and lowest if not
+#[inline(always)]
+pub fn swar_load<const BIG_ENDIAN: bool>(chunk: &[u8]) -> u64 {
+    let bytes = chunk.try_into().unwrap();
+    match BIG_ENDIAN {
+        true => u64::from_be_bytes(bytes),
+        false => u64::from_le_bytes(bytes),
+    }
+}
+
+/// The index of the first byte flagged in `mask` (which isn't 0) of a word loaded by
+/// [`swar_load`], clearing its flag. The first byte is the highest of a big-endian word, so it's
+/// found from the top with `leading_zeros`: `trailing_zeros` would mirror the indices.
+#[inline(always)]
+pub fn swar_pop_first<const BIG_ENDIAN: bool>(mask: &mut u64) -> usize {
+    match BIG_ENDIAN {
+        true => {
+            let zeros = mask.leading_zeros();
+            *mask &= !(1 << 63 >> zeros);
+            zeros as usize / 8
+        }
+        false => {
+            let zeros = mask.trailing_zeros();
+            *mask &= *mask - 1;
+            zeros as usize / 8
+        }
+    }
+}
+
+/// 8 bytes at a time in a `u64` (see [`swar_newlines`]), for targets without SIMD. The words are
+/// in the target's byte order, big-endian on s390x and ppc64.
+pub fn swar<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    swar_with::<SWAR_BIG_ENDIAN>(input, out)
+}
+
+/// [`swar`] with words of either byte order, so both can be tested on any target
+pub fn swar_with<'input, const BIG_ENDIAN: bool>(input: &'input str, out: &mut Vec<&'input str>) {
+    let mut line_start = 0;
+    for (chunk_i, chunk) in input.as_bytes().chunks_exact(8).enumerate() {
+        let mut mask = swar_newlines(swar_load::<BIG_ENDIAN>(chunk));
+        while mask != 0 {
+            let line_end = chunk_i * 8 + swar_pop_first::<BIG_ENDIAN>(&mut mask);
+            out.push(unsafe { input.get_unchecked(line_start..line_end) });
+            line_start = line_end + 1;
+        }
+    }
+    tail(line_start, 8, input, out);
+}
+
+/// The lines in the bytes after the last whole chunk, and the last line if it has no newline
+fn tail<'input>(
+    mut line_start: usize,
+    chunk_size: usize,
+    input: &'input str,
+    out: &mut Vec<&'input str>,
+) {
+    // handle last bytes
+    for i in (input.len() & !(chunk_size - 1))..input.len() {
+        if input.as_bytes()[i] != b'\n' {
+            continue;
+        }
+        out.push(unsafe { input.get_unchecked(line_start..i) });
+        line_start = i + 1;
+    }
+    // handle last line. omit if empty
+    if line_start != input.len() {
+        out.push(unsafe { input.get_unchecked(line_start..) });
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::tail;
+    use alloc::vec::Vec;
+    use core::arch::x86_64::*;
+
+    pub fn sse2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        // scan 16-byte chunks, then handle tail
+        let mut line_start = 0;
+        unsafe {
+            let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
+            for (chunk_i, chunk) in input.as_bytes().chunks_exact(16).enumerate() {
+                let v = _mm_loadu_si128(chunk.as_ptr().cast());
+                let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
+                while mask != 0 {
+                    /*
+                    abcdefNhijklNmoN
+                    (reversed, so first char is lowest bit)
+                    1001000001000000
+                     */
+                    let bit_pos = mask.trailing_zeros() as usize;
+                    let line_end = chunk_i * 16 + bit_pos;
+                    out.push(&input[line_start..line_end]);
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+            }
+        }
+        tail(line_start, 16, input, out);
+    }
+
+    pub fn sse2_unsafe<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        // scan 16-byte chunks, then handle tail
+        let mut line_start = 0;
+        unsafe {
+            let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
+            for (chunk_i, chunk) in input.as_bytes().chunks_exact(16).enumerate() {
+                let v = _mm_loadu_si128(chunk.as_ptr().cast());
+                let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
+                while mask != 0 {
+                    let bit_pos = mask.trailing_zeros() as usize;
+                    let line_end = chunk_i * 16 + bit_pos;
+                    out.push(input.get_unchecked(line_start..line_end));
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+            }
+        }
+        tail(line_start, 16, input, out);
+    }
+
+    pub fn sse2_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        // Key idea is to pull the allocation out of the innermost loop
+
+        let mut line_start = 0;
+        unsafe {
+            let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
+            let mut chunk_i = 0;
+            let stop_chunk_i = input.len() / 16;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.reserve(256);
+                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i < (256 - 16) && chunk_i < stop_chunk_i {
+                    let v = _mm_loadu_si128(input.as_ptr().byte_add(chunk_i * 16).cast());
+                    let mut mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, nl_v)) as u16;
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as usize;
+                        let line_end = chunk_i * 16 + bit_pos;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.set_len(out.len() + write_i);
+            }
+        }
+        tail(line_start, 16, input, out);
+    }
+
+    pub fn sse2_unrollx4<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        unsafe {
+            let nl_v = _mm_loadu_si128([b'\n'; 16].as_ptr().cast());
+            let mut chunk_i = 0;
+            let stop_chunk_i = input.len() / 64;
+            while chunk_i < stop_chunk_i {
+                let mut write_i = 0;
+                out.reserve(256);
+                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i < (256 - 64) && chunk_i < stop_chunk_i {
+                    use core::arch::x86_64::{
+                        _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load,
+                        _mm_movemask_epi8 as movemask,
+                    };
+                    let in_ptr = input.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
+                    let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
+                    let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
+                    let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
+                    let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
+                    let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
+                    while mask != 0 {
+                        let bit_pos = mask.trailing_zeros() as usize;
+                        let line_end = chunk_i * 64 + bit_pos;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.set_len(out.len() + write_i);
+            }
+        }
+        tail(line_start, 64, input, out);
+    }
+
+    /// Newline mask of the up to 64 bytes of `rest`. They're copied into a padded stack buffer
+    /// first, which stands in for AVX-512's masked load: the vector loads can't read past the
+    /// input, and the padding never matches.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn newline_mask_copied(rest: &[u8]) -> u64 {
+        delim_mask_copied::<b'\n'>(rest)
+    }
+
+    /// [`newline_mask_copied`] for another delimiter, padded with `!DELIM` so `\0` works too
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn delim_mask_copied<const DELIM: u8>(rest: &[u8]) -> u64 {
+        let mut buf = [!DELIM; 64];
+        buf[..rest.len()].copy_from_slice(rest);
+        let nl_v = _mm256_set1_epi8(DELIM as i8);
+        let v1 = _mm256_loadu_si256(buf.as_ptr().cast());
+        let v2 = _mm256_loadu_si256(buf.as_ptr().add(32).cast());
+        ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64) << 32)
+            | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64)
+    }
+
+    /// [`tail`] with one vector compare instead of a loop over the bytes. `chunk_size` must be
+    /// at most 64.
+    #[inline]
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    unsafe fn tail_avx2<'input>(
+        mut line_start: usize,
+        chunk_size: usize,
+        input: &'input str,
+        out: &mut Vec<&'input str>,
+    ) {
+        debug_assert!(chunk_size <= 64);
+        let base = input.len() & !(chunk_size - 1);
+        let mut mask = newline_mask_copied(input.as_bytes().get_unchecked(base..));
+        while mask != 0 {
+            let line_end = base + mask.trailing_zeros() as usize;
+            out.push(input.get_unchecked(line_start..line_end));
+            line_start = line_end + 1;
+            mask &= mask - 1;
+        }
+        if line_start != input.len() {
+            out.push(input.get_unchecked(line_start..));
+        }
+    }
+
+    pub fn can_run_avx2() -> bool {
+        // in practice, avx2 also implies bmi1 and popcnt
+        has_x86_features!("avx2", "bmi1", "popcnt")
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        // scan 32-byte chunks, then handle tail
+        let mut line_start = 0;
+        let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(32).enumerate() {
+            let v = _mm256_loadu_si256(chunk.as_ptr().cast());
+            let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
+            while mask != 0 {
+                let bit_pos = mask.trailing_zeros() as usize;
+                let line_end = chunk_i * 32 + bit_pos;
+                out.push(&input[line_start..line_end]);
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail_avx2(line_start, 32, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unsafe<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        // scan 32-byte chunks, then handle tail
+        let mut line_start = 0;
+        let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(32).enumerate() {
+            let v = _mm256_loadu_si256(chunk.as_ptr().cast());
+            let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
+            while mask != 0 {
+                let bit_pos = mask.trailing_zeros() as usize;
+                let line_end = chunk_i * 32 + bit_pos;
+                out.push(input.get_unchecked(line_start..line_end));
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail_avx2(line_start, 32, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        // Key idea is to pull the allocation out of the innermost loop
+        let mut line_start = 0;
+        let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 32;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            // this is the only function call in the loop. Vector registers have to be reloaded
+            // after a function call. That's why we go through the trouble of removing it from the
+            // inner loop.
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            // at most 32 items will be added per chunk
+            while write_i <= (256 - 32) && chunk_i < stop_chunk_i {
+                let v = _mm256_loadu_si256(input.as_ptr().byte_add(chunk_i * 32).cast());
+                let mut mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, nl_v)) as u32;
+                while mask != 0 {
+                    let bit_pos = mask.trailing_zeros() as usize;
+                    let line_end = chunk_i * 32 + bit_pos;
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..line_end));
+                    write_i += 1;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx2(line_start, 32, input, out);
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_unrollx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        let nl_v = _mm256_loadu_si256([b'\n'; 32].as_ptr().cast());
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            // this is the only function call in the loop. Vector registers have to be reloaded
+            // after a function call. That's why we go through the trouble of removing it from the
+            // inner loop.
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            // at most 64 items will be added per chunk
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().byte_add(chunk_i * 64);
+                let v1 = load(ptr.cast());
+                let v2 = load(ptr.byte_add(32).cast());
+                let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                    | (movemask(eq(v1, nl_v)) as u32 as u64);
+                while mask != 0 {
+                    let bit_pos = mask.trailing_zeros() as usize;
+                    let line_end = chunk_i * 64 + bit_pos;
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..line_end));
+                    write_i += 1;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx2(line_start, 64, input, out);
+    }
+
+    /// [`tail`] for the `for_each_line` kernels, only looking at the bytes after the last whole
+    /// 64-byte chunk (the last line can start long before them)
+    fn for_each_tail_line<'input>(
+        mut line_start: usize,
+        input: &'input str,
+        mut f: impl FnMut(&'input str),
+    ) {
+        for i in (input.len() & !63)..input.len() {
+            if input.as_bytes()[i] == b'\n' {
+                f(unsafe { input.get_unchecked(line_start..i) });
+                line_start = i + 1;
+            }
+        }
+        if line_start != input.len() {
+            f(unsafe { input.get_unchecked(line_start..) });
+        }
+    }
+
+    /// [`avx2_unrollx2`]'s scan, handing each line to `f` instead of writing it out
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_for_each_line<'input>(input: &'input str, mut f: impl FnMut(&'input str)) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        for chunk_i in 0..input.len() / 64 {
+            let ptr = input.as_ptr().byte_add(chunk_i * 64);
+            let v1 = load(ptr.cast());
+            let v2 = load(ptr.byte_add(32).cast());
+            let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                | (movemask(eq(v1, nl_v)) as u32 as u64);
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                f(input.get_unchecked(line_start..line_end));
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        for_each_tail_line(line_start, input, f)
+    }
+
+    /// [`avx2_for_each_line`] with four SSE2 compares per chunk, for CPUs without AVX2
+    pub fn sse2_for_each_line<'input>(input: &'input str, mut f: impl FnMut(&'input str)) {
+        use core::arch::x86_64::{
+            _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        unsafe {
+            let nl_v = _mm_set1_epi8(b'\n' as i8);
+            for chunk_i in 0..input.len() / 64 {
+                let ptr = input.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
+                let mask0 = movemask(eq(load(ptr), nl_v)) as u16 as u64;
+                let mask1 = movemask(eq(load(ptr.byte_add(16)), nl_v)) as u16 as u64;
+                let mask2 = movemask(eq(load(ptr.byte_add(32)), nl_v)) as u16 as u64;
+                let mask3 = movemask(eq(load(ptr.byte_add(48)), nl_v)) as u16 as u64;
+                let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
+                while mask != 0 {
+                    let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                    f(input.get_unchecked(line_start..line_end));
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+            }
+        }
+        for_each_tail_line(line_start, input, f)
+    }
+
+    /// [`avx2_unrollx2`], software-pipelined: the next chunk is loaded and compared before the
+    /// current chunk's mask is drained, so the drain loop doesn't wait on the load
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_pipelined<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mask_at = |chunk_i: usize| {
+            let ptr = input.as_ptr().byte_add(chunk_i * 64);
+            let v1 = load(ptr.cast());
+            let v2 = load(ptr.byte_add(32).cast());
+            ((movemask(eq(v2, nl_v)) as u32 as u64) << 32) | (movemask(eq(v1, nl_v)) as u32 as u64)
+        };
+        let stop_chunk_i = input.len() / 64;
+        let mut chunk_i = 0;
+        let mut mask = if stop_chunk_i > 0 { mask_at(0) } else { 0 };
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                // the last chunk loads itself again rather than branching
+                let next_mask = mask_at((chunk_i + 1).min(stop_chunk_i - 1));
+                while mask != 0 {
+                    let bit_pos = mask.trailing_zeros() as usize;
+                    let line_end = chunk_i * 64 + bit_pos;
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..line_end));
+                    write_i += 1;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                mask = next_mask;
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx2(line_start, 64, input, out);
+    }
+
+    /// The positions of an 8-bit mask's set bits, packed into the low bytes, one per byte
+    const POSITIONS_LUT: [u64; 256] = {
+        let mut t = [0u64; 256];
+        let mut t_i = 0;
+        while t_i < 256 {
+            let mut bit_i = 0;
+            let mut packed_i = 0;
+            while bit_i < 8 {
+                if t_i & (1 << bit_i) != 0 {
+                    t[t_i] |= (bit_i as u64) << (packed_i * 8);
+                    packed_i += 1;
+                }
+                bit_i += 1;
+            }
+            t_i += 1;
+        }
+        t
+    };
+
+    /// [`avx2_unrollx2`] with the mask decoded a byte at a time, like simdjson's `flatten_bits`:
+    /// a lookup gives the positions of the byte's newlines, all 8 are written, and the write
+    /// index moves by the byte's popcount. The lines are then made from the positions, so the
+    /// loop writing them doesn't wait on a `trailing_zeros` and a clear of the lowest bit per line.
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_lut<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        let mut line_start = 0;
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        // the chunk's newline positions, with room for the last byte's 8
+        let mut ends = [0u8; 64 + 8];
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().byte_add(chunk_i * 64);
+                let v1 = load(ptr.cast());
+                let v2 = load(ptr.byte_add(32).cast());
+                let mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                    | (movemask(eq(v1, nl_v)) as u32 as u64);
+                if mask != 0 {
+                    let mut count = 0;
+                    for (byte_i, byte) in mask.to_le_bytes().into_iter().enumerate() {
+                        // positions are under 64, so adding 8 * byte_i to every byte can't carry
+                        let packed = *POSITIONS_LUT.get_unchecked(byte as usize)
+                            + byte_i as u64 * 0x0808_0808_0808_0808;
+                        ends.as_mut_ptr()
+                            .add(count)
+                            .cast::<u64>()
+                            .write_unaligned(packed);
+                        count += byte.count_ones() as usize;
+                    }
+                    for &end in ends.get_unchecked(..count) {
+                        let line_end = chunk_i * 64 + end as usize;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                    }
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx2(line_start, 64, input, out);
+    }
+
+    pub fn can_run_avx2_bmi2() -> bool {
+        has_x86_features!("avx2", "bmi1", "bmi2", "popcnt")
+    }
+
+    /// [`avx2_lut`] with each mask byte's positions from BMI2 instead of the table: `pdep`
+    /// spreads the byte's bits to the low bit of each byte, the multiply fills those bytes, and
+    /// `pext` pulls the matching bytes of `0x0706050403020100` to the front. `pdep` and `pext`
+    /// are microcoded before Zen 3, taking time that grows with the set bits of their mask.
+    #[target_feature(enable = "avx2,bmi1,bmi2,popcnt")]
+    pub unsafe fn avx2_bmi2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+            _pdep_u64 as pdep, _pext_u64 as pext,
+        };
+        let mut line_start = 0;
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        // the chunk's newline positions, with room for the last byte's 8
+        let mut ends = [0u8; 64 + 8];
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().byte_add(chunk_i * 64);
+                let v1 = load(ptr.cast());
+                let v2 = load(ptr.byte_add(32).cast());
+                let mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                    | (movemask(eq(v1, nl_v)) as u32 as u64);
+                if mask != 0 {
+                    let mut count = 0;
+                    for (byte_i, byte) in mask.to_le_bytes().into_iter().enumerate() {
+                        let byte_mask = pdep(byte as u64, 0x0101_0101_0101_0101) * 0xff;
+                        let packed = pext(0x0706_0504_0302_0100, byte_mask)
+                            + byte_i as u64 * 0x0808_0808_0808_0808;
+                        ends.as_mut_ptr()
+                            .add(count)
+                            .cast::<u64>()
+                            .write_unaligned(packed);
+                        count += byte.count_ones() as usize;
+                    }
+                    for &end in ends.get_unchecked(..count) {
+                        let line_end = chunk_i * 64 + end as usize;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                    }
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx2(line_start, 64, input, out);
+    }
+
+    pub fn can_run_avx512() -> bool {
+        has_x86_features!("avx512f", "avx512bw", "bmi1", "popcnt")
+    }
+
+    /// [`tail`] with masked loads, which don't touch the bytes masked out. `chunk_size` must be
+    /// a power of two.
+    #[inline]
+    #[target_feature(enable = "avx512f,avx512bw,bmi1,popcnt")]
+    unsafe fn tail_avx512<'input>(
+        mut line_start: usize,
+        chunk_size: usize,
+        input: &'input str,
+        out: &mut Vec<&'input str>,
+    ) {
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let base = input.len() & !(chunk_size - 1);
+        let rest = input.as_bytes().get_unchecked(base..);
+        for (piece_i, piece) in rest.chunks(64).enumerate() {
+            let load_mask = u64::MAX >> (64 - piece.len());
+            let v = _mm512_maskz_loadu_epi8(load_mask, piece.as_ptr().cast());
+            let mut mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+            while mask != 0 {
+                let line_end = base + piece_i * 64 + mask.trailing_zeros() as usize;
+                out.push(input.get_unchecked(line_start..line_end));
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        if line_start != input.len() {
+            out.push(input.get_unchecked(line_start..));
+        }
+    }
+
+    /// [`avx2_unsafe`] on 64-byte chunks: one load, and a compare straight into a mask register,
+    /// where AVX2 needs two of each and a `movemask` per half
+    #[target_feature(enable = "avx512f,avx512bw,bmi1,popcnt")]
+    pub unsafe fn avx512<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(64).enumerate() {
+            let v = _mm512_loadu_si512(chunk.as_ptr().cast());
+            let mut mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                out.push(input.get_unchecked(line_start..line_end));
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail_avx512(line_start, 64, input, out);
+    }
+
+    /// [`avx2_unrollx2`] with the 64-byte chunk's mask from one AVX-512 compare
+    #[target_feature(enable = "avx512f,avx512bw,bmi1,popcnt")]
+    pub unsafe fn avx512_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            // at most 64 items will be added per chunk
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let v = _mm512_loadu_si512(input.as_ptr().byte_add(chunk_i * 64).cast());
+                let mut mask = _mm512_cmpeq_epi8_mask(v, nl_v);
+                while mask != 0 {
+                    let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..line_end));
+                    write_i += 1;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx512(line_start, 64, input, out);
+    }
+
+    /// [`avx512_unroll`] on 128 bytes per iteration: both chunks are loaded and compared before
+    /// the first one's mask is drained
+    #[target_feature(enable = "avx512f,avx512bw,bmi1,popcnt")]
+    pub unsafe fn avx512_unrollx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let nl_v = _mm512_set1_epi8(b'\n' as i8);
+        let mut pair_i = 0;
+        let stop_pair_i = input.len() / 128;
+        while pair_i < stop_pair_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            // at most 128 items will be added per pair of chunks
+            while write_i <= (256 - 128) && pair_i < stop_pair_i {
+                let ptr = input.as_ptr().byte_add(pair_i * 128);
+                let masks = [
+                    _mm512_cmpeq_epi8_mask(_mm512_loadu_si512(ptr.cast()), nl_v),
+                    _mm512_cmpeq_epi8_mask(_mm512_loadu_si512(ptr.byte_add(64).cast()), nl_v),
+                ];
+                for (half, mut mask) in masks.into_iter().enumerate() {
+                    let base = pair_i * 128 + half * 64;
+                    while mask != 0 {
+                        let line_end = base + mask.trailing_zeros() as usize;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                }
+                pair_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail_avx512(line_start, 128, input, out);
+    }
+
+    /// Shared setup for the reverse kernels. Returns where to stop scanning: a trailing
+    /// newline doesn't start a new line, so it's skipped.
+    fn rev_scan_end(input: &str) -> usize {
+        input.len() - input.ends_with('\n') as usize
+    }
+
+    /// Scalar part of the reverse kernels: the bytes past the last full 64-byte block.
+    /// Returns the new `line_end`, or `None` if `max_lines` was reached.
+    fn rev_tail<'input>(
+        scan_end: usize,
+        max_lines: usize,
+        input: &'input str,
+        out: &mut Vec<&'input str>,
+    ) -> Option<usize> {
+        let mut line_end = scan_end;
+        for i in ((scan_end & !63)..scan_end).rev() {
+            if input.as_bytes()[i] != b'\n' {
+                continue;
+            }
+            if out.len() == max_lines {
+                return None;
+            }
+            out.push(&input[i + 1..line_end]);
+            line_end = i;
+        }
+        Some(line_end)
+    }
+
+    /// Pushes up to `max_lines` lines onto `out`, starting from the last one. With no limit,
+    /// the output is exactly the forward kernels' output, reversed.
+    pub fn sse2_rev<'input>(input: &'input str, max_lines: usize, out: &mut Vec<&'input str>) {
+        use core::arch::x86_64::{
+            _mm_cmpeq_epi8 as eq, _mm_loadu_si128 as load, _mm_movemask_epi8 as movemask,
+        };
+        if input.is_empty() {
+            return;
+        }
+        let max_lines = out.len().saturating_add(max_lines);
+        let scan_end = rev_scan_end(input);
+        let Some(mut line_end) = rev_tail(scan_end, max_lines, input, out) else {
+            return;
+        };
+        unsafe {
+            let nl_v = load([b'\n'; 16].as_ptr().cast());
+            for chunk_i in (0..scan_end / 64).rev() {
+                let in_ptr = input.as_ptr().byte_add(chunk_i * 64).cast::<__m128i>();
+                let mask0 = movemask(eq(load(in_ptr), nl_v)) as u64;
+                let mask1 = movemask(eq(load(in_ptr.byte_add(16)), nl_v)) as u64;
+                let mask2 = movemask(eq(load(in_ptr.byte_add(32)), nl_v)) as u64;
+                let mask3 = movemask(eq(load(in_ptr.byte_add(48)), nl_v)) as u64;
+                let mut mask = mask0 | (mask1 << 16) | (mask2 << 32) | (mask3 << 48);
+                while mask != 0 {
+                    // highest set bit is the last newline in the block
+                    let bit_pos = 63 - mask.leading_zeros() as usize;
+                    if out.len() == max_lines {
+                        return;
+                    }
+                    let line_start = chunk_i * 64 + bit_pos + 1;
+                    out.push(input.get_unchecked(line_start..line_end));
+                    line_end = line_start - 1;
+                    mask ^= 1 << bit_pos;
+                }
+            }
+        }
+        if out.len() != max_lines {
+            out.push(&input[..line_end]);
+        }
+    }
+
+    /// AVX2 version of [`sse2_rev`]
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2_rev<'input>(
+        input: &'input str,
+        max_lines: usize,
+        out: &mut Vec<&'input str>,
+    ) {
+        use core::arch::x86_64::{
+            _mm256_cmpeq_epi8 as eq, _mm256_loadu_si256 as load, _mm256_movemask_epi8 as movemask,
+        };
+        if input.is_empty() {
+            return;
+        }
+        let max_lines = out.len().saturating_add(max_lines);
+        let scan_end = rev_scan_end(input);
+        let Some(mut line_end) = rev_tail(scan_end, max_lines, input, out) else {
+            return;
+        };
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        for chunk_i in (0..scan_end / 64).rev() {
+            let ptr = input.as_ptr().byte_add(chunk_i * 64);
+            let v1 = load(ptr.cast());
+            let v2 = load(ptr.byte_add(32).cast());
+            let mut mask = ((movemask(eq(v2, nl_v)) as u32 as u64) << 32)
+                | (movemask(eq(v1, nl_v)) as u32 as u64);
+            while mask != 0 {
+                let bit_pos = 63 - mask.leading_zeros() as usize;
+                if out.len() == max_lines {
+                    return;
+                }
+                let line_start = chunk_i * 64 + bit_pos + 1;
+                out.push(input.get_unchecked(line_start..line_end));
+                line_end = line_start - 1;
+                mask ^= 1 << bit_pos;
+            }
+        }
+        if out.len() != max_lines {
+            out.push(&input[..line_end]);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub mod aarch64 {
+    use super::tail;
+    use alloc::vec::Vec;
+    use core::arch::aarch64::*;
+
+    /// The newlines in the 16 bytes at `ptr`, as the top bit of a nibble per byte. NEON has
+    /// no movemask, but narrowing the compare result with a 4-bit shift (`shrn`) packs it
+    /// into 64 bits.
+    #[inline]
+    pub unsafe fn newline_nibbles(ptr: *const u8) -> u64 {
+        let eq = vceqq_u8(vld1q_u8(ptr), vdupq_n_u8(b'\n'));
+        let nibbles = vshrn_n_u16::<4>(vreinterpretq_u16_u8(eq));
+        vget_lane_u64::<0>(vreinterpret_u64_u8(nibbles)) & 0x8888_8888_8888_8888
+    }
+
+    /// NEON is part of the aarch64 baseline, so this needs no detection
+    pub fn neon<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(16).enumerate() {
+            let mut mask = unsafe { newline_nibbles(chunk.as_ptr()) };
+            while mask != 0 {
+                let line_end = chunk_i * 16 + mask.trailing_zeros() as usize / 4;
+                out.push(unsafe { input.get_unchecked(line_start..line_end) });
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail(line_start, 16, input, out);
+    }
+
+    /// The newlines in the 64 bytes at `ptr`, one bit per byte like an x86 movemask: each
+    /// compare result keeps bit `i % 8` of byte `i`, and three rounds of pairwise adds (`addp`)
+    /// sum every 8 bytes into one, as simdjson does
+    #[inline]
+    pub unsafe fn newline_mask(ptr: *const u8) -> u64 {
+        const BITS: [u8; 16] = [1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128];
+        let nl_v = vdupq_n_u8(b'\n');
+        let bits_v = vld1q_u8(BITS.as_ptr());
+        let quarter = |i: usize| vandq_u8(vceqq_u8(vld1q_u8(ptr.add(i * 16)), nl_v), bits_v);
+        let halves = (
+            vpaddq_u8(quarter(0), quarter(1)),
+            vpaddq_u8(quarter(2), quarter(3)),
+        );
+        let quads = vpaddq_u8(halves.0, halves.1);
+        vgetq_lane_u64::<0>(vreinterpretq_u64_u8(vpaddq_u8(quads, quads)))
+    }
+
+    /// [`neon`] writing into spare capacity, like `x86_64::avx2_unroll`
+    pub fn neon_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 16;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            unsafe {
+                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+                // at most 16 items will be added per chunk
+                while write_i <= (256 - 16) && chunk_i < stop_chunk_i {
+                    let mut mask = newline_nibbles(input.as_ptr().add(chunk_i * 16));
+                    while mask != 0 {
+                        let line_end = chunk_i * 16 + mask.trailing_zeros() as usize / 4;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.set_len(out.len() + write_i);
+            }
+        }
+        tail(line_start, 16, input, out);
+    }
+
+    /// Four compares per 64-byte chunk into one [`newline_mask`], like `x86_64::sse2_unrollx4`
+    pub fn neon_unrollx4<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            unsafe {
+                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let mut mask = newline_mask(input.as_ptr().add(chunk_i * 64));
+                    while mask != 0 {
+                        let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.set_len(out.len() + write_i);
+            }
+        }
+        tail(line_start, 64, input, out);
+    }
+
+    #[cfg(feature = "nightly")]
+    pub fn can_run_sve() -> bool {
+        #[cfg(feature = "std")]
+        return std::arch::is_aarch64_feature_detected!("sve");
+        #[cfg(not(feature = "std"))]
+        cfg!(target_feature = "sve")
+    }
+
+    /// Bytes in an SVE vector: 16 to 256, a multiple of 16, fixed by the CPU (and the OS)
+    #[cfg(feature = "nightly")]
+    #[target_feature(enable = "sve")]
+    pub unsafe fn sve_vector_bytes() -> usize {
+        let bytes: usize;
+        core::arch::asm!("cntb {}", out(reg) bytes, options(pure, nomem, nostack));
+        bytes
+    }
+
+    /// The newlines in the vector of bytes from `start` on, as one bit per byte in `masks`,
+    /// which a predicate is already: it's stored as is (`str p`), where NEON needs the `shrn`
+    /// or `addp` above to make a mask. The load is predicated on `whilelo`, so bytes past the end
+    /// are neither read nor matched, and there's no scalar tail. Bits past the vector's length
+    /// aren't written.
+    #[cfg(feature = "nightly")]
+    #[inline]
+    #[target_feature(enable = "sve")]
+    pub unsafe fn sve_newline_mask(input: &[u8], start: usize, masks: &mut [u64; 4]) {
+        core::arch::asm!(
+            "whilelo p0.b, {start}, {end}",
+            "ld1b {{ z0.b }}, p0/z, [{base}, {start}]",
+            "cmpeq p1.b, p0/z, z0.b, #10",
+            "str p1, [{masks}]",
+            base = in(reg) input.as_ptr(),
+            start = in(reg) start,
+            end = in(reg) input.len(),
+            masks = in(reg) masks.as_mut_ptr(),
+            out("v0") _,
+            out("p0") _,
+            out("p1") _,
+            options(nostack, preserves_flags),
+        );
+    }
+
+    /// A vector of bytes per step, as long as the CPU's: the same code runs 16 bytes at a time
+    /// on Neoverse V2 and 32 on V1 (Graviton 3). Inactive lanes make the last step the tail.
+    #[cfg(feature = "nightly")]
+    #[target_feature(enable = "sve")]
+    pub unsafe fn sve<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let vector_bytes = sve_vector_bytes();
+        let words = vector_bytes.div_ceil(64);
+        let mut masks = [0u64; 4];
+        let mut line_start = 0;
+        let mut chunk_start = 0;
+        while chunk_start < input.len() {
+            sve_newline_mask(input.as_bytes(), chunk_start, &mut masks);
+            for (word_i, &word) in masks.get_unchecked(..words).iter().enumerate() {
+                // a vector of 16 or 48 bytes doesn't fill its last word
+                let mut mask = word & (u64::MAX >> (64 - (vector_bytes - word_i * 64).min(64)));
+                while mask != 0 {
+                    let line_end = chunk_start + word_i * 64 + mask.trailing_zeros() as usize;
+                    out.push(input.get_unchecked(line_start..line_end));
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+            }
+            chunk_start += vector_bytes;
+        }
+        if line_start != input.len() {
+            out.push(input.get_unchecked(line_start..));
+        }
+    }
+}
+
+/// Only built with `-C target-feature=+simd128`: wasm has no runtime feature detection, and a
+/// module using an instruction the engine doesn't have fails to load at all
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub mod wasm32 {
+    use super::tail;
+    use alloc::vec::Vec;
+    use core::arch::wasm32::*;
+
+    /// The newlines in the 64 bytes at `ptr`: four 16-byte compares, with `i8x16_bitmask` as
+    /// the movemask
+    #[inline]
+    pub unsafe fn newline_mask(ptr: *const u8) -> u64 {
+        let nl_v = u8x16_splat(b'\n');
+        let quarter = |i: usize| {
+            let v = v128_load(ptr.add(i * 16).cast());
+            i8x16_bitmask(i8x16_eq(v, nl_v)) as u64
+        };
+        quarter(0) | quarter(1) << 16 | quarter(2) << 32 | quarter(3) << 48
+    }
+
+    /// [`newline_mask`] per 64-byte chunk, the four compares of `x86_64::sse2_unrollx4`
+    pub fn simd128<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(64).enumerate() {
+            let mut mask = unsafe { newline_mask(chunk.as_ptr()) };
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                out.push(unsafe { input.get_unchecked(line_start..line_end) });
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail(line_start, 64, input, out);
+    }
+
+    /// [`simd128`] writing into spare capacity, like `x86_64::sse2_unrollx4` does
+    pub fn simd128_unroll<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            unsafe {
+                let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+                while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                    let mut mask = newline_mask(input.as_ptr().add(chunk_i * 64));
+                    while mask != 0 {
+                        let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                        out_arr
+                            .get_unchecked_mut(write_i)
+                            .write(input.get_unchecked(line_start..line_end));
+                        write_i += 1;
+                        line_start = line_end + 1;
+                        mask &= mask - 1;
+                    }
+                    chunk_i += 1;
+                }
+                out.set_len(out.len() + write_i);
+            }
+        }
+        tail(line_start, 64, input, out);
+    }
+}
+
+/// Only built with `-C target-feature=+v`: `is_riscv_feature_detected!` isn't stable, so like
+/// simd128 on wasm, the vector extension is picked at compile time
+#[cfg(all(target_arch = "riscv64", target_feature = "v"))]
+pub mod riscv64 {
+    use alloc::vec::Vec;
+
+    /// Bytes per step, at most. Offsets are 16-bit lanes, and `out` has to hold a step's worth
+    pub const STEP: usize = 256;
+
+    /// Writes the offsets of the newlines among the first `avl` bytes at `ptr` (at most
+    /// [`STEP`]), plus `base`, to `out`, and returns how many bytes were scanned, which is fewer
+    /// than `avl` if the vector registers are shorter, and how many offsets were written. RVV
+    /// 1.0: `vmseq` makes the mask, `vid` the offsets and `vcompress` packs the ones under the
+    /// mask, stored with `vl` set to their count, so nothing past them is written.
+    #[inline]
+    pub unsafe fn newline_offsets(
+        ptr: *const u8,
+        avl: usize,
+        base: u16,
+        out: *mut u16,
+    ) -> (usize, usize) {
+        let (vl, count): (usize, usize);
+        core::arch::asm!(
+            "vsetvli {vl}, {avl}, e8, m4, ta, ma",
+            "vle8.v v8, ({ptr})",
+            "vmseq.vi v0, v8, 10",
+            "vcpop.m {count}, v0",
+            // the same lane count: SEW / LMUL stays 2
+            "vsetvli zero, {vl}, e16, m8, ta, ma",
+            "vid.v v16",
+            "vadd.vx v16, v16, {base}",
+            "vcompress.vm v24, v16, v0",
+            "vsetvli zero, {count}, e16, m8, ta, ma",
+            "vse16.v v24, ({out})",
+            avl = in(reg) avl,
+            ptr = in(reg) ptr,
+            base = in(reg) base as usize,
+            out = in(reg) out,
+            vl = out(reg) vl,
+            count = out(reg) count,
+            out("v0") _,
+            out("v8") _,
+            out("v9") _,
+            out("v10") _,
+            out("v11") _,
+            out("v16") _,
+            out("v17") _,
+            out("v18") _,
+            out("v19") _,
+            out("v20") _,
+            out("v21") _,
+            out("v22") _,
+            out("v23") _,
+            out("v24") _,
+            out("v25") _,
+            out("v26") _,
+            out("v27") _,
+            out("v28") _,
+            out("v29") _,
+            out("v30") _,
+            out("v31") _,
+            options(nostack),
+        );
+        (vl, count)
+    }
+
+    /// A step of up to [`STEP`] bytes at a time, as many as the vector registers hold, with the
+    /// newlines' offsets compressed into a buffer the lines are made from. The last step is the
+    /// tail: `vsetvli` shortens it to the bytes left, so there's no scalar loop.
+    pub fn rvv<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut offsets = [0u16; STEP];
+        let mut line_start = 0;
+        let mut chunk_start = 0;
+        while chunk_start < input.len() {
+            let avl = (input.len() - chunk_start).min(STEP);
+            let (scanned, count) = unsafe {
+                newline_offsets(
+                    input.as_ptr().add(chunk_start),
+                    avl,
+                    0,
+                    offsets.as_mut_ptr(),
+                )
+            };
+            for &offset in &offsets[..count] {
+                let line_end = chunk_start + offset as usize;
+                out.push(unsafe { input.get_unchecked(line_start..line_end) });
+                line_start = line_end + 1;
+            }
+            chunk_start += scanned;
+        }
+        if line_start != input.len() {
+            out.push(unsafe { input.get_unchecked(line_start..) });
+        }
+    }
+}
+
+/// LSX (128-bit) and LASX (256-bit) vectors, as on the Loongson 3A6000. Their intrinsics aren't
+/// stable, so the compares are inline asm, but the target features and their detection are.
+#[cfg(target_arch = "loongarch64")]
+pub mod loongarch64 {
+    use super::tail;
+    use alloc::vec::Vec;
+
+    pub fn can_run_lsx() -> bool {
+        #[cfg(feature = "std")]
+        return std::arch::is_loongarch_feature_detected!("lsx");
+        #[cfg(not(feature = "std"))]
+        cfg!(target_feature = "lsx")
+    }
+
+    pub fn can_run_lasx() -> bool {
+        #[cfg(feature = "std")]
+        return std::arch::is_loongarch_feature_detected!("lasx");
+        #[cfg(not(feature = "std"))]
+        cfg!(target_feature = "lasx")
+    }
+
+    /// The newlines in the 64 bytes at `ptr`: four 16-byte compares against an immediate
+    /// (`vseqi.b`), each turned into 16 bits by `vmskltz.b`, LoongArch's movemask, which leaves
+    /// them in the vector's low halfword for `vpickve2gr` to move out
+    #[inline]
+    #[target_feature(enable = "lsx")]
+    pub unsafe fn lsx_newline_mask(ptr: *const u8) -> u64 {
+        let (q0, q1, q2, q3): (u64, u64, u64, u64);
+        core::arch::asm!(
+            "vld $vr8, {ptr}, 0",
+            "vld $vr9, {ptr}, 16",
+            "vld $vr10, {ptr}, 32",
+            "vld $vr11, {ptr}, 48",
+            "vseqi.b $vr8, $vr8, 10",
+            "vseqi.b $vr9, $vr9, 10",
+            "vseqi.b $vr10, $vr10, 10",
+            "vseqi.b $vr11, $vr11, 10",
+            "vmskltz.b $vr8, $vr8",
+            "vmskltz.b $vr9, $vr9",
+            "vmskltz.b $vr10, $vr10",
+            "vmskltz.b $vr11, $vr11",
+            "vpickve2gr.hu {q0}, $vr8, 0",
+            "vpickve2gr.hu {q1}, $vr9, 0",
+            "vpickve2gr.hu {q2}, $vr10, 0",
+            "vpickve2gr.hu {q3}, $vr11, 0",
+            ptr = in(reg) ptr,
+            q0 = out(reg) q0,
+            q1 = out(reg) q1,
+            q2 = out(reg) q2,
+            q3 = out(reg) q3,
+            // the vector registers alias the float ones, which is all asm! can name
+            out("$f8") _,
+            out("$f9") _,
+            out("$f10") _,
+            out("$f11") _,
+            options(pure, readonly, nostack, preserves_flags),
+        );
+        q0 | q1 << 16 | q2 << 32 | q3 << 48
+    }
+
+    /// [`lsx_newline_mask`] with two 32-byte compares. `xvmskltz.b` works on each 128-bit half
+    /// on its own, so every vector gives two 16-bit masks, in words 0 and 4.
+    #[inline]
+    #[target_feature(enable = "lasx")]
+    pub unsafe fn lasx_newline_mask(ptr: *const u8) -> u64 {
+        let (q0, q1, q2, q3): (u64, u64, u64, u64);
+        core::arch::asm!(
+            "xvld $xr8, {ptr}, 0",
+            "xvld $xr9, {ptr}, 32",
+            "xvseqi.b $xr8, $xr8, 10",
+            "xvseqi.b $xr9, $xr9, 10",
+            "xvmskltz.b $xr8, $xr8",
+            "xvmskltz.b $xr9, $xr9",
+            "xvpickve2gr.wu {q0}, $xr8, 0",
+            "xvpickve2gr.wu {q1}, $xr8, 4",
+            "xvpickve2gr.wu {q2}, $xr9, 0",
+            "xvpickve2gr.wu {q3}, $xr9, 4",
+            ptr = in(reg) ptr,
+            q0 = out(reg) q0,
+            q1 = out(reg) q1,
+            q2 = out(reg) q2,
+            q3 = out(reg) q3,
+            out("$f8") _,
+            out("$f9") _,
+            options(pure, readonly, nostack, preserves_flags),
+        );
+        q0 | q1 << 16 | q2 << 32 | q3 << 48
+    }
+
+    /// [`lsx_newline_mask`] per 64-byte chunk, like `wasm32::simd128`
+    #[target_feature(enable = "lsx")]
+    pub unsafe fn lsx<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(64).enumerate() {
+            let mut mask = lsx_newline_mask(chunk.as_ptr());
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                out.push(input.get_unchecked(line_start..line_end));
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail(line_start, 64, input, out);
+    }
+
+    /// [`lasx_newline_mask`] per 64-byte chunk, written into spare capacity like
+    /// `x86_64::avx2_unroll`
+    #[target_feature(enable = "lasx")]
+    pub unsafe fn lasx<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let mut line_start = 0;
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let mut mask = lasx_newline_mask(input.as_ptr().add(chunk_i * 64));
+                while mask != 0 {
+                    let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..line_end));
+                    write_i += 1;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        tail(line_start, 64, input, out);
+    }
+}
+
+/// `core::simd` (nightly only): one source for every target, which LLVM lowers to SSE2 or AVX2 on
+/// x86_64, NEON on aarch64 and simd128 on wasm, or to scalar code where there's no vector unit
+#[cfg(feature = "nightly")]
+pub mod portable {
+    use super::tail;
+    use alloc::vec::Vec;
+    use core::simd::prelude::*;
+
+    /// Two `Simd<u8, 32>` compares per 64-byte chunk, each turned into 32 bits of the mask with
+    /// `to_bitmask`, then the same bit loop as `x86_64::avx2_unsafe`. Lane `i` is byte `i` and
+    /// `to_bitmask` puts it in bit `i` on either byte order, so big-endian targets need nothing
+    /// different.
+    #[inline(always)]
+    fn split_chunks<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let nl_v = u8x32::splat(b'\n');
+        let mut line_start = 0;
+        for (chunk_i, chunk) in input.as_bytes().chunks_exact(64).enumerate() {
+            let half = |i: usize| {
+                u8x32::from_slice(&chunk[i * 32..])
+                    .simd_eq(nl_v)
+                    .to_bitmask()
+            };
+            let mut mask = half(0) | half(1) << 32;
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                out.push(unsafe { input.get_unchecked(line_start..line_end) });
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        }
+        tail(line_start, 64, input, out);
+    }
+
+    /// For the target features the crate is compiled with: SSE2 on a plain x86_64 build
+    pub fn simd<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        split_chunks(input, out)
+    }
+
+    /// The same source compiled for AVX2, next to the intrinsics of `x86_64::avx2_unsafe`
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn simd_avx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        split_chunks(input, out)
+    }
+}
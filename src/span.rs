@@ -0,0 +1,128 @@
+//! The line around a byte offset, without an index: scan back from the offset for the newline
+//! before it and forward for the one after it, 32 bytes at a time. Same signatures and results as
+//! the `line-span` crate's `find_line_start`, `find_line_end` and `find_line_range`, so a line
+//! ends before its `\n` or `\r\n` (unlike the kernels, which keep the `\r`). Handy for turning a
+//! parser's error offset into the line to print.
+
+use core::ops::Range;
+
+/// Start of the line `pos` is in. A `pos` on a newline is in the line that newline ends.
+pub fn find_line_start(input: &str, pos: usize) -> usize {
+    assert!(pos <= input.len());
+    prev_newline(input.as_bytes(), pos).map_or(0, |nl| nl + 1)
+}
+
+/// End of the line `pos` is in, before its `\n` or `\r\n`
+pub fn find_line_end(input: &str, pos: usize) -> usize {
+    assert!(pos <= input.len());
+    let bytes = input.as_bytes();
+    let end = next_newline(bytes, pos).unwrap_or(input.len());
+    end - (end > 0 && bytes[end - 1] == b'\r') as usize
+}
+
+/// Byte range of the line `pos` is in, without its `\n` or `\r\n`
+pub fn find_line_range(input: &str, pos: usize) -> Range<usize> {
+    find_line_start(input, pos)..find_line_end(input, pos)
+}
+
+/// [`find_line_range`] with `str`'s `rfind` and `find`, the bench's baseline
+pub fn std_line_range(input: &str, pos: usize) -> Range<usize> {
+    let start = input[..pos].rfind('\n').map_or(0, |nl| nl + 1);
+    let end = input[pos..].find('\n').map_or(input.len(), |i| pos + i);
+    start..end - (end > 0 && input.as_bytes()[end - 1] == b'\r') as usize
+}
+
+/// Offset of the first newline from `from` on
+pub fn next_newline(bytes: &[u8], from: usize) -> Option<usize> {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::find_newline(bytes, from) };
+    }
+    bytes[from..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|i| from + i)
+}
+
+/// Offset of the last newline before `end`
+pub fn prev_newline(bytes: &[u8], end: usize) -> Option<usize> {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::rfind_newline(bytes, end) };
+    }
+    bytes[..end].iter().rposition(|&b| b == b'\n')
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use crate::slice::x86_64::newline_mask_copied;
+    use core::arch::x86_64::*;
+
+    /// Newline mask of the 32 bytes from `ptr`
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn mask_32(ptr: *const u8) -> u32 {
+        let v = _mm256_loadu_si256(ptr.cast());
+        _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'\n' as i8))) as u32
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn find_newline(bytes: &[u8], from: usize) -> Option<usize> {
+        let mut i = from;
+        while i + 32 <= bytes.len() {
+            let mask = mask_32(bytes.as_ptr().add(i));
+            if mask != 0 {
+                return Some(i + mask.trailing_zeros() as usize);
+            }
+            i += 32;
+        }
+        let mask = newline_mask_copied(bytes.get_unchecked(i..));
+        (mask != 0).then(|| i + mask.trailing_zeros() as usize)
+    }
+
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn rfind_newline(bytes: &[u8], end: usize) -> Option<usize> {
+        let mut i = end;
+        while i >= 32 {
+            let mask = mask_32(bytes.as_ptr().add(i - 32));
+            if mask != 0 {
+                return Some(i - 1 - mask.leading_zeros() as usize);
+            }
+            i -= 32;
+        }
+        let mask = newline_mask_copied(bytes.get_unchecked(..i));
+        (mask != 0).then(|| 63 - mask.leading_zeros() as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_line_range() {
+        let long = "a".repeat(100);
+        let inputs = [
+            "".to_owned(),
+            "\n".to_owned(),
+            "a\r\n\r\nb\n".to_owned(),
+            "\r".to_owned(),
+            format!("{long}\n{long}\r\n\n{long}"),
+            format!("\n{long}{long}\n"),
+            "é\nx\r\n".repeat(40),
+        ];
+        for input in &inputs {
+            crate::guard::with_guard_page(input, |input| {
+                for pos in (0..=input.len()).filter(|&pos| input.is_char_boundary(pos)) {
+                    let expected = std_line_range(input, pos);
+                    assert_eq!(find_line_range(input, pos), expected, "{input:?} at {pos}");
+                    assert_eq!(find_line_start(input, pos), expected.start);
+                    assert_eq!(find_line_end(input, pos), expected.end);
+                }
+            });
+        }
+        assert_eq!(find_line_range("ab\ncd\r\nef", 4), 3..5);
+        assert_eq!(find_line_range("ab\ncd\r\nef", 2), 0..2);
+        assert_eq!(find_line_range("ab\ncd\r\nef", 9), 7..9);
+    }
+}
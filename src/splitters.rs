@@ -0,0 +1,78 @@
+//! Safe handles for the `#[target_feature]` kernels. Those are `unsafe` only because calling them
+//! on a CPU without the features is UB, so each handle does the runtime check once, in `new`, and
+//! then exposes the kernels as safe methods. Holding one is proof the check passed.
+
+use crate::compressed::{self, LineIndex};
+use crate::{jsonl, slice};
+use alloc::vec::Vec;
+
+/// AVX2 (with BMI1 and POPCNT)
+#[derive(Clone, Copy)]
+pub struct Avx2Splitter(());
+
+impl Avx2Splitter {
+    pub fn new() -> Option<Self> {
+        slice::x86_64::can_run_avx2().then_some(Avx2Splitter(()))
+    }
+
+    pub fn split<'input>(self, input: &'input str, out: &mut Vec<&'input str>) {
+        unsafe { slice::x86_64::avx2_unrollx2(input, out) }
+    }
+
+    /// See [`slice::split_last`]
+    pub fn split_last<'input>(
+        self,
+        input: &'input str,
+        max_lines: usize,
+        out: &mut Vec<&'input str>,
+    ) {
+        unsafe { slice::x86_64::avx2_rev(input, max_lines, out) }
+    }
+
+    /// See [`slice::for_each_line`]
+    pub fn for_each_line<'input>(self, input: &'input str, f: impl FnMut(&'input str)) {
+        unsafe { slice::x86_64::avx2_for_each_line(input, f) }
+    }
+
+    pub fn build_index(self, input: &str, out: &mut LineIndex) {
+        unsafe { compressed::x86_64::avx2_unrollx2_interleavex2(input, out) }
+    }
+
+    /// See [`jsonl::split`]
+    pub fn split_jsonl<'input>(
+        self,
+        input: &'input str,
+        lines: &mut Vec<&'input str>,
+        flags: &mut Vec<bool>,
+    ) {
+        unsafe { jsonl::x86_64::avx2_unrollx2(input, lines, flags) }
+    }
+}
+
+/// SSE2 plus POPCNT, which is the only SSE4.2-era feature the kernel needs
+#[derive(Clone, Copy)]
+pub struct Sse42Splitter(());
+
+impl Sse42Splitter {
+    pub fn new() -> Option<Self> {
+        compressed::x86_64::can_run_sse42().then_some(Sse42Splitter(()))
+    }
+
+    pub fn build_index(self, input: &str, out: &mut LineIndex) {
+        unsafe { compressed::x86_64::sse42_unrollx4_interleavex2(input, out) }
+    }
+}
+
+/// AVX-512 F/BW/VBMI2 (for `vpcompressb`)
+#[derive(Clone, Copy)]
+pub struct Avx512Splitter(());
+
+impl Avx512Splitter {
+    pub fn new() -> Option<Self> {
+        compressed::x86_64::can_run_avx512_compress().then_some(Avx512Splitter(()))
+    }
+
+    pub fn build_index(self, input: &str, out: &mut LineIndex) {
+        unsafe { compressed::x86_64::avx512_compress(input, out) }
+    }
+}
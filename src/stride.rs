@@ -0,0 +1,125 @@
+//! Fixed-width records, every line the same length, as in mainframe and instrument dumps. Once
+//! that's checked, the lines start at multiples of the width, so splitting is writing out
+//! arithmetic offsets without looking at the bytes between them. Checking is cheaper than
+//! splitting: a few records spread over the input are read first, so other inputs are turned away
+//! early, then each 64-byte chunk's newline mask is compared to the one the width predicts, which
+//! has no bit loop to mispredict.
+
+/// Records whose end is read before all of them are
+const SAMPLES: usize = 64;
+
+/// The length of each record, newline included, if every line of `input` is as long as the first
+/// one and the last one ends with a newline
+pub fn detect(input: &str) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let stride = crate::roofline::memchr(b'\n', bytes)? + 1;
+    if !bytes.len().is_multiple_of(stride) {
+        return None;
+    }
+    let records = bytes.len() / stride;
+    let ends_record = |record: usize| bytes[record * stride + stride - 1] == b'\n';
+    if !(0..SAMPLES).all(|sample| ends_record(sample * records / SAMPLES)) {
+        return None;
+    }
+    has_stride(bytes, stride).then_some(stride)
+}
+
+/// Whether the newlines of `bytes` are exactly its every `stride`th byte
+fn has_stride(bytes: &[u8], stride: usize) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::has_stride(bytes, stride) };
+    }
+    bytes
+        .iter()
+        .enumerate()
+        .all(|(pos, &b)| (b == b'\n') == ((pos + 1) % stride == 0))
+}
+
+/// The lines of `input` if [`detect`] found a width, otherwise those of `slice::split`
+pub fn split<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    match detect(input) {
+        Some(stride) => from_stride(input, stride, out),
+        None => crate::slice::split(input, out),
+    }
+}
+
+/// The lines of an input whose records all take `stride` bytes, which must have been checked
+pub fn from_stride<'input>(input: &'input str, stride: usize, out: &mut Vec<&'input str>) {
+    assert!(input.len().is_multiple_of(stride));
+    let records = input.len() / stride;
+    out.reserve(records);
+    let slots = &mut out.spare_capacity_mut()[..records];
+    for (record, slot) in slots.iter_mut().enumerate() {
+        // records end in a newline, so they start on a char boundary
+        slot.write(unsafe { input.get_unchecked(record * stride..(record + 1) * stride - 1) });
+    }
+    // every slot up to `records` was just written
+    unsafe { out.set_len(out.len() + records) };
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use crate::slice::x86_64::newline_mask_copied;
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn has_stride(bytes: &[u8], stride: usize) -> bool {
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        // offset of the next newline there should be
+        let mut next = stride - 1;
+        let mut expected_at = |base: usize| {
+            let mut expected = 0;
+            while next < (base + 64).min(bytes.len()) {
+                expected |= 1 << (next - base);
+                next += stride;
+            }
+            expected
+        };
+        let stop_chunk_i = bytes.len() / 64;
+        for chunk_i in 0..stop_chunk_i {
+            let ptr = bytes.as_ptr().add(chunk_i * 64);
+            let v1 = _mm256_loadu_si256(ptr.cast());
+            let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+            let mask = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64) << 32)
+                | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+            if mask != expected_at(chunk_i * 64) {
+                return false;
+            }
+        }
+        // the padding has no newlines
+        let base = stop_chunk_i * 64;
+        newline_mask_copied(&bytes[base..]) == expected_at(base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(""), None);
+        assert_eq!(detect("abc"), None);
+        assert_eq!(detect(&"abc\n".repeat(1000)), Some(4));
+        assert_eq!(detect(&"\n".repeat(1000)), Some(1));
+        // no newline at the end, and a short record
+        assert_eq!(detect(&("abc\n".repeat(1000) + "abc")), None);
+        assert_eq!(detect(&("abc\n".repeat(1000) + "a\n\n")), None);
+        // only caught by the full check, every sampled record still ends in a newline
+        let mut input = "abc\n".repeat(1000).into_bytes();
+        input[1001] = b'\n';
+        assert_eq!(detect(std::str::from_utf8(&input).unwrap()), None);
+    }
+
+    #[test]
+    fn test_split() {
+        for input in ["", "abc", "abc\nde\n", &"abcdef\n".repeat(777)] {
+            crate::guard::with_guard_page(input, |input| {
+                let mut lines = Vec::new();
+                split(input, &mut lines);
+                assert_eq!(lines, crate::slice::std(input));
+            });
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Splitting on a short substring, like `"\r\n"`, `"\n\n"` or a MIME boundary, as
+//! `str::split_terminator` would: matches don't overlap, the leftmost one wins, and a trailing
+//! delimiter doesn't add an empty field. The SIMD kernel compares every position to the first and
+//! the last byte of the delimiter and only checks the bytes in between for positions where both
+//! match, so a 2-byte delimiter needs no check at all.
+
+use alloc::vec::Vec;
+
+pub fn split<'input>(input: &'input str, delim: &str, out: &mut Vec<&'input str>) {
+    assert!(!delim.is_empty(), "an empty delimiter");
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, delim, out) };
+    }
+    scalar(input, delim, out)
+}
+
+pub fn scalar<'input>(input: &'input str, delim: &str, out: &mut Vec<&'input str>) {
+    out.extend(input.split_terminator(delim));
+}
+
+/// What a single-byte split leaves to do: splits on newlines, then joins the lines back into
+/// fields, ending one wherever the text so far ends with `delim`, which must end with a newline
+pub fn split_then_join<'input>(input: &'input str, delim: &str, out: &mut Vec<&'input str>) {
+    assert!(delim.ends_with('\n'), "a delimiter ending with a newline");
+    let mut lines = Vec::new();
+    crate::slice::split(input, &mut lines);
+    let mut field_start = 0;
+    for line in lines {
+        // just past the line's newline
+        let end = line.as_ptr() as usize - input.as_ptr() as usize + line.len() + 1;
+        if end > input.len() {
+            // the last line, without one
+            break;
+        }
+        if end - field_start >= delim.len() && input.as_bytes()[..end].ends_with(delim.as_bytes()) {
+            out.push(&input[field_start..end - delim.len()]);
+            field_start = end;
+        }
+    }
+    if field_start < input.len() {
+        out.push(&input[field_start..]);
+    }
+}
+
+/// Pushes the fields ended by the matches at `from..`, after those up to `line_start`, and the
+/// last one
+#[cfg_attr(not(target_arch = "x86_64"), allow(dead_code))]
+fn scalar_from<'input>(
+    from: usize,
+    mut line_start: usize,
+    input: &'input str,
+    delim: &str,
+    out: &mut Vec<&'input str>,
+) {
+    let mut from = from.max(line_start);
+    // back to the start of a char, which `line_start` is, and so is any match
+    while !input.is_char_boundary(from) {
+        from -= 1;
+    }
+    while let Some(i) = input[from..].find(delim) {
+        out.push(&input[line_start..from + i]);
+        line_start = from + i + delim.len();
+        from = line_start;
+    }
+    if line_start < input.len() {
+        out.push(&input[line_start..]);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::*;
+    use core::arch::x86_64::*;
+
+    /// 64 bits, one per byte from `ptr` on, set for the bytes equal to `v`'s
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn eq_mask(ptr: *const u8, v: __m256i) -> u64 {
+        let lo = _mm256_cmpeq_epi8(_mm256_loadu_si256(ptr.cast()), v);
+        let hi = _mm256_cmpeq_epi8(_mm256_loadu_si256(ptr.add(32).cast()), v);
+        ((_mm256_movemask_epi8(hi) as u32 as u64) << 32) | (_mm256_movemask_epi8(lo) as u32 as u64)
+    }
+
+    /// Bit i of a chunk's mask is set where the bytes from i on start with the delimiter's first
+    /// byte and have its last byte `delim.len() - 1` on; the bytes in between are compared after
+    #[target_feature(enable = "avx2,bmi1")]
+    pub unsafe fn avx2<'input>(input: &'input str, delim: &str, out: &mut Vec<&'input str>) {
+        let bytes = input.as_bytes();
+        let delim_bytes = delim.as_bytes();
+        let last = delim.len() - 1;
+        let first_v = _mm256_set1_epi8(delim_bytes[0] as i8);
+        let last_v = _mm256_set1_epi8(delim_bytes[last] as i8);
+        let mut line_start = 0;
+        // the loads for the last byte reach `last` bytes past the chunk
+        let stop_chunk_i = bytes.len().saturating_sub(last) / 64;
+        for chunk_i in 0..stop_chunk_i {
+            let ptr = bytes.as_ptr().add(chunk_i * 64);
+            let mut mask = eq_mask(ptr, first_v) & eq_mask(ptr.add(last), last_v);
+            while mask != 0 {
+                let i = chunk_i * 64 + mask.trailing_zeros() as usize;
+                // a match can't start inside the one before it
+                if i >= line_start && bytes.get_unchecked(i + 1..i + last) == &delim_bytes[1..last]
+                {
+                    out.push(input.get_unchecked(line_start..i));
+                    line_start = i + delim.len();
+                }
+                mask &= mask - 1;
+            }
+        }
+        scalar_from(stop_chunk_i * 64, line_start, input, delim, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type SubstringFn = for<'a> fn(&'a str, &str, &mut Vec<&'a str>);
+
+    #[test]
+    fn test_split() {
+        let cases: &[(&str, &str, &[&str])] = &[
+            ("", "\r\n", &[]),
+            ("a\r\nb\r\n", "\r\n", &["a", "b"]),
+            ("a\rb\n\r\n\r\nc", "\r\n", &["a\rb\n", "", "c"]),
+            ("a\n\n\nb\n\n", "\n\n", &["a", "\nb"]),
+            ("\n\n\n\n", "\n\n", &["", ""]),
+            ("x\n--\ny\n--\n", "\n--\n", &["x", "y"]),
+            ("é\n\né\n", "\n\n", &["é", "é\n"]),
+        ];
+        for &(input, delim, expected) in cases {
+            let kernels: [(&str, SubstringFn); 3] = [
+                ("split", split),
+                ("split_then_join", split_then_join),
+                ("scalar", scalar),
+            ];
+            for (name, kernel) in kernels {
+                let mut out = vec!["before"];
+                kernel(input, delim, &mut out);
+                assert_eq!(out[1..], *expected, "{name}, input: {input:?}, {delim:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_chunk_boundaries() {
+        // matches, runs of overlapping ones and near misses on both sides of 64-byte boundaries
+        // and multibyte chars across them
+        let long = "ab\r\n\n\n\n--\n-\n---\n\r\né\r\r\n\n\n\nx\n---\n\n".repeat(200) + "\n--";
+        for delim in ["\r\n", "\n\n", "\n--\n", "\n---\n", "\r\n\r\n"] {
+            for start in 0..64 {
+                let Some(input) = long.get(start..) else {
+                    continue;
+                };
+                crate::guard::with_guard_page(input, |input| {
+                    let mut expected = Vec::new();
+                    scalar(input, delim, &mut expected);
+                    let (mut fused, mut joined) = (Vec::new(), Vec::new());
+                    split(input, delim, &mut fused);
+                    split_then_join(input, delim, &mut joined);
+                    assert_eq!(fused, expected, "from {start}, {delim:?}");
+                    assert_eq!(joined, expected, "from {start}, {delim:?}");
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Log-ingestion style preprocessing: every line with its trailing `\r`s and spaces trimmed, and
+//! the lines that are left empty dropped. Either while splitting, or as a pass over the output of
+//! `slice::split`, which is what the bench compares. The fused kernel trims each line when it finds
+//! its newline, so the bytes it looks at were loaded a moment ago.
+
+/// Leading whitespace is kept
+pub fn split<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, out) };
+    }
+    scalar(input, out)
+}
+
+/// Splits into `out`, then trims and compacts the new lines in place
+pub fn split_then_filter<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    let first = out.len();
+    crate::slice::split(input, out);
+    let mut kept = first;
+    for i in first..out.len() {
+        let line = out[i].trim_end_matches([' ', '\r']);
+        if !line.is_empty() {
+            out[kept] = line;
+            kept += 1;
+        }
+    }
+    out.truncate(kept);
+}
+
+pub fn scalar<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    out.extend(
+        input
+            .split_terminator('\n')
+            .map(|line| line.trim_end_matches([' ', '\r']))
+            .filter(|line| !line.is_empty()),
+    );
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use std::arch::x86_64::*;
+
+    /// Whether `byte` is trimmed from the end of a line
+    #[inline(always)]
+    fn is_trailing_space(byte: u8) -> bool {
+        matches!(byte, b' ' | b'\r')
+    }
+
+    /// `slice::x86_64::avx2_unrollx2`, trimming each line before writing it
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+        let bytes = input.as_bytes();
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mut line_start = 0;
+        let mut chunk_i = 0;
+        let stop_chunk_i = input.len() / 64;
+        while chunk_i < stop_chunk_i {
+            let mut write_i = 0;
+            out.reserve(256);
+            let out_arr = out.spare_capacity_mut().get_unchecked_mut(..256);
+            while write_i <= (256 - 64) && chunk_i < stop_chunk_i {
+                let ptr = input.as_ptr().add(chunk_i * 64);
+                let v1 = _mm256_loadu_si256(ptr.cast());
+                let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+                let mut mask = ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64)
+                    << 32)
+                    | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64);
+                while mask != 0 {
+                    let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                    let mut end = line_end;
+                    while end > line_start && is_trailing_space(*bytes.get_unchecked(end - 1)) {
+                        end -= 1;
+                    }
+                    // always written, only kept if it isn't empty
+                    out_arr
+                        .get_unchecked_mut(write_i)
+                        .write(input.get_unchecked(line_start..end));
+                    write_i += (end != line_start) as usize;
+                    line_start = line_end + 1;
+                    mask &= mask - 1;
+                }
+                chunk_i += 1;
+            }
+            out.set_len(out.len() + write_i);
+        }
+        // the newlines after the last whole chunk, then the last line if it has none
+        let mut push = |start: usize, end: usize| {
+            let line = input[start..end].trim_end_matches([' ', '\r']);
+            if !line.is_empty() {
+                out.push(line);
+            }
+        };
+        let tail_start = stop_chunk_i * 64;
+        for (i, &byte) in bytes[tail_start..].iter().enumerate() {
+            if byte == b'\n' {
+                push(line_start, tail_start + i);
+                line_start = tail_start + i + 1;
+            }
+        }
+        push(line_start, input.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let cases: &[(&str, &[&str])] = &[
+            ("", &[]),
+            ("\n\r\n \n", &[]),
+            ("a", &["a"]),
+            ("a \r", &["a"]),
+            (" a\r\n\r\nb c  \n\n", &[" a", "b c"]),
+            ("a\r\rb\r\n", &["a\r\rb"]),
+            ("é \n", &["é"]),
+        ];
+        for &(input, expected) in cases {
+            let kernels: [(&str, crate::SliceSplitFn); 3] = [
+                ("split", split),
+                ("split_then_filter", split_then_filter),
+                ("scalar", scalar),
+            ];
+            for (name, kernel) in kernels {
+                let mut out = vec!["before"];
+                kernel(input, &mut out);
+                assert_eq!(out[1..], *expected, "{name}, input: {input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_chunk_boundaries() {
+        // trailing spaces and blank lines on both sides of 64-byte boundaries
+        let long = "ab \r\n  \r\n\n0123456789  \n \n".repeat(5_000) + "last  ";
+        for len in [0, 1, 63, 64, 65, 127, 1000, long.len()] {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let mut expected = Vec::new();
+                scalar(input, &mut expected);
+                let (mut fused, mut filtered) = (Vec::new(), Vec::new());
+                split(input, &mut fused);
+                split_then_filter(input, &mut filtered);
+                assert_eq!(fused, expected, "len {len}");
+                assert_eq!(filtered, expected, "len {len}");
+            });
+        }
+    }
+}
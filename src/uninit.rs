@@ -0,0 +1,195 @@
+//! Splitting into memory the caller owns: the lines go into a `&mut [MaybeUninit<&str>]` and the
+//! kernel returns how many it wrote, with no `Vec` involved, for embedders with their own arenas
+//! or fixed buffers. When the slots run out, the rest of the input is still scanned, so the error
+//! can say how many slots it takes.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::MaybeUninit;
+
+/// `out` had fewer slots than the input has lines
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TooSmall {
+    /// Slots it takes to hold every line
+    pub needed: usize,
+}
+
+impl fmt::Display for TooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the output needs room for {} lines", self.needed)
+    }
+}
+
+impl core::error::Error for TooSmall {}
+
+/// Writes the lines of `input` (the ones `slice::split` finds) to the start of `out` and returns
+/// how many there are. When they don't all fit, the first `out.len()` are written anyway.
+pub fn split_into<'input>(
+    input: &'input str,
+    out: &mut [MaybeUninit<&'input str>],
+) -> Result<usize, TooSmall> {
+    #[cfg(target_arch = "x86_64")]
+    if crate::slice::x86_64::can_run_avx2() {
+        return unsafe { x86_64::avx2(input, out) };
+    }
+    scalar(input, out)
+}
+
+/// [`split_into`] on `out`'s spare capacity, reserving what the error asks for and starting over
+/// if it's too small, which is how a caller with a `Vec` would use it
+pub fn split_into_vec<'input>(input: &'input str, out: &mut Vec<&'input str>) {
+    let written = match split_into(input, out.spare_capacity_mut()) {
+        Ok(written) => written,
+        Err(TooSmall { needed }) => {
+            out.reserve(needed);
+            split_into(input, out.spare_capacity_mut()).unwrap()
+        }
+    };
+    // the first `written` spare slots were just initialized
+    unsafe { out.set_len(out.len() + written) };
+}
+
+pub fn scalar<'input>(
+    input: &'input str,
+    out: &mut [MaybeUninit<&'input str>],
+) -> Result<usize, TooSmall> {
+    let mut lines = 0;
+    for line in input.split_terminator('\n') {
+        if let Some(slot) = out.get_mut(lines) {
+            slot.write(line);
+        }
+        lines += 1;
+    }
+    match lines <= out.len() {
+        true => Ok(lines),
+        false => Err(TooSmall { needed: lines }),
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub mod x86_64 {
+    use super::TooSmall;
+    use crate::slice::x86_64::newline_mask_copied;
+    use core::arch::x86_64::*;
+    use core::mem::MaybeUninit;
+
+    /// `slice::x86_64::avx2_unrollx2`, writing to `out` without a check per line while it has
+    /// room for a whole chunk's worth, then checking each line, then only counting them
+    #[target_feature(enable = "avx2,bmi1,popcnt")]
+    pub unsafe fn avx2<'input>(
+        input: &'input str,
+        out: &mut [MaybeUninit<&'input str>],
+    ) -> Result<usize, TooSmall> {
+        let nl_v = _mm256_set1_epi8(b'\n' as i8);
+        let mask_at = |chunk_i: usize| {
+            let ptr = input.as_ptr().add(chunk_i * 64);
+            let v1 = _mm256_loadu_si256(ptr.cast());
+            let v2 = _mm256_loadu_si256(ptr.add(32).cast());
+            ((_mm256_movemask_epi8(_mm256_cmpeq_epi8(v2, nl_v)) as u32 as u64) << 32)
+                | (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v1, nl_v)) as u32 as u64)
+        };
+        let mut line_start = 0;
+        let mut lines = 0;
+        let stop_chunk_i = input.len() / 64;
+        let mut chunk_i = 0;
+        // at most 64 lines end in a chunk
+        while chunk_i < stop_chunk_i && out.len() - lines >= 64 {
+            let mut mask = mask_at(chunk_i);
+            while mask != 0 {
+                let line_end = chunk_i * 64 + mask.trailing_zeros() as usize;
+                out.get_unchecked_mut(lines)
+                    .write(input.get_unchecked(line_start..line_end));
+                lines += 1;
+                line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+            chunk_i += 1;
+        }
+        // the last few slots, and the lines past them, which are only counted
+        let mut drain = |base: usize, mut mask: u64, line_start: &mut usize| {
+            while mask != 0 {
+                let line_end = base + mask.trailing_zeros() as usize;
+                if let Some(slot) = out.get_mut(lines) {
+                    slot.write(input.get_unchecked(*line_start..line_end));
+                }
+                lines += 1;
+                *line_start = line_end + 1;
+                mask &= mask - 1;
+            }
+        };
+        while chunk_i < stop_chunk_i {
+            drain(chunk_i * 64, mask_at(chunk_i), &mut line_start);
+            chunk_i += 1;
+        }
+        let base = stop_chunk_i * 64;
+        drain(
+            base,
+            newline_mask_copied(input.as_bytes().get_unchecked(base..)),
+            &mut line_start,
+        );
+        if line_start != input.len() {
+            if let Some(slot) = out.get_mut(lines) {
+                slot.write(input.get_unchecked(line_start..));
+            }
+            lines += 1;
+        }
+        match lines <= out.len() {
+            true => Ok(lines),
+            false => Err(TooSmall { needed: lines }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_into() {
+        let long = "ab\n\n\ncdefghijklmnopqrstuvwxyz0123456789\n".repeat(40) + "x";
+        for len in (0..=300).chain([long.len() - 1, long.len()]) {
+            crate::guard::with_guard_page(&long[..len], |input| {
+                let expected = crate::slice::std(input);
+                let kernels: &[(&str, fn(_, &mut _) -> _)] = &[
+                    ("split_into", split_into),
+                    ("scalar", scalar),
+                    #[cfg(target_arch = "x86_64")]
+                    ("avx2", |input, out| unsafe { x86_64::avx2(input, out) }),
+                ];
+                for (name, kernel) in kernels {
+                    #[cfg(target_arch = "x86_64")]
+                    if *name == "avx2" && !crate::slice::x86_64::can_run_avx2() {
+                        continue;
+                    }
+                    // room for all of them, exactly, and a few short (on and off a chunk's worth)
+                    for slots in [expected.len() + 70, expected.len(), 0, 1, 63, 64, 65] {
+                        let mut out = vec![MaybeUninit::uninit(); slots];
+                        let result = kernel(input, &mut out);
+                        let written = slots.min(expected.len());
+                        let lines: Vec<&str> = out[..written]
+                            .iter()
+                            .map(|slot| unsafe { slot.assume_init() })
+                            .collect();
+                        assert_eq!(lines, expected[..written], "{name}, len {len}, {slots}");
+                        let expected_result = match expected.len() <= slots {
+                            true => Ok(expected.len()),
+                            false => Err(TooSmall {
+                                needed: expected.len(),
+                            }),
+                        };
+                        assert_eq!(result, expected_result, "{name}, len {len}, {slots}");
+                    }
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_split_into_vec() {
+        let input = "ab\n\ncd\n".repeat(1000);
+        let mut out = vec!["before"];
+        split_into_vec(&input, &mut out);
+        assert_eq!(out[0], "before");
+        assert_eq!(out[1..], crate::slice::std(&input));
+    }
+}
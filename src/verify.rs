@@ -0,0 +1,199 @@
+//! Checking a kernel's lines against the reference's (`--verify`). Comparing two `Vec<&str>`s
+//! reads every byte of the input again on one thread, which for a billion lines takes about as
+//! long as the bench itself. By default ([`Verify::Hash`]) a kernel's output is instead reduced to
+//! a [`Fingerprint`] on every core, reading only the slices, and compared to the reference's,
+//! which is made once per stage. A sample of lines is also compared by content, which catches
+//! slices into the wrong buffer and makes for a readable message when something is off.
+
+use crate::rng::Rng;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verify {
+    /// Only the line counts are checked
+    Off,
+    /// Line counts, every [`SAMPLE_STRIDE`]th line, and a fingerprint of all of them
+    Hash,
+    /// Every line, compared one by one
+    Full,
+}
+
+impl FromStr for Verify {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "off" => Ok(Verify::Off),
+            "hash" => Ok(Verify::Hash),
+            "full" => Ok(Verify::Full),
+            _ => Err("expected one of off, hash, full".to_owned()),
+        }
+    }
+}
+
+/// Lines compared by content with [`Verify::Hash`]
+const SAMPLE_STRIDE: usize = 4096;
+const BLOCK_BITS: u32 = 16;
+
+/// Per 64 KiB block, a hash of the number, offset and length of every line starting in it.
+/// Offsets are relative to the first line, so the fingerprints of lines found in different copies
+/// of an input match.
+pub struct Fingerprint {
+    /// One per block, and one for lines that start outside of the input
+    blocks: Vec<u64>,
+}
+
+impl Fingerprint {
+    /// Hashes `lines` of an input of `input_len` bytes on every core
+    pub fn new(input_len: usize, lines: &[&str]) -> Self {
+        let block_count = (input_len >> BLOCK_BITS) + 2;
+        let base = lines.first().map_or(0, |line| line.as_ptr() as usize);
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let per_thread = lines.len().div_ceil(threads).max(1);
+        let partials: Vec<Vec<u64>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = lines
+                .chunks(per_thread)
+                .enumerate()
+                .map(|(chunk, chunk_lines)| {
+                    scope.spawn(move || {
+                        let mut blocks = vec![0u64; block_count];
+                        for (i, line) in chunk_lines.iter().enumerate() {
+                            let offset = (line.as_ptr() as usize).wrapping_sub(base);
+                            let block = (offset >> BLOCK_BITS).min(block_count - 1);
+                            // adding makes the order the threads finish in irrelevant, the line
+                            // number keeps the order of the lines relevant
+                            blocks[block] = blocks[block].wrapping_add(mix(
+                                chunk * per_thread + i,
+                                offset,
+                                line.len(),
+                            ));
+                        }
+                        blocks
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut blocks = vec![0u64; block_count];
+        for partial in partials {
+            for (sum, hash) in blocks.iter_mut().zip(partial) {
+                *sum = sum.wrapping_add(hash);
+            }
+        }
+        Fingerprint { blocks }
+    }
+}
+
+fn mix(line: usize, offset: usize, len: usize) -> u64 {
+    let key = (line as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (offset as u64).rotate_left(21)
+        ^ (len as u64).rotate_left(42);
+    Rng::new(key).next_u64()
+}
+
+/// The reference's lines for one input, in whatever form the mode compares them
+pub struct Expected<'input> {
+    mode: Verify,
+    input_len: usize,
+    /// Every line with [`Verify::Full`], every [`SAMPLE_STRIDE`]th line with [`Verify::Hash`],
+    /// none with [`Verify::Off`]
+    lines: Vec<&'input str>,
+    line_count: usize,
+    fingerprint: Option<Fingerprint>,
+}
+
+impl<'input> Expected<'input> {
+    pub fn new(mode: Verify, input: &'input str, lines: Vec<&'input str>) -> Self {
+        let line_count = lines.len();
+        let (lines, fingerprint) = match mode {
+            Verify::Off => (Vec::new(), None),
+            Verify::Hash => (
+                lines.iter().step_by(SAMPLE_STRIDE).copied().collect(),
+                Some(Fingerprint::new(input.len(), &lines)),
+            ),
+            Verify::Full => (lines, None),
+        };
+        Expected {
+            mode,
+            input_len: input.len(),
+            lines,
+            line_count,
+            fingerprint,
+        }
+    }
+
+    /// Describes the first difference between `lines` and the reference's
+    pub fn check(&self, lines: &[&str]) -> Result<(), String> {
+        if lines.len() != self.line_count {
+            return Err(format!(
+                "{} lines instead of {}",
+                lines.len(),
+                self.line_count
+            ));
+        }
+        let stride = match self.mode {
+            Verify::Off => return Ok(()),
+            Verify::Hash => SAMPLE_STRIDE,
+            Verify::Full => 1,
+        };
+        let found = lines.iter().step_by(stride);
+        if let Some((i, (line, expected))) = found
+            .zip(&self.lines)
+            .enumerate()
+            .find(|(_, (line, expected))| line != expected)
+        {
+            return Err(format!(
+                "line {} is {line:?} instead of {expected:?}",
+                i * stride
+            ));
+        }
+        let Some(expected) = &self.fingerprint else {
+            return Ok(());
+        };
+        let fingerprint = Fingerprint::new(self.input_len, lines);
+        match expected
+            .blocks
+            .iter()
+            .zip(&fingerprint.blocks)
+            .position(|(a, b)| a != b)
+        {
+            None => Ok(()),
+            Some(block) => Err(format!(
+                "the lines starting in bytes {}..{} differ",
+                block << BLOCK_BITS,
+                (block + 1) << BLOCK_BITS
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let input = "ab\n\ncd\n".repeat(50_000);
+        let copy = input.clone();
+        let lines: Vec<&str> = input.split_terminator('\n').collect();
+        for mode in [Verify::Off, Verify::Hash, Verify::Full] {
+            let expected = Expected::new(mode, &input, lines.clone());
+            // a copy's lines are at other addresses
+            let same: Vec<&str> = copy.split_terminator('\n').collect();
+            assert_eq!(expected.check(&same), Ok(()));
+            assert!(expected.check(&same[1..]).is_err());
+
+            // between samples
+            let mut wrong = same.clone();
+            wrong[100_000] = "x";
+            assert_eq!(expected.check(&wrong).is_err(), mode != Verify::Off);
+        }
+        // an empty line pointing at the start of the next one: same content, wrong offset
+        let mut wrong = lines.clone();
+        wrong[100_000] = &wrong[100_001][..0];
+        assert_eq!(
+            Expected::new(Verify::Hash, &input, lines.clone()).check(&wrong),
+            Err("the lines starting in bytes 196608..262144 differ".to_owned())
+        );
+    }
+}
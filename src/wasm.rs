@@ -0,0 +1,144 @@
+//! The wasm entry point (`--features wasm`, built for `wasm32-unknown-unknown` by `wasm/build.sh`),
+//! a reduced bench for comparing engines, and `simd128` builds with plain ones. [`bench`] times a
+//! few kernels on a few of the bench's stages and returns the results as JSON. Wasm has no clock
+//! of its own, so runs are timed with the host's `performance.now()`, which browsers round to
+//! 5-100 µs; the default stage size keeps each run well above that.
+
+use crate::compressed::{self, LineIndex, NewlineIndex};
+use crate::{dispatch, flat, simd_lines, slice};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance)]
+    fn now() -> f64;
+}
+
+/// SplitMix64, as in the bench's `rng`
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (((z ^ (z >> 31)) as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// `lines` lines of M to N `a`s each, like the bench's `prep_vec_range`
+fn prep_range<const M: usize, const N: usize>(lines: usize, rng: &mut Rng) -> String {
+    let mut input = String::new();
+    for _ in 0..lines {
+        let len = M + rng.below(N - M + 1);
+        input.extend(core::iter::repeat_n('a', len));
+        input.push('\n');
+    }
+    input
+}
+
+/// The bench's stages, fewer of them: its sparse, dense and all-newline ends, and a single line
+/// of as many bytes as the 40-120 stage
+type PrepFn = fn(usize, &mut Rng) -> String;
+const STAGES: &[(&str, PrepFn)] = &[
+    ("single line", |lines, _| "a".repeat(lines * 81)),
+    ("1-20", prep_range::<1, 20>),
+    ("40-120", prep_range::<40, 120>),
+    ("0-0", |lines, _| "\n".repeat(lines)),
+];
+
+type SliceSplitFn = for<'input> fn(&'input str, &mut Vec<&'input str>);
+const SLICE_CASES: &[(&str, SliceSplitFn)] = &[
+    ("std", |input, out| *out = slice::std(input)),
+    ("std_reuse", slice::std_reuse),
+    ("split", slice::split),
+    ("SimdLines", simd_lines::split),
+    ("swar", slice::swar),
+    #[cfg(target_feature = "simd128")]
+    ("simd128", slice::wasm32::simd128),
+    #[cfg(target_feature = "simd128")]
+    ("simd128_unroll", slice::wasm32::simd128_unroll),
+];
+
+/// Median milliseconds of `iterations` runs of `run`, after one to warm up
+fn time(iterations: usize, mut run: impl FnMut()) -> f64 {
+    run();
+    let mut times = (0..iterations.max(1))
+        .map(|_| {
+            let start = now();
+            run();
+            now() - start
+        })
+        .collect::<Vec<_>>();
+    times.sort_by(f64::total_cmp);
+    times[times.len() / 2]
+}
+
+/// Runs the reduced bench on stages of `lines` lines, each kernel `iterations` times, and returns
+/// `{"kernel", "simd128", "lines", "iterations", "results"}`, where `kernel` is the one
+/// `slice::split` dispatches to and `results` has one record per measurement, with the fields of
+/// the bench's `--history` records (`table`, `algo`, `stage`, and `thrpt` in MB/s). Fails if a
+/// kernel finds the wrong number of lines.
+#[wasm_bindgen]
+pub fn bench(lines: usize, iterations: usize) -> Result<String, JsError> {
+    let mut rng = Rng(0);
+    let mut results = Vec::new();
+    let mut record = |table: &str, algo: &str, stage: &str, len: usize, ms: f64| {
+        let thrpt = len as f64 / ms / 1000.;
+        results.push(alloc::format!(
+            "{{\"table\":\"{table}\",\"algo\":\"{algo}\",\"stage\":\"{stage}\",\"thrpt\":{thrpt}}}"
+        ));
+    };
+    for (stage, prep) in STAGES {
+        let input = prep(lines, &mut rng);
+        let expected_lines = input.lines().count();
+        let check = |table: &str, algo: &str, found: usize| match found == expected_lines {
+            true => Ok(()),
+            false => Err(JsError::new(&alloc::format!(
+                "{table} {algo} found {found} lines in {stage}, not {expected_lines}"
+            ))),
+        };
+
+        let mut out = Vec::new();
+        for (algo, split) in SLICE_CASES {
+            let ms = time(iterations, || {
+                out.clear();
+                split(&input, &mut out);
+            });
+            check("slice", algo, out.len())?;
+            record("slice", algo, stage, input.len(), ms);
+        }
+
+        let mut index = LineIndex::default();
+        let ms = time(iterations, || {
+            index.lows.clear();
+            index.high_starts.clear();
+            compressed::build(&input, &mut index);
+        });
+        check("compressed", "build", index.line_count(input.len()))?;
+        record("compressed", "build", stage, input.len(), ms);
+
+        let mut offsets = Vec::new();
+        let ms = time(iterations, || {
+            offsets.clear();
+            flat::build(&input, &mut offsets);
+        });
+        check("flat", "build", offsets.line_count(input.len()))?;
+        record("flat", "build", stage, input.len(), ms);
+    }
+
+    let mut json = String::new();
+    write!(
+        json,
+        "{{\"kernel\":\"{}\",\"simd128\":{},\"lines\":{lines},\"iterations\":{iterations},\"results\":[{}]}}",
+        dispatch::global().kernel().name(),
+        cfg!(target_feature = "simd128"),
+        results.join(",")
+    )
+    .unwrap();
+    Ok(json)
+}
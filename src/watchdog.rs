@@ -0,0 +1,50 @@
+//! `--timeout`'s last resort, for a run that doesn't come back at all (a kernel stuck in a
+//! fault-and-retry loop, say). A run that comes back too slow is recorded as timed out by the
+//! bench and the suite moves on, but one that never returns can't be stopped from inside the
+//! process, so a thread watches the clock and ends the process with an error naming the case,
+//! rather than leaving a CI job hanging until it's killed without one.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the deadline is checked
+const POLL: Duration = Duration::from_millis(50);
+
+/// Exit status when a run is given up on, distinct from the 1 of an error
+pub const EXIT_CODE: i32 = 3;
+
+/// (table, algo, stage) of the run being watched
+pub type Case = (&'static str, &'static str, &'static str);
+
+pub struct Watchdog {
+    armed: Arc<Mutex<Option<(Instant, Case)>>>,
+}
+
+impl Watchdog {
+    pub fn spawn() -> Self {
+        let armed: Arc<Mutex<Option<(Instant, Case)>>> = Arc::default();
+        let watched = Arc::clone(&armed);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(POLL);
+            if let Some((deadline, (table, algo_name, stage_label))) = *watched.lock().unwrap() {
+                if Instant::now() > deadline {
+                    eprintln!(
+                        "error: ({table}) {algo_name} hasn't returned during {stage_label}, \
+                         well past its --timeout, giving up on the bench"
+                    );
+                    std::process::exit(EXIT_CODE);
+                }
+            }
+        });
+        Watchdog { armed }
+    }
+
+    /// Ends the process if `case` still runs after `limit`
+    pub fn arm(&self, limit: Duration, case: Case) {
+        *self.armed.lock().unwrap() = Some((Instant::now() + limit, case));
+    }
+
+    pub fn disarm(&self) {
+        *self.armed.lock().unwrap() = None;
+    }
+}
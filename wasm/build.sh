@@ -0,0 +1,18 @@
+#!/bin/sh
+# Builds the `wasm` feature's entry point twice, into wasm/pkg (baseline wasm32) and
+# wasm/pkg-simd128 (`-C target-feature=+simd128`), for node.mjs and index.html. Needs the
+# wasm32-unknown-unknown target and a `wasm-bindgen` CLI of the same version as the crate in
+# Cargo.lock (`cargo install wasm-bindgen-cli --version ...`).
+set -e
+cd "$(dirname "$0")/.."
+for variant in pkg pkg-simd128; do
+    case $variant in
+        pkg) flags="" ;;
+        pkg-simd128) flags="-C target-feature=+simd128" ;;
+    esac
+    # a cdylib only for this build, so the native library stays an rlib
+    RUSTFLAGS="$flags" CARGO_TARGET_DIR="target/wasm/$variant" cargo rustc --lib --release \
+        --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+    wasm-bindgen --target web --out-dir "wasm/$variant" \
+        "target/wasm/$variant/wasm32-unknown-unknown/release/split_bench.wasm"
+done
@@ -0,0 +1,10 @@
+// Runs the reduced bench under node (18+) and prints its JSON, after `wasm/build.sh`.
+//
+// usage: node wasm/node.mjs [pkg|pkg-simd128] [lines] [iterations]
+import { readFile } from "node:fs/promises";
+
+const [variant = "pkg", lines = "100000", iterations = "10"] = process.argv.slice(2);
+const dir = new URL(`./${variant}/`, import.meta.url);
+const { default: init, bench } = await import(new URL("split_bench.js", dir));
+await init({ module_or_path: await readFile(new URL("split_bench_bg.wasm", dir)) });
+console.log(bench(Number(lines), Number(iterations)));